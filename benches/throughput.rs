@@ -0,0 +1,247 @@
+//! how fast the hash, the search page parser and the srt repair are
+//!
+//! criterion can't be a dependency yet, so this is a small harness of its own. `cargo bench`
+//! runs everything, `cargo bench --bench throughput -- hash` only what has `hash` in its
+//! name. to compare two versions save the numbers of one and measure the other against them,
+//! `--bench throughput` keeps the flags from the library's test harness, which refuses them:
+//!
+//! ```text
+//! git stash && cargo bench --bench throughput -- --save-baseline before
+//! git stash pop && cargo bench --bench throughput -- --baseline before
+//! ```
+//!
+//! baselines are kept in `target/benches`. the hashed files are written once per run and
+//! stay in the page cache, so the hash numbers are the cost of the hash, not of the disk
+use opensubtitlescli::{
+    crawler::{self, Ranking},
+    hash::{hash_with, Method},
+    srt::{self, RepairOptions},
+};
+use std::{
+    collections::BTreeMap,
+    hint::black_box,
+    io::{Seek, SeekFrom, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// how long a sample runs for, the iterations in it are picked to fill it
+const SAMPLE: Duration = Duration::from_millis(50);
+const SAMPLES: usize = 30;
+
+/// xorshift, the same data on every run
+fn random_bytes(seed: u64, size: usize) -> Vec<u8> {
+    let mut state = seed.max(1);
+    (0..size)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+/// a sparse file of `size` bytes with random data where the hash reads
+fn movie(dir: &tempfile::TempDir, size: u64) -> PathBuf {
+    let path = dir.path().join(format!("{size}.mkv"));
+    let mut file = std::fs::File::create(&path).unwrap();
+    file.set_len(size).unwrap();
+    file.write_all(&random_bytes(size, 65536)).unwrap();
+    file.seek(SeekFrom::Start(size - 65536)).unwrap();
+    file.write_all(&random_bytes(size + 1, 65536)).unwrap();
+    path
+}
+
+/// the fixture's results repeated until there are `rows` of them
+fn search_page(rows: usize) -> String {
+    let fixture = include_str!("../tests/fixtures/search.html");
+    let start = fixture.find("<tr class=").unwrap();
+    let end = fixture.rfind("</tr>").unwrap() + "</tr>".len();
+    let results = fixture[start..end]
+        .split_inclusive("</tr>")
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    let rows = (0..rows)
+        .map(|n| {
+            results[n % results.len()].replace(
+                "/download/sub/100000",
+                &format!("/download/sub/{}", 2_000_000 + n * 10),
+            )
+        })
+        .collect::<String>();
+    format!("{}{rows}{}", &fixture[..start], &fixture[end..])
+}
+
+/// `cues` cues, some of them out of order, repeated or overlapping the next one
+fn messy_srt(cues: usize) -> String {
+    let mut text = String::new();
+    for n in 0..cues {
+        let n = match n % 97 {
+            0 if n > 0 => n - 1,
+            _ => n,
+        };
+        let start = n as i64 * 3000;
+        let end = start + [2500, 3500, -500][n % 3];
+        let timestamp = |millis: i64| srt::Timestamp(millis.max(0)).to_string();
+        text += &format!(
+            "{}\n{} --> {}\n- Line number {n}.\n<i>- And the answer to it.</i>\n\n",
+            n + 1,
+            timestamp(start),
+            timestamp(end)
+        );
+    }
+    text
+}
+
+/// time per iteration of every sample
+fn measure(f: &mut dyn FnMut()) -> Vec<Duration> {
+    let started = Instant::now();
+    let mut warmup = 0u32;
+    while started.elapsed() < SAMPLE {
+        f();
+        warmup += 1;
+    }
+    let iterations = warmup.max(1);
+    (0..SAMPLES)
+        .map(|_| {
+            let started = Instant::now();
+            for _ in 0..iterations {
+                f();
+            }
+            started.elapsed() / iterations
+        })
+        .collect()
+}
+
+struct Args {
+    filter: Option<String>,
+    save: Option<String>,
+    baseline: Option<String>,
+}
+
+impl Args {
+    /// what's after `cargo bench --`, cargo adds a `--bench` of its own
+    fn parse() -> Self {
+        let mut args = std::env::args().skip(1);
+        let mut parsed = Self {
+            filter: None,
+            save: None,
+            baseline: None,
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--save-baseline" => parsed.save = args.next(),
+                "--baseline" => parsed.baseline = args.next(),
+                flag if flag.starts_with("--") => {}
+                filter => parsed.filter = Some(filter.to_string()),
+            }
+        }
+        parsed
+    }
+}
+
+fn baseline_path(name: &str) -> PathBuf {
+    let target = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target"));
+    target.join("benches").join(format!("{name}.json"))
+}
+
+struct Bencher {
+    args: Args,
+    /// nanoseconds per iteration, the median of the samples
+    baseline: BTreeMap<String, u128>,
+    medians: BTreeMap<String, u128>,
+}
+
+impl Bencher {
+    fn new(args: Args) -> Self {
+        let baseline = args
+            .baseline
+            .as_deref()
+            .map(|name| {
+                let path = baseline_path(name);
+                let contents =
+                    std::fs::read(&path).unwrap_or_else(|e| panic!("no baseline in {path:?}: {e}"));
+                serde_json::from_slice(&contents).unwrap()
+            })
+            .unwrap_or_default();
+        Self {
+            args,
+            baseline,
+            medians: BTreeMap::new(),
+        }
+    }
+
+    fn bench(&mut self, name: &str, mut f: impl FnMut()) {
+        if let Some(filter) = &self.args.filter {
+            if !name.contains(filter.as_str()) {
+                return;
+            }
+        }
+        let mut samples = measure(&mut f);
+        samples.sort();
+        let median = samples[samples.len() / 2];
+        let line = format!(
+            "{name:<32} {:>12?} median  {:>12?} fastest",
+            median, samples[0]
+        );
+        match self.baseline.get(name) {
+            Some(&before) => {
+                let change = (median.as_nanos() as f64 / before as f64 - 1.0) * 100.0;
+                println!("{line}  {change:+.1}% against the baseline");
+            }
+            None => println!("{line}"),
+        }
+        self.medians.insert(name.to_string(), median.as_nanos());
+    }
+
+    fn finish(self) {
+        if let Some(name) = &self.args.save {
+            let path = baseline_path(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, serde_json::to_vec_pretty(&self.medians).unwrap()).unwrap();
+            println!("saved the baseline to {path:?}");
+        }
+    }
+}
+
+fn main() {
+    let mut bencher = Bencher::new(Args::parse());
+
+    let dir = tempfile::tempdir().unwrap();
+    for (label, size) in [
+        ("128 KiB", 131_072),
+        ("700 MiB", 700 << 20),
+        ("4.4 GiB", 4_700_000_000),
+    ] {
+        let path = movie(&dir, size);
+        for method in [Method::Mmap, Method::Read] {
+            let name = format!("hash {label} {method:?}").to_lowercase();
+            bencher.bench(&name, || {
+                black_box(hash_with(&path, method).unwrap());
+            });
+        }
+    }
+
+    let ranking = Ranking::default();
+    for rows in [2, 500] {
+        let page = search_page(rows);
+        bencher.bench(&format!("search page {rows} results"), || {
+            black_box(crawler::top_rated_subs(&page, &ranking).unwrap());
+        });
+    }
+
+    let text = messy_srt(2000);
+    bencher.bench("srt parse 2000 cues", || {
+        black_box(srt::parse_lenient(&text));
+    });
+    let parsed = srt::parse_lenient(&text);
+    // repairing takes the file, cloning it is measured too
+    bencher.bench("srt repair 2000 cues", || {
+        black_box(parsed.clone().repair(RepairOptions::default()));
+    });
+
+    bencher.finish();
+}
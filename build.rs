@@ -0,0 +1,7 @@
+//! the target triple, `self-update` picks the release asset built for it
+fn main() {
+    println!(
+        "cargo:rustc-env=TARGET={}",
+        std::env::var("TARGET").expect("cargo sets it")
+    );
+}
@@ -0,0 +1,132 @@
+//! the XML-RPC api of opensubtitles.org, for what needs an account: uploads, votes and reports
+use crate::{
+    http::{HttpFetch, RateLimited, Request},
+    xmlrpc::{self, Value},
+};
+use eyre::{bail, Result, WrapErr};
+use reqwest::Url;
+
+pub static API_URL: &str = "https://api.opensubtitles.org/xml-rpc";
+
+/// an opensubtitles.org account, the api serves nobody anonymous
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// a call answered with anything but `200 OK`, found in the report's chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusError {
+    pub method: String,
+    /// `401 Unauthorized` for a wrong password
+    pub status: String,
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self { method, status } = self;
+        match status.get(..3) {
+            Some("401") => write!(f, "{method} failed ({status}), is the password right?"),
+            Some("414") => write!(
+                f,
+                "{method} failed ({status}), the api only takes user agents registered with it, \
+                 pass one with --user-agent"
+            ),
+            _ => write!(f, "{method} failed ({status})"),
+        }
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// `method` called, the struct answered once its status is `200 OK`
+async fn call(http: &dyn HttpFetch, url: &Url, method: &str, params: &[Value]) -> Result<Value> {
+    let request =
+        Request::post(url.clone(), xmlrpc::call(method, params)).header("Content-Type", "text/xml");
+    let response = http
+        .get_text(request)
+        .await
+        .wrap_err_with(|| format!("calling {method}"))?;
+    match response.status {
+        200 => {}
+        429 => {
+            let retry_after = response.retry_after;
+            return Err(RateLimited { retry_after }.into());
+        }
+        status => bail!("calling {method} failed ({status})"),
+    }
+    let value = xmlrpc::response(&response.body).wrap_err_with(|| format!("reading {method}"))?;
+    let status = value
+        .get("status")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    match status.starts_with("200") {
+        true => Ok(value),
+        false => Err(StatusError {
+            method: method.to_string(),
+            status: status.to_string(),
+        }
+        .into()),
+    }
+}
+
+/// logged in, every call goes with the token
+#[derive(Debug)]
+pub struct Session<'a> {
+    http: &'a dyn HttpFetch,
+    url: &'a Url,
+    token: String,
+}
+
+impl<'a> Session<'a> {
+    /// `user_agent` has to be one registered with the api
+    pub async fn log_in(
+        http: &'a dyn HttpFetch,
+        url: &'a Url,
+        credentials: &Credentials,
+        user_agent: &str,
+    ) -> Result<Self> {
+        let logged_in = call(
+            http,
+            url,
+            "LogIn",
+            &[
+                Value::string(&credentials.username),
+                Value::string(&credentials.password),
+                Value::string("en"),
+                Value::string(user_agent),
+            ],
+        )
+        .await?;
+        let token = logged_in
+            .get("token")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+        Ok(Self { http, url, token })
+    }
+
+    /// `method` with the token ahead of `params`
+    pub async fn call(&self, method: &str, params: Vec<Value>) -> Result<Value> {
+        let params = std::iter::once(Value::string(&self.token))
+            .chain(params)
+            .collect::<Vec<_>>();
+        call(self.http, self.url, method, &params).await
+    }
+
+    /// the session expires on its own, a failed logout changes nothing
+    pub async fn log_out(self) {
+        if let Err(report) = self.call("LogOut", vec![]).await {
+            tracing::debug!(?report, "logging out failed");
+        }
+    }
+}
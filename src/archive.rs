@@ -0,0 +1,931 @@
+//! downloaded subtitle archives, zip natively and rar through the `unrar`/`bsdtar` binaries
+//! with the `rar` feature
+use crate::{
+    release,
+    subtitle::{FormatPreference, SubtitleFormat},
+};
+use encoding_rs::Encoding;
+use eyre::{bail, eyre, Report, Result, WrapErr};
+use itertools::Itertools;
+use ordered_float::OrderedFloat;
+use std::{
+    cmp::Reverse,
+    io::{Cursor, Read, Write},
+    path::{Path, PathBuf},
+};
+use tap::prelude::*;
+use tracing::{debug, warn};
+use zip::result::ZipError;
+
+#[cfg(feature = "rar")]
+mod rar;
+#[cfg(feature = "rar")]
+pub use rar::RarArchive;
+
+const ZIP_MAGIC: &[u8] = b"PK";
+const RAR_MAGIC: &[u8] = b"Rar!\x1a\x07";
+
+/// whether the bytes look like an archive we know how to open
+pub fn is_archive(bytes: &[u8]) -> bool {
+    bytes.starts_with(ZIP_MAGIC) || bytes.starts_with(RAR_MAGIC)
+}
+
+pub trait ArchiveReader {
+    /// decoded names of all the entries, in archive order
+    fn file_names(&self) -> Vec<String>;
+    /// decompresses the entry into `out` and returns its size, entries are looked up by
+    /// position as decoded names don't always round-trip
+    fn copy_to(&mut self, entry: &Entry, out: &mut dyn Write) -> Result<u64>;
+
+    fn read(&mut self, entry: &Entry) -> Result<Vec<u8>> {
+        let started = std::time::Instant::now();
+        let mut buf = Vec::new();
+        let size = self.copy_to(entry, &mut buf)?;
+        debug!(%entry, size, elapsed = ?started.elapsed(), "extracted archive entry");
+        Ok(buf)
+    }
+
+    /// whether entries can only be read after `set_password`
+    fn is_encrypted(&self) -> bool {
+        false
+    }
+
+    fn set_password(&mut self, _password: &str) {}
+
+    /// symbolic links, their contents are where they point and never subtitles
+    fn is_link(&self, _index: usize) -> bool {
+        false
+    }
+
+    /// decompresses everything so a damaged download is caught before the quota gets spent
+    /// on choosing from it
+    fn verify(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// entries with a name that is safe to use on the filesystem, the rest is dropped
+    fn entries(&self) -> Vec<Entry> {
+        self.file_names()
+            .into_iter()
+            .enumerate()
+            // directories
+            .filter(|(_, name)| !name.ends_with(['/', '\\']))
+            .filter(|(index, name)| {
+                let link = self.is_link(*index);
+                if link {
+                    warn!(%name, "skipping symlink archive entry");
+                }
+                !link
+            })
+            .filter_map(|(index, name)| {
+                Entry::new(index, &name).tap_none(|| warn!(%name, "skipping unsafe archive entry"))
+            })
+            .collect()
+    }
+}
+
+/// archive failures worth telling apart from the rest, found in the report's chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveError {
+    PasswordRequired,
+    WrongPassword,
+    Corrupt,
+}
+
+impl ArchiveError {
+    pub fn find(report: &Report) -> Option<Self> {
+        report
+            .chain()
+            .find_map(|e| e.downcast_ref::<Self>())
+            .copied()
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::PasswordRequired | Self::WrongPassword => 3,
+            Self::Corrupt => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::PasswordRequired => "the archive is password protected",
+            Self::WrongPassword => "wrong archive password",
+            Self::Corrupt => "the archive is corrupt or truncated",
+        })
+    }
+}
+
+impl std::error::Error for ArchiveError {}
+
+/// archive entry, `name` as stored in the archive and `path` stripped of anything that could
+/// escape the output directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub index: usize,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl Entry {
+    pub fn new(index: usize, name: &str) -> Option<Self> {
+        sanitized_path(name).map(|path| Self {
+            index,
+            name: name.to_string(),
+            path,
+        })
+    }
+
+    /// final path component, archives sometimes nest the subtitles in folders
+    pub fn file_name(&self) -> &str {
+        self.path
+            .file_name()
+            .and_then(|v| v.to_str())
+            .unwrap_or(&self.name)
+    }
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.file_name())
+    }
+}
+
+/// windows refuses these as a file name, with any extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// a name any filesystem takes: characters NTFS refuses become `_`, trailing dots and
+/// spaces windows would drop are removed and device names like `CON` get a `_` in front
+pub fn sanitized_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.trim_end_matches(['.', ' ']);
+    let device = name.split('.').next().unwrap_or_default().trim_end();
+    match RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(device))
+    {
+        true => format!("_{name}"),
+        false => name.to_string(),
+    }
+}
+
+/// relative path made of the normal components of an entry name: drive prefixes, root,
+/// `.` and `..` are dropped, both `/` and `\` separate components and every component is
+/// [`sanitized_file_name`]
+pub fn sanitized_path(name: &str) -> Option<PathBuf> {
+    let name = name.replace('\\', "/");
+    let name = match name.as_bytes() {
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => &name[2..],
+        _ => &name[..],
+    };
+    let path = name
+        .split('/')
+        .filter(|component| !matches!(*component, "" | "." | ".."))
+        .map(sanitized_file_name)
+        .filter(|component| !component.is_empty())
+        .collect::<PathBuf>();
+    (path.components().count() > 0).then_some(path)
+}
+
+/// sizes above which downloads and archive entries are refused
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_download_size: u64,
+    pub max_entry_size: u64,
+    /// decompressed to compressed size, anything above is a suspected zip bomb
+    pub max_compression_ratio: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_download_size: 50 * 1024 * 1024,
+            max_entry_size: 20 * 1024 * 1024,
+            max_compression_ratio: 1000,
+        }
+    }
+}
+
+impl Limits {
+    fn check_entry_size(&self, file_name: &str, size: u64) -> Result<()> {
+        match size > self.max_entry_size {
+            true => bail!(
+                "{file_name} is {size} bytes, more than the limit of {} bytes (see --max-entry-mb)",
+                self.max_entry_size
+            ),
+            false => Ok(()),
+        }
+    }
+
+    fn check_compression_ratio(&self, file_name: &str, size: u64, compressed: u64) -> Result<()> {
+        match size / compressed.max(1) > self.max_compression_ratio {
+            true => bail!(
+                "{file_name} decompresses from {compressed} to {size} bytes, suspected zip bomb \
+                 (see --max-compression-ratio)"
+            ),
+            false => Ok(()),
+        }
+    }
+}
+
+/// file extension matching the archive's magic bytes
+pub fn extension(bytes: &[u8]) -> &'static str {
+    match bytes.starts_with(RAR_MAGIC) {
+        true => "rar",
+        false => "zip",
+    }
+}
+
+/// how many archives deep `open` looks for the subtitles
+const MAX_NESTING: usize = 2;
+
+/// picks the reader matching the archive's magic bytes, `legacy_encoding` decodes zip entry
+/// names not flagged as utf-8 (cp437 when not given). an archive holding nothing but another
+/// archive is skipped in favour of the inner one
+pub fn open(bytes: Vec<u8>, options: &Options) -> Result<Box<dyn ArchiveReader>> {
+    let Options {
+        limits,
+        legacy_encoding,
+        filter,
+    } = options;
+    let legacy_encoding = *legacy_encoding;
+    let mut archive = open_single(bytes, limits, legacy_encoding)?;
+    for depth in 1..MAX_NESTING {
+        let inner = match archive
+            .entries()
+            .into_iter()
+            .filter(|e| !filter.is_excluded(e))
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [single] if is_archive_name(single.file_name()) => single.clone(),
+            _ => break,
+        };
+        debug!(%inner, depth, "opening nested archive");
+        let bytes = archive.read(&inner)?;
+        archive = open_single(bytes, limits, legacy_encoding)
+            .wrap_err_with(|| format!("opening nested archive {inner}"))?;
+    }
+    Ok(archive)
+}
+
+fn is_archive_name(file_name: &str) -> bool {
+    matches!(
+        file_extension(file_name)
+            .map(|v| v.to_lowercase())
+            .as_deref(),
+        Ok("zip" | "rar")
+    )
+}
+
+/// everything needed to open a downloaded archive
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub limits: Limits,
+    /// for zip entry names not flagged as utf-8, cp437 when not given
+    pub legacy_encoding: Option<&'static Encoding>,
+    pub filter: EntryFilter,
+}
+
+/// junk commonly bundled with subtitles
+pub const DEFAULT_EXCLUDED: [&str; 8] = ["nfo", "txt", "jpg", "png", "url", "exe", "com", "diz"];
+pub const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "sub", "ass", "ssa", "vtt", "smi", "idx"];
+
+/// which archive entries are offered, by extension
+#[derive(Debug, Clone)]
+pub struct EntryFilter {
+    pub excluded: Vec<String>,
+    /// only offer extensions listed in `SUBTITLE_EXTENSIONS`
+    pub subtitles_only: bool,
+}
+
+impl Default for EntryFilter {
+    fn default() -> Self {
+        Self {
+            excluded: DEFAULT_EXCLUDED.iter().map(|v| v.to_string()).collect(),
+            subtitles_only: true,
+        }
+    }
+}
+
+impl EntryFilter {
+    fn extension(entry: &Entry) -> String {
+        file_extension(entry.file_name())
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+    }
+
+    pub fn is_excluded(&self, entry: &Entry) -> bool {
+        let extension = Self::extension(entry);
+        self.excluded.iter().any(|excluded| {
+            excluded
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(&extension)
+        })
+    }
+
+    pub fn accepts(&self, entry: &Entry) -> bool {
+        !self.is_excluded(entry)
+            && (!self.subtitles_only
+                || SUBTITLE_EXTENSIONS.contains(&Self::extension(entry).as_str()))
+    }
+}
+
+fn open_single(
+    bytes: Vec<u8>,
+    limits: &Limits,
+    legacy_encoding: Option<&'static Encoding>,
+) -> Result<Box<dyn ArchiveReader>> {
+    if bytes.starts_with(RAR_MAGIC) {
+        #[cfg(feature = "rar")]
+        return RarArchive::new(&bytes, limits.clone())
+            .map(|v| Box::new(v) as Box<dyn ArchiveReader>)
+            .wrap_err("reading rar");
+        #[cfg(not(feature = "rar"))]
+        bail!("the archive is a rar, this build was made without the `rar` feature");
+    }
+    ZipArchive::new(bytes, limits.clone(), legacy_encoding)
+        .map(|v| Box::new(v) as Box<dyn ArchiveReader>)
+        .wrap_err("reading zip")
+}
+
+/// the file type bits of a unix mode and the type of a symbolic link
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+pub struct ZipArchive {
+    inner: ::zip::ZipArchive<Cursor<Vec<u8>>>,
+    file_names: Vec<String>,
+    /// indices of the entries stored as symbolic links
+    links: Vec<usize>,
+    encrypted: bool,
+    password: Option<Vec<u8>>,
+    limits: Limits,
+}
+
+impl ZipArchive {
+    pub fn new(
+        bytes: Vec<u8>,
+        limits: Limits,
+        legacy_encoding: Option<&'static Encoding>,
+    ) -> Result<Self> {
+        let mut inner = ::zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| {
+            Report::new(ArchiveError::Corrupt).wrap_err(format!("not a valid zip archive: {e}"))
+        })?;
+        // opening an entry is enough to tell, nothing gets decompressed yet
+        let encrypted = (0..inner.len()).any(|index| {
+            matches!(
+                inner.by_index(index),
+                Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED))
+            )
+        });
+        let file_names = (0..inner.len())
+            .map(|index| {
+                inner
+                    .by_index_raw(index)
+                    .map(|entry| decode_file_name(entry.name(), entry.name_raw(), legacy_encoding))
+                    .wrap_err_with(|| format!("reading entry number [{index}]"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let links = (0..inner.len())
+            .filter(|index| {
+                inner
+                    .by_index_raw(*index)
+                    .ok()
+                    .and_then(|entry| entry.unix_mode())
+                    .is_some_and(|mode| mode & S_IFMT == S_IFLNK)
+            })
+            .collect();
+        Ok(Self {
+            inner,
+            file_names,
+            links,
+            encrypted,
+            password: None,
+            limits,
+        })
+    }
+
+    fn open_entry(&mut self, index: usize) -> Result<::zip::read::ZipFile<'_>> {
+        match &self.password {
+            Some(password) => self
+                .inner
+                .by_index_decrypt(index, password)?
+                .map_err(|_| Report::new(ArchiveError::WrongPassword)),
+            None => self.inner.by_index(index).map_err(|e| match e {
+                ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => {
+                    Report::new(ArchiveError::PasswordRequired)
+                }
+                e => e.into(),
+            }),
+        }
+    }
+}
+
+/// the zip crate decodes names as utf-8 when the entry is flagged so and as cp437 otherwise,
+/// names that aren't the utf-8 reading of the raw bytes were therefore not flagged
+fn decode_file_name(name: &str, raw: &[u8], legacy_encoding: Option<&'static Encoding>) -> String {
+    match (std::str::from_utf8(raw), legacy_encoding) {
+        (Ok(utf8), _) if utf8 == name => name.to_string(),
+        (_, Some(encoding)) => encoding.decode_without_bom_handling(raw).0.into_owned(),
+        (_, None) => name.to_string(),
+    }
+}
+
+impl ArchiveReader for ZipArchive {
+    fn file_names(&self) -> Vec<String> {
+        self.file_names.clone()
+    }
+
+    fn copy_to(&mut self, file: &Entry, out: &mut dyn Write) -> Result<u64> {
+        let file_name = &file.name;
+        let limits = self.limits.clone();
+        let entry = self
+            .open_entry(file.index)
+            .wrap_err_with(|| format!("extracting {file_name} from the archive"))?;
+        // the declared sizes come first so nothing gets decompressed needlessly
+        limits.check_entry_size(file_name, entry.size())?;
+        limits.check_compression_ratio(file_name, entry.size(), entry.compressed_size())?;
+        // and the declared size can lie
+        let size = std::io::copy(&mut entry.take(limits.max_entry_size + 1), out)
+            // checksum mismatches surface as read errors
+            .map_err(|e| {
+                Report::new(ArchiveError::Corrupt).wrap_err(format!("reading {file_name}: {e}"))
+            })?;
+        limits.check_entry_size(file_name, size)?;
+        Ok(size)
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    fn set_password(&mut self, password: &str) {
+        self.password = Some(password.as_bytes().to_vec());
+    }
+
+    fn is_link(&self, index: usize) -> bool {
+        self.links.contains(&index)
+    }
+
+    fn verify(&mut self) -> Result<()> {
+        let max_entry_size = self.limits.max_entry_size;
+        for index in 0..self.inner.len() {
+            let entry = self.open_entry(index)?;
+            let name = entry.name().to_string();
+            std::io::copy(&mut entry.take(max_entry_size + 1), &mut std::io::sink()).map_err(
+                |e| Report::new(ArchiveError::Corrupt).wrap_err(format!("reading {name}: {e}")),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// archive entry with how closely its name matches the movie file
+#[derive(Debug, Clone)]
+pub struct ScoredEntry {
+    pub entry: Entry,
+    /// the `.sub` half of a VobSub `.idx`, useless one without the other
+    pub companion: Option<Entry>,
+    pub score: f32,
+}
+
+impl ScoredEntry {
+    /// the entry followed by its companion
+    pub fn entries(&self) -> Vec<Entry> {
+        std::iter::once(self.entry.clone())
+            .chain(self.companion.clone())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ScoredEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.companion.is_some() {
+            f.write_str("VobSub (idx+sub) ")?;
+        }
+        write!(f, "{} ({:.2})", self.entry, self.score)
+    }
+}
+
+fn has_extension(entry: &Entry, extension: &str) -> bool {
+    file_extension(entry.file_name()).is_ok_and(|v| v.eq_ignore_ascii_case(extension))
+}
+
+/// `movie.sub` next to `movie.idx`
+fn vobsub_companion<'a>(idx: &Entry, entries: &'a [Entry]) -> Option<&'a Entry> {
+    has_extension(idx, "idx")
+        .then(|| {
+            entries.iter().find(|entry| {
+                has_extension(entry, "sub")
+                    && entry.path.with_extension("") == idx.path.with_extension("")
+            })
+        })
+        .flatten()
+}
+
+/// archive entries worth offering, preferred formats first and the ones named most like the
+/// movie file first within a format
+pub fn subtitle_entries(
+    archive: &dyn ArchiveReader,
+    preference: &FormatPreference,
+    filter: &EntryFilter,
+    movie_file: &Path,
+) -> Vec<ScoredEntry> {
+    let movie_name = movie_file
+        .file_stem()
+        .and_then(|v| v.to_str())
+        .unwrap_or_default();
+    let entries = archive
+        .entries()
+        .into_iter()
+        .filter(|entry| {
+            filter.accepts(entry).tap(|accepted| {
+                if !accepted {
+                    debug!(%entry, "skipping excluded archive entry")
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let companions = entries
+        .iter()
+        .filter_map(|entry| vobsub_companion(entry, &entries))
+        .collect::<Vec<_>>();
+    entries
+        .iter()
+        .filter(|entry| !companions.contains(entry))
+        .map(|entry| {
+            let entry_name = Path::new(entry.file_name())
+                .file_stem()
+                .and_then(|v| v.to_str())
+                .unwrap_or_default();
+            let score = release::file_similarity(entry_name, movie_name);
+            ScoredEntry {
+                entry: entry.clone(),
+                companion: vobsub_companion(entry, &entries).cloned(),
+                score,
+            }
+        })
+        .sorted_by_key(|v| {
+            let rank = SubtitleFormat::from_file_name(v.entry.file_name())
+                .map_or(usize::MAX, |format| preference.rank(&format));
+            (rank, Reverse(OrderedFloat(v.score)))
+        })
+        .collect()
+}
+
+pub fn file_extension(file_name: &str) -> Result<&str> {
+    Path::new(file_name)
+        .extension()
+        .and_then(|v| v.to_str())
+        .ok_or_else(|| eyre!("{file_name} has no extension"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Component;
+    use zip::write::{FileOptions, ZipWriter};
+
+    /// a zip of `entries` as they're named, plus symlinks named by the first of the pair
+    /// pointing at the second
+    fn zip(entries: &[&str], links: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for name in entries {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer
+                .write_all(b"1\r\n00:00:01,000 --> 00:00:02,000\r\nHi\r\n")
+                .unwrap();
+        }
+        for (name, target) in links {
+            writer
+                .add_symlink(*name, *target, FileOptions::default())
+                .unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// where the entries would be written to, checked to stay inside the output directory
+    fn paths(bytes: Vec<u8>) -> Vec<PathBuf> {
+        let archive = open(bytes, &Options::default()).unwrap();
+        let paths = archive
+            .entries()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect::<Vec<_>>();
+        for path in &paths {
+            assert!(
+                path.components()
+                    .all(|component| matches!(component, Component::Normal(_))),
+                "{path:?} escapes the output directory"
+            );
+        }
+        paths
+    }
+
+    #[test]
+    fn parent_directories_are_dropped() {
+        let bytes = zip(
+            &[
+                "../../.bashrc.srt",
+                "subs/../../../movie.srt",
+                "..\\..\\movie.ass",
+            ],
+            &[],
+        );
+        assert_eq!(
+            paths(bytes),
+            [
+                PathBuf::from(".bashrc.srt"),
+                PathBuf::from("subs/movie.srt"),
+                PathBuf::from("movie.ass"),
+            ]
+        );
+    }
+
+    #[test]
+    fn absolute_paths_become_relative() {
+        let bytes = zip(
+            &[
+                "/etc/cron.d/movie.srt",
+                "\\\\server\\share\\movie.srt",
+                "//movie.vtt",
+            ],
+            &[],
+        );
+        assert_eq!(
+            paths(bytes),
+            [
+                PathBuf::from("etc/cron.d/movie.srt"),
+                PathBuf::from("server/share/movie.srt"),
+                PathBuf::from("movie.vtt"),
+            ]
+        );
+    }
+
+    #[test]
+    fn drive_letters_are_dropped() {
+        let bytes = zip(
+            &["C:\\Windows\\movie.srt", "d:/movie.srt", "C:movie.ass"],
+            &[],
+        );
+        assert_eq!(
+            paths(bytes),
+            [
+                PathBuf::from("Windows/movie.srt"),
+                PathBuf::from("movie.srt"),
+                PathBuf::from("movie.ass"),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_of_nothing_but_dots_are_skipped() {
+        let bytes = zip(&["..", "../..", "./.", "movie.srt"], &[]);
+        assert_eq!(paths(bytes), [PathBuf::from("movie.srt")]);
+    }
+
+    /// the names of the fixture's entries, the ones not flagged as utf-8 read as
+    /// `legacy_encoding`
+    fn decoded_names(legacy_encoding: Option<&'static Encoding>) -> Vec<String> {
+        let bytes = include_bytes!("../tests/fixtures/legacy_names.zip").to_vec();
+        let options = Options {
+            legacy_encoding,
+            ..Default::default()
+        };
+        open(bytes, &options)
+            .unwrap()
+            .entries()
+            .into_iter()
+            .map(|entry| entry.file_name().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn decodes_names_not_flagged_as_utf8() {
+        // a polish name in windows-1250, a spanish one in cp437 and one flagged as utf-8
+        let names = decoded_names(Some(encoding_rs::WINDOWS_1250));
+        assert_eq!(names[0], "Zażółć gęślą jaźń.srt");
+        assert_eq!(names[2], "Ünïcode.srt");
+        let names = decoded_names(None);
+        assert_eq!(names[1], "Café Ñoño.srt");
+        assert_eq!(names[2], "Ünïcode.srt");
+    }
+
+    #[test]
+    fn symlinks_are_skipped() {
+        let bytes = zip(
+            &["movie.srt"],
+            &[
+                ("passwd.srt", "/etc/passwd"),
+                ("../outside.srt", "../../.ssh/id_rsa"),
+            ],
+        );
+        assert_eq!(paths(bytes), [PathBuf::from("movie.srt")]);
+    }
+
+    /// the entries `subtitle_entries` offers for `movie`, best first, srt files preferred
+    fn ranked(entries: &[&str], movie: &str) -> Vec<String> {
+        let archive = open(zip(entries, &[]), &Options::default()).unwrap();
+        let preference = FormatPreference(vec![SubtitleFormat::Srt]);
+        subtitle_entries(
+            archive.as_ref(),
+            &preference,
+            &EntryFilter::default(),
+            Path::new(movie),
+        )
+        .into_iter()
+        .map(|file| file.entry.name)
+        .collect()
+    }
+
+    #[test]
+    fn season_packs_offer_the_episode_first() {
+        let entries = [
+            "Show.S02E01.1080p.WEB.h264-GRP.srt",
+            "Show.S02E02.1080p.WEB.h264-GRP.srt",
+            "Show.S02E03.1080p.WEB.h264-GRP.srt",
+            "Show.S02E10.1080p.WEB.h264-GRP.srt",
+        ];
+        let ranked = ranked(&entries, "Show.S02E03.1080p.WEB.h264-GRP.mkv");
+        assert_eq!(ranked[0], "Show.S02E03.1080p.WEB.h264-GRP.srt");
+        assert_eq!(ranked.len(), entries.len());
+    }
+
+    #[test]
+    fn season_packs_in_folders_by_episode() {
+        let entries = [
+            "Show.S01.720p.HDTV/Show.S01E01.720p.HDTV.x264-KILLERS.srt",
+            "Show.S01.720p.HDTV/Show.S01E02.720p.HDTV.x264-KILLERS.srt",
+            "Show.S01.720p.HDTV/Show.S01E02.720p.HDTV.x264-KILLERS.nfo",
+            "Show.S01.720p.HDTV/Cover.jpg",
+        ];
+        let ranked = ranked(&entries, "show.s01e02.720p.hdtv.x264-killers.mkv");
+        assert_eq!(
+            ranked,
+            [
+                "Show.S01.720p.HDTV/Show.S01E02.720p.HDTV.x264-KILLERS.srt",
+                "Show.S01.720p.HDTV/Show.S01E01.720p.HDTV.x264-KILLERS.srt",
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_release_zips_offer_the_movies_release_first() {
+        let entries = [
+            "Movie.2019.720p.WEB-DL.DD5.1.H264-FGT.srt",
+            "Movie.2019.BDRip.XviD-AMIABLE.srt",
+            "Movie.2019.1080p.BluRay.x264-SPARKS.srt",
+            "Movie.2019.2160p.UHD.BluRay.x265-TERMINAL.srt",
+        ];
+        assert_eq!(
+            ranked(&entries, "Movie.2019.1080p.BluRay.x264-SPARKS.mkv")[0],
+            "Movie.2019.1080p.BluRay.x264-SPARKS.srt"
+        );
+        assert_eq!(
+            ranked(&entries, "Movie 2019 BDRip XviD-AMIABLE.avi")[0],
+            "Movie.2019.BDRip.XviD-AMIABLE.srt"
+        );
+    }
+
+    #[test]
+    fn preferred_formats_go_before_closer_names() {
+        let entries = [
+            "Movie.2019.1080p.BluRay.x264-SPARKS.ass",
+            "Movie.2019.1080p.BluRay.x264-SPARKS.sub",
+            "Movie.2019.720p.WEB-DL.srt",
+            "movie.srt",
+        ];
+        let ranked = ranked(&entries, "Movie.2019.1080p.BluRay.x264-SPARKS.mkv");
+        assert_eq!(&ranked[..2], ["Movie.2019.720p.WEB-DL.srt", "movie.srt"]);
+        assert_eq!(ranked.len(), entries.len());
+    }
+
+    #[test]
+    fn vobsub_pairs_are_offered_once() {
+        let entries = [
+            "Movie.2019.1080p.BluRay.x264-SPARKS.idx",
+            "Movie.2019.1080p.BluRay.x264-SPARKS.sub",
+            "Movie.2019.1080p.BluRay.x264-SPARKS.srt",
+        ];
+        assert_eq!(
+            ranked(&entries, "Movie.2019.1080p.BluRay.x264-SPARKS.mkv"),
+            [
+                "Movie.2019.1080p.BluRay.x264-SPARKS.srt",
+                "Movie.2019.1080p.BluRay.x264-SPARKS.idx",
+            ]
+        );
+    }
+
+    /// a zip of `entries` with the given contents, names ending in `/` are folders
+    fn zip_of(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        for (name, contents) in entries {
+            match name.ends_with('/') {
+                true => writer.add_directory(*name, FileOptions::default()).unwrap(),
+                false => {
+                    writer.start_file(*name, FileOptions::default()).unwrap();
+                    writer.write_all(contents).unwrap();
+                }
+            }
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn names(archive: &dyn ArchiveReader) -> Vec<String> {
+        archive
+            .entries()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect()
+    }
+
+    #[test]
+    fn opens_an_archive_nested_in_another() {
+        let inner = zip(&["Movie.2019.srt", "Movie.2019.SDH.srt"], &[]);
+        // the junk next to it doesn't count
+        let outer = zip_of(&[("Movie.2019.zip", &inner), ("opensubtitles.nfo", b"")]);
+        let mut archive = open(outer, &Options::default()).unwrap();
+        assert_eq!(
+            names(archive.as_ref()),
+            ["Movie.2019.srt", "Movie.2019.SDH.srt"]
+        );
+        let entry = archive.entries().remove(0);
+        assert!(archive
+            .read(&entry)
+            .unwrap()
+            .starts_with(b"1\r\n00:00:01,000"));
+    }
+
+    #[test]
+    fn opens_one_level_of_nesting_only() {
+        let innermost = zip(&["Movie.2019.srt"], &[]);
+        let inner = zip_of(&[("Movie.2019.Subs.zip", &innermost)]);
+        let outer = zip_of(&[("Movie.2019.zip", &inner)]);
+        let archive = open(outer, &Options::default()).unwrap();
+        assert_eq!(names(archive.as_ref()), ["Movie.2019.Subs.zip"]);
+    }
+
+    #[test]
+    fn nested_archives_are_left_alone_next_to_subtitles() {
+        let inner = zip(&["Movie.2019.SDH.srt"], &[]);
+        let outer = zip_of(&[("Movie.2019.srt", b"1"), ("Extras.zip", &inner)]);
+        let archive = open(outer, &Options::default()).unwrap();
+        assert_eq!(names(archive.as_ref()), ["Movie.2019.srt", "Extras.zip"]);
+    }
+
+    #[test]
+    fn reads_subtitles_out_of_folders() {
+        let srt = b"1\r\n00:00:01,000 --> 00:00:02,000\r\nHi\r\n";
+        let bytes = zip_of(&[
+            ("Movie.2019.1080p/", b""),
+            ("Movie.2019.1080p/Subs/", b""),
+            ("Movie.2019.1080p/Subs/Polish.srt", srt),
+            ("Movie.2019.1080p/Subs/English.srt", srt),
+            ("Movie.2019.1080p/Movie.2019.1080p.nfo", b""),
+        ]);
+        let archive = open(bytes, &Options::default()).unwrap();
+        let entries = archive.entries();
+        assert_eq!(
+            entries
+                .iter()
+                .map(|entry| (entry.file_name(), entry.path.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "Polish.srt",
+                    PathBuf::from("Movie.2019.1080p/Subs/Polish.srt")
+                ),
+                (
+                    "English.srt",
+                    PathBuf::from("Movie.2019.1080p/Subs/English.srt")
+                ),
+                (
+                    "Movie.2019.1080p.nfo",
+                    PathBuf::from("Movie.2019.1080p/Movie.2019.1080p.nfo")
+                ),
+            ]
+        );
+        let preference = FormatPreference(vec![SubtitleFormat::Srt]);
+        let offered = subtitle_entries(
+            archive.as_ref(),
+            &preference,
+            &EntryFilter::default(),
+            Path::new("Movie.2019.1080p.mkv"),
+        );
+        assert_eq!(offered.len(), 2);
+    }
+}
@@ -0,0 +1,448 @@
+//! rar archives, through the `unrar` or `bsdtar` binaries
+use super::{ArchiveError, ArchiveReader, Entry, Limits};
+use eyre::{bail, eyre, Report, Result, WrapErr};
+use std::{
+    ffi::OsString,
+    io::{Read, Write},
+    process::{Command, Stdio},
+};
+use tracing::debug;
+
+/// external programs able to list and print rar entries
+#[derive(Debug, Clone, Copy)]
+enum RarTool {
+    Unrar,
+    Bsdtar,
+}
+
+/// what `unrar` exits with when the password is missing or wrong, and on a checksum mismatch
+const UNRAR_CRC_ERROR: i32 = 3;
+const UNRAR_NO_FILES: i32 = 10;
+const UNRAR_BAD_PASSWORD: i32 = 11;
+
+impl RarTool {
+    fn find() -> Result<Self> {
+        let available = |program: &str| Command::new(program).arg("--version").output().is_ok();
+        if available("unrar") {
+            Ok(Self::Unrar)
+        } else if available("bsdtar") {
+            Ok(Self::Bsdtar)
+        } else {
+            bail!("extracting rar archives requires `unrar` or `bsdtar`, neither was found on PATH")
+        }
+    }
+
+    fn command(self) -> Command {
+        match self {
+            Self::Unrar => Command::new("unrar"),
+            Self::Bsdtar => Command::new("bsdtar"),
+        }
+    }
+
+    fn run(self, args: &[OsString]) -> Result<Vec<u8>> {
+        let output = self
+            .command()
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .wrap_err_with(|| format!("running {self:?}"))?;
+        match output.status.success() {
+            true => Ok(output.stdout),
+            false => Err(self.failure(output.status, &output.stderr)),
+        }
+    }
+
+    /// the password and checksum failures told apart, by the exit code of `unrar` and the
+    /// message of `bsdtar`
+    fn failure(self, status: std::process::ExitStatus, stderr: &[u8]) -> Report {
+        let stderr = String::from_utf8_lossy(stderr);
+        let message = format!("{self:?} failed with [{status}]: {}", stderr.trim());
+        let password =
+            stderr.to_lowercase().contains("password") || stderr.to_lowercase().contains("encrypt");
+        let error = match (self, status.code()) {
+            (Self::Unrar, Some(UNRAR_BAD_PASSWORD)) => Some(ArchiveError::WrongPassword),
+            (Self::Unrar, Some(UNRAR_CRC_ERROR)) => Some(ArchiveError::Corrupt),
+            _ if password => Some(ArchiveError::PasswordRequired),
+            _ => None,
+        };
+        match error {
+            Some(error) => Report::new(error).wrap_err(message),
+            None => eyre!(message),
+        }
+    }
+}
+
+/// an entry of `unrar lt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Listed {
+    name: String,
+    size: Option<u64>,
+    encrypted: bool,
+}
+
+/// the technical listing of `unrar lt`, one block of `Key: value` lines for every entry.
+/// directories are left out
+fn parse_technical_listing(listing: &str) -> Vec<Listed> {
+    let mut entries = vec![];
+    let mut current: Option<(Listed, bool)> = None;
+    for line in listing.lines() {
+        let Some((key, value)) = line.split_once(": ") else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Name" => {
+                entries.extend(current.take().filter(|(_, file)| *file).map(|(v, _)| v));
+                let listed = Listed {
+                    name: value.to_string(),
+                    size: None,
+                    encrypted: false,
+                };
+                current = Some((listed, true));
+            }
+            "Type" => {
+                if let Some((_, file)) = &mut current {
+                    *file = value == "File";
+                }
+            }
+            "Size" => {
+                if let Some((listed, _)) = &mut current {
+                    listed.size = value.parse().ok();
+                }
+            }
+            "Flags" => {
+                if let Some((listed, _)) = &mut current {
+                    listed.encrypted = value.split(',').any(|flag| flag.trim() == "encrypted");
+                }
+            }
+            _ => {}
+        }
+    }
+    entries.extend(current.filter(|(_, file)| *file).map(|(v, _)| v));
+    entries
+}
+
+/// whether `unrar` takes `name` for a mask matching `other`. `*` and `?` can't be escaped,
+/// `*` is taken to match across folders to err on the side of refusing
+fn mask_matches(name: &str, other: &str) -> bool {
+    fn matches(mask: &[char], name: &[char]) -> bool {
+        match (mask.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&mask[1..], name) || (!name.is_empty() && matches(mask, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&mask[1..], &name[1..]),
+            (Some(m), Some(n)) if m.eq_ignore_ascii_case(n) => matches(&mask[1..], &name[1..]),
+            _ => false,
+        }
+    }
+    let mask = name.chars().collect::<Vec<_>>();
+    let other = other.chars().collect::<Vec<_>>();
+    matches(&mask, &other)
+}
+
+/// `name` as a `bsdtar` pattern matching only itself
+fn bsdtar_pattern(name: &str) -> String {
+    name.chars()
+        .flat_map(|c| match c {
+            '*' | '?' | '[' | ']' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+pub struct RarArchive {
+    tool: RarTool,
+    limits: Limits,
+    /// the tools only work on files
+    file: tempfile::NamedTempFile,
+    file_names: Vec<String>,
+    /// as listed, `None` where the tool doesn't tell
+    sizes: Vec<Option<u64>>,
+    /// entries or the headers need a password, with encrypted headers nothing is listed
+    /// until it's given
+    encrypted: bool,
+    password: Option<String>,
+}
+
+impl RarArchive {
+    pub fn new(bytes: &[u8], limits: Limits) -> Result<Self> {
+        let tool = RarTool::find()?;
+        let mut file = tempfile::Builder::new()
+            .suffix(".rar")
+            .tempfile()
+            .wrap_err("creating a temporary file for the rar archive")?;
+        file.write_all(bytes)
+            .wrap_err("writing the rar archive to a temporary file")?;
+        let mut archive = Self {
+            tool,
+            limits,
+            file,
+            file_names: vec![],
+            sizes: vec![],
+            encrypted: false,
+            password: None,
+        };
+        match archive.list() {
+            Ok(()) => {}
+            // encrypted headers, listed again with the password
+            Err(report)
+                if ArchiveError::find(&report).is_some_and(|e| e != ArchiveError::Corrupt) =>
+            {
+                debug!(?report, "the rar archive's names are encrypted");
+                archive.encrypted = true;
+            }
+            Err(report) => return Err(report.wrap_err("listing rar entries")),
+        }
+        debug!(?tool, file_names = ?archive.file_names, "opened rar archive");
+        Ok(archive)
+    }
+
+    fn password_switch(&self) -> OsString {
+        match &self.password {
+            Some(password) => format!("-p{password}").into(),
+            // keeps unrar from waiting for a password on stdin
+            None => "-p-".into(),
+        }
+    }
+
+    fn list(&mut self) -> Result<()> {
+        let path = self.file.path().as_os_str().to_owned();
+        match self.tool {
+            RarTool::Unrar => {
+                let listing =
+                    self.tool
+                        .run(&["lt".into(), self.password_switch(), "--".into(), path])?;
+                let listed = parse_technical_listing(&String::from_utf8_lossy(&listing));
+                self.encrypted |= listed.iter().any(|entry| entry.encrypted);
+                self.sizes = listed.iter().map(|entry| entry.size).collect();
+                self.file_names = listed.into_iter().map(|entry| entry.name).collect();
+            }
+            RarTool::Bsdtar => {
+                let listing = self.tool.run(&["-tf".into(), path])?;
+                self.file_names = String::from_utf8_lossy(&listing)
+                    .lines()
+                    .map(|line| line.trim().to_string())
+                    .filter(|line| !line.is_empty() && !line.ends_with('/'))
+                    .collect();
+                self.sizes = vec![None; self.file_names.len()];
+            }
+        }
+        Ok(())
+    }
+
+    /// the arguments printing the entry on stdout, refused when `unrar` would print others too
+    fn print_args(&self, entry: &Entry) -> Result<Vec<OsString>> {
+        let path = self.file.path().as_os_str().to_owned();
+        let name = entry.name.as_str();
+        match self.tool {
+            RarTool::Unrar => {
+                let matching = self
+                    .file_names
+                    .iter()
+                    .filter(|other| {
+                        let base = other.rsplit(['/', '\\']).next().unwrap_or(other);
+                        mask_matches(name, other) || mask_matches(name, base)
+                    })
+                    .count();
+                if matching > 1 {
+                    bail!("{name} names {matching} entries of the archive, it can't be told apart");
+                }
+                Ok(vec![
+                    "p".into(),
+                    "-inul".into(),
+                    self.password_switch(),
+                    "--".into(),
+                    path,
+                    name.into(),
+                ])
+            }
+            RarTool::Bsdtar => Ok(vec!["-xOf".into(), path, bsdtar_pattern(name).into()]),
+        }
+    }
+}
+
+impl ArchiveReader for RarArchive {
+    fn file_names(&self) -> Vec<String> {
+        self.file_names.clone()
+    }
+
+    fn copy_to(&mut self, entry: &Entry, out: &mut dyn Write) -> Result<u64> {
+        let file_name = entry.name.as_str();
+        let args = self.print_args(entry)?;
+        let mut child = self
+            .tool
+            .command()
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .wrap_err_with(|| format!("running {:?}", self.tool))?;
+        let stdout = child.stdout.take().expect("piped");
+        // streamed, whatever is past the limit is never read
+        let copied = std::io::copy(&mut stdout.take(self.limits.max_entry_size + 1), out);
+        if !matches!(copied, Ok(size) if size <= self.limits.max_entry_size) {
+            child.kill().ok();
+            child.wait().ok();
+        }
+        let size = copied.wrap_err("writing the extracted entry")?;
+        self.limits.check_entry_size(file_name, size)?;
+        let output = child
+            .wait_with_output()
+            .wrap_err_with(|| format!("running {:?}", self.tool))?;
+        if !output.status.success() {
+            let no_files = output.status.code() == Some(UNRAR_NO_FILES);
+            let report = match (self.tool, no_files) {
+                (RarTool::Unrar, true) => eyre!("{file_name} isn't in the archive"),
+                _ => self.tool.failure(output.status, &output.stderr),
+            };
+            return Err(report.wrap_err(format!("extracting {file_name} from the archive")));
+        }
+        let compressed = self.file.as_file().metadata().map(|v| v.len()).unwrap_or(1);
+        self.limits
+            .check_compression_ratio(file_name, size, compressed)?;
+        // another entry printed along with it would add up to more
+        match self.sizes.get(entry.index).copied().flatten() {
+            Some(listed) if listed != size => bail!(
+                "{file_name} came out as {size} bytes, the archive lists {listed} bytes for it"
+            ),
+            _ => Ok(size),
+        }
+    }
+
+    fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    fn set_password(&mut self, password: &str) {
+        self.password = Some(password.to_string());
+        if self.file_names.is_empty() {
+            if let Err(report) = self.list() {
+                debug!(?report, "listing the rar archive with the password failed");
+            }
+        }
+    }
+
+    /// an archive with encrypted names that still lists nothing took a wrong password
+    fn verify(&mut self) -> Result<()> {
+        match self.encrypted && self.file_names.is_empty() {
+            true => Err(Report::new(match self.password {
+                Some(_) => ArchiveError::WrongPassword,
+                None => ArchiveError::PasswordRequired,
+            })),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `unrar lt` of an archive with a folder, a protected entry and a plain one
+    const LISTING: &str = "
+UNRAR 6.24 freeware      Copyright (c) 1993-2023 Alexander Roshal
+
+Archive: subtitles.rar
+Details: RAR 5
+
+        Name: Subs
+        Type: Directory
+  Attributes: drwxr-xr-x
+     Host OS: Unix
+
+        Name: Subs/Movie.2019.pol.srt
+        Type: File
+        Size: 48213
+ Packed size: 17040
+       Ratio: 35%
+       mtime: 2019-11-02 21:14:03,000000000
+  Attributes: -rw-r--r--
+       CRC32: 5AC1F2D0
+     Host OS: Unix
+ Compression: RAR 5.0(v50) -m3 -md=1M
+       Flags: encrypted
+
+        Name: Movie.2019.nfo
+        Type: File
+        Size: 312
+ Packed size: 250
+       Ratio: 80%
+       mtime: 2019-11-02 21:10:00,000000000
+  Attributes: -rw-r--r--
+       CRC32: 01020304
+     Host OS: Unix
+ Compression: RAR 5.0(v50) -m3 -md=1M
+
+";
+
+    #[test]
+    fn reads_the_technical_listing() {
+        assert_eq!(
+            parse_technical_listing(LISTING),
+            [
+                Listed {
+                    name: "Subs/Movie.2019.pol.srt".to_string(),
+                    size: Some(48213),
+                    encrypted: true,
+                },
+                Listed {
+                    name: "Movie.2019.nfo".to_string(),
+                    size: Some(312),
+                    encrypted: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn wildcards_in_names_match_other_entries() {
+        assert!(mask_matches("Movie?.srt", "Movie1.srt"));
+        assert!(mask_matches("Movie*.srt", "Movie.2019/Extra.srt"));
+        assert!(mask_matches("movie.srt", "Movie.SRT"));
+        assert!(!mask_matches("Movie?.srt", "Movie.2019.srt"));
+        assert!(!mask_matches("Movie.srt", "Movie.srt.bak"));
+    }
+
+    #[test]
+    fn bsdtar_patterns_match_only_the_name() {
+        assert_eq!(
+            bsdtar_pattern("Movie [2019] *.srt"),
+            "Movie \\[2019\\] \\*.srt"
+        );
+        assert_eq!(bsdtar_pattern("a\\b?.srt"), "a\\\\b\\?.srt");
+        assert_eq!(bsdtar_pattern("Movie.srt"), "Movie.srt");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failures_say_what_went_wrong() {
+        use std::os::unix::process::ExitStatusExt;
+        let exited = |code: i32| std::process::ExitStatus::from_raw(code << 8);
+        let failure = |tool: RarTool, code, stderr: &str| {
+            ArchiveError::find(&tool.failure(exited(code), stderr.as_bytes()))
+        };
+        assert_eq!(
+            failure(RarTool::Unrar, UNRAR_BAD_PASSWORD, ""),
+            Some(ArchiveError::WrongPassword)
+        );
+        assert_eq!(
+            failure(RarTool::Unrar, UNRAR_CRC_ERROR, ""),
+            Some(ArchiveError::Corrupt)
+        );
+        assert_eq!(
+            failure(
+                RarTool::Unrar,
+                1,
+                "Program aborted, the password is incorrect"
+            ),
+            Some(ArchiveError::PasswordRequired)
+        );
+        assert_eq!(
+            failure(RarTool::Bsdtar, 1, "bsdtar: Encrypted file is unsupported"),
+            Some(ArchiveError::PasswordRequired)
+        );
+        assert_eq!(failure(RarTool::Bsdtar, 1, "bsdtar: Truncated input"), None);
+    }
+}
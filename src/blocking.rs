@@ -0,0 +1,110 @@
+//! [`crate::Client`] for code that isn't async, with the `blocking` feature
+//!
+//! the client owns a single threaded tokio runtime and waits on the async calls in it. the
+//! config, the entries and the errors are the async client's. calling it from inside another
+//! tokio runtime panics, async code should use [`crate::Client`] instead
+//!
+//! ```no_run
+//! use opensubtitlescli::{archive, blocking, hash_for_file, Ranking};
+//!
+//! fn main() -> eyre::Result<()> {
+//!     let client = blocking::Client::builder().build_blocking()?;
+//!     let hash = hash_for_file("movie.mkv")?;
+//!     let candidates = client.search_by_hash("pol", &hash, &Ranking::default())?;
+//!     let Some(best) = candidates.first() else { return Ok(()) };
+//!     let archive = archive::open(client.download_entry(&best.entry)?, &Default::default())?;
+//!     println!("{:?}", archive.file_names());
+//!     Ok(())
+//! }
+//! ```
+use crate::{
+    client::ClientBuilder,
+    crawler::{Candidate, Ranking, SubsEntry},
+    feedback::Feedback,
+    timings::Timings,
+    upload::{Upload, Uploaded},
+};
+use eyre::{Result, WrapErr};
+use reqwest::Url;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+/// fetches pages and archives from opensubtitles.org, every call waits for its answer
+#[derive(Debug)]
+pub struct Client {
+    inner: crate::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// the async client's builder, [`ClientBuilder::build_blocking`] finishes it
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// runs `client`'s requests on a runtime of its own
+    pub fn new(client: crate::Client) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .wrap_err("starting the runtime of the blocking client")?;
+        Ok(Self {
+            inner: client,
+            runtime,
+        })
+    }
+
+    /// where searches and downloads are timed
+    pub fn timings(&self) -> &Arc<Timings> {
+        self.inner.timings()
+    }
+
+    /// see [`crate::Client::search_by_hash`]
+    pub fn search_by_hash(
+        &self,
+        language: &str,
+        hash: &str,
+        ranking: &Ranking,
+    ) -> Result<Vec<Candidate>> {
+        self.runtime
+            .block_on(self.inner.search_by_hash(language, hash, ranking))
+    }
+
+    /// see [`crate::Client::search_by_query`]
+    pub fn search_by_query(
+        &self,
+        language: &str,
+        query: &str,
+        ranking: &Ranking,
+    ) -> Result<Vec<Candidate>> {
+        self.runtime
+            .block_on(self.inner.search_by_query(language, query, ranking))
+    }
+
+    /// the archive behind `url`, still packed
+    pub fn download(&self, url: Url) -> Result<Vec<u8>> {
+        self.runtime.block_on(self.inner.download(url))
+    }
+
+    /// the archive of the entry, still packed, `archive::open` reads it
+    pub fn download_entry(&self, entry: &SubsEntry) -> Result<Vec<u8>> {
+        self.runtime.block_on(self.inner.download_entry(entry))
+    }
+
+    /// contributes `upload`, logged in with [`ClientBuilder::credentials`]
+    pub fn upload(&self, upload: &Upload) -> Result<Uploaded> {
+        self.runtime.block_on(self.inner.upload(upload))
+    }
+
+    /// a vote or a report, logged in with [`ClientBuilder::credentials`]
+    pub fn feedback(&self, feedback: &Feedback) -> Result<()> {
+        self.runtime.block_on(self.inner.feedback(feedback))
+    }
+}
+
+impl ClientBuilder {
+    /// [`ClientBuilder::build`] for the [`Client`] that blocks
+    pub fn build_blocking(self) -> Result<Client> {
+        Client::new(self.build()?)
+    }
+}
@@ -0,0 +1,181 @@
+//! subtitle text encodings, uploads in legacy codepages are converted to utf-8
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{
+    DecoderResult, Encoding, IBM866, ISO_8859_13, ISO_8859_2, ISO_8859_7, ISO_8859_8, KOI8_R,
+    KOI8_U, UTF_8, WINDOWS_1250, WINDOWS_1251, WINDOWS_1253, WINDOWS_1254, WINDOWS_1255,
+    WINDOWS_1256, WINDOWS_1257,
+};
+use eyre::{bail, Result};
+use tracing::{info, trace};
+
+pub const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// how the source encoding of a subtitle is decided
+#[derive(Debug, Clone)]
+pub struct Transcode {
+    /// `--encoding`, when detection guesses wrong
+    pub source: Option<&'static Encoding>,
+    /// opensubtitles language code, narrows down the guess
+    pub language: String,
+}
+
+/// top level domain chardetng associates with the legacy encodings used for a language
+fn tld_hint(language: &str) -> Option<&'static str> {
+    Some(match language {
+        "pol" => "pl",
+        "cze" | "ces" => "cz",
+        "slo" | "slk" => "sk",
+        "hun" => "hu",
+        "rum" | "ron" => "ro",
+        "hrv" => "hr",
+        "scc" | "srp" => "rs",
+        "slv" => "si",
+        "bos" => "ba",
+        "rus" => "ru",
+        "ukr" => "ua",
+        "bul" => "bg",
+        "mac" | "mkd" => "mk",
+        "bel" => "by",
+        "gre" | "ell" => "gr",
+        "tur" => "tr",
+        "heb" => "il",
+        "ara" => "eg",
+        "per" | "fas" => "ir",
+        "est" => "ee",
+        "lav" => "lv",
+        "lit" => "lt",
+        "vie" => "vn",
+        "tha" => "th",
+        "chi" | "zho" => "cn",
+        "zht" => "tw",
+        "jpn" => "jp",
+        "kor" => "kr",
+        _ => return None,
+    })
+}
+
+/// encodings subtitles in the language usually come in, most likely first, and the letters
+/// a correct decoding is full of
+fn candidates(language: &str) -> Option<(&'static [&'static Encoding], &'static str)> {
+    static CENTRAL_EUROPEAN: [&Encoding; 3] = [UTF_8, WINDOWS_1250, ISO_8859_2];
+    static RUSSIAN: [&Encoding; 4] = [UTF_8, WINDOWS_1251, KOI8_R, IBM866];
+    static UKRAINIAN: [&Encoding; 3] = [UTF_8, WINDOWS_1251, KOI8_U];
+    static CYRILLIC_ENCODINGS: [&Encoding; 2] = [UTF_8, WINDOWS_1251];
+    static GREEK: [&Encoding; 3] = [UTF_8, WINDOWS_1253, ISO_8859_7];
+    static TURKISH: [&Encoding; 2] = [UTF_8, WINDOWS_1254];
+    static HEBREW: [&Encoding; 3] = [UTF_8, WINDOWS_1255, ISO_8859_8];
+    static ARABIC: [&Encoding; 2] = [UTF_8, WINDOWS_1256];
+    static BALTIC: [&Encoding; 3] = [UTF_8, WINDOWS_1257, ISO_8859_13];
+    const CYRILLIC: &str = "абвгдеёжзийклмнопрстуфхцчшщъыьэюя";
+    Some(match language {
+        "pol" => (&CENTRAL_EUROPEAN, "ąćęłńóśźżĄĆĘŁŃÓŚŹŻ"),
+        "cze" | "ces" => (&CENTRAL_EUROPEAN, "áčďéěíňóřšťúůýžÁČĎÉĚÍŇÓŘŠŤÚŮÝŽ"),
+        "slo" | "slk" => (&CENTRAL_EUROPEAN, "áäčďéíĺľňóôŕšťúýžÁÄČĎÉÍĹĽŇÓÔŔŠŤÚÝŽ"),
+        "hun" => (&CENTRAL_EUROPEAN, "áéíóöőúüűÁÉÍÓÖŐÚÜŰ"),
+        "hrv" | "scc" | "slv" | "bos" => (&CENTRAL_EUROPEAN, "čćđšžČĆĐŠŽ"),
+        "rum" | "ron" => (&CENTRAL_EUROPEAN, "ăâîșțşţĂÂÎȘȚŞŢ"),
+        "rus" => (&RUSSIAN, CYRILLIC),
+        "ukr" => (&UKRAINIAN, "абвгґдеєжзиіїйклмнопрстуфхцчшщьюя"),
+        "bul" | "mac" | "mkd" | "bel" | "srp" => (&CYRILLIC_ENCODINGS, CYRILLIC),
+        "gre" | "ell" => (&GREEK, "αβγδεζηθικλμνξοπρστυφχψωάέήίόύώςϊϋ"),
+        "tur" => (&TURKISH, "çğıöşüÇĞİÖŞÜ"),
+        "heb" => (&HEBREW, "אבגדהוזחטיכךלמםנןסעפףצץקרשת"),
+        "ara" | "per" | "fas" => (&ARABIC, "ابتثجحخدذرزسشصضطظعغفقكلمنهويپچژگکی"),
+        "lit" => (&BALTIC, "ąčęėįšųūžĄČĘĖĮŠŲŪŽ"),
+        "lav" => (&BALTIC, "āčēģīķļņšūžĀČĒĢĪĶĻŅŠŪŽ"),
+        "est" => (&BALTIC, "äöõüšžÄÖÕÜŠŽ"),
+        _ => return None,
+    })
+}
+
+/// how much the non-ascii part of the text looks like the language: its letters count the
+/// most, other lowercase letters a bit, uppercase ones nothing (a wrong cyrillic codepage
+/// swaps the case) and symbols or control characters against
+fn plausibility(text: &str, letters: &str) -> i64 {
+    text.chars()
+        .filter(|c| !c.is_ascii())
+        .map(|c| match c {
+            c if letters.contains(c) => 2,
+            '\u{80}'..='\u{9f}' => -10,
+            c if c.is_alphabetic() && c.is_uppercase() => 0,
+            c if c.is_alphabetic() => 1,
+            _ => -1,
+        })
+        .sum()
+}
+
+/// binary formats (VobSub `.sub`) are left alone, text never contains NUL
+pub fn is_text(contents: &[u8]) -> bool {
+    !contents.contains(&0)
+}
+
+impl Transcode {
+    /// the byte order mark wins, then `--encoding`, then the language's usual encodings and
+    /// the detector for languages we know nothing about
+    pub fn source_encoding(&self, contents: &[u8]) -> &'static Encoding {
+        if let Some((encoding, _)) = Encoding::for_bom(contents) {
+            return encoding;
+        }
+        let scored = || {
+            let (candidates, letters) = candidates(&self.language)?;
+            candidates
+                .iter()
+                // ties go to the more likely encoding
+                .rev()
+                .filter_map(|encoding| {
+                    encoding
+                        .decode_without_bom_handling_and_without_replacement(contents)
+                        .map(|text| (*encoding, plausibility(&text, letters)))
+                })
+                .inspect(|(encoding, score)| {
+                    trace!(encoding = encoding.name(), score, "encoding candidate")
+                })
+                .max_by_key(|(_, score)| *score)
+                .map(|(encoding, _)| encoding)
+        };
+        self.source.or_else(scored).unwrap_or_else(|| {
+            let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+            detector.feed(contents, true);
+            let tld = tld_hint(&self.language);
+            detector.guess(tld.map(str::as_bytes), Utf8Detection::Allow)
+        })
+    }
+
+    /// contents as utf-8, failing rather than replacing bytes the source encoding can't map.
+    /// a byte order mark stays one, `--bom` decides what happens to it later
+    pub fn to_utf8(&self, contents: &[u8]) -> Result<Vec<u8>> {
+        let encoding = self.source_encoding(contents);
+        let (bom, contents) = match Encoding::for_bom(contents) {
+            Some((_, bom_length)) => (UTF8_BOM, &contents[bom_length..]),
+            None => (&[][..], contents),
+        };
+        match encoding.decode_without_bom_handling_and_without_replacement(contents) {
+            Some(text) => {
+                info!("decoded as {}", encoding.name());
+                Ok([bom, text.as_bytes()].concat())
+            }
+            None => {
+                bail!(
+                    "subtitles aren't valid {} (unmappable byte at offset {}), pass --encoding to \
+                     choose another one or --keep-encoding",
+                    encoding.name(),
+                    malformed_offset(encoding, contents)
+                )
+            }
+        }
+    }
+}
+
+/// offset of the first byte sequence the encoding can't map
+fn malformed_offset(encoding: &'static Encoding, contents: &[u8]) -> usize {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut output = String::with_capacity(
+        decoder
+            .max_utf8_buffer_length_without_replacement(contents.len())
+            .unwrap_or(contents.len() * 3),
+    );
+    match decoder.decode_to_string_without_replacement(contents, &mut output, true) {
+        (DecoderResult::Malformed(bad, _), read) => read - bad as usize,
+        (_, read) => read,
+    }
+}
@@ -0,0 +1,123 @@
+//! cheap signs a downloaded subtitle belongs to another movie or another cut of it
+use crate::{
+    langid,
+    markup::{self, Token},
+    release,
+    srt::{Srt, Timestamp},
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// the last cue may end this far after the end of the movie, whichever is more. ending
+/// early is normal, nobody talks over the credits
+const DURATION_TOLERANCE_MS: i64 = 3 * 60 * 1000;
+const DURATION_TOLERANCE_RATIO: f64 = 0.03;
+/// dialogue starting later than this share of the movie means the timing is off
+const LATEST_FIRST_CUE_RATIO: f64 = 0.25;
+
+/// the timing of the subtitles against a movie this long, in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DurationCheck {
+    pub movie_duration_ms: i64,
+    pub first_cue_start_ms: i64,
+    pub last_cue_end_ms: i64,
+    /// how far past the end of the movie the last cue may end
+    pub tolerance_ms: i64,
+    /// how late the first cue may start
+    pub latest_first_cue_ms: i64,
+}
+
+/// the numbers `duration_mismatch` goes by, nothing for subtitles without cues
+pub fn duration_check(srt: &Srt, duration: Timestamp) -> Option<DurationCheck> {
+    let first = srt.cues.first()?;
+    let last_end = srt.cues.iter().map(|cue| cue.end).max()?;
+    Some(DurationCheck {
+        movie_duration_ms: duration.0,
+        first_cue_start_ms: first.start.0,
+        last_cue_end_ms: last_end.0,
+        tolerance_ms: DURATION_TOLERANCE_MS
+            .max((duration.0 as f64 * DURATION_TOLERANCE_RATIO) as i64),
+        latest_first_cue_ms: (duration.0 as f64 * LATEST_FIRST_CUE_RATIO) as i64,
+    })
+}
+
+impl DurationCheck {
+    /// what's wrong with the timing, if anything
+    pub fn mismatch(&self) -> Option<String> {
+        let duration = Timestamp(self.movie_duration_ms);
+        if self.last_cue_end_ms - self.movie_duration_ms > self.tolerance_ms {
+            return Some(format!(
+                "the last cue ends at {} but the movie is {duration} long",
+                Timestamp(self.last_cue_end_ms)
+            ));
+        }
+        (self.first_cue_start_ms > self.latest_first_cue_ms).then(|| {
+            format!(
+                "the first cue starts at {}, over a quarter into the {duration} movie",
+                Timestamp(self.first_cue_start_ms)
+            )
+        })
+    }
+}
+
+/// what's wrong with the timing of the subtitles for a movie this long
+pub fn duration_mismatch(srt: &Srt, duration: Timestamp) -> Option<String> {
+    duration_check(srt, duration)?.mismatch()
+}
+
+/// `--verify-language`, `--verify-episode`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CheckMode {
+    Off,
+    /// warn and keep the subtitles
+    #[default]
+    Warn,
+    /// reject the subtitles and pick others
+    Strict,
+}
+
+/// the cues read like another language than `language`, nothing when the guess isn't sure or
+/// the language can't be guessed
+pub fn language_mismatch(srt: &Srt, language: &str) -> Option<String> {
+    let requested = language
+        .split(',')
+        .filter_map(langid::family)
+        .collect::<Vec<_>>();
+    if requested.is_empty() {
+        return None;
+    }
+    let text = srt
+        .cues
+        .iter()
+        .flat_map(|cue| &cue.lines)
+        .flat_map(|line| markup::tokens(line))
+        .filter_map(|token| match token {
+            Token::Text(text) => Some(text),
+            Token::Open(..) | Token::Close(..) => None,
+        })
+        .join(" ");
+    let detected = langid::detect(&text)?;
+    (!requested.contains(&detected))
+        .then(|| format!("the subtitles look like {detected}, not {language}"))
+}
+
+/// `S02E05` in the names when the movie file is `S02E06`, names without an episode can't
+/// tell and any of them naming the right one is enough
+pub fn episode_mismatch<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    (season, episode): (u32, u32),
+) -> Option<String> {
+    let named = names
+        .into_iter()
+        .filter_map(release::episode)
+        .unique()
+        .collect::<Vec<_>>();
+    let wrong = !named.is_empty() && !named.contains(&(season, episode));
+    wrong.then(|| {
+        let named = named
+            .iter()
+            .map(|(season, episode)| format!("S{season:02}E{episode:02}"))
+            .join(", ");
+        format!("the subtitles are for {named}, not S{season:02}E{episode:02}")
+    })
+}
@@ -0,0 +1,148 @@
+//! `clean`, subtitle files already on disk run through the same cleaning as downloads
+use crate::{diff, output, subtitle::SubtitleFormat};
+use eyre::{bail, Result, WrapErr};
+use itertools::Itertools;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// subtitle files among `paths`, directories are searched one level deep
+fn subtitle_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let is_subtitle = |path: &Path| {
+        matches!(
+            SubtitleFormat::from_path(path),
+            Some(
+                SubtitleFormat::Srt
+                    | SubtitleFormat::Sub
+                    | SubtitleFormat::Ass
+                    | SubtitleFormat::Ssa
+                    | SubtitleFormat::Vtt
+            )
+        )
+    };
+    let mut files = vec![];
+    for path in paths {
+        match path.is_dir() {
+            true => files.extend(
+                fs::read_dir(path)
+                    .wrap_err_with(|| format!("listing subtitles in {path:?}"))?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.is_file() && is_subtitle(path))
+                    .sorted(),
+            ),
+            false => files.push(path.clone()),
+        }
+    }
+    Ok(files)
+}
+
+/// where `clean` puts its results
+pub enum Target {
+    DryRun,
+    /// in place unless `output` is given, overwritten files are kept as `<name>.bak`
+    Write {
+        output: Option<PathBuf>,
+        backup: bool,
+    },
+}
+
+/// unchanged lines around the changes `clean --dry-run` shows
+const DIFF_CONTEXT: usize = 2;
+
+/// `clean`, every subtitle file among `paths`
+pub async fn clean(
+    paths: &[PathBuf],
+    target: Target,
+    writer: &output::SubtitleWriter,
+) -> Result<()> {
+    let files = subtitle_files(paths)?;
+    if files.is_empty() {
+        bail!("no subtitle files in {paths:?}");
+    }
+    if let Target::Write {
+        output: Some(output),
+        ..
+    } = &target
+    {
+        if !output.is_dir() && files.len() > 1 {
+            bail!("{output:?} is not a directory, it can only take one cleaned file");
+        }
+    }
+    for file in files {
+        let contents = fs::read(&file).wrap_err_with(|| format!("reading {file:?}"))?;
+        let format = SubtitleFormat::from_path(&file);
+        if matches!(format, Some(SubtitleFormat::Ass | SubtitleFormat::Ssa))
+            && writer.convert_to.is_none()
+        {
+            info!(
+                ?file,
+                "only line endings and the byte order mark of ASS subtitles are cleaned, \
+                 --convert-to cleans the cues"
+            );
+        }
+        let processed = writer.process(&file, &contents)?;
+        match &target {
+            Target::DryRun => {
+                let old = match &writer.transcode {
+                    Some(transcode) => transcode.to_utf8(&contents).unwrap_or(contents),
+                    None => contents,
+                };
+                let old = String::from_utf8_lossy(&old);
+                for (path, contents) in processed {
+                    let new = String::from_utf8_lossy(&contents);
+                    let lines = diff::lines(&old, &new);
+                    let count = |changed: fn(&diff::Line) -> bool| {
+                        lines.iter().filter(|line| changed(line)).count()
+                    };
+                    let removed = count(|line| matches!(line, diff::Line::Removed(_)));
+                    let added = count(|line| matches!(line, diff::Line::Added(_)));
+                    match removed == 0 && added == 0 && path == file {
+                        true => println!("{}: unchanged", output::quoted(&path)),
+                        false => {
+                            println!(
+                                "{}: {removed} lines removed, {added} added",
+                                output::quoted(&path)
+                            );
+                            diff::hunks(&lines, DIFF_CONTEXT)
+                                .iter()
+                                .for_each(|hunk| println!("{hunk}"));
+                        }
+                    }
+                }
+            }
+            Target::Write { output, backup } => {
+                if output.as_ref().is_some_and(|output| !output.is_dir()) && processed.len() > 1 {
+                    bail!(
+                        "cleaning {file:?} gives {} files, --output needs to be a directory",
+                        processed.len()
+                    );
+                }
+                for (path, contents) in processed {
+                    let path = match output {
+                        Some(output) if output.is_dir() => {
+                            output.join(path.file_name().unwrap_or_default())
+                        }
+                        Some(output) => output.clone(),
+                        None => path,
+                    };
+                    if writer.is_identical(&path, &contents).await {
+                        info!(?path, "unchanged");
+                        println!("{}", output::quoted(&path));
+                        continue;
+                    }
+                    if *backup && path.is_file() {
+                        let mut backup = path.clone().into_os_string();
+                        backup.push(".bak");
+                        fs::copy(&path, &backup)
+                            .wrap_err_with(|| format!("backing up {path:?}"))?;
+                    }
+                    output::write_atomic(&path, &contents).await?;
+                    println!("{}", output::quoted(&path));
+                }
+            }
+        }
+    }
+    Ok(())
+}
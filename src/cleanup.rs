@@ -0,0 +1,129 @@
+//! Ctrl-C during a run, whatever was half written is removed before exiting
+use crate::{messages::text, progress::Stopped};
+use eyre::Result;
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// files being written and files done, shared by every stage of a run
+#[derive(Debug, Default)]
+pub struct Cleanup {
+    token: CancellationToken,
+    partial: Mutex<Vec<PathBuf>>,
+    completed: Mutex<Vec<PathBuf>>,
+    /// the run this one is part of, which sees its files too
+    parent: Option<Arc<Cleanup>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Cleanup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// cancelled by the first Ctrl-C
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// a run inside this one, like a request to the daemon: it's cancelled with this one, and
+    /// what it writes this one is told about as well
+    pub fn child(self: &Arc<Self>) -> Arc<Self> {
+        Arc::new(Self {
+            token: self.token.child_token(),
+            parent: Some(self.clone()),
+            ..Self::default()
+        })
+    }
+
+    /// this run and the ones it's part of
+    fn lineage(&self) -> impl Iterator<Item = &Self> {
+        std::iter::successors(Some(self), |cleanup| cleanup.parent.as_deref())
+    }
+
+    /// the first Ctrl-C cancels the run, the second exits right away
+    pub fn cancel_on_ctrl_c(self: &Arc<Self>) {
+        let cleanup = self.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            eprintln!("{}", text("interrupted"));
+            cleanup.token.cancel();
+            if tokio::signal::ctrl_c().await.is_ok() {
+                std::process::exit(Stopped::Interrupted.exit_code());
+            }
+        });
+    }
+
+    /// `future`, unless the run is cancelled first. dropping it stops whatever it was doing,
+    /// programs it ran are killed
+    pub async fn cancellable<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        tokio::select! {
+            biased;
+            output = future => output,
+            () = self.token.cancelled() => Err(Stopped::Interrupted.into()),
+        }
+    }
+
+    /// `paths` are removed when the run is cancelled while `future` writes them
+    pub async fn guard<F: Future>(
+        &self,
+        paths: impl IntoIterator<Item = PathBuf>,
+        future: F,
+    ) -> F::Output {
+        let paths = paths.into_iter().collect::<Vec<_>>();
+        for cleanup in self.lineage() {
+            lock(&cleanup.partial).extend(paths.iter().cloned());
+        }
+        let output = future.await;
+        for cleanup in self.lineage() {
+            let mut partial = lock(&cleanup.partial);
+            for path in &paths {
+                if let Some(position) = partial.iter().position(|partial| partial == path) {
+                    partial.remove(position);
+                }
+            }
+        }
+        output
+    }
+
+    /// `path` was written in full
+    pub fn completed(&self, path: &Path) {
+        for cleanup in self.lineage() {
+            let mut completed = lock(&cleanup.completed);
+            if !completed.iter().any(|completed| completed == path) {
+                completed.push(path.to_owned());
+            }
+        }
+    }
+
+    /// what was written in full, oldest first
+    pub fn completed_files(&self) -> Vec<PathBuf> {
+        lock(&self.completed).clone()
+    }
+
+    /// removes the files that were being written, returns the ones that were there
+    pub fn remove_partial(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut *lock(&self.partial))
+            .into_iter()
+            .filter(|path| path.exists())
+            .filter(|path| match std::fs::remove_file(path) {
+                Ok(()) => true,
+                Err(message) => {
+                    warn!(?message, ?path, "removing the partial file failed");
+                    false
+                }
+            })
+            .collect()
+    }
+}
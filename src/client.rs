@@ -0,0 +1,512 @@
+//! the opensubtitles.org side: searching by movie hash and downloading what was found
+use crate::{
+    api::{self, Credentials},
+    archive::Limits,
+    crawler::{self, Candidate, Ranking, SubsEntry},
+    dump::HtmlDump,
+    feedback::{self, Feedback},
+    http::{HttpFetch, ReqwestFetch},
+    timings::Timings,
+    upload::{self, Upload, Uploaded},
+};
+use eyre::{Result, WrapErr};
+use reqwest::Url;
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::{sync::Mutex, time::Instant};
+
+pub static BASE_URL: &str = "https://www.opensubtitles.org";
+
+/// search results for the movie with `hash` in `lang`
+pub fn url(lang: &str, hash: String) -> Result<Url> {
+    format!("{BASE_URL}/pl/search/sublanguageid-{lang}/moviehash-{hash}")
+        .parse()
+        .wrap_err("invalid url")
+}
+
+/// search results for movies titled like `query` in `lang`
+pub fn query_url(lang: &str, query: &str) -> Result<Url> {
+    let mut url: Url = format!("{BASE_URL}/pl/search/sublanguageid-{lang}")
+        .parse()
+        .wrap_err("invalid url")?;
+    url.path_segments_mut()
+        .map_err(|_| eyre::eyre!("invalid url"))?
+        .push(&format!("moviename-{query}"));
+    Ok(url)
+}
+
+/// links on the site are relative to it
+pub fn to_url_in_base(url: &str) -> Result<Url> {
+    let url = match url.starts_with(BASE_URL) {
+        true => url.to_string(),
+        false => format!("{BASE_URL}{url}"),
+    };
+    url.parse().wrap_err_with(|| format!("invalid url: {url}"))
+}
+
+/// settings the client can't be built with, found in the report's chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    InvalidBaseUrl(String),
+    InvalidApiUrl(String),
+    InvalidProxy(String),
+    InvalidUserAgent(String),
+    ZeroTimeout,
+    /// built for uploads, votes or reports without the account they log in as
+    MissingCredentials,
+    /// proxy, user agent, timeout and `insecure` configure reqwest, a custom transport ignores
+    /// them
+    TransportOptionsWithCustomHttp,
+}
+
+impl ConfigError {
+    pub fn find(report: &eyre::Report) -> Option<&Self> {
+        report.chain().find_map(|e| e.downcast_ref::<Self>())
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidBaseUrl(url) => write!(f, "{url} can't be used as the base url"),
+            Self::InvalidApiUrl(url) => write!(f, "{url} can't be used as the api url"),
+            Self::InvalidProxy(proxy) => write!(f, "invalid proxy: {proxy}"),
+            Self::InvalidUserAgent(agent) => write!(f, "invalid user agent: {agent}"),
+            Self::ZeroTimeout => f.write_str("the request timeout can't be zero"),
+            Self::MissingCredentials => {
+                f.write_str("uploads, votes and reports need an opensubtitles.org account")
+            }
+            Self::TransportOptionsWithCustomHttp => f.write_str(
+                "proxy, user agent, timeout and insecure don't apply to a custom transport",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// everything a [`Client`] is built from, the defaults match the command line's
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// [`BASE_URL`] unless pointed at a mirror or a local test server
+    pub base_url: String,
+    /// where uploads, votes and reports go, [`api::API_URL`] unless pointed elsewhere
+    pub api_url: String,
+    /// `--dump-html`, keeps every fetched page
+    pub dump_html: Option<PathBuf>,
+    pub limits: Limits,
+    /// for the whole request, `None` waits as long as the server takes
+    pub timeout: Option<Duration>,
+    /// `http://`, `https://` or `socks5://`, every request goes through it
+    pub proxy: Option<String>,
+    /// reqwest sends none by default
+    pub user_agent: Option<String>,
+    /// the least time between two requests, spares the site when running in batches
+    pub min_interval: Duration,
+    /// `--insecure`, the site's certificate isn't verified
+    pub insecure: bool,
+    /// the account uploads, votes and reports log in as
+    pub credentials: Option<Credentials>,
+    /// the client is for an upload, a vote or a report, it isn't built without `credentials`
+    pub account_required: bool,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            base_url: BASE_URL.to_string(),
+            api_url: api::API_URL.to_string(),
+            dump_html: None,
+            limits: Limits::default(),
+            timeout: None,
+            proxy: None,
+            user_agent: None,
+            min_interval: Duration::ZERO,
+            insecure: false,
+            credentials: None,
+            account_required: false,
+        }
+    }
+}
+
+/// builds the [`Client`], checking the settings fit together
+#[derive(Debug, Default)]
+pub struct ClientBuilder {
+    config: ClientConfig,
+    http: Option<Arc<dyn HttpFetch>>,
+    timings: Arc<Timings>,
+}
+
+impl ClientBuilder {
+    pub fn new(config: ClientConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.config.base_url = base_url.into();
+        self
+    }
+
+    pub fn api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.config.api_url = api_url.into();
+        self
+    }
+
+    pub fn dump_html(mut self, dir: Option<PathBuf>) -> Self {
+        self.config.dump_html = dir;
+        self
+    }
+
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Option<String>) -> Self {
+        self.config.proxy = proxy;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: Option<String>) -> Self {
+        self.config.user_agent = user_agent;
+        self
+    }
+
+    pub fn min_interval(mut self, min_interval: Duration) -> Self {
+        self.config.min_interval = min_interval;
+        self
+    }
+
+    /// accept any certificate, for proxies that intercept tls
+    pub fn insecure(mut self, insecure: bool) -> Self {
+        self.config.insecure = insecure;
+        self
+    }
+
+    pub fn credentials(mut self, credentials: Option<Credentials>) -> Self {
+        self.config.credentials = credentials;
+        self
+    }
+
+    /// refuse to build without [`ClientBuilder::credentials`]
+    pub fn account_required(mut self, account_required: bool) -> Self {
+        self.config.account_required = account_required;
+        self
+    }
+
+    /// instead of reqwest, [`crate::http::FakeHttp`] in tests
+    pub fn http(mut self, http: Arc<dyn HttpFetch>) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// where the searches and downloads are timed, for `--timings`
+    pub fn timings(mut self, timings: Arc<Timings>) -> Self {
+        self.timings = timings;
+        self
+    }
+
+    fn reqwest(config: &ClientConfig) -> Result<ReqwestFetch> {
+        let mut builder = reqwest::Client::builder();
+        // with both features on rustls is picked, reqwest would take native-tls
+        #[cfg(feature = "rustls-tls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+        // a build without tls has no certificates to skip
+        #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+        if config.insecure {
+            tracing::warn!(
+                "certificates aren't verified (--insecure), anyone between here and the site can \
+                 read and change what's downloaded"
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy =
+                reqwest::Proxy::all(proxy).map_err(|_| ConfigError::InvalidProxy(proxy.clone()))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(user_agent) = &config.user_agent {
+            let header = reqwest::header::HeaderValue::from_str(user_agent)
+                .map_err(|_| ConfigError::InvalidUserAgent(user_agent.clone()))?;
+            builder = builder.user_agent(header);
+        }
+        builder
+            .build()
+            .map(ReqwestFetch::new)
+            .wrap_err("setting up the http client")
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let Self {
+            config,
+            http,
+            timings,
+        } = self;
+        let base_url = config
+            .base_url
+            .parse::<Url>()
+            .ok()
+            .filter(|url| !url.cannot_be_a_base())
+            .ok_or_else(|| ConfigError::InvalidBaseUrl(config.base_url.clone()))?;
+        let api_url = config
+            .api_url
+            .parse::<Url>()
+            .map_err(|_| ConfigError::InvalidApiUrl(config.api_url.clone()))?;
+        if config.timeout == Some(Duration::ZERO) {
+            return Err(ConfigError::ZeroTimeout.into());
+        }
+        if config.account_required && config.credentials.is_none() {
+            return Err(ConfigError::MissingCredentials.into());
+        }
+        let transport_options = config.timeout.is_some()
+            || config.proxy.is_some()
+            || config.user_agent.is_some()
+            || config.insecure;
+        let http = match http {
+            Some(_) if transport_options => {
+                return Err(ConfigError::TransportOptionsWithCustomHttp.into())
+            }
+            Some(http) => http,
+            None => Arc::new(Self::reqwest(&config)?),
+        };
+        Ok(Client {
+            dump: config.dump_html.as_deref().map(HtmlDump::new).transpose()?,
+            limits: config.limits,
+            http,
+            base_url,
+            api_url,
+            user_agent: config.user_agent,
+            credentials: config.credentials,
+            min_interval: config.min_interval,
+            last_request: Mutex::new(None),
+            timings,
+        })
+    }
+}
+
+/// fetches pages and archives from opensubtitles.org
+///
+/// ```no_run
+/// # async fn run() -> eyre::Result<()> {
+/// use opensubtitlescli::{crawler::Ranking, hash::hash_for_file, Client};
+///
+/// let client = Client::builder().user_agent(Some("my-daemon/1.0".into())).build()?;
+/// let hash = hash_for_file("movie.mkv")?;
+/// let ranking = Ranking::default();
+/// let candidates = client.search_by_hash("pol", &hash, &ranking).await?;
+/// if let Some(candidate) = candidates.first() {
+///     let archive = client.download_entry(&candidate.entry).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Client {
+    dump: Option<HtmlDump>,
+    limits: Limits,
+    http: Arc<dyn HttpFetch>,
+    base_url: Url,
+    api_url: Url,
+    /// sent along when logging in to the api as well
+    user_agent: Option<String>,
+    credentials: Option<Credentials>,
+    min_interval: Duration,
+    /// when the last request went out, for `min_interval`
+    last_request: Mutex<Option<Instant>>,
+    timings: Arc<Timings>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::builder().build().expect("the defaults are valid")
+    }
+}
+
+impl Client {
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// where searches and downloads are timed
+    pub fn timings(&self) -> &Arc<Timings> {
+        &self.timings
+    }
+
+    /// what the requests go through, for services reached the same way as the site
+    pub fn http(&self) -> &Arc<dyn HttpFetch> {
+        &self.http
+    }
+
+    /// waits until `min_interval` passed since the last request
+    async fn pace(&self) {
+        let mut last_request = self.last_request.lock().await;
+        if let Some(last_request) = *last_request {
+            tokio::time::sleep_until(last_request + self.min_interval).await;
+        }
+        *last_request = Some(Instant::now());
+    }
+
+    /// `url` on the site moved onto `base_url`, links in the pages always point at the site
+    fn rebased(&self, mut url: Url) -> Url {
+        if url.as_str().starts_with(BASE_URL) {
+            let path = url.path().to_string();
+            let query = url.query().map(|query| query.to_string());
+            url = self.base_url.clone();
+            url.set_path(&path);
+            url.set_query(query.as_deref());
+        }
+        url
+    }
+
+    fn rebase_entry(&self, entry: &mut SubsEntry) {
+        entry.download_url = self.rebased(entry.download_url.clone());
+        entry.detail_url = entry.detail_url.take().map(|url| self.rebased(url));
+    }
+
+    /// candidates for the movie with `hash` in `language`, ordered and filtered by `ranking`
+    pub async fn search_by_hash(
+        &self,
+        language: &str,
+        hash: &str,
+        ranking: &Ranking,
+    ) -> Result<Vec<Candidate>> {
+        self.search(url(language, hash.to_string())?, ranking).await
+    }
+
+    /// candidates for movies titled like `query`, less precise than the hash as every release
+    /// of the movie matches
+    pub async fn search_by_query(
+        &self,
+        language: &str,
+        query: &str,
+        ranking: &Ranking,
+    ) -> Result<Vec<Candidate>> {
+        self.search(query_url(language, query)?, ranking).await
+    }
+
+    async fn search(&self, url: Url, ranking: &Ranking) -> Result<Vec<Candidate>> {
+        self.pace().await;
+        let page = crawler::get_page(
+            self.http.as_ref(),
+            self.rebased(url),
+            self.dump.as_ref(),
+            "search",
+        );
+        let page = self.timings.time("search", page).await?;
+        let mut candidates = self
+            .timings
+            .time_blocking("parse", || crawler::top_rated_subs(&page.body, ranking))
+            .wrap_err_with(|| page.context("parsing search results"))?;
+        for candidate in &mut candidates {
+            self.rebase_entry(&mut candidate.entry);
+            candidate
+                .extra_parts
+                .iter_mut()
+                .for_each(|part| self.rebase_entry(part));
+        }
+        Ok(self.read_details(candidates, ranking).await)
+    }
+
+    /// the bad reports and comments of the candidates' own pages, a page that fails to load
+    /// leaves the numbers of the search results
+    async fn read_details(
+        &self,
+        mut candidates: Vec<Candidate>,
+        ranking: &Ranking,
+    ) -> Vec<Candidate> {
+        if candidates
+            .iter()
+            .all(|candidate| candidate.entry.detail_url.is_none())
+        {
+            return candidates;
+        }
+        for candidate in &mut candidates {
+            let Some(url) = candidate.entry.detail_url.clone() else {
+                continue;
+            };
+            self.pace().await;
+            match crawler::get_details(self.http.as_ref(), url, self.dump.as_ref()).await {
+                Ok(details) => details.apply(&mut candidate.entry),
+                Err(report) => tracing::warn!(
+                    ?report,
+                    subtitle_id = candidate.entry.subtitle_id,
+                    "reading the subtitle's page failed"
+                ),
+            }
+        }
+        crawler::rerank(candidates, ranking)
+    }
+
+    /// the archive behind `url`, still packed
+    pub async fn download(&self, url: Url) -> Result<Vec<u8>> {
+        self.pace().await;
+        let subject = Some(url.to_string());
+        let archive =
+            crawler::get_archive(self.http.as_ref(), url, self.dump.as_ref(), &self.limits);
+        self.timings.time_of("download", subject, archive).await
+    }
+
+    /// the archive of the entry, still packed, `archive::open` reads it
+    pub async fn download_entry(&self, entry: &SubsEntry) -> Result<Vec<u8>> {
+        self.download(entry.download_url.clone()).await
+    }
+
+    /// the api only takes user agents registered with it, without one configured
+    /// `opensubtitlescli v<version>` is sent
+    fn api_user_agent(&self) -> String {
+        self.user_agent
+            .clone()
+            .unwrap_or_else(|| format!("opensubtitlescli v{}", env!("CARGO_PKG_VERSION")))
+    }
+
+    /// what the api is logged in to with
+    fn credentials(&self) -> Result<&Credentials> {
+        Ok(self
+            .credentials
+            .as_ref()
+            .ok_or(ConfigError::MissingCredentials)?)
+    }
+
+    /// contributes `upload`, logged in with [`ClientBuilder::credentials`]
+    pub async fn upload(&self, upload: &Upload) -> Result<Uploaded> {
+        let credentials = self.credentials()?;
+        self.pace().await;
+        let subject = Some(upload.subtitle_file_name.clone());
+        let user_agent = self.api_user_agent();
+        let uploaded = upload::upload(
+            self.http.as_ref(),
+            &self.api_url,
+            credentials,
+            &user_agent,
+            upload,
+        );
+        self.timings.time_of("upload", subject, uploaded).await
+    }
+
+    /// a vote or a report, logged in with [`ClientBuilder::credentials`]
+    pub async fn feedback(&self, feedback: &Feedback) -> Result<()> {
+        let credentials = self.credentials()?;
+        self.pace().await;
+        let user_agent = self.api_user_agent();
+        feedback::send(
+            self.http.as_ref(),
+            &self.api_url,
+            credentials,
+            &user_agent,
+            feedback,
+        )
+        .await
+    }
+}
@@ -0,0 +1,97 @@
+//! `--copy-path`, through `wl-copy`, `xclip` or `xsel` on linux, `pbcopy` on macos and `clip`
+//! on windows. over ssh those reach the wrong machine's clipboard, there the terminal is asked
+//! to copy with an OSC 52 sequence
+use base64::Engine;
+use std::{
+    io::{IsTerminal, Write},
+    process::{Command, Stdio},
+};
+use tap::prelude::*;
+use tracing::debug;
+
+/// how the text got onto the clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Copied {
+    Program(&'static str),
+    /// only as good as the terminal, plenty of them ignore it
+    Osc52,
+}
+
+/// the programs that can copy here, with their arguments, in the order they're tried
+fn programs() -> Vec<(&'static str, &'static [&'static str])> {
+    let var = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+    match std::env::consts::OS {
+        "macos" => vec![("pbcopy", &[])],
+        "windows" => vec![("clip", &[])],
+        _ => [
+            (var("WAYLAND_DISPLAY"), "wl-copy", &[][..]),
+            (var("DISPLAY"), "xclip", &["-selection", "clipboard"]),
+            (var("DISPLAY"), "xsel", &["--clipboard", "--input"]),
+        ]
+        .into_iter()
+        .filter(|(display, _, _)| *display)
+        .map(|(_, program, args)| (program, args))
+        .collect(),
+    }
+}
+
+fn run(program: &str, args: &[&str], text: &str) -> std::io::Result<bool> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child
+        .stdin
+        .take()
+        .expect("piped")
+        .write_all(text.as_bytes())?;
+    Ok(child.wait()?.success())
+}
+
+fn with_program(text: &str) -> Option<Copied> {
+    programs().into_iter().find_map(|(program, args)| {
+        match run(program, args, text) {
+            Ok(true) => return Some(Copied::Program(program)),
+            Ok(false) => debug!(program, "copying failed"),
+            Err(message) => debug!(program, ?message, "can't copy"),
+        }
+        None
+    })
+}
+
+/// the escape sequence that has the terminal copy `text`, passed through tmux to the
+/// terminal it runs in
+pub fn osc52(text: &str, tmux: bool) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    match tmux {
+        true => format!("\x1bPtmux;\x1b\x1b]52;c;{encoded}\x07\x1b\\"),
+        false => format!("\x1b]52;c;{encoded}\x07"),
+    }
+}
+
+/// straight to the terminal, stdout may be piped into another program
+fn with_osc52(text: &str) -> Option<Copied> {
+    let sequence = osc52(text, std::env::var_os("TMUX").is_some());
+    let written = match std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+        Err(_) if std::io::stderr().is_terminal() => {
+            std::io::stderr().write_all(sequence.as_bytes())
+        }
+        Err(message) => Err(message),
+    };
+    written
+        .tap_err(|message| debug!(?message, "no terminal to copy with"))
+        .ok()
+        .map(|()| Copied::Osc52)
+}
+
+/// `text` onto the clipboard, `None` when nothing could take it
+pub fn copy(text: &str) -> Option<Copied> {
+    let over_ssh = std::env::var_os("SSH_TTY").is_some();
+    match over_ssh {
+        true => with_osc52(text).or_else(|| with_program(text)),
+        false => with_program(text).or_else(|| with_osc52(text)),
+    }
+}
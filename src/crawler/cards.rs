@@ -0,0 +1,112 @@
+//! the redesigned results page opensubtitles is A/B testing, one card per subtitle
+use super::*;
+
+/// `<article class="subtitle-card" data-subtitle-id="...">` elements
+pub struct CardLayout;
+
+impl ParserStrategy for CardLayout {
+    fn name(&self) -> &'static str {
+        "cards"
+    }
+
+    fn parse(&self, html: &Html) -> Option<Vec<SubsEntry>> {
+        let card_selector = Selector::parse("article.subtitle-card").ok()?;
+        let cards = html.select(&card_selector).collect::<Vec<_>>();
+        (!cards.is_empty()).then(|| {
+            cards
+                .into_iter()
+                .filter_map(|card| {
+                    from_card_element(card)
+                        .wrap_err_with(|| format!("parsing card:\n{}", card.html()))
+                        .tap_err(|message| {
+                            warn!(?message, "parsing failed");
+                        })
+                        .ok()
+                })
+                .collect()
+        })
+    }
+}
+
+fn select_first<'a>(card: ElementRef<'a>, selector: &str) -> Result<Option<ElementRef<'a>>> {
+    let selector = Selector::parse(selector).map_err(|e| eyre!("{e:?}"))?;
+    Ok(card.select(&selector).next())
+}
+
+fn select_text(card: ElementRef<'_>, selector: &str) -> Result<Option<String>> {
+    select_first(card, selector).map(|element| {
+        element
+            .map(|v| normalize_text(&v.text().join(" ")))
+            .filter(|text| !text.is_empty())
+    })
+}
+
+fn from_card_element(card: ElementRef<'_>) -> Result<SubsEntry> {
+    let release_name_selector = Selector::parse(".release-names li").map_err(|e| eyre!("{e:?}"))?;
+    let download_url = select_first(card, "a.download")?
+        .and_then(|v| v.value().attr("href"))
+        .ok_or_else(|| eyre!("no download link"))
+        .and_then(to_url_in_base)?;
+    let subtitle_id = match card.value().attr("data-subtitle-id") {
+        Some(id) => id
+            .parse()
+            .wrap_err_with(|| format!("invalid subtitle id [{id}]"))?,
+        None => subtitle_id(&download_url)?,
+    };
+    let release_names = card
+        .select(&release_name_selector)
+        .map(|v| normalize_text(&untruncated(v)))
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+    let name = select_text(card, ".title")?
+        .or_else(|| release_names.first().cloned())
+        .ok_or_else(|| eyre!("no title"))?;
+    let count = |selector: &str| -> Result<Option<u32>> {
+        select_text(card, selector)?
+            // `2 048 downloads`, the separator is the same space the words are split by
+            .map(|text| parse_count(text.split(char::is_alphabetic).next().unwrap_or_default()))
+            .transpose()
+    };
+    let decimal = |selector: &str| -> Result<Option<f32>> {
+        Ok(select_text(card, selector)?.and_then(|text| parse_decimal(&text).ok()))
+    };
+    Ok(SubsEntry {
+        subtitle_id,
+        name,
+        release_names,
+        language: card
+            .value()
+            .attr("data-language")
+            .map(LanguageCode::new)
+            .ok_or_else(|| eyre!("no language"))?,
+        cd_count: count(".cd-count")?
+            .unwrap_or(1)
+            .try_into()
+            .wrap_err("cd count out of range")?,
+        uploaded_at: select_first(card, "time")?
+            .and_then(|v| v.value().attr("datetime"))
+            .and_then(parse_date),
+        download_url,
+        downloads: count("a.download")?.unwrap_or_default(),
+        format: select_text(card, ".format")?
+            .map(|format| SubtitleFormat::from_name(&format))
+            .unwrap_or_else(|| SubtitleFormat::Other("unknown".to_string())),
+        rating: decimal(".rating")?,
+        edits: count(".comments")?.unwrap_or_default(),
+        comments: 0,
+        bad_reports: count(".bad-reports")?.unwrap_or_default(),
+        detail_url: detail_url(card),
+        imdb_rating: decimal(".imdb-rating")?,
+        uploaded_by: select_text(card, ".uploader")?.unwrap_or_default(),
+        featured: card
+            .value()
+            .classes()
+            .any(|class| FEATURED_CLASSES.contains(&class)),
+        foreign_parts_only: card
+            .value()
+            .classes()
+            .any(|class| class == "foreign-parts-only")
+            || foreign_parts_only(card),
+        fps: decimal(".fps")?.or_else(|| frame_rate(card)),
+    })
+}
@@ -0,0 +1,699 @@
+//! reading search results and download links out of opensubtitles.org pages
+use crate::{
+    archive::{self, Limits},
+    client::to_url_in_base,
+    dump::HtmlDump,
+    http::{HttpFetch, RateLimited, Request, Response, TooLarge},
+    language::LanguageCode,
+    release,
+    subtitle::{FormatPreference, SubtitleFormat},
+};
+use chrono::NaiveDate;
+use eyre::{eyre, Result, WrapErr};
+use itertools::Itertools;
+use ordered_float::OrderedFloat;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tap::prelude::*;
+use tracing::{info, instrument, warn, Span};
+
+mod cards;
+mod table;
+
+impl std::fmt::Display for SubsEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{} (rating: ", self.download_url)?;
+        match self.rating {
+            Some(rating) => write!(f, "{rating})]"),
+            None => write!(f, "none)]"),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubsEntry {
+    /// opensubtitles id, the last segment of the download url
+    pub subtitle_id: u64,
+    pub name: String,
+    /// every release this subtitle was made for, one per line of the name cell
+    pub release_names: Vec<String>,
+    pub language: LanguageCode,
+    pub cd_count: u8,
+    pub uploaded_at: Option<NaiveDate>,
+    pub download_url: Url,
+    pub downloads: u32,
+    pub format: SubtitleFormat,
+    /// `None` when nobody voted yet
+    pub rating: Option<f32>,
+    /// the comments column of the search results
+    pub edits: u32,
+    /// comments on the subtitle's own page, 0 until [`Details::apply`] read them
+    pub comments: u32,
+    /// how many users reported this subtitle as bad
+    pub bad_reports: u32,
+    /// the subtitle's own page, the name links to it
+    pub detail_url: Option<Url>,
+    pub imdb_rating: Option<f32>,
+    pub uploaded_by: String,
+    /// pinned at the top of the table by the site rather than an organic result
+    pub featured: bool,
+    /// only translates the foreign language parts of the movie
+    pub foreign_parts_only: bool,
+    /// frame rate the subtitle was timed for, when the uploader said
+    pub fps: Option<f32>,
+}
+
+/// entry as shown in the selection prompt, with the release name closest to the movie file
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub entry: SubsEntry,
+    /// further parts of a release split across separate rows (CD2, CD3...)
+    pub extra_parts: Vec<SubsEntry>,
+    pub release_name: Option<String>,
+}
+
+impl Candidate {
+    fn from_parts(mut parts: Vec<SubsEntry>) -> Option<Self> {
+        parts.sort_by_key(part_number);
+        let mut parts = parts.into_iter();
+        parts.next().map(|entry| Self {
+            entry,
+            extra_parts: parts.collect(),
+            release_name: None,
+        })
+    }
+
+    pub fn for_movie(self, movie_file: &Path) -> Self {
+        let movie_name = movie_file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let release_name =
+            release::best_match(&self.entry.release_names, &movie_name).map(|v| v.to_string());
+        Self {
+            release_name,
+            ..self
+        }
+    }
+
+    /// number of subtitle files making up the whole movie
+    pub fn part_count(&self) -> usize {
+        (self.entry.cd_count as usize).max(1 + self.extra_parts.len())
+    }
+
+    pub fn download_urls(&self) -> Vec<Url> {
+        std::iter::once(&self.entry)
+            .chain(&self.extra_parts)
+            .map(|part| part.download_url.clone())
+            .collect()
+    }
+}
+
+fn part_number(entry: &SubsEntry) -> Option<u8> {
+    entry
+        .release_names
+        .iter()
+        .find_map(|name| release::part_number(name))
+}
+
+/// rows uploaded separately for each cd share everything except the part number
+fn part_group_key(entry: &SubsEntry) -> Option<(String, String, Vec<String>)> {
+    (entry.cd_count <= 1)
+        .then(|| part_number(entry))
+        .flatten()
+        .map(|_| {
+            (
+                entry.name.clone(),
+                entry.uploaded_by.clone(),
+                entry
+                    .release_names
+                    .iter()
+                    .map(|name| release::without_part_number(name))
+                    .collect(),
+            )
+        })
+}
+
+fn group_parts(entries: Vec<SubsEntry>) -> Vec<Candidate> {
+    let mut groups: Vec<Vec<SubsEntry>> = vec![];
+    for entry in entries {
+        let key = part_group_key(&entry);
+        let group = key.as_ref().and_then(|key| {
+            groups
+                .iter()
+                .position(|group| part_group_key(&group[0]).as_ref() == Some(key))
+        });
+        match group {
+            Some(idx) => groups[idx].push(entry),
+            None => groups.push(vec![entry]),
+        }
+    }
+    groups
+        .into_iter()
+        .filter_map(Candidate::from_parts)
+        .collect()
+}
+
+impl std::fmt::Display for Candidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.release_name.as_deref().unwrap_or(&self.entry.name);
+        match self.entry.rating {
+            Some(rating) => write!(f, "[{name} (rating: {rating})]")?,
+            None => write!(f, "[{name} (unrated)]")?,
+        }
+        write!(f, " {}", self.entry.format)?;
+        if self.part_count() > 1 {
+            write!(f, " {} parts", self.part_count())?;
+        }
+        if self.entry.foreign_parts_only {
+            write!(f, " (foreign parts only)")?;
+        }
+        if self.entry.featured {
+            write!(f, " featured")?;
+        }
+        if self.entry.bad_reports > 0 {
+            write!(f, " \x1b[31m⚠ reported\x1b[0m")?;
+        }
+        Ok(())
+    }
+}
+
+/// what the one-line prompt leaves out: every release name, the best match to the movie marked
+/// with `*`, and the entry's numbers
+pub struct Summary<'a>(&'a Candidate);
+
+impl Candidate {
+    pub fn details(&self) -> Summary<'_> {
+        Summary(self)
+    }
+}
+
+impl std::fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Candidate {
+            entry,
+            release_name,
+            ..
+        } = self.0;
+        writeln!(f, "{}", entry.name)?;
+        for name in &entry.release_names {
+            match release_name.as_ref() == Some(name) {
+                true => writeln!(f, "  * {name}")?,
+                false => writeln!(f, "    {name}")?,
+            }
+        }
+        write!(
+            f,
+            "  {} {}, {} downloads",
+            entry.language.as_str(),
+            entry.format,
+            entry.downloads,
+        )?;
+        if entry.comments > 0 {
+            write!(f, ", {} comments", entry.comments)?;
+        }
+        if entry.bad_reports > 0 {
+            write!(f, ", reported as bad {} times", entry.bad_reports)?;
+        }
+        write!(f, ", uploaded by {}", entry.uploaded_by)?;
+        if let Some(uploaded_at) = entry.uploaded_at {
+            write!(f, " on {uploaded_at}")?;
+        }
+        match entry.rating {
+            Some(rating) => write!(f, ", rated {rating}"),
+            None => write!(f, ", unrated"),
+        }
+    }
+}
+
+impl SubsEntry {
+    /// ranking score, the rating penalized by the bad subtitle reports
+    pub fn score(&self) -> f32 {
+        self.rating.unwrap_or_default() - BAD_REPORT_PENALTY * self.bad_reports as f32
+    }
+}
+
+/// maps non-breaking and thin spaces to regular ones, collapses whitespace runs and trims
+pub fn normalize_text(text: &str) -> String {
+    text.split(|c: char| {
+        c.is_whitespace() || matches!(c, '\u{a0}' | '\u{2007}' | '\u{2009}' | '\u{202f}')
+    })
+    .filter(|word| !word.is_empty())
+    .join(" ")
+}
+
+/// the visible text, or the element's (or its descendant's) `title` attribute when the text was
+/// cut with an ellipsis and the title holds the complete value
+fn untruncated(element: ElementRef<'_>) -> String {
+    let text = normalize_text(&element.text().join(" "));
+    let visible = match text.strip_suffix('…').or_else(|| text.strip_suffix("...")) {
+        Some(visible) => visible.trim_end(),
+        None => return text,
+    };
+    std::iter::once(element)
+        .chain(element.descendants().filter_map(ElementRef::wrap))
+        .filter_map(|v| v.value().attr("title"))
+        .find(|title| title.trim_start().starts_with(visible))
+        .map(|title| title.trim().to_string())
+        .unwrap_or(text)
+}
+
+/// integer with optional thousands separators (`1,234`, `1 234`, `1.234`)
+fn parse_count<T: std::str::FromStr>(text: &str) -> Result<T> {
+    normalize_text(text)
+        .chars()
+        .filter(|c| !matches!(c, ',' | '.' | '\'' | ' '))
+        .collect::<String>()
+        .parse()
+        .map_err(|_| eyre!("not an int: [{text}]"))
+}
+
+/// decimal with optional thousands separators, a lone comma is treated as the decimal point
+fn parse_decimal(text: &str) -> Result<f32> {
+    let text = normalize_text(text).replace([' ', '\''], "");
+    let text = match text.contains('.') {
+        true => text.replace(',', ""),
+        false => text.replace(',', "."),
+    };
+    text.parse()
+        .wrap_err_with(|| format!("not a float: [{text}]"))
+}
+
+/// `2019-05-01T12:00:00`, `2019-05-01`, `01/05/2019` or `01.05.2019`
+fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text = normalize_text(text);
+    text.get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .or_else(|| {
+            ["%d/%m/%Y", "%d.%m.%Y"]
+                .into_iter()
+                .find_map(|format| NaiveDate::parse_from_str(&text, format).ok())
+        })
+}
+
+fn subtitle_id(download_url: &Url) -> Result<u64> {
+    download_url
+        .path_segments()
+        .and_then(|mut segments| segments.rfind(|segment| !segment.is_empty()))
+        .and_then(|segment| segment.parse().ok())
+        .ok_or_else(|| eyre!("no subtitle id in [{download_url}]"))
+}
+
+/// the site marks these with an icon titled "foreign parts only"
+fn foreign_parts_only(element: ElementRef<'_>) -> bool {
+    element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .flat_map(|v| [v.value().attr("title"), v.value().attr("alt")])
+        .flatten()
+        .any(|label| label.to_lowercase().contains("foreign parts only"))
+}
+
+/// `23.976 fps` somewhere in the text or a title like `FPS: 25.000`
+fn frame_rate(element: ElementRef<'_>) -> Option<f32> {
+    let titles = element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter_map(|v| v.value().attr("title"))
+        .map(|title| title.to_string());
+    std::iter::once(element.text().join(" "))
+        .chain(titles)
+        .find_map(|text| {
+            let text = normalize_text(&text).to_lowercase().replace("fps", " fps ");
+            let words = text
+                .split([' ', ':'])
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>();
+            let idx = words.iter().position(|word| *word == "fps")?;
+            [idx.checked_sub(1), Some(idx + 1)]
+                .into_iter()
+                .flatten()
+                .find_map(|idx| words.get(idx).and_then(|word| parse_decimal(word).ok()))
+        })
+        .filter(|fps| *fps > 0.0)
+}
+
+/// the subtitle's page, linked from the row's or the card's name
+fn detail_url(element: ElementRef<'_>) -> Option<Url> {
+    element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter_map(|v| v.value().attr("href"))
+        .find(|href| href.contains("/subtitles/"))
+        .and_then(|href| to_url_in_base(href).ok())
+}
+
+/// the site titles its icon on reported subtitles "reported as bad 3 times"
+const BAD_REPORT_MARKER: &str = "reported as bad";
+
+/// the count of a bad report marker, 0 when it gives none, `None` for other labels
+fn bad_report_count(label: &str) -> Option<u32> {
+    let label = label.to_lowercase();
+    let (_, count) = label.split_once(BAD_REPORT_MARKER)?;
+    Some(
+        count
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|digits| !digits.is_empty())
+            .and_then(|digits| digits.parse().ok())
+            .unwrap_or(0),
+    )
+}
+
+/// the bad reports of the markers in `element`, `None` without any marker
+fn bad_reports(element: ElementRef<'_>) -> Option<u32> {
+    element
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter_map(|v| v.value().attr("title"))
+        .filter_map(bad_report_count)
+        .reduce(u32::saturating_add)
+}
+
+const BAD_REPORT_PENALTY: f32 = 2.0;
+const FEATURED_CLASSES: &[&str] = &["featured", "sponsored"];
+
+/// fails with [`RateLimited`] on `429 Too Many Requests`, whatever the body says
+fn rate_limited<B>(response: Response<B>) -> Result<Response<B>> {
+    match response.status == 429 {
+        true => Err(RateLimited {
+            retry_after: response.retry_after,
+        }
+        .into()),
+        false => Ok(response),
+    }
+}
+
+/// fetched html along with where `--dump-html` saved a copy of it
+#[derive(Debug)]
+pub struct Page {
+    pub body: String,
+    pub dump_path: Option<PathBuf>,
+}
+
+impl Page {
+    /// error context pointing at the dumped copy of the page, if any
+    pub fn context(&self, action: &str) -> String {
+        match &self.dump_path {
+            Some(path) => format!("{action} (page dumped to {path:?})"),
+            None => action.to_string(),
+        }
+    }
+}
+
+/// `kind` names the dumped copy
+#[instrument(skip(http, dump), fields(url=%url, status))]
+pub async fn get_page(
+    http: &dyn HttpFetch,
+    url: Url,
+    dump: Option<&HtmlDump>,
+    kind: &str,
+) -> Result<Page> {
+    info!("fetching page");
+    let response = http.get_text(Request::get(url.clone())).await?;
+    Span::current().record("status", response.status);
+    let response = rate_limited(response)?;
+    let body = response.body;
+    let dump_path = dump
+        .map(|dump| {
+            dump.write(
+                kind,
+                &url,
+                response.status,
+                response.content_type.as_deref(),
+                body.as_bytes(),
+            )
+        })
+        .transpose()?;
+    Ok(Page { body, dump_path })
+}
+/// how candidates are filtered and ordered
+#[derive(Debug, Clone)]
+pub struct Ranking {
+    pub top_n: usize,
+    pub max_bad_reports: Option<u32>,
+    /// the site's own order, featured rows pinned on top, instead of ranking the results
+    pub featured_first: bool,
+    pub format_preference: FormatPreference,
+    /// drop formats missing from the preference list
+    pub only_preferred_formats: bool,
+    pub exclude_foreign_parts_only: bool,
+}
+
+/// what the command line defaults to
+impl Default for Ranking {
+    fn default() -> Self {
+        Self {
+            top_n: 1,
+            max_bad_reports: None,
+            featured_first: false,
+            format_preference: FormatPreference::default(),
+            only_preferred_formats: false,
+            exclude_foreign_parts_only: false,
+        }
+    }
+}
+
+impl Ranking {
+    /// the preferred formats first, as `--format-preference` reorders the candidates, then the
+    /// score, featured rows last among equals
+    fn key(&self, entry: &SubsEntry) -> (usize, OrderedFloat<f32>, bool) {
+        let score = OrderedFloat(-entry.score());
+        let format = self.format_preference.rank(&entry.format);
+        (format, score, entry.featured)
+    }
+
+    /// `items` ranked, or left in the order of the page with `featured_first`
+    fn ordered<T>(&self, items: Vec<T>, entry: impl Fn(&T) -> &SubsEntry) -> Vec<T> {
+        match self.featured_first {
+            true => items,
+            false => items
+                .into_iter()
+                .sorted_by_key(|item| self.key(entry(item)))
+                .collect(),
+        }
+    }
+
+    fn accepts(&self, entry: &SubsEntry) -> bool {
+        self.max_bad_reports
+            .is_none_or(|max| entry.bad_reports <= max)
+            && (!self.only_preferred_formats || self.format_preference.contains(&entry.format))
+            && !(self.exclude_foreign_parts_only && entry.foreign_parts_only)
+    }
+}
+
+/// one way of reading the search results out of a page layout
+pub trait ParserStrategy {
+    fn name(&self) -> &'static str;
+    /// `None` when the page is not in this layout
+    fn parse(&self, html: &Html) -> Option<Vec<SubsEntry>>;
+}
+
+/// tried in order, the stable layout goes first
+pub fn parser_strategies() -> Vec<Box<dyn ParserStrategy>> {
+    vec![Box::new(table::TableLayout), Box::new(cards::CardLayout)]
+}
+
+pub fn top_rated_subs(page: &str, ranking: &Ranking) -> Result<Vec<Candidate>> {
+    let html = Html::parse_document(page);
+    parser_strategies()
+        .into_iter()
+        .find_map(|strategy| {
+            strategy.parse(&html).tap_some(|entries| {
+                info!(
+                    strategy = strategy.name(),
+                    found = entries.len(),
+                    "parsed search results"
+                );
+            })
+        })
+        .ok_or_else(|| eyre!("no search result table"))
+        .map(|entries| {
+            entries
+                .into_iter()
+                .filter(|v| ranking.accepts(v))
+                .collect::<Vec<_>>()
+                .pipe(|entries| ranking.ordered(entries, |entry| entry))
+                .pipe(group_parts)
+                .into_iter()
+                .take(ranking.top_n)
+                .collect::<Vec<_>>()
+        })
+}
+
+/// what a subtitle's own page tells on top of its search result, `None` for what it doesn't
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Details {
+    pub bad_reports: Option<u32>,
+    pub comments: Option<u32>,
+}
+
+impl Details {
+    /// the bad report markers of the page and the `.comment`s under `#comments`
+    pub fn parse(page: &str) -> Result<Self> {
+        let html = Html::parse_document(page);
+        let comments_selector = Selector::parse("#comments").map_err(|e| eyre!("{e:?}"))?;
+        let comment_selector = Selector::parse(".comment").map_err(|e| eyre!("{e:?}"))?;
+        Ok(Self {
+            bad_reports: bad_reports(html.root_element()),
+            comments: html
+                .select(&comments_selector)
+                .next()
+                .map(|comments| comments.select(&comment_selector).count() as u32),
+        })
+    }
+
+    /// the page knows better than the search results
+    pub fn apply(&self, entry: &mut SubsEntry) {
+        if let Some(bad_reports) = self.bad_reports {
+            entry.bad_reports = bad_reports;
+        }
+        if let Some(comments) = self.comments {
+            entry.comments = comments;
+        }
+    }
+}
+
+pub async fn get_details(
+    http: &dyn HttpFetch,
+    url: Url,
+    dump: Option<&HtmlDump>,
+) -> Result<Details> {
+    let page = get_page(http, url, dump, "details").await?;
+    Details::parse(&page.body).wrap_err_with(|| page.context("parsing the subtitle's page"))
+}
+
+/// `candidates` filtered and ordered again, after their details changed the numbers
+pub fn rerank(candidates: Vec<Candidate>, ranking: &Ranking) -> Vec<Candidate> {
+    let candidates = candidates
+        .into_iter()
+        .filter(|candidate| ranking.accepts(&candidate.entry))
+        .collect();
+    ranking.ordered(candidates, |candidate| &candidate.entry)
+}
+
+pub fn sub_download_url(page: String) -> Result<Url> {
+    let html = Html::parse_document(&page);
+    let selector = Selector::parse("tr").map_err(|e| eyre!("{e:?}"))?;
+
+    html.select(&selector)
+        .next()
+        .ok_or_else(|| eyre!("no element on page"))
+        .and_then(|v| {
+            v.value()
+                .attr("href")
+                .ok_or_else(|| eyre!("no link present"))
+                .and_then(to_url_in_base)
+        })
+}
+
+/// a download that turned out to be something else, most often a page about the quota or
+/// logging in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotAnArchive {
+    pub content_type: Option<String>,
+    /// where `--dump-html` wrote the response
+    pub dumped: Option<PathBuf>,
+}
+
+impl std::fmt::Display for NotAnArchive {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("download is not an archive")?;
+        if let Some(content_type) = &self.content_type {
+            write!(f, " but {content_type}")?;
+        }
+        if let Some(path) = &self.dumped {
+            write!(f, " (response dumped to {path:?})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NotAnArchive {}
+
+/// downloads a subtitle archive, zip or rar. anything else is refused with [`NotAnArchive`]
+#[instrument(skip(http, dump, limits), fields(url=%url, status))]
+pub async fn get_archive(
+    http: &dyn HttpFetch,
+    url: Url,
+    dump: Option<&HtmlDump>,
+    limits: &Limits,
+) -> Result<Vec<u8>> {
+    let response = http
+        .get_bytes(Request::get(url.clone()), limits.max_download_size)
+        .await
+        .map_err(|report| match report.downcast_ref::<TooLarge>() {
+            Some(TooLarge(size)) => eyre!(
+                "download of {size} bytes exceeds the limit of {} bytes (see --max-download-mb)",
+                limits.max_download_size
+            ),
+            None => report,
+        })?;
+    Span::current().record("status", response.status);
+    let response = rate_limited(response)?;
+    let bytes = response.body;
+    if !archive::is_archive(&bytes) {
+        let content_type = response.content_type;
+        let dumped = dump
+            .map(|dump| {
+                dump.write(
+                    "download",
+                    &url,
+                    response.status,
+                    content_type.as_deref(),
+                    &bytes,
+                )
+            })
+            .transpose()?;
+        return Err(eyre::Report::new(NotAnArchive {
+            content_type,
+            dumped,
+        }));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_cell_whitespace() {
+        // the name cell of a results row, indentation and all
+        let cell = "\n\t\t\t\tBig Buck Bunny\u{a0}(2008)\n\t\t\t\t\n\t\t\t";
+        assert_eq!(normalize_text(cell), "Big Buck Bunny (2008)");
+        assert_eq!(normalize_text("9\u{2009}\u{202f}/\u{2007}10 "), "9 / 10");
+        assert_eq!(normalize_text(" \u{a0}\t\n"), "");
+    }
+
+    #[test]
+    fn reads_counts_with_separators() {
+        assert_eq!(parse_count::<u32>("\n\t\t1523\u{a0}\n").unwrap(), 1523);
+        assert_eq!(parse_count::<u32>("1,234,567").unwrap(), 1_234_567);
+        assert_eq!(parse_count::<u32>("12\u{a0}345").unwrap(), 12_345);
+        assert_eq!(parse_count::<u32>("1.234").unwrap(), 1_234);
+        assert_eq!(parse_count::<u32>("1'234").unwrap(), 1_234);
+        assert!(parse_count::<u32>("-").is_err());
+        assert!(parse_count::<u8>("300").is_err());
+    }
+
+    #[test]
+    fn reads_decimals_with_either_point() {
+        assert_eq!(parse_decimal("\n\t\t8.5\u{a0}\n\t").unwrap(), 8.5);
+        assert_eq!(parse_decimal("8,5").unwrap(), 8.5);
+        assert_eq!(parse_decimal("1,234.5").unwrap(), 1234.5);
+        assert_eq!(parse_decimal("1\u{202f}234,5").unwrap(), 1234.5);
+        assert!(parse_decimal("-").is_err());
+        assert!(parse_decimal("").is_err());
+    }
+
+    #[test]
+    fn reads_dates_in_every_form() {
+        let day = NaiveDate::from_ymd_opt(2020, 5, 1);
+        assert_eq!(parse_date("2020-05-01T12:30:00"), day);
+        assert_eq!(parse_date(" 2020-05-01 "), day);
+        assert_eq!(parse_date("01/05/2020"), day);
+        assert_eq!(parse_date("01.05.2020\u{a0}"), day);
+        assert_eq!(parse_date("yesterday"), None);
+    }
+}
@@ -0,0 +1,188 @@
+//! the results table layout opensubtitles has been serving for years
+use super::*;
+
+/// `<table id="search_results">`, one `<tr>` per subtitle
+pub struct TableLayout;
+
+impl ParserStrategy for TableLayout {
+    fn name(&self) -> &'static str {
+        "table"
+    }
+
+    fn parse(&self, html: &Html) -> Option<Vec<SubsEntry>> {
+        let tr_selector = Selector::parse("tr").ok()?;
+        let search_results_selector = Selector::parse("table#search_results").ok()?;
+        html.select(&search_results_selector).next().map(|table| {
+            table
+                .select(&tr_selector)
+                .skip(1)
+                .filter_map(|tr| {
+                    SubsEntry::from_table_row_element(tr)
+                        .wrap_err_with(|| format!("parsing tr:\n{}", tr.html()))
+                        .tap_err(|message| {
+                            warn!(?message, "parsing failed");
+                        })
+                        .ok()
+                })
+                .collect()
+        })
+    }
+}
+
+/// the name cell holds the title (in `<strong>`) followed by `<br>` separated release names,
+/// long ones are cut with an ellipsis and wrapped in an element carrying the full name as `title`
+fn name_and_release_names(td: ElementRef<'_>) -> Result<(String, Vec<String>)> {
+    let strong_selector = Selector::parse("strong").map_err(|e| eyre!("{e:?}"))?;
+    let release_names = td
+        .children()
+        .flat_map(|node| match ElementRef::wrap(node) {
+            Some(element) => match element.value().name() {
+                "strong" | "br" => vec![],
+                _ => element
+                    .value()
+                    .attr("title")
+                    .is_some()
+                    .then(|| untruncated(element))
+                    .into_iter()
+                    .flat_map(|names| names.split('\n').map(normalize_text).collect::<Vec<_>>())
+                    .collect(),
+            },
+            None => node
+                .value()
+                .as_text()
+                .map(|text| normalize_text(text))
+                .into_iter()
+                .collect(),
+        })
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>();
+    let name = td
+        .select(&strong_selector)
+        .next()
+        .map(|strong| untruncated(strong).pipe(|name| normalize_text(&name)))
+        .or_else(|| release_names.first().cloned())
+        .unwrap_or_else(|| cell_text(td));
+    Ok((name, release_names))
+}
+
+impl SubsEntry {
+    fn from_table_row_element(element: ElementRef<'_>) -> Result<Self> {
+        let tr_selector = Selector::parse("td").map_err(|e| eyre!("{e:?}"))?;
+        let a_selector = Selector::parse("a").map_err(|e| eyre!("{e:?}"))?;
+        let mut trs = element.select(&tr_selector);
+        let mut idx: i32 = -1;
+        let mut next = || {
+            idx += 1;
+            trs.next()
+                .ok_or_else(|| eyre!("fetching entry number [{idx}]"))
+        };
+        let (name, release_names) = next().and_then(name_and_release_names)?;
+        let language = next().map(language_code)?;
+        let cd_count = next().and_then(|v| {
+            let text = cell_text(v);
+            parse_count(text.trim_end_matches(|c: char| c.is_alphabetic()))
+                .wrap_err_with(|| format!("not a cd count: [{text}]"))
+        })?;
+        let uploaded_at = next().map(upload_date)?;
+        let (download_url, downloads, format) = next().and_then(|tr| {
+            let link = tr
+                .select(&a_selector)
+                .next()
+                .ok_or_else(|| eyre!("no a element"))?;
+            let download_url = link
+                .value()
+                .attr("href")
+                .ok_or_else(|| eyre!("no href element"))
+                .and_then(to_url_in_base)
+                .wrap_err_with(|| format!("extracting download url from [{}]", tr.html()))?;
+            // the link text reads like `1234x`, or `12 345x` with a separator
+            let downloads = parse_count(cell_text(link).trim_end_matches('x')).unwrap_or_default();
+            Ok((download_url, downloads, subtitle_format(tr)?))
+        })?;
+        Ok(Self {
+            subtitle_id: subtitle_id(&download_url)?,
+            name,
+            release_names,
+            language,
+            cd_count,
+            uploaded_at,
+            download_url,
+            downloads,
+            format,
+            rating: next().map(optional_float)?,
+            edits: next().and_then(|v| parse_count(&cell_text(v)))?,
+            comments: 0,
+            imdb_rating: next().map(optional_float)?,
+            uploaded_by: next().map(cell_text)?,
+            bad_reports: bad_reports(element).unwrap_or_default(),
+            detail_url: detail_url(element),
+            featured: element
+                .value()
+                .classes()
+                .any(|class| FEATURED_CLASSES.contains(&class)),
+            foreign_parts_only: foreign_parts_only(element),
+            fps: frame_rate(element),
+        })
+    }
+}
+
+/// the flag cell links to `.../sublanguageid-pol/...` and holds a `<div class="flag pl">`
+fn language_code(td: ElementRef<'_>) -> LanguageCode {
+    let from_link = td
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter_map(|v| v.value().attr("href"))
+        .find_map(|href| {
+            href.split('/')
+                .find_map(|segment| segment.strip_prefix("sublanguageid-"))
+        })
+        .map(|code| code.to_string());
+    let from_flag = || {
+        td.descendants()
+            .filter_map(ElementRef::wrap)
+            .find(|v| v.value().classes().any(|class| class == "flag"))
+            .and_then(|v| v.value().classes().find(|class| *class != "flag"))
+            .map(|code| code.to_string())
+    };
+    LanguageCode::new(
+        &from_link
+            .or_else(from_flag)
+            .unwrap_or_else(|| cell_text(td)),
+    )
+}
+
+/// the download cell names the format in a `<span class="p">srt</span>` under the link
+fn subtitle_format(td: ElementRef<'_>) -> Result<SubtitleFormat> {
+    let format_selector = Selector::parse("span.p").map_err(|e| eyre!("{e:?}"))?;
+    Ok(td
+        .select(&format_selector)
+        .map(cell_text)
+        .find(|text| !text.is_empty())
+        .or_else(|| {
+            cell_text(td)
+                .split(' ')
+                .find(|word| word.chars().all(|c| c.is_ascii_alphabetic()) && word.len() > 1)
+                .map(|word| word.to_string())
+        })
+        .map(|format| SubtitleFormat::from_name(&format))
+        .unwrap_or_else(|| SubtitleFormat::Other("unknown".to_string())))
+}
+
+/// the upload cell has a `<time datetime="...">` element or just the date as text
+fn upload_date(td: ElementRef<'_>) -> Option<NaiveDate> {
+    td.descendants()
+        .filter_map(ElementRef::wrap)
+        .find_map(|v| v.value().attr("datetime"))
+        .and_then(parse_date)
+        .or_else(|| parse_date(&cell_text(td)))
+}
+
+/// empty and placeholder cells (`-`) mean there is no value
+fn optional_float(td: ElementRef<'_>) -> Option<f32> {
+    parse_decimal(&cell_text(td)).ok()
+}
+
+/// normalized text of all the text nodes in the element
+fn cell_text(element: ElementRef<'_>) -> String {
+    normalize_text(&element.text().join(" "))
+}
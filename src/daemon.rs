@@ -0,0 +1,331 @@
+//! `daemon` and `send`: json requests over a local socket, one per line, each answered with
+//! a line of its own. a unix socket where there are any, localhost tcp elsewhere
+use crate::{
+    cleanup::Cleanup,
+    hash,
+    notify::{self, Notification},
+    progress,
+    timings::Timings,
+    unattended::{Ahead, Outcome, Unattended},
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use futures::{stream::FuturesUnordered, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{future::Future, path::PathBuf, sync::Arc};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::Semaphore,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// `{"action":"download","path":"/movies/Movie.mkv","language":"pol"}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Request {
+    /// subtitles for the movie at `path`, in the daemon's language unless given
+    Download {
+        path: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+}
+
+/// `{"ok":true,"written":[...]}` or `{"ok":false,"error":"..."}`
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    /// the files the request wrote
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub written: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    pub fn written(written: Vec<PathBuf>) -> Self {
+        Self {
+            ok: true,
+            written,
+            error: None,
+        }
+    }
+
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            written: vec![],
+            error: Some(error.into()),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub type Stream = tokio::net::UnixStream;
+#[cfg(not(unix))]
+pub type Stream = tokio::net::TcpStream;
+
+/// `$XDG_RUNTIME_DIR/opensubtitlescli.sock`, a socket of this user's in the temporary
+/// directory without it
+#[cfg(unix)]
+pub fn default_address() -> String {
+    let path = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("opensubtitlescli.sock"),
+        None => {
+            // getuid always succeeds
+            let uid = unsafe { libc::getuid() };
+            std::env::temp_dir().join(format!("opensubtitlescli-{uid}.sock"))
+        }
+    };
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(not(unix))]
+pub fn default_address() -> String {
+    "127.0.0.1:47110".to_string()
+}
+
+/// where the daemon takes requests, a unix socket is removed once it's dropped
+#[derive(Debug)]
+pub struct Listener {
+    #[cfg(unix)]
+    inner: tokio::net::UnixListener,
+    #[cfg(unix)]
+    path: PathBuf,
+    #[cfg(not(unix))]
+    inner: tokio::net::TcpListener,
+}
+
+impl Listener {
+    /// a socket left behind by a daemon that didn't stop cleanly is replaced, one another
+    /// daemon listens on isn't
+    #[cfg(unix)]
+    pub async fn bind(address: &str) -> Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+        let path = PathBuf::from(address);
+        if path.exists() {
+            if Stream::connect(&path).await.is_ok() {
+                bail!("another daemon listens on {path:?}");
+            }
+            std::fs::remove_file(&path).wrap_err_with(|| format!("removing the stale {path:?}"))?;
+        }
+        let inner = tokio::net::UnixListener::bind(&path)
+            .wrap_err_with(|| format!("listening on {path:?}"))?;
+        // whoever can connect can make the daemon write next to any movie it can reach
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .wrap_err_with(|| format!("making {path:?} private"))?;
+        Ok(Self { inner, path })
+    }
+
+    #[cfg(not(unix))]
+    pub async fn bind(address: &str) -> Result<Self> {
+        let inner = tokio::net::TcpListener::bind(address)
+            .await
+            .wrap_err_with(|| format!("listening on {address}"))?;
+        match inner.local_addr()?.ip().is_loopback() {
+            true => Ok(Self { inner }),
+            false => bail!(
+                "{address} is reachable from other machines, the daemon only listens on localhost"
+            ),
+        }
+    }
+
+    pub async fn accept(&self) -> Result<Stream> {
+        let (stream, _) = self
+            .inner
+            .accept()
+            .await
+            .wrap_err("accepting a connection")?;
+        Ok(stream)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+pub async fn connect(address: &str) -> Result<Stream> {
+    Stream::connect(address)
+        .await
+        .wrap_err_with(|| format!("connecting to the daemon on {address}, is it running?"))
+}
+
+/// `message` as a line of json
+pub async fn write_message(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &impl Serialize,
+) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    writer.flush().await.wrap_err("sending a message")
+}
+
+/// the next line parsed, `None` once the other side hung up
+pub async fn read_message<T: DeserializeOwned>(
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> Result<Option<T>> {
+    let mut line = String::new();
+    match reader.read_line(&mut line).await? {
+        0 => Ok(None),
+        _ => serde_json::from_str(&line)
+            .wrap_err_with(|| format!("invalid message: {}", line.trim_end()))
+            .map(Some),
+    }
+}
+
+/// `request` sent to the daemon on `address`, and its answer
+pub async fn send(address: &str, request: &Request) -> Result<Response> {
+    let (reader, mut writer) = tokio::io::split(connect(address).await?);
+    write_message(&mut writer, request).await?;
+    read_message(&mut BufReader::new(reader))
+        .await?
+        .ok_or_else(|| eyre!("the daemon hung up without answering"))
+}
+
+/// resolves on SIGTERM, never where there are no unix signals
+pub async fn terminated() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            terminate.recv().await;
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+/// what every request to the daemon runs with
+struct Daemon<'a, U, R> {
+    /// the flags for a request's movie and language, the ones the daemon was started with
+    /// otherwise
+    unattended: &'a U,
+    run: &'a R,
+    /// `--jobs` of them, a request waits for one
+    permits: &'a Semaphore,
+    /// the waiting requests hash their movies a few at a time
+    hashing: &'a Semaphore,
+    timings: &'a Timings,
+    cleanup: &'a Arc<Cleanup>,
+    /// once terminated, connections get no more requests in
+    stopping: &'a CancellationToken,
+}
+
+impl<C, U, R, F> Daemon<'_, U, R>
+where
+    U: Fn(PathBuf, Option<String>) -> Result<Unattended<C>>,
+    R: Fn(C, Option<Ahead>, Arc<Cleanup>) -> F,
+    F: Future<Output = Result<Outcome>>,
+{
+    /// answers the requests of one connection until it hangs up or the daemon stops
+    async fn serve(&self, stream: Stream) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut reader = BufReader::new(reader);
+        loop {
+            let request = tokio::select! {
+                request = read_message::<Request>(&mut reader) => request,
+                () = self.stopping.cancelled() => return,
+            };
+            let response = match request {
+                Ok(None) => return,
+                Ok(Some(request)) => self.handle(request).await,
+                Err(report) => Response::failed(format!("{report:#}")),
+            };
+            if let Err(report) = write_message(&mut writer, &response).await {
+                warn!(?report, "answering a request failed");
+                return;
+            }
+        }
+    }
+
+    async fn handle(&self, request: Request) -> Response {
+        let Request::Download { path, language } = request;
+        let movie = match (self.unattended)(path.clone(), language) {
+            Ok(movie) => movie,
+            Err(report) => return Response::failed(format!("{report:#}")),
+        };
+        // a season pack's folder is checked and hashed by the episode it's searched by
+        let ahead = match path.is_file() {
+            true => {
+                let _hashing = self.hashing.acquire().await.expect("never closed");
+                Some(Ahead::of(&movie, &path, self.timings).await)
+            }
+            false => None,
+        };
+        let _permit = self.permits.acquire().await.expect("never closed");
+        // the files this request wrote, and still the daemon's to remove on Ctrl-C
+        let cleanup = self.cleanup.child();
+        let run = (self.run)(movie.run, ahead, cleanup.clone());
+        let response = match Box::pin(run).await {
+            Ok(_) => Response::written(cleanup.completed_files()),
+            Err(report) => {
+                error!(?path, ?report, "the request failed");
+                Response::failed(format!("{report:#}"))
+            }
+        };
+        if movie.notify {
+            let language = &movie.language;
+            let notification = match &response.error {
+                None => Notification::downloaded(&path, language, &response.written),
+                Some(error) => Notification::failed(&path, language, error),
+            };
+            notify::send(notification).await;
+        }
+        response
+    }
+}
+
+/// `daemon`, every request run by `run` with the flags `unattended` gives it, until SIGTERM.
+/// then it stops listening and finishes the requests it took, running and waiting ones alike
+pub async fn serve<C, F>(
+    address: &str,
+    jobs: usize,
+    unattended: impl Fn(PathBuf, Option<String>) -> Result<Unattended<C>>,
+    run: impl Fn(C, Option<Ahead>, Arc<Cleanup>) -> F,
+    timings: &Timings,
+    cleanup: &Arc<Cleanup>,
+) -> Result<()>
+where
+    F: Future<Output = Result<Outcome>>,
+{
+    let listener = Listener::bind(address).await?;
+    progress::finish_on_terminate();
+    let permits = Semaphore::new(jobs.max(1));
+    let hashing = Semaphore::new(hash::HASH_CONCURRENCY);
+    let stopping = CancellationToken::new();
+    let daemon = Daemon {
+        unattended: &unattended,
+        run: &run,
+        permits: &permits,
+        hashing: &hashing,
+        timings,
+        cleanup,
+        stopping: &stopping,
+    };
+    let mut connections = FuturesUnordered::new();
+    let terminated = terminated();
+    tokio::pin!(terminated);
+    info!(%address, jobs, "listening");
+    loop {
+        tokio::select! {
+            () = &mut terminated => break,
+            accepted = listener.accept() => match accepted {
+                Ok(stream) => connections.push(daemon.serve(stream)),
+                Err(report) => warn!(?report, "taking a connection failed"),
+            },
+            Some(()) = connections.next(), if !connections.is_empty() => {}
+        }
+    }
+    drop(listener);
+    stopping.cancel();
+    info!(
+        connections = connections.len(),
+        "terminated, finishing the requests taken"
+    );
+    while connections.next().await.is_some() {}
+    Ok(())
+}
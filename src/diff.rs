@@ -0,0 +1,265 @@
+//! `clean --dry-run`, a line diff of what cleaning would change
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// the shortest edit between the middles of `old` and `new`, Myers' algorithm. the
+/// furthest reaching `x` of every diagonal is kept for each number of edits to walk back
+/// through
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Line<'a>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = n + m;
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let idx = |k: isize| (k + max) as usize;
+    // `trace[d]` is `v` from before the `d`th edit, diagonals `-d..=d`
+    let mut trace = vec![];
+    'search: for d in 0..=max {
+        trace.push(v[idx(-d)..=idx(d)].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let down = k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]);
+            let mut x = match down {
+                true => v[idx(k + 1)],
+                false => v[idx(k - 1)] + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    let mut lines = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().skip(1).rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let down = k == -d || (k != d && at(k - 1) < at(k + 1));
+        let previous_k = match down {
+            true => k + 1,
+            false => k - 1,
+        };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            lines.push(Line::Same(old[x as usize]));
+        }
+        match down {
+            true => {
+                y -= 1;
+                lines.push(Line::Added(new[y as usize]));
+            }
+            false => {
+                x -= 1;
+                lines.push(Line::Removed(old[x as usize]));
+            }
+        }
+    }
+    // what's left is the snake the search started with
+    while x > 0 {
+        x -= 1;
+        lines.push(Line::Same(old[x as usize]));
+    }
+    lines.reverse();
+    lines
+}
+
+/// every line of both texts, trailing whitespace (and `\r`) doesn't count
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old = old.lines().map(str::trim_end).collect::<Vec<_>>();
+    let new = new.lines().map(str::trim_end).collect::<Vec<_>>();
+    // most edits are a few lines here and there, what's the same at both ends needs no search
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let middle = edits(
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    old[..prefix]
+        .iter()
+        .map(|line| Line::Same(line))
+        .chain(middle)
+        .chain(
+            old[old.len() - suffix..]
+                .iter()
+                .map(|line| Line::Same(line)),
+        )
+        .collect()
+}
+
+/// changed lines with the unchanged ones around them, numbered from 1 like `diff -u` does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk<'a> {
+    pub old_start: usize,
+    pub new_start: usize,
+    pub lines: Vec<Line<'a>>,
+}
+
+/// changes at most `2 * context` unchanged lines apart share a hunk
+pub fn hunks<'a>(lines: &[Line<'a>], context: usize) -> Vec<Hunk<'a>> {
+    let changes = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(_)))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for change in changes {
+        let start = change.saturating_sub(context);
+        let end = (change + context + 1).min(lines.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    let before = |end: usize, counts: fn(&Line) -> bool| {
+        lines[..end].iter().filter(|line| counts(line)).count()
+    };
+    ranges
+        .into_iter()
+        .map(|(start, end)| Hunk {
+            old_start: before(start, |line| !matches!(line, Line::Added(_))) + 1,
+            new_start: before(start, |line| !matches!(line, Line::Removed(_))) + 1,
+            lines: lines[start..end].to_vec(),
+        })
+        .collect()
+}
+
+impl fmt::Display for Hunk<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old = self
+            .lines
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new = self
+            .lines
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        write!(
+            f,
+            "@@ -{},{old} +{},{new} @@",
+            self.old_start, self.new_start
+        )?;
+        for line in &self.lines {
+            match line {
+                Line::Same(line) => write!(f, "\n {line}")?,
+                Line::Removed(line) => write!(f, "\n-{line}")?,
+                Line::Added(line) => write!(f, "\n+{line}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `old` rebuilt from the diff, and `new`
+    fn sides(lines: &[Line]) -> (String, String) {
+        let side = |keep: for<'a> fn(&Line<'a>) -> Option<&'a str>| {
+            lines
+                .iter()
+                .filter_map(keep)
+                .map(|line| format!("{line}\n"))
+                .collect::<String>()
+        };
+        (
+            side(|line| match line {
+                Line::Same(line) | Line::Removed(line) => Some(line),
+                Line::Added(_) => None,
+            }),
+            side(|line| match line {
+                Line::Same(line) | Line::Added(line) => Some(line),
+                Line::Removed(_) => None,
+            }),
+        )
+    }
+
+    fn changes(lines: &[Line]) -> usize {
+        lines
+            .iter()
+            .filter(|line| !matches!(line, Line::Same(_)))
+            .count()
+    }
+
+    #[test]
+    fn moved_lines_are_changes() {
+        // counting lines would call this unchanged
+        let lines = lines("a\nb\nc\n", "c\nb\na\n");
+        assert_eq!(sides(&lines), ("a\nb\nc\n".into(), "c\nb\na\n".into()));
+        assert_eq!(changes(&lines), 4);
+    }
+
+    #[test]
+    fn finds_the_shortest_edit() {
+        // the example of Myers' paper, an edit of five
+        let old = "a\nb\nc\na\nb\nb\na\n";
+        let new = "c\nb\na\nb\na\nc\n";
+        let lines = lines(old, new);
+        assert_eq!(sides(&lines), (old.into(), new.into()));
+        assert_eq!(changes(&lines), 5);
+    }
+
+    #[test]
+    fn edge_cases() {
+        assert_eq!(lines("", ""), []);
+        assert_eq!(lines("a\n", ""), [Line::Removed("a")]);
+        assert_eq!(lines("", "a\n"), [Line::Added("a")]);
+        assert_eq!(
+            lines("a\r\nb \n", "a\nb\n"),
+            [Line::Same("a"), Line::Same("b")]
+        );
+        assert_eq!(
+            lines("a\nb\n", "a\nc\n"),
+            [Line::Same("a"), Line::Removed("b"), Line::Added("c")]
+        );
+    }
+
+    #[test]
+    fn hunks_keep_some_context() {
+        let text = |lines: &[String]| lines.iter().map(|line| format!("{line}\n")).collect();
+        let numbers = (1..=20).map(|n| n.to_string()).collect::<Vec<_>>();
+        let old: String = text(&numbers);
+        let mut edited = numbers.clone();
+        edited[2] = "three".to_string();
+        edited.remove(16);
+        let new: String = text(&edited);
+        let lines = lines(&old, &new);
+        assert_eq!(
+            hunks(&lines, 2)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "@@ -1,5 +1,5 @@\n 1\n 2\n-3\n+three\n 4\n 5",
+                "@@ -15,5 +15,4 @@\n 15\n 16\n-17\n 18\n 19",
+            ]
+        );
+        // close changes share one
+        let mut edited = numbers.clone();
+        edited[2] = "three".to_string();
+        edited[5] = "six".to_string();
+        let new: String = text(&edited);
+        assert_eq!(hunks(&super::lines(&old, &new), 2).len(), 1);
+        assert_eq!(hunks(&super::lines(&old, &old), 2), []);
+    }
+}
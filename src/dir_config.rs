@@ -0,0 +1,325 @@
+//! `.opensubtitlescli.toml`, options for the movies of a directory and the ones below it. the
+//! nearest file up from the movie counts, its values win over the environment and lose to the
+//! flags given
+//!
+//! ```toml
+//! language = "eng"
+//! strip-hi = "aggressive"
+//! format-preference = ["srt", "ass"]
+//! ```
+//!
+//! the keys are the flags' names. the files come along with downloads nobody checked, so only
+//! the options in [`ALLOWED`] are taken: what is searched for and how it's written, nothing
+//! that runs a program, talks to another server, logs in or writes elsewhere
+use eyre::{eyre, Result, WrapErr};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+pub const FILE_NAME: &str = ".opensubtitlescli.toml";
+
+/// the flags a per-directory file may set, every other key is ignored with a warning
+pub const ALLOWED: &[&str] = &[
+    // searching and picking
+    "query",
+    "language",
+    "top-n",
+    "max-bad-reports",
+    "include-featured-first",
+    "format-preference",
+    "only-preferred-formats",
+    "auto",
+    "max-attempts",
+    // unpacking
+    "archive-codepage",
+    "archive-exclude",
+    "subtitles-only",
+    "extract-all",
+    "season-pack",
+    // writing
+    "preserve-times",
+    "match-perms",
+    "ignore-line-endings",
+    "keep-encoding",
+    "encoding",
+    "bom",
+    "line-endings",
+    "no-clean",
+    "ad-patterns",
+    "strip-hi",
+    "strip-tags",
+    "keep-tags",
+    "max-line-length",
+    "repair",
+    "no-sort",
+    "keep-duplicates",
+    "no-merge-repeats",
+    "keep-empty",
+    "shift",
+    "fps",
+    "convert-to",
+    "keep-original",
+    "retime-fps",
+    "auto-retime",
+    // checking
+    "strict-duration",
+    "skip-if-audio-matches",
+    "verify-language",
+    "verify-episode",
+];
+
+/// a value of the toml subset the files are read as, no tables and no dates
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+/// the value as a flag takes it, arrays comma separated
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(string) => f.write_str(string),
+            Self::Integer(integer) => write!(f, "{integer}"),
+            Self::Float(float) => write!(f, "{float}"),
+            Self::Bool(bool) => write!(f, "{bool}"),
+            Self::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirConfig {
+    pub path: PathBuf,
+    /// the allowed keys, in the file's order
+    pub values: Vec<(String, Value)>,
+    /// the keys left out
+    pub refused: Vec<String>,
+}
+
+impl DirConfig {
+    /// the file nearest to `movie`, in its directory or one above. a directory of episodes
+    /// counts as the movie's directory itself
+    pub fn find(movie: &Path) -> Result<Option<Self>> {
+        let start = match movie.is_dir() {
+            true => movie,
+            false => movie.parent().unwrap_or(Path::new("")),
+        };
+        let start = match start.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => start,
+        };
+        let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+        start
+            .ancestors()
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.is_file())
+            .map(|path| Self::read(&path))
+            .transpose()
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).wrap_err_with(|| format!("reading {path:?}"))?;
+        let (values, refused) = parse(&text)
+            .wrap_err_with(|| format!("reading {path:?}"))?
+            .into_iter()
+            .partition::<Vec<_>, _>(|(key, _)| ALLOWED.contains(&key.as_str()));
+        Ok(Self {
+            path: path.to_path_buf(),
+            values,
+            refused: refused.into_iter().map(|(key, _)| key).collect(),
+        })
+    }
+}
+
+/// the `key = value` pairs of `text`, in order
+pub fn parse(text: &str) -> Result<Vec<(String, Value)>> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        at: 0,
+    };
+    let mut pairs: Vec<(String, Value)> = vec![];
+    loop {
+        parser.skip_blank(true);
+        let Some(next) = parser.peek() else {
+            return Ok(pairs);
+        };
+        if next == '[' {
+            return Err(parser.error("tables aren't taken, the options are top level keys"));
+        }
+        let key = parser.key()?;
+        if pairs.iter().any(|(seen, _)| *seen == key) {
+            return Err(parser.error(&format!("`{key}` is set twice")));
+        }
+        parser.skip_blank(false);
+        if !parser.eat('=') {
+            return Err(parser.error(&format!("expected `=` after `{key}`")));
+        }
+        parser.skip_blank(false);
+        let value = parser.value()?;
+        parser.skip_blank(false);
+        match parser.peek() {
+            None | Some('\n') => {}
+            Some(_) => return Err(parser.error("expected the end of the line")),
+        }
+        pairs.push((key, value));
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.at).copied()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        let eaten = self.peek() == Some(expected);
+        self.at += usize::from(eaten);
+        eaten
+    }
+
+    fn error(&self, message: &str) -> eyre::Report {
+        let line = 1 + self.chars[..self.at.min(self.chars.len())]
+            .iter()
+            .filter(|char| **char == '\n')
+            .count();
+        eyre!("line {line}: {message}")
+    }
+
+    /// spaces and comments, and line breaks with `newlines`
+    fn skip_blank(&mut self, newlines: bool) {
+        while let Some(char) = self.peek() {
+            match char {
+                ' ' | '\t' | '\r' => self.at += 1,
+                '\n' if newlines => self.at += 1,
+                '#' => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.at += 1;
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<String> {
+        match self.peek() {
+            Some('"') | Some('\'') => self.string(),
+            _ => {
+                let key = self.word();
+                match key.is_empty() {
+                    true => Err(self.error("expected a key")),
+                    false => Ok(key),
+                }
+            }
+        }
+    }
+
+    /// a bare key, a number, `true` or `false`
+    fn word(&mut self) -> String {
+        let start = self.at;
+        let in_word = |char: char| char.is_ascii_alphanumeric() || "_-+.".contains(char);
+        while self.peek().is_some_and(in_word) {
+            self.at += 1;
+        }
+        self.chars[start..self.at].iter().collect()
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') | Some('\'') => self.string().map(Value::String),
+            Some('[') => self.array(),
+            _ => {
+                let word = self.word();
+                let number = word.replace('_', "");
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "" => Err(self.error("expected a value")),
+                    _ => number
+                        .parse()
+                        .map(Value::Integer)
+                        .or_else(|_| number.parse().map(Value::Float))
+                        .map_err(|_| self.error(&format!("`{word}` isn't a value, quote strings"))),
+                }
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.at += 1;
+        let mut values = vec![];
+        loop {
+            self.skip_blank(true);
+            if self.eat(']') {
+                return Ok(Value::Array(values));
+            }
+            values.push(self.value()?);
+            self.skip_blank(true);
+            match self.eat(',') || self.peek() == Some(']') {
+                true => {}
+                false => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    /// `"basic"` with escapes or `'literal'`, on a single line
+    fn string(&mut self) -> Result<String> {
+        let quote = self.chars[self.at];
+        self.at += 1;
+        let mut string = String::new();
+        loop {
+            let Some(char) = self.peek().filter(|char| *char != '\n') else {
+                return Err(self.error("the string isn't closed"));
+            };
+            self.at += 1;
+            match char {
+                char if char == quote => return Ok(string),
+                '\\' if quote == '"' => string.push(self.escaped()?),
+                char => string.push(char),
+            }
+        }
+    }
+
+    fn escaped(&mut self) -> Result<char> {
+        let Some(char) = self.peek() else {
+            return Err(self.error("the string isn't closed"));
+        };
+        self.at += 1;
+        let length = match char {
+            'n' => return Ok('\n'),
+            't' => return Ok('\t'),
+            'r' => return Ok('\r'),
+            '"' | '\\' => return Ok(char),
+            'u' => 4,
+            'U' => 8,
+            _ => return Err(self.error(&format!("`\\{char}` isn't an escape"))),
+        };
+        let digits = self
+            .chars
+            .get(self.at..self.at + length)
+            .map(|digits| digits.iter().collect::<String>())
+            .unwrap_or_default();
+        self.at += length;
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error(&format!("`\\{char}{digits}` isn't a character")))
+    }
+}
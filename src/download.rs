@@ -0,0 +1,972 @@
+//! a download once the search found candidates: one is picked, downloaded, unpacked and
+//! written, then checked against the movie. whatever fails there has another one picked
+use crate::{
+    archive, charset, check,
+    cleanup::Cleanup,
+    crawler::Candidate,
+    http,
+    messages::{filled, text},
+    output, postprocess, probe, progress, prompt, release,
+    results::Results,
+    srt,
+    subtitle::{FormatPreference, SubtitleFormat},
+    timings, translate, Client,
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use itertools::Itertools;
+use reqwest::Url;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tap::prelude::*;
+use tracing::{debug, info, warn};
+
+/// the written subtitles are translated with this, `--translate-from` found nothing better
+pub struct MachineTranslation {
+    pub from: String,
+    pub to: String,
+    pub translator: translate::Translator,
+    pub http: Arc<dyn http::HttpFetch>,
+    pub timings: Arc<timings::Timings>,
+    pub cleanup: Arc<Cleanup>,
+}
+
+impl MachineTranslation {
+    /// every SubRip file in `files` replaced by its translation, `movie.machine.srt`. other
+    /// formats are left as they are
+    pub async fn apply(&self, files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+        let mut translated = vec![];
+        for file in files {
+            if SubtitleFormat::from_path(&file) != Some(SubtitleFormat::Srt) {
+                warn!(?file, "only SubRip subtitles are translated, left as it is");
+                translated.push(file);
+                continue;
+            }
+            let contents = fs::read(&file).wrap_err_with(|| format!("reading {file:?}"))?;
+            translated.push(self.translate(&file, &contents).await?);
+            fs::remove_file(&file).ok();
+        }
+        Ok(translated)
+    }
+
+    /// like [`MachineTranslation::apply`] for subtitles not written yet, SubRip files are only
+    /// ever written translated
+    pub async fn apply_prepared(
+        &self,
+        mut prepared: output::Prepared,
+        writer: &output::SubtitleWriter,
+    ) -> Result<Vec<PathBuf>> {
+        let srt =
+            prepared.take(|path| SubtitleFormat::from_path(path) == Some(SubtitleFormat::Srt));
+        let mut translated = vec![];
+        for (file, contents) in srt {
+            translated.push(self.translate(&file, &contents).await?);
+        }
+        for (file, _) in &prepared.files {
+            warn!(?file, "only SubRip subtitles are translated, left as it is");
+        }
+        translated.extend(writer.write_prepared(prepared).await?);
+        Ok(translated)
+    }
+
+    /// writes the translation of `contents`, the SubRip subtitles of `file`, next to it
+    async fn translate(&self, file: &Path, contents: &[u8]) -> Result<PathBuf> {
+        let mut srt = srt::parse_lenient(&String::from_utf8_lossy(contents)).srt;
+        let translating =
+            self.translator
+                .translate(self.http.as_ref(), &mut srt, &self.from, &self.to);
+        let subject = Some(file.display().to_string());
+        let outcome = self
+            .timings
+            .time_of("translation", subject, translating)
+            .await
+            .wrap_err_with(|| format!("translating {file:?}"))?;
+        if !outcome.failed.is_empty() {
+            warn!(
+                ?file,
+                failed = ?outcome.failed,
+                "some cues couldn't be translated and kept their text"
+            );
+        }
+        // written the way the file it was translated from is
+        let postprocess = postprocess::PostProcess {
+            bom: match contents.starts_with(charset::UTF8_BOM) {
+                true => postprocess::Bom::Add,
+                false => postprocess::Bom::Keep,
+            },
+            line_endings: match contents.windows(2).any(|pair| pair == b"\r\n") {
+                true => postprocess::LineEndings::Crlf,
+                false => postprocess::LineEndings::Keep,
+            },
+            ..Default::default()
+        };
+        let path = translate::machine_path(file);
+        let contents = postprocess.apply(&path, srt.to_string().into_bytes());
+        self.cleanup
+            .guard(
+                [output::temporary_path(&path)?],
+                output::write_atomic(&path, &contents),
+            )
+            .await
+            .wrap_err_with(|| format!("writing the translation to {path:?}"))?;
+        self.cleanup.completed(&path);
+        info!(?path, from = %self.from, "machine translated");
+        Ok(path)
+    }
+}
+
+/// `files` as they are without a translation
+async fn machine_translated(
+    translation: Option<&MachineTranslation>,
+    files: Vec<PathBuf>,
+) -> Result<Vec<PathBuf>> {
+    match translation {
+        Some(translation) => translation.apply(files).await,
+        None => Ok(files),
+    }
+}
+
+/// what MicroDVD frames are converted with when neither `--fps` nor the file says
+async fn movie_frame_rate(movie_file: &Path) -> Option<f64> {
+    probe::frame_rate(movie_file)
+        .await
+        .tap_err(|message| warn!(?message, "probing the movie's frame rate failed"))
+        .ok()
+        .flatten()
+}
+
+/// `None` when the frame rates already match
+pub fn frame_rate_retime(rates: srt::FrameRates) -> Option<srt::Linear> {
+    match rates.is_same() {
+        true => {
+            info!(fps = rates.from, "frame rates match, not retiming");
+            None
+        }
+        false => {
+            let linear = rates.linear();
+            info!(
+                from = rates.from,
+                to = rates.to,
+                factor = linear.scale,
+                "retiming for another frame rate"
+            );
+            Some(linear)
+        }
+    }
+}
+
+fn prompt_unless_single<T: Clone + std::fmt::Display>(prompt: &str, values: Vec<T>) -> Result<T> {
+    match &values[..] {
+        [single] => Ok(single.clone()),
+        values => prompt::select(prompt, values.to_vec()).wrap_err("invalid selection"),
+    }
+}
+
+/// in `--auto` mode the first (best ranked) value is taken without asking
+pub fn choose<T: Clone + std::fmt::Display>(auto: bool, prompt: &str, values: Vec<T>) -> Result<T> {
+    match auto {
+        true => values
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre!("nothing to choose from")),
+        false => prompt_unless_single(prompt, values),
+    }
+}
+
+/// downloads every part of a multi-cd subtitle, the extension and contents of each in order
+async fn fetch_parts(
+    candidate: &Candidate,
+    movie_file: &Path,
+    client: &Client,
+    preference: &FormatPreference,
+    options: &archive::Options,
+    auto: bool,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let download_urls = candidate.download_urls();
+    // a combined `2CD` row ships every part in a single archive
+    let parts_per_archive = (candidate.part_count() / download_urls.len()).max(1);
+    let mut parts = vec![];
+    for download_url in download_urls {
+        let (_, mut archive) = fetch_archive(download_url, client, options, auto).await?;
+        let files =
+            archive::subtitle_entries(archive.as_ref(), preference, &options.filter, movie_file)
+                .into_iter()
+                .map(|file| file.entry)
+                .sorted_by_key(|file| {
+                    let rank = SubtitleFormat::from_file_name(file.file_name())
+                        .map_or(usize::MAX, |format| preference.rank(&format));
+                    (rank, file.file_name().to_lowercase())
+                })
+                .take(parts_per_archive)
+                .collect::<Vec<_>>();
+        info!(?files, "found parts");
+        for file in files {
+            let contents = archive.read(&file)?;
+            parts.push((
+                archive::file_extension(file.file_name())?.to_string(),
+                contents,
+            ));
+        }
+    }
+    if parts.len() != candidate.part_count() {
+        warn!(
+            expected = candidate.part_count(),
+            found = parts.len(),
+            "unexpected number of parts"
+        );
+    }
+    Ok(parts)
+}
+
+/// writes the parts as `movie.cd1.srt`, `movie.cd2.srt`...
+async fn write_parts(
+    parts: Vec<(String, Vec<u8>)>,
+    movie_file: &Path,
+    writer: &output::SubtitleWriter,
+) -> Result<Vec<PathBuf>> {
+    let mut written = vec![];
+    for (idx, (extension, contents)) in parts.into_iter().enumerate() {
+        let subtitle_file = movie_file.with_extension(format!("cd{}.{extension}", idx + 1));
+        written.extend(writer.write(&subtitle_file, &contents).await?);
+    }
+    Ok(written)
+}
+
+/// `--join-parts`, the SubRip parts as one file for the whole movie, the offset of each from
+/// the `lengths` of the parts before it
+async fn join_parts(
+    parts: Vec<(String, Vec<u8>)>,
+    lengths: &[srt::Timestamp],
+    subtitle_file: &Path,
+    writer: &output::SubtitleWriter,
+) -> Result<output::Prepared> {
+    let bom = parts
+        .first()
+        .is_some_and(|(_, contents)| contents.starts_with(charset::UTF8_BOM));
+    let mut srts = vec![];
+    for (extension, contents) in parts {
+        if !extension.eq_ignore_ascii_case("srt") {
+            bail!("only SubRip parts can be joined, one is {extension}");
+        }
+        let text = match &writer.transcode {
+            Some(transcode) => transcode.to_utf8(&contents)?,
+            None => contents,
+        };
+        srts.push(srt::parse_lenient(&String::from_utf8_lossy(&text)).srt);
+    }
+    let joined = srt::Srt::concatenate(srts, lengths)?;
+    let bom = match bom {
+        true => charset::UTF8_BOM,
+        false => &[],
+    };
+    let contents = [bom, joined.to_string().as_bytes()].concat();
+    // already utf-8, decoding it again could only get it wrong
+    let writer = output::SubtitleWriter {
+        transcode: None,
+        ..writer.clone()
+    };
+    writer.prepare(subtitle_file, &contents).await
+}
+
+/// asks for the password of a protected archive, `--auto` has nobody to ask
+fn unlock(archive: &mut dyn archive::ArchiveReader, auto: bool) -> Result<()> {
+    if archive.is_encrypted() {
+        if auto {
+            return Err(eyre::Report::new(archive::ArchiveError::PasswordRequired));
+        }
+        let password = prompt::password(text("prompt-archive-password"))
+            .wrap_err("reading the archive password")?;
+        archive.set_password(&password);
+    }
+    Ok(())
+}
+
+/// downloads and opens the archive, a damaged download can be retried once as the transfer
+/// itself is the usual culprit
+async fn fetch_archive(
+    url: Url,
+    client: &Client,
+    options: &archive::Options,
+    auto: bool,
+) -> Result<(Vec<u8>, Box<dyn archive::ArchiveReader>)> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let bytes = client.download(url.clone()).await?;
+        let opened = client.timings().time_blocking("extraction", || {
+            archive::open(bytes.clone(), options).and_then(|mut archive| {
+                unlock(archive.as_mut(), auto)?;
+                archive.verify()?;
+                Ok(archive)
+            })
+        });
+        match opened {
+            Err(report)
+                if attempt == 1
+                    && !auto
+                    && archive::ArchiveError::find(&report)
+                        == Some(archive::ArchiveError::Corrupt)
+                    && prompt::confirm(
+                        &filled("prompt-download-again", &[("error", &report)]),
+                        true,
+                    )
+                    .unwrap_or_default() =>
+            {
+                warn!(?report, "downloading the archive again");
+            }
+            opened => return opened.map(|archive| (bytes, archive)),
+        }
+    }
+}
+
+/// video files in the directory which name an episode (`S01E02`), sorted
+pub fn episode_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(dir)
+        .wrap_err_with(|| format!("listing episodes in {dir:?}"))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| release::is_video(path))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|v| v.to_str())
+                .and_then(release::episode)
+                .is_some()
+        })
+        .sorted()
+        .collect())
+}
+
+/// `--season-pack`, writes the best ranked entry of every episode next to it and lists whatever
+/// couldn't be paired
+async fn write_season_pack(
+    archive: &mut dyn archive::ArchiveReader,
+    files: Vec<archive::Entry>,
+    episodes: &[PathBuf],
+    copy_metadata: output::CopyMetadata,
+    writer: &output::SubtitleWriter,
+    translation: Option<&MachineTranslation>,
+    results: &Results,
+) -> Result<Vec<(PathBuf, Vec<PathBuf>)>> {
+    let episode_of = |path: &Path| {
+        path.file_name()
+            .and_then(|v| v.to_str())
+            .and_then(release::episode)
+    };
+    let mut unmatched_entries = vec![];
+    let mut matched_episodes = vec![];
+    let mut written = vec![];
+    for file in files {
+        let episode = release::episode(file.file_name()).and_then(|episode| {
+            episodes
+                .iter()
+                .find(|path| episode_of(path) == Some(episode))
+        });
+        match episode {
+            // files are ranked, the first one for an episode wins
+            Some(episode) if matched_episodes.contains(episode) => {
+                debug!(%file, ?episode, "episode already has a subtitle");
+            }
+            Some(episode) => {
+                let extension = archive::file_extension(file.file_name())?;
+                let subtitle_file = episode.with_extension(extension);
+                let contents = archive.read(&file)?;
+                let subtitle_files = writer.write(&subtitle_file, &contents).await?;
+                let subtitle_files = machine_translated(translation, subtitle_files).await?;
+                for subtitle_file in &subtitle_files {
+                    copy_metadata.apply(episode, subtitle_file);
+                    results.written(subtitle_file);
+                }
+                written.push((episode.clone(), subtitle_files));
+                matched_episodes.push(episode.clone());
+            }
+            None => unmatched_entries.push(file),
+        }
+    }
+    if !unmatched_entries.is_empty() {
+        results.line(text("season-unmatched-entries"));
+        unmatched_entries
+            .iter()
+            .for_each(|entry| results.line(&format!("  {entry}")));
+    }
+    let unmatched_episodes = episodes
+        .iter()
+        .filter(|episode| !matched_episodes.contains(episode))
+        .collect::<Vec<_>>();
+    if !unmatched_episodes.is_empty() {
+        results.line(text("season-unmatched-episodes"));
+        unmatched_episodes
+            .iter()
+            .for_each(|episode| results.line(&format!("  {}", output::quoted(episode))));
+    }
+    Ok(written)
+}
+
+/// `--extract-all`, writes every entry as `movie.<language>.<entry name>.<extension>`
+async fn extract_entries(
+    archive: &mut dyn archive::ArchiveReader,
+    files: Vec<archive::Entry>,
+    movie_file: &Path,
+    language: &str,
+    writer: &output::SubtitleWriter,
+) -> Result<Vec<output::Prepared>> {
+    let mut prepared: Vec<output::Prepared> = vec![];
+    for file in files {
+        let extension = archive::file_extension(file.file_name())?;
+        let stem = Path::new(file.file_name())
+            .file_stem()
+            .and_then(|v| v.to_str())
+            .unwrap_or("subtitle");
+        // entries from different folders can share a name
+        let subtitle_file = (1..)
+            .map(|n| match n {
+                1 => format!("{language}.{stem}.{extension}"),
+                n => format!("{language}.{stem}.{n}.{extension}"),
+            })
+            .map(|extension| movie_file.with_extension(extension))
+            .find(|path| {
+                let mut taken = prepared.iter().flat_map(|prepared| &prepared.files);
+                !taken.any(|(taken, _)| taken == path)
+            })
+            .expect("infinite iterator");
+        let contents = archive.read(&file)?;
+        prepared.push(writer.prepare(&subtitle_file, &contents).await?);
+    }
+    Ok(prepared)
+}
+
+/// drops the subtitles from the candidates, an error when nothing is left to try
+fn reject(candidates: &mut Vec<Candidate>, link: &Candidate, mismatch: &str) -> Result<()> {
+    warn!(%mismatch, subtitle_id = link.entry.subtitle_id, "rejecting the subtitles");
+    candidates.retain(|candidate| candidate.entry.subtitle_id != link.entry.subtitle_id);
+    match candidates.is_empty() {
+        true => bail!("no subtitles fit the movie: {mismatch}"),
+        false => Ok(()),
+    }
+}
+
+/// how a try at a candidate ended, before its files are checked
+enum Written {
+    /// subtitles split into parts, nothing more to check
+    Parts(Vec<PathBuf>),
+    /// every episode with the subtitles written for it, nothing more to check
+    SeasonPack(Vec<(PathBuf, Vec<PathBuf>)>),
+    /// no file in the archive fits, another candidate is picked
+    Rejected(String),
+    /// cleaned and converted, written once they pass the checks
+    Files(Vec<output::Prepared>),
+}
+
+/// what the user doesn't want recovered from: Ctrl-C and a prompt escaped
+fn recoverable(report: &eyre::Report) -> bool {
+    let stopped = matches!(
+        progress::Stopped::find(report),
+        Some(progress::Stopped::Interrupted | progress::Stopped::Terminated)
+    );
+    #[cfg(feature = "tui")]
+    let stopped = stopped
+        || report.chain().any(|e| {
+            matches!(
+                e.downcast_ref::<inquire::InquireError>(),
+                Some(
+                    inquire::InquireError::OperationCanceled
+                        | inquire::InquireError::OperationInterrupted
+                )
+            )
+        });
+    !stopped
+}
+
+/// after a candidate failed to download, unpack or write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Recovery {
+    Retry,
+    Another,
+    Abort,
+}
+
+impl std::fmt::Display for Recovery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(text(match self {
+            Self::Retry => "recovery-retry",
+            Self::Another => "recovery-another",
+            Self::Abort => "recovery-abort",
+        }))
+    }
+}
+
+/// a candidate in the prompt, with what went wrong the last time it was tried
+#[derive(Clone)]
+struct Offered {
+    candidate: Candidate,
+    failure: Option<String>,
+}
+
+impl std::fmt::Display for Offered {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.failure {
+            Some(failure) => write!(f, "{} \x1b[31m✗ {failure}\x1b[0m", self.candidate),
+            None => write!(f, "{}", self.candidate),
+        }
+    }
+}
+
+/// the candidates that failed during a run, the search isn't made again for another pick
+#[derive(Debug, Default)]
+struct Failures {
+    /// the last failure of each, by subtitle id
+    reports: Vec<(u64, String)>,
+    attempts: usize,
+}
+
+impl Failures {
+    fn marked(&self, candidates: &[Candidate]) -> Vec<Offered> {
+        candidates
+            .iter()
+            .map(|candidate| Offered {
+                candidate: candidate.clone(),
+                failure: self
+                    .reports
+                    .iter()
+                    .find(|(id, _)| *id == candidate.entry.subtitle_id)
+                    .map(|(_, failure)| failure.clone()),
+            })
+            .collect()
+    }
+
+    /// `--auto` moves on to the next candidate until `max_attempts` of them failed, the
+    /// prompt asks whether to retry the same one, pick another or abort. `link` comes back
+    /// when it's retried
+    fn recover(
+        &mut self,
+        report: eyre::Report,
+        link: Candidate,
+        candidates: &mut Vec<Candidate>,
+        auto: bool,
+        max_attempts: usize,
+    ) -> Result<Option<Candidate>> {
+        if !recoverable(&report) {
+            return Err(report);
+        }
+        let subtitle_id = link.entry.subtitle_id;
+        warn!(?report, subtitle_id, "the subtitles failed");
+        self.attempts += 1;
+        self.reports.retain(|(id, _)| *id != subtitle_id);
+        self.reports.push((subtitle_id, format!("{report:#}")));
+        let others = candidates
+            .iter()
+            .any(|candidate| candidate.entry.subtitle_id != subtitle_id);
+        match auto {
+            true => {
+                candidates.retain(|candidate| candidate.entry.subtitle_id != subtitle_id);
+                match others && self.attempts < max_attempts {
+                    true => Ok(None),
+                    false => {
+                        Err(report
+                            .wrap_err(filled("error-gave-up", &[("attempts", &self.attempts)])))
+                    }
+                }
+            }
+            false => {
+                let choices = [Recovery::Retry]
+                    .into_iter()
+                    .chain(others.then_some(Recovery::Another))
+                    .chain([Recovery::Abort])
+                    .collect();
+                match prompt::select(&filled("prompt-recover", &[("error", &report)]), choices)? {
+                    Recovery::Retry => Ok(Some(link)),
+                    Recovery::Another => Ok(None),
+                    Recovery::Abort => Err(report),
+                }
+            }
+        }
+    }
+}
+
+/// the first complaint `check` has about the srt files about to be written
+fn subtitle_mismatch(
+    prepared: &[output::Prepared],
+    check: impl Fn(&srt::Srt) -> Option<String>,
+) -> Option<String> {
+    prepared
+        .iter()
+        .flat_map(|prepared| &prepared.files)
+        .filter(|(path, _)| SubtitleFormat::from_path(path) == Some(SubtitleFormat::Srt))
+        .find_map(|(_, contents)| {
+            check(&srt::parse_lenient(&String::from_utf8_lossy(contents)).srt)
+        })
+}
+
+/// what a download of one language goes by, the flags and what's known about the movie
+#[derive(Clone, Copy)]
+pub struct Download<'a> {
+    pub client: &'a Client,
+    pub movie_file: &'a Path,
+    pub language: &'a str,
+    /// what the subtitles are in before `translation`
+    pub subtitle_language: &'a str,
+    /// `movie.pol.srt` rather than `movie.srt`, a run of several languages
+    pub named_by_language: bool,
+    pub auto: bool,
+    pub max_attempts: usize,
+    /// `--season-pack`, the episodes entries are paired with
+    pub episodes: Option<&'a [PathBuf]>,
+    /// the episode of the movie file, for `--verify-episode`
+    pub movie_episode: Option<(u32, u32)>,
+    pub movie_duration: Option<srt::Timestamp>,
+    pub verify_episode: check::CheckMode,
+    pub verify_language: check::CheckMode,
+    pub strict_duration: bool,
+    pub retime_fps: Option<srt::FrameRates>,
+    /// `--join-parts`, the lengths of every part but the last
+    pub join_parts: Option<&'a [srt::Timestamp]>,
+    pub auto_retime: bool,
+    /// what archive entries are picked by
+    pub entry_preference: &'a FormatPreference,
+    pub archive_options: &'a archive::Options,
+    /// `--keep-archive`, `Some(None)` saves it next to the movie
+    pub keep_archive: Option<Option<&'a Path>>,
+    pub extract_all: bool,
+    pub copy_metadata: output::CopyMetadata,
+    pub translation: Option<&'a MachineTranslation>,
+    pub results: &'a Results,
+    pub cleanup: &'a Cleanup,
+}
+
+/// what a download ended with, `link` is the candidate the subtitles came from
+pub enum Downloaded {
+    /// the movie's, checked against it and translated
+    Files {
+        link: Candidate,
+        files: Vec<PathBuf>,
+    },
+    /// subtitles split into parts, `movie.cd1.srt`...
+    Parts {
+        link: Candidate,
+        files: Vec<PathBuf>,
+    },
+    /// `--season-pack`, every episode with what was written for it
+    SeasonPack {
+        link: Candidate,
+        episodes: Vec<(PathBuf, Vec<PathBuf>)>,
+    },
+}
+
+/// the subtitles written in one language, embedded together with the other languages'
+pub struct Fetched {
+    pub language: String,
+    pub files: Vec<PathBuf>,
+    /// `movie.cd1.srt`, `movie.cd2.srt`, a track of half the movie each
+    pub parts: bool,
+}
+
+impl Download<'_> {
+    /// picks from `candidates` until the subtitles of one are written and fit the movie, an
+    /// error once none are left or the user gave up
+    pub async fn run(
+        &self,
+        mut candidates: Vec<Candidate>,
+        writer: &mut output::SubtitleWriter,
+    ) -> Result<Downloaded> {
+        let Self {
+            client,
+            movie_file,
+            language,
+            subtitle_language,
+            named_by_language,
+            auto,
+            max_attempts,
+            episodes,
+            movie_episode,
+            movie_duration,
+            verify_episode,
+            verify_language,
+            strict_duration,
+            retime_fps,
+            join_parts: part_lengths,
+            auto_retime,
+            entry_preference,
+            archive_options,
+            keep_archive,
+            extract_all,
+            copy_metadata,
+            translation,
+            results,
+            cleanup,
+        } = *self;
+        // candidates that failed, marked when they're offered again
+        let mut failures = Failures::default();
+        let mut retrying = None;
+        // rejected and failed subtitles come back here to pick others
+        loop {
+            let link = match retrying.take() {
+                Some(link) => link,
+                None => {
+                    choose(
+                        auto,
+                        text("prompt-which-subtitle"),
+                        failures.marked(&candidates),
+                    )
+                    .wrap_err("selecting url to download")?
+                    .candidate
+                }
+            };
+            info!(release_names=?link.entry.release_names, "selected subtitle");
+            results.chosen(&link.entry);
+            if !auto {
+                eprintln!("{}", link.details());
+            }
+            let names = link.entry.release_names.iter().chain([&link.entry.name]);
+            let names = names.map(String::as_str);
+            let episode_mismatch =
+                movie_episode.and_then(|episode| check::episode_mismatch(names, episode));
+            if let Some(mismatch) = episode_mismatch {
+                match verify_episode {
+                    check::CheckMode::Strict => {
+                        reject(&mut candidates, &link, &mismatch)?;
+                        continue;
+                    }
+                    _ => warn!(%mismatch, "the subtitles may be for another episode"),
+                }
+            }
+            // the download and unpacking, what fails here can be recovered from
+            let written = async {
+                let frame_rates = match (retime_fps, auto_retime) {
+                    (Some(rates), _) => Some(rates),
+                    (None, true) => {
+                        let from = link
+                            .entry
+                            .fps
+                            .ok_or_else(|| eyre!(text("error-retime-subtitle-fps")))?;
+                        let to = probe::frame_rate(movie_file)
+                            .await?
+                            .ok_or_else(|| eyre!(text("error-retime-movie-fps")))?;
+                        Some(srt::FrameRates {
+                            from: from.into(),
+                            to,
+                        })
+                    }
+                    (None, false) => None,
+                };
+                writer.postprocess.linear = frame_rates.and_then(frame_rate_retime);
+                if writer.fps.is_none() && link.entry.format == SubtitleFormat::Sub {
+                    writer.movie_fps = movie_frame_rate(movie_file).await;
+                }
+                if link.part_count() > 1 && named_by_language && part_lengths.is_none() {
+                    let mismatch = "split into parts, one language at a time only";
+                    return Ok(Written::Rejected(mismatch.to_string()));
+                }
+                if link.part_count() > 1 {
+                    let parts = fetch_parts(
+                        &link,
+                        movie_file,
+                        client,
+                        entry_preference,
+                        archive_options,
+                        auto,
+                    )
+                    .await?;
+                    if keep_archive.is_some() {
+                        warn!("--keep-archive is not supported for subtitles split into parts");
+                    }
+                    // one file is checked and embedded like any other
+                    if let Some(lengths) = part_lengths {
+                        let subtitle_file = match named_by_language {
+                            true => movie_file.with_extension(format!("{language}.srt")),
+                            false => movie_file.with_extension("srt"),
+                        };
+                        let joined = join_parts(parts, lengths, &subtitle_file, writer).await?;
+                        return Ok(Written::Files(vec![joined]));
+                    }
+                    let written = write_parts(parts, movie_file, writer).await?;
+                    let written = machine_translated(translation, written).await?;
+                    return Ok(Written::Parts(written));
+                }
+                let download_url = link.entry.download_url.clone();
+                let (bytes, mut archive) =
+                    fetch_archive(download_url, client, archive_options, auto).await?;
+                if let Some(path) = keep_archive {
+                    let path = path.map(Path::to_path_buf).unwrap_or_else(|| {
+                        let extension = archive::extension(&bytes);
+                        movie_file.with_extension(format!("{language}.{extension}"))
+                    });
+                    cleanup
+                        .guard(
+                            [output::temporary_path(&path)?],
+                            output::write_atomic(&path, &bytes),
+                        )
+                        .await
+                        .wrap_err_with(|| format!("saving the archive to {path:?}"))?;
+                    cleanup.completed(&path);
+                    results.archive(&path);
+                }
+                let files = archive::subtitle_entries(
+                    archive.as_ref(),
+                    entry_preference,
+                    &archive_options.filter,
+                    movie_file,
+                );
+                info!(?files, "found files");
+                let has_sub = files.iter().flat_map(|file| file.entries()).any(|entry| {
+                    archive::file_extension(entry.file_name())
+                        .is_ok_and(|v| v.eq_ignore_ascii_case("sub"))
+                });
+                if writer.fps.is_none() && writer.movie_fps.is_none() && has_sub {
+                    writer.movie_fps = movie_frame_rate(movie_file).await;
+                }
+                if let Some(episodes) = &episodes {
+                    let files = files.into_iter().map(|file| file.entry).collect();
+                    let written = write_season_pack(
+                        archive.as_mut(),
+                        files,
+                        episodes,
+                        copy_metadata,
+                        writer,
+                        translation,
+                        results,
+                    )
+                    .await?;
+                    return Ok(Written::SeasonPack(written));
+                }
+
+                let written = match extract_all {
+                    true => {
+                        let files = files.iter().flat_map(|file| file.entries()).collect();
+                        let archive = archive.as_mut();
+                        extract_entries(archive, files, movie_file, language, writer).await?
+                    }
+                    false => {
+                        let files = match movie_episode {
+                            Some(episode) if verify_episode == check::CheckMode::Strict => {
+                                let count = files.len();
+                                let files = files
+                                    .into_iter()
+                                    .filter(|file| {
+                                        let name = file.entry.file_name();
+                                        check::episode_mismatch([name], episode).is_none()
+                                    })
+                                    .collect::<Vec<_>>();
+                                if files.is_empty() && count > 0 {
+                                    let mismatch = "no file in the archive is for the episode";
+                                    return Ok(Written::Rejected(mismatch.to_string()));
+                                }
+                                files
+                            }
+                            _ => files,
+                        };
+                        let file = choose(auto, text("prompt-which-file"), files)
+                            .wrap_err("choosing subtitle file")?;
+                        let episode_mismatch = movie_episode.and_then(|episode| {
+                            check::episode_mismatch([file.entry.file_name()], episode)
+                        });
+                        if let Some(mismatch) = episode_mismatch {
+                            warn!(%mismatch, "the subtitle file may be for another episode");
+                        }
+                        if file.companion.is_some() {
+                            warn!(
+                                "VobSub subtitles are images, text processing does not apply \
+                                 to them"
+                            );
+                        }
+                        let mut prepared = vec![];
+                        for entry in file.entries() {
+                            let extension = archive::file_extension(entry.file_name())?;
+                            let contents = archive.read(&entry)?;
+                            let subtitle_file = match named_by_language {
+                                true => format!("{language}.{extension}"),
+                                false => extension.to_string(),
+                            };
+                            let subtitle_file = movie_file.with_extension(subtitle_file);
+                            prepared.push(writer.prepare(&subtitle_file, &contents).await?);
+                        }
+                        prepared
+                    }
+                };
+                Ok(Written::Files(written))
+            }
+            .await;
+            let prepared = match written {
+                Ok(Written::Files(prepared)) => prepared,
+                Ok(Written::Parts(files)) => return Ok(Downloaded::Parts { link, files }),
+                Ok(Written::SeasonPack(episodes)) => {
+                    return Ok(Downloaded::SeasonPack { link, episodes })
+                }
+                Ok(Written::Rejected(mismatch)) => {
+                    reject(&mut candidates, &link, &mismatch)?;
+                    continue;
+                }
+                Err(report) => {
+                    retrying =
+                        failures.recover(report, link, &mut candidates, auto, max_attempts)?;
+                    continue;
+                }
+            };
+            let check_duration = |srt: &srt::Srt| {
+                let check = check::duration_check(srt, movie_duration?)?;
+                info!(
+                    movie_duration_ms = check.movie_duration_ms,
+                    first_cue_start_ms = check.first_cue_start_ms,
+                    last_cue_end_ms = check.last_cue_end_ms,
+                    tolerance_ms = check.tolerance_ms,
+                    "checked the subtitles against the movie's duration"
+                );
+                results.duration_checked(check);
+                check.mismatch()
+            };
+            let duration_mismatch = subtitle_mismatch(&prepared, check_duration);
+            let language_mismatch = match verify_language {
+                check::CheckMode::Off => None,
+                _ => subtitle_mismatch(&prepared, |srt| {
+                    check::language_mismatch(srt, subtitle_language)
+                }),
+            };
+            let rejected = [
+                (&duration_mismatch, strict_duration),
+                (
+                    &language_mismatch,
+                    verify_language == check::CheckMode::Strict,
+                ),
+            ]
+            .into_iter()
+            .find_map(|(mismatch, strict)| mismatch.clone().filter(|_| strict));
+            if let Some(mismatch) = &language_mismatch {
+                warn!(%mismatch, "the subtitles may be in another language");
+            }
+            match rejected {
+                Some(mismatch) => reject(&mut candidates, &link, &mismatch)?,
+                None => {
+                    if let Some(mismatch) = duration_mismatch {
+                        warn!(%mismatch, "the subtitles may be for another cut of the movie");
+                    }
+                    // nothing is on disk before here, a rejected candidate leaves no trace. a
+                    // failed write is recovered from like a failed download
+                    let written = async {
+                        let mut files = vec![];
+                        for prepared in prepared {
+                            files.extend(match translation {
+                                Some(translation) => {
+                                    translation.apply_prepared(prepared, writer).await?
+                                }
+                                None => writer.write_prepared(prepared).await?,
+                            });
+                        }
+                        Ok(files)
+                    }
+                    .await;
+                    match written {
+                        Ok(files) => return Ok(Downloaded::Files { link, files }),
+                        Err(report) => {
+                            retrying = failures.recover(
+                                report,
+                                link,
+                                &mut candidates,
+                                auto,
+                                max_attempts,
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
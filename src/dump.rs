@@ -0,0 +1,81 @@
+//! `--dump-html`, keeps a copy of every fetched page so parser failures can be reproduced
+use eyre::{Result, WrapErr};
+use reqwest::Url;
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tracing::debug;
+
+#[derive(Debug)]
+pub struct HtmlDump {
+    dir: PathBuf,
+    counter: AtomicUsize,
+}
+
+#[derive(Serialize)]
+struct Metadata<'a> {
+    kind: &'a str,
+    /// without the query, fragment and credentials
+    url: String,
+    status: u16,
+    content_type: Option<&'a str>,
+    fetched_at: String,
+}
+
+/// strips everything that could carry a session or an api key
+fn redacted(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.set_username("").ok();
+    url.set_password(None).ok();
+    url.to_string()
+}
+
+impl HtmlDump {
+    pub fn new(dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .wrap_err_with(|| format!("creating html dump directory {dir:?}"))?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            counter: AtomicUsize::new(0),
+        })
+    }
+
+    /// writes `<timestamp>-<n>-<kind>.html` with a `.json` file describing the response next to it
+    pub fn write(
+        &self,
+        kind: &str,
+        url: &Url,
+        status: u16,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<PathBuf> {
+        let now = chrono::Local::now();
+        let stem = format!(
+            "{}-{}-{kind}",
+            now.format("%Y%m%dT%H%M%S%.3f"),
+            self.counter.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = self.dir.join(format!("{stem}.html"));
+        std::fs::write(&path, body).wrap_err_with(|| format!("dumping page to {path:?}"))?;
+        let metadata = Metadata {
+            kind,
+            url: redacted(url),
+            status,
+            content_type,
+            fetched_at: now.to_rfc3339(),
+        };
+        let metadata_path = self.dir.join(format!("{stem}.json"));
+        serde_json::to_vec_pretty(&metadata)
+            .wrap_err("serializing dump metadata")
+            .and_then(|metadata| {
+                std::fs::write(&metadata_path, metadata)
+                    .wrap_err_with(|| format!("writing dump metadata to {metadata_path:?}"))
+            })?;
+        debug!(?path, "dumped page");
+        Ok(path)
+    }
+}
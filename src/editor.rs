@@ -0,0 +1,118 @@
+//! `--edit`, the written subtitles opened in an editor for a quick fix before they're embedded
+use eyre::{bail, eyre, Result, WrapErr};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// an editor closing sooner than this without a change most likely detached from the terminal
+const DETACHED: Duration = Duration::from_secs(1);
+
+/// a command line the file is handed to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Editor {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+impl Editor {
+    /// `--edit-wait-cmd`, then `$VISUAL`, `$EDITOR` and the platform's own. `env` looks up the
+    /// variables
+    pub fn find(wait_cmd: Option<&str>, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let configured = wait_cmd
+            .map(String::from)
+            .or_else(|| env("VISUAL"))
+            .or_else(|| env("EDITOR"))
+            .filter(|command| !command.trim().is_empty());
+        match configured {
+            Some(command) => Self::parse(&command),
+            None => Ok(Self::platform_default()),
+        }
+    }
+
+    /// `code --wait`, words split like a shell would but without expanding anything, quotes
+    /// keep spaces in a word
+    pub fn parse(command: &str) -> Result<Self> {
+        let mut words: Vec<String> = vec![];
+        let mut word: Option<String> = None;
+        let mut quote = None;
+        for char in command.chars() {
+            match (quote, char) {
+                (Some(open), char) if char == open => quote = None,
+                (Some(_), char) => word.get_or_insert_with(String::new).push(char),
+                (None, '"' | '\'') => {
+                    quote = Some(char);
+                    word.get_or_insert_with(String::new);
+                }
+                (None, char) if char.is_whitespace() => words.extend(word.take()),
+                (None, char) => word.get_or_insert_with(String::new).push(char),
+            }
+        }
+        if quote.is_some() {
+            bail!("the quote in the editor command [{command}] isn't closed");
+        }
+        words.extend(word);
+        let mut words = words.into_iter();
+        let program = words
+            .next()
+            .ok_or_else(|| eyre!("the editor command is empty"))?;
+        Ok(Self {
+            program,
+            args: words.collect(),
+        })
+    }
+
+    /// notepad on windows, TextEdit waited for on macos, nano or vi elsewhere
+    fn platform_default() -> Self {
+        let (program, args): (&str, &[&str]) = match std::env::consts::OS {
+            "windows" => ("notepad", &[]),
+            "macos" => ("open", &["-W", "-t"]),
+            _ => match crate::tools::on_path("nano") {
+                true => ("nano", &[]),
+                false => ("vi", &[]),
+            },
+        };
+        Self {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    /// the arguments with `path` in place of `{}`, after them when none is
+    pub fn args_for(&self, path: &Path) -> Vec<String> {
+        let path = path.display().to_string();
+        match self.args.iter().any(|arg| arg.contains("{}")) {
+            true => self
+                .args
+                .iter()
+                .map(|arg| arg.replace("{}", &path))
+                .collect(),
+            false => self.args.iter().cloned().chain([path]).collect(),
+        }
+    }
+
+    /// opens `path` and waits for the editor to close, returning whether the file changed
+    pub async fn edit(&self, path: &Path) -> Result<bool> {
+        let before = std::fs::read(path).wrap_err_with(|| format!("reading {path:?}"))?;
+        info!(?path, editor = %self.program, "waiting for the editor to close");
+        let started = Instant::now();
+        let status = tokio::process::Command::new(&self.program)
+            .args(self.args_for(path))
+            .status()
+            .await
+            .wrap_err_with(|| format!("running the editor {}", self.program))?;
+        if !status.success() {
+            bail!("the editor {} failed ({status})", self.program);
+        }
+        let changed = std::fs::read(path).wrap_err_with(|| format!("reading {path:?}"))? != before;
+        if !changed && started.elapsed() < DETACHED {
+            warn!(
+                editor = %self.program,
+                "the editor closed at once, one that opens a window needs to be told to wait: \
+                 --edit-wait-cmd \"code --wait\""
+            );
+        }
+        Ok(changed)
+    }
+}
@@ -0,0 +1,1268 @@
+//! soft-embedding subtitles into a copy of the movie, ffmpeg for anything and mkvmerge for
+//! matroska, which carries SubRip and ASS as they are
+use crate::{
+    cleanup::Cleanup,
+    download::Fetched,
+    language::LanguageCode,
+    messages::{filled, text},
+    probe::{self, SubtitleStream},
+    progress, prompt,
+    recorder::Recorder,
+    srt::Timestamp,
+    timings::Timings,
+    tools,
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use itertools::Itertools;
+use std::{
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::Duration,
+};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// a subtitle file becoming a track of the movie
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub path: PathBuf,
+    pub language: LanguageCode,
+    /// `--track-title`, players show it in their menu
+    pub title: Option<String>,
+    /// `--set-default`, players pick the track without being asked
+    pub default: bool,
+    /// `--set-forced`, shown even with subtitles turned off
+    pub forced: bool,
+}
+
+impl Track {
+    /// `default+forced` for `-disposition`, `None` leaves ffmpeg's choice
+    fn disposition(&self) -> Option<&'static str> {
+        match (self.default, self.forced) {
+            (true, true) => Some("default+forced"),
+            (true, false) => Some("default"),
+            (false, true) => Some("forced"),
+            (false, false) => None,
+        }
+    }
+}
+
+/// lowercase extension of the movie file
+pub fn container(path: &Path) -> String {
+    path.extension()
+        .and_then(|v| v.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// VobSub, `.idx` next to its `.sub`
+pub fn is_image_based(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("idx"))
+}
+
+/// what the subtitles are embedded into, `--embed-container` remuxes the movie into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Container {
+    Mkv,
+    Mp4,
+    Webm,
+}
+
+impl Container {
+    pub fn of(path: &Path) -> Result<Self> {
+        match container(path).as_str() {
+            "mkv" | "mka" | "mk3d" => Ok(Self::Mkv),
+            "mp4" | "m4v" | "mov" => Ok(Self::Mp4),
+            "webm" => Ok(Self::Webm),
+            "avi" => bail!("avi can't carry text subtitles, --embed-container mkv remuxes it"),
+            other => bail!("embedding subtitles into [{other}] files isn't supported"),
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Mkv => "mkv",
+            Self::Mp4 => "mp4",
+            Self::Webm => "webm",
+        }
+    }
+
+    /// ffmpeg's codec for the subtitle file in this container, `None` when it can't carry it
+    pub fn subtitle_codec(self, subtitle_file: &Path) -> Option<&'static str> {
+        let is_ass = matches!(container(subtitle_file).as_str(), "ass" | "ssa");
+        match self {
+            Self::Mkv if is_image_based(subtitle_file) => Some("copy"),
+            Self::Mkv if is_ass => Some("ass"),
+            Self::Mkv => Some("srt"),
+            // image based subtitles can only be carried by matroska
+            Self::Mp4 | Self::Webm if is_image_based(subtitle_file) => None,
+            Self::Mp4 => Some("mov_text"),
+            Self::Webm => Some("webvtt"),
+        }
+    }
+}
+
+impl std::fmt::Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+pub trait Embedder {
+    fn name(&self) -> &'static str;
+
+    /// what the output is written as
+    fn container(&self) -> Container;
+
+    /// writes `output`, `movie_file` with every track added to it in one pass. the new tracks
+    /// come after the `existing` streams the movie keeps
+    fn command(
+        &self,
+        movie_file: &Path,
+        existing: &Existing,
+        tracks: &[Track],
+        output: &Path,
+    ) -> Command;
+
+    fn succeeded(&self, status: ExitStatus) -> bool {
+        status.success()
+    }
+
+    /// whether the output carries the movie's attachments, fonts of ASS subtitles mostly
+    fn keeps_attachments(&self) -> bool;
+}
+
+pub struct FfmpegEmbedder {
+    pub container: Container,
+    /// `--drop-attachments`
+    pub drop_attachments: bool,
+}
+
+impl Embedder for FfmpegEmbedder {
+    fn name(&self) -> &'static str {
+        "ffmpeg"
+    }
+
+    fn container(&self) -> Container {
+        self.container
+    }
+
+    fn keeps_attachments(&self) -> bool {
+        !self.drop_attachments && self.container == Container::Mkv
+    }
+
+    fn command(
+        &self,
+        movie_file: &Path,
+        existing: &Existing,
+        tracks: &[Track],
+        output: &Path,
+    ) -> Command {
+        let mut command = tools::command("ffmpeg");
+        // an existing output was either `--force`d or is a stale temporary file
+        command.arg("-y").arg("-i").arg(movie_file);
+        for track in tracks {
+            command.arg("-i").arg(&track.path);
+        }
+        // some ffmpeg versions leave attachments out unless they're mapped on their own, so
+        // they're unmapped and mapped again. only matroska carries them
+        command.args(["-map", "0", "-map", "-0:t?", "-map_chapters", "0"]);
+        if self.keeps_attachments() {
+            command.args(["-map", "0:t?"]);
+        }
+        for dropped in &existing.dropped {
+            command.arg("-map").arg(format!("-0:s:{dropped}"));
+        }
+        for input in 1..=tracks.len() {
+            command.arg("-map").arg(input.to_string());
+        }
+        command.args(["-c", "copy"]);
+        for (idx, track) in tracks.iter().enumerate() {
+            // output stream specifiers count the movie's own subtitles too
+            let stream = existing.kept() + idx;
+            let subtitle_codec = self.container.subtitle_codec(&track.path).unwrap_or("copy");
+            command
+                .arg(format!("-c:s:{stream}"))
+                .arg(subtitle_codec)
+                .arg(format!("-metadata:s:s:{stream}"))
+                .arg(format!("language={}", track.language.container_tag()));
+            if let Some(title) = &track.title {
+                command
+                    .arg(format!("-metadata:s:s:{stream}"))
+                    .arg(format!("title={title}"));
+            }
+            if let Some(disposition) = track.disposition() {
+                command
+                    .arg(format!("-disposition:s:{stream}"))
+                    .arg(disposition);
+            }
+        }
+        command
+            .args(["-progress", "pipe:1", "-nostats"])
+            .arg(output);
+        command
+    }
+}
+
+pub struct MkvmergeEmbedder {
+    /// `--drop-attachments`
+    pub drop_attachments: bool,
+}
+
+impl Embedder for MkvmergeEmbedder {
+    fn name(&self) -> &'static str {
+        "mkvmerge"
+    }
+
+    fn container(&self) -> Container {
+        Container::Mkv
+    }
+
+    /// the subtitle files' only track is always `0`
+    fn command(
+        &self,
+        movie_file: &Path,
+        existing: &Existing,
+        tracks: &[Track],
+        output: &Path,
+    ) -> Command {
+        let mut command = tools::command("mkvmerge");
+        command.arg("-o").arg(output);
+        if !existing.dropped.is_empty() {
+            // options before a file apply to it, track ids count every stream
+            let ids = existing
+                .dropped
+                .iter()
+                .map(|dropped| existing.streams[*dropped].index.to_string())
+                .join(",");
+            command.arg("--subtitle-tracks").arg(format!("!{ids}"));
+        }
+        if self.drop_attachments {
+            command.arg("--no-attachments");
+        }
+        command.arg(movie_file);
+        let flag = |set: bool| match set {
+            true => "yes",
+            false => "no",
+        };
+        for track in tracks {
+            let name = match &track.title {
+                Some(title) => title.as_str(),
+                None => track.language.name().unwrap_or(track.language.as_str()),
+            };
+            command
+                .arg("--language")
+                .arg(format!("0:{}", track.language.container_tag()))
+                .arg("--track-name")
+                .arg(format!("0:{name}"))
+                // mkvmerge makes new tracks default unless told otherwise
+                .arg("--default-track-flag")
+                .arg(format!("0:{}", flag(track.default)))
+                .arg("--forced-display-flag")
+                .arg(format!("0:{}", flag(track.forced)))
+                .arg(&track.path);
+        }
+        command
+    }
+
+    /// 1 is success with warnings
+    fn succeeded(&self, status: ExitStatus) -> bool {
+        matches!(status.code(), Some(0 | 1))
+    }
+
+    fn keeps_attachments(&self) -> bool {
+        !self.drop_attachments
+    }
+}
+
+/// the subtitle streams the movie already has, the `dropped` ones are left out of the output
+#[derive(Debug, Clone, Default)]
+pub struct Existing {
+    pub streams: Vec<SubtitleStream>,
+    /// positions in `streams`, i.e. `s:N`
+    pub dropped: Vec<usize>,
+}
+
+impl Existing {
+    pub async fn of(movie_file: &Path) -> Result<Self> {
+        let streams = probe::subtitle_streams(movie_file)
+            .await
+            .wrap_err("listing the movie's subtitle streams")?;
+        Ok(Self {
+            streams,
+            dropped: vec![],
+        })
+    }
+
+    /// how many streams the output keeps, the new tracks are numbered after them
+    pub fn kept(&self) -> usize {
+        self.streams.len() - self.dropped.len()
+    }
+
+    /// positions of the streams already in `language`, `ger` and `deu` are the same
+    pub fn in_language(&self, language: &LanguageCode) -> Vec<usize> {
+        probe::in_language(&self.streams, language)
+    }
+}
+
+/// what happens to a track in a language the movie already has subtitles in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExistingTrack {
+    Skip,
+    Add,
+    Replace,
+}
+
+impl std::fmt::Display for ExistingTrack {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Skip => "skip it, keep the embedded track",
+            Self::Add => "add it as another track",
+            Self::Replace => "replace the embedded track",
+        })
+    }
+}
+
+/// `--embedder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum EmbedderChoice {
+    /// mkvmerge for matroska when it's installed, ffmpeg otherwise
+    #[default]
+    Auto,
+    Ffmpeg,
+    Mkvmerge,
+}
+
+impl EmbedderChoice {
+    /// checked before downloading anything, `None` when the subtitles can't be embedded and
+    /// nothing was asked for explicitly
+    pub fn embedder(
+        self,
+        movie_file: &Path,
+        embed_container: Option<Container>,
+        drop_attachments: bool,
+    ) -> Result<Option<Box<dyn Embedder>>> {
+        let container = match embed_container {
+            Some(container) => container,
+            None => match Container::of(movie_file) {
+                Ok(container) => container,
+                Err(message) if self == Self::Auto => {
+                    info!(%message, "not offering to embed the subtitles");
+                    return Ok(None);
+                }
+                Err(message) => return Err(message),
+            },
+        };
+        let ffmpeg = || tools::available("ffmpeg");
+        let mkvmerge = || tools::available("mkvmerge");
+        let is_matroska = container == Container::Mkv;
+        let ffmpeg_embedder = FfmpegEmbedder {
+            container,
+            drop_attachments,
+        };
+        let mkvmerge_embedder = MkvmergeEmbedder { drop_attachments };
+        // both embedders count the movie's subtitle streams with ffprobe first
+        if !tools::available("ffprobe") {
+            let hint = tools::install_hint("ffprobe");
+            match self {
+                Self::Auto => {
+                    info!("{hint}, not offering to embed the subtitles");
+                    return Ok(None);
+                }
+                _ => bail!("embedding needs ffprobe: {hint}"),
+            }
+        }
+        match self {
+            Self::Auto if is_matroska && mkvmerge() => Ok(Some(Box::new(mkvmerge_embedder))),
+            Self::Auto if ffmpeg() => Ok(Some(Box::new(ffmpeg_embedder))),
+            Self::Auto => {
+                let hint = tools::install_hint("ffmpeg");
+                info!("{hint}, not offering to embed the subtitles");
+                Ok(None)
+            }
+            Self::Ffmpeg if ffmpeg() => Ok(Some(Box::new(ffmpeg_embedder))),
+            Self::Ffmpeg => bail!(tools::install_hint("ffmpeg")),
+            Self::Mkvmerge if !is_matroska => {
+                bail!("mkvmerge only writes matroska, --embed-container mkv remuxes the movie")
+            }
+            Self::Mkvmerge if mkvmerge() => Ok(Some(Box::new(mkvmerge_embedder))),
+            Self::Mkvmerge => bail!(tools::install_hint("mkvmerge")),
+        }
+    }
+}
+
+/// a failed or stopped run leaves no partial `output` behind
+pub async fn embed(
+    embedder: &dyn Embedder,
+    movie_file: &Path,
+    existing: &Existing,
+    tracks: &[Track],
+    output: &Path,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    info!(
+        embedder = embedder.name(),
+        ?output,
+        "saving video with subs to new path"
+    );
+    // only draws the progress, a movie ffprobe can't time is still embedded
+    let duration = probe::duration(movie_file).await.ok().flatten();
+    let embedded = match progress::run(
+        embedder.command(movie_file, existing, tracks, output),
+        "embedding",
+        duration,
+        timeout,
+        |status| embedder.succeeded(status),
+    )
+    .await
+    {
+        Ok(()) => verify_streams(embedder, movie_file, existing, tracks, output)
+            .await
+            .wrap_err_with(|| format!("checking the streams of {output:?}")),
+        Err(message) => Err(message),
+    };
+    if embedded.is_err() {
+        tokio::fs::remove_file(output).await.ok();
+    }
+    embedded?;
+    info!("file with subtitles available at {output:?}");
+    report_tracks(output, existing.kept(), tracks).await;
+    Ok(())
+}
+
+/// the output has the movie's video, audio and attachments, its subtitles less the dropped
+/// ones and the new tracks
+async fn verify_streams(
+    embedder: &dyn Embedder,
+    movie_file: &Path,
+    existing: &Existing,
+    tracks: &[Track],
+    output: &Path,
+) -> Result<()> {
+    let (movie, muxed) = (
+        probe::streams(movie_file).await?,
+        probe::streams(output).await?,
+    );
+    let count = |streams: &[probe::Stream], kind: &str| {
+        streams
+            .iter()
+            .filter(|stream| stream.codec_type.as_deref() == Some(kind))
+            .count()
+    };
+    let expected = [
+        ("video", count(&movie, "video")),
+        ("audio", count(&movie, "audio")),
+        ("subtitle", existing.kept() + tracks.len()),
+        (
+            "attachment",
+            match embedder.keeps_attachments() {
+                true => count(&movie, "attachment"),
+                false => 0,
+            },
+        ),
+    ];
+    for (kind, expected) in expected {
+        let found = count(&muxed, kind);
+        if found != expected {
+            bail!("{found} {kind} streams, expected {expected}");
+        }
+    }
+    Ok(())
+}
+
+/// the added tracks as ffprobe sees them, complaining about flags that didn't stick
+async fn report_tracks(output: &Path, subtitle_streams: usize, tracks: &[Track]) {
+    let streams = match probe::subtitle_streams(output).await {
+        Ok(streams) => streams,
+        Err(message) => {
+            debug!(?message, "listing the embedded subtitle streams failed");
+            return;
+        }
+    };
+    let added = streams.iter().skip(subtitle_streams);
+    for (track, stream) in tracks.iter().zip(added) {
+        info!(
+            path = ?track.path,
+            language = stream.language,
+            title = stream.title,
+            default = stream.default,
+            forced = stream.forced,
+            "embedded subtitle track"
+        );
+        if (track.default && !stream.default) || (track.forced && !stream.forced) {
+            warn!(path = ?track.path, "the track's default or forced flag wasn't applied");
+        }
+    }
+}
+
+/// `--crf` and `--preset` of `--burn-in`, `overwrite` is `--force`
+#[derive(Debug, Clone)]
+pub struct BurnIn {
+    pub crf: u8,
+    pub preset: String,
+    pub overwrite: bool,
+}
+
+/// `<movie>.burned-in.<ext>` next to the movie
+pub fn burn_in_output(movie_file: &Path) -> PathBuf {
+    movie_file.with_extension(format!(
+        "burned-in.{}",
+        movie_file
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("mkv")
+    ))
+}
+
+/// `subtitles=filename='...'` rendering the file into the picture. the path is escaped once,
+/// for the option parser splitting at `:`, and quoted for the filtergraph so `[`, `,` and `;`
+/// are taken as they are. a quote ends the quoting, it's escaped outside of it
+pub fn burn_in_filter(subtitle_file: &Path) -> String {
+    let filter = match container(subtitle_file).as_str() {
+        "ass" | "ssa" => "ass",
+        _ => "subtitles",
+    };
+    let value = subtitle_file
+        .to_string_lossy()
+        .chars()
+        .fold(String::new(), |mut value, c| {
+            match c {
+                '\\' | ':' => value.extend(['\\', c]),
+                // `\'` for the option parser, its backslash and quote escaped for the filtergraph
+                '\'' => value.push_str(r"'\\\''"),
+                c => value.push(c),
+            }
+            value
+        });
+    format!("{filter}=filename='{value}'")
+}
+
+/// re-encodes `movie_file` with the subtitles drawn into the picture. an existing `output` is
+/// only replaced with `overwrite`, ffmpeg would ask otherwise
+pub fn burn_in_command(
+    movie_file: &Path,
+    subtitle_file: &Path,
+    output: &Path,
+    options: &BurnIn,
+) -> Command {
+    let mut command = tools::command("ffmpeg");
+    command
+        .arg(match options.overwrite {
+            true => "-y",
+            false => "-n",
+        })
+        .arg("-i")
+        .arg(movie_file)
+        .arg("-vf")
+        .arg(burn_in_filter(subtitle_file))
+        .args(["-c:a", "copy"]);
+    match container(output).as_str() {
+        // webm only takes vp8/vp9, which knows no presets
+        "webm" => command.args(["-c:v", "libvpx-vp9", "-b:v", "0"]),
+        _ => command.args(["-c:v", "libx264", "-preset", options.preset.as_str()]),
+    };
+    command
+        .arg("-crf")
+        .arg(options.crf.to_string())
+        .args(["-progress", "pipe:1", "-nostats"])
+        .arg(output);
+    command
+}
+
+/// `--burn-in`, `duration` gives the progress as a percentage
+pub async fn burn_in(
+    movie_file: &Path,
+    subtitle_file: &Path,
+    output: &Path,
+    options: &BurnIn,
+    duration: Option<Timestamp>,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    if is_image_based(subtitle_file) {
+        bail!("image based subtitles can't be burned in");
+    }
+    info!(
+        ?subtitle_file,
+        ?output,
+        "burning the subtitles into the picture"
+    );
+    let burned_in = progress::run(
+        burn_in_command(movie_file, subtitle_file, output, options),
+        "burning in",
+        duration,
+        timeout,
+        |status| status.success(),
+    )
+    .await;
+    if burned_in.is_err() {
+        tokio::fs::remove_file(output).await.ok();
+    }
+    burned_in?;
+    info!("file with burned in subtitles available at {output:?}");
+    Ok(())
+}
+
+/// `--embed-output-template` as it was before templates
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{stem}.with-subs.{container}";
+
+/// where the movie with subtitles goes. `{stem}` is the movie's file name without its
+/// extension, `{container}` the extension of the output. relative results are put in `dir`,
+/// or next to the movie without one
+pub fn output_path(
+    movie_file: &Path,
+    template: &str,
+    dir: Option<&Path>,
+    language: &LanguageCode,
+    container: &str,
+) -> Result<PathBuf> {
+    let stem = movie_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .ok_or_else(|| eyre!("{movie_file:?} has no file name"))?;
+    let rendered = [
+        ("{stem}", stem.as_str()),
+        ("{language}", language.as_str()),
+        ("{container}", container),
+    ]
+    .into_iter()
+    .fold(template.to_string(), |rendered, (placeholder, value)| {
+        rendered.replace(placeholder, value)
+    });
+    if rendered.contains(['{', '}']) {
+        bail!("{template:?} has placeholders other than {{stem}}, {{language}} and {{container}}");
+    }
+    let rendered = PathBuf::from(rendered);
+    if rendered.is_absolute() {
+        return Ok(rendered);
+    }
+    let dir = dir.or_else(|| movie_file.parent()).unwrap_or(Path::new(""));
+    Ok(dir.join(rendered))
+}
+
+/// `--print-embed-command`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PrintCommand {
+    /// one line to paste into the shell
+    #[default]
+    Shell,
+    /// an array of the program and its arguments, for scripts to run as it is
+    Json,
+}
+
+/// the program and arguments `embed` or `embed_in_place` would run
+pub fn command_arguments(
+    embedder: &dyn Embedder,
+    movie_file: &Path,
+    existing: &Existing,
+    tracks: &[Track],
+    output: Option<&Path>,
+) -> Vec<String> {
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| in_place_temporary(movie_file));
+    tools::arguments(&embedder.command(movie_file, existing, tracks, &output))
+}
+
+/// `--print-embed-command`, the line printed for `arguments`
+pub fn format_command(arguments: &[String], format: PrintCommand) -> Result<String> {
+    Ok(match format {
+        PrintCommand::Shell => arguments
+            .iter()
+            .map(|argument| tools::shell_quote(argument))
+            .join(" "),
+        PrintCommand::Json => serde_json::to_string(&arguments)?,
+    })
+}
+
+/// `.<stem>.embedding.<extension>` next to the movie, the extension tells ffmpeg the muxer
+pub fn in_place_temporary(movie_file: &Path) -> PathBuf {
+    let stem = movie_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    movie_file.with_file_name(format!(".{stem}.embedding.{}", container(movie_file)))
+}
+
+/// the muxed file has something in it and the movie's length, `embed` checked its streams
+async fn verify_in_place(movie_file: &Path, muxed: &Path) -> Result<()> {
+    let size = tokio::fs::metadata(muxed)
+        .await
+        .wrap_err_with(|| format!("reading {muxed:?}"))?
+        .len();
+    if size == 0 {
+        bail!("{muxed:?} is empty");
+    }
+    let (original, muxed_duration) = (
+        probe::duration(movie_file).await?,
+        probe::duration(muxed).await?,
+    );
+    match (original, muxed_duration) {
+        (Some(original), Some(duration)) if (original.0 - duration.0).abs() > 1000 => {
+            bail!("{muxed:?} is {duration} long, the movie {original}")
+        }
+        (Some(_), None) => bail!("ffprobe can't tell how long {muxed:?} is"),
+        _ => Ok(()),
+    }
+}
+
+/// `--embed-in-place`, muxes into a temporary file next to the movie and renames it over the
+/// movie once it checks out. the movie is left alone when anything fails, `backup` keeps it
+/// as `<name>.bak` and returns where
+pub async fn embed_in_place(
+    embedder: &dyn Embedder,
+    movie_file: &Path,
+    existing: &Existing,
+    tracks: &[Track],
+    backup: bool,
+    timeout: Option<Duration>,
+    cleanup: &Cleanup,
+) -> Result<Option<PathBuf>> {
+    let movie_size = tokio::fs::metadata(movie_file)
+        .await
+        .wrap_err_with(|| format!("reading {movie_file:?}"))?
+        .len();
+    let dir = movie_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    // the temporary copy needs the whole movie's worth of space until it replaces it
+    if let Some(free) = tools::free_space(dir).await {
+        if free < movie_size + movie_size / 20 {
+            bail!(
+                "{} MiB free in {dir:?}, embedding in place needs about {} MiB",
+                free / 1024 / 1024,
+                movie_size / 1024 / 1024
+            );
+        }
+    }
+    let temporary = in_place_temporary(movie_file);
+    // only partial until it checks out, after that it's about to become the movie
+    let embedded = cleanup.guard([temporary.clone()], async {
+        embed(embedder, movie_file, existing, tracks, &temporary, timeout).await?;
+        verify_in_place(movie_file, &temporary).await
+    });
+    if let Err(message) = embedded.await {
+        tokio::fs::remove_file(&temporary).await.ok();
+        return Err(message.wrap_err("the movie was left as it was"));
+    }
+    let backup = replace(movie_file, &temporary, backup).await?;
+    info!(?movie_file, "embedded the subtitles in place");
+    Ok(backup)
+}
+
+/// renames `temporary` over `movie_file`, keeping the movie as `<name>.bak` first with
+/// `backup`. a movie already backed up is renamed back when the muxed one can't take its place
+async fn replace(movie_file: &Path, temporary: &Path, backup: bool) -> Result<Option<PathBuf>> {
+    let backup = match backup {
+        true => {
+            let mut backup = movie_file.as_os_str().to_owned();
+            backup.push(".bak");
+            let backup = PathBuf::from(backup);
+            if let Err(message) = tokio::fs::rename(movie_file, &backup).await {
+                tokio::fs::remove_file(temporary).await.ok();
+                return Err(message).wrap_err_with(|| format!("backing up {movie_file:?}"));
+            }
+            info!(?backup, "kept the movie without the new subtitles");
+            Some(backup)
+        }
+        false => None,
+    };
+    if let Err(message) = tokio::fs::rename(temporary, movie_file).await {
+        if let Some(backup) = &backup {
+            if let Err(message) = tokio::fs::rename(backup, movie_file).await {
+                warn!(?message, ?backup, "putting the movie back failed");
+            }
+        }
+        return Err(message).wrap_err_with(|| {
+            format!("replacing {movie_file:?}, the muxed movie is {temporary:?}")
+        });
+    }
+    Ok(backup)
+}
+
+/// soft-embedding and burning in, run once the subtitles are written
+#[derive(Clone, clap::Args)]
+pub struct Embedding {
+    /// what embeds the subtitles into the movie
+    #[arg(long, value_enum, default_value_t)]
+    pub embedder: EmbedderChoice,
+    /// remux the movie into this container when embedding, e.g. mkv for image based subtitles
+    #[arg(long, value_enum)]
+    pub embed_container: Option<Container>,
+    /// title of the embedded subtitle tracks, e.g. `Polish (opensubtitles)`
+    #[arg(long)]
+    pub track_title: Option<String>,
+    /// mark the embedded tracks as default, or only the ones in these languages (`pol,eng`)
+    #[arg(long, num_args = 0..=1)]
+    pub set_default: Option<Option<String>>,
+    /// mark the embedded tracks as forced, or only the ones in these languages
+    #[arg(long, num_args = 0..=1)]
+    pub set_forced: Option<Option<String>>,
+    /// render the subtitles into the picture for players ignoring subtitle tracks, this
+    /// re-encodes the whole movie
+    #[arg(long)]
+    pub burn_in: bool,
+    /// quality of the re-encoded video, lower is better and bigger
+    #[arg(long, default_value_t = 20, requires = "burn_in")]
+    pub crf: u8,
+    /// x264 preset of the re-encoded video, slower ones compress better
+    #[arg(long, default_value = "medium", requires = "burn_in")]
+    pub preset: String,
+    /// embed into the movie file itself instead of a `with-subs` copy, it's replaced only once
+    /// the result checks out
+    #[arg(long, conflicts_with = "embed_container")]
+    pub embed_in_place: bool,
+    /// keep the movie from before --embed-in-place as `<name>.bak`
+    #[arg(long, requires = "embed_in_place")]
+    pub backup: bool,
+    /// name of the movie with subtitles, `{stem}`, `{language}` and `{container}` are filled in
+    #[arg(long, default_value = DEFAULT_OUTPUT_TEMPLATE, conflicts_with = "embed_in_place")]
+    pub embed_output_template: String,
+    /// where the movie with subtitles is written, next to the movie by default
+    #[arg(long, conflicts_with = "embed_in_place")]
+    pub embed_output_dir: Option<PathBuf>,
+    /// overwrite an existing movie with subtitles or with them burned in
+    #[arg(long)]
+    pub force: bool,
+    /// print the ffmpeg or mkvmerge command embedding would run instead of running it
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "shell")]
+    pub print_embed_command: Option<PrintCommand>,
+    /// leave the movie's attachments, e.g. fonts, out of the movie with subtitles
+    #[arg(long)]
+    pub drop_attachments: bool,
+    /// drop the movie's subtitle tracks in the language being embedded instead of asking
+    #[arg(long, conflicts_with = "skip_if_embedded")]
+    pub replace_existing_track: bool,
+    /// don't embed subtitles in a language the movie already has a track in
+    #[arg(long)]
+    pub skip_if_embedded: bool,
+}
+
+/// subtitle files worth offering for embedding, ffmpeg finds the `.sub` of a VobSub `.idx`
+/// on its own
+fn embeddable(subtitle_files: &[PathBuf]) -> Vec<PathBuf> {
+    let has_extension = |path: &Path, extension: &str| {
+        path.extension()
+            .is_some_and(|v| v.eq_ignore_ascii_case(extension))
+    };
+    subtitle_files
+        .iter()
+        .filter(|path| {
+            !(has_extension(path, "sub")
+                && subtitle_files.iter().any(|other| {
+                    has_extension(other, "idx")
+                        && other.with_extension("") == path.with_extension("")
+                }))
+        })
+        .cloned()
+        .collect()
+}
+
+/// `--embed-*` and `--burn-in`, worked out before anything is downloaded
+pub struct PreparedEmbedding {
+    options: Embedding,
+    /// `None` with `--auto` or when nothing can embed into the movie
+    embedder: Option<Box<dyn Embedder>>,
+    with_subtitles_name: PathBuf,
+    timeout: Option<std::time::Duration>,
+}
+
+/// what embedding leaves for the results
+pub enum Embedded {
+    /// `--burn-in`, the movie the subtitles were drawn into
+    BurnedIn(PathBuf),
+    /// `--print-embed-command`, the program, its arguments and the line printed for them
+    Command {
+        arguments: Vec<String>,
+        line: String,
+    },
+}
+
+impl Embedding {
+    /// fails before the download when the movie with subtitles couldn't be written
+    pub async fn prepare(
+        self,
+        movie_file: &Path,
+        language: &str,
+        auto: bool,
+        timeout: Option<std::time::Duration>,
+    ) -> Result<PreparedEmbedding> {
+        if self.burn_in {
+            for program in ["ffmpeg", "ffprobe"] {
+                if !tools::available(program) {
+                    bail!(
+                        "--burn-in needs {program}: {}",
+                        tools::install_hint(program)
+                    );
+                }
+            }
+        }
+        // `--auto` never embeds
+        let embedder = match auto {
+            true => None,
+            false => {
+                self.embedder
+                    .embedder(movie_file, self.embed_container, self.drop_attachments)?
+            }
+        };
+        let needed = embedder
+            .as_ref()
+            .map(|embedder| embedder.name())
+            .into_iter()
+            .chain(self.burn_in.then_some("ffmpeg"))
+            .collect::<Vec<_>>();
+        if !needed.is_empty() {
+            for program in needed.into_iter().chain(["ffprobe"]).unique() {
+                tools::log_version(program).await;
+            }
+        }
+        let with_subtitles_name = match &embedder {
+            Some(embedder) if !self.embed_in_place => {
+                let container = match self.embed_container {
+                    Some(container) => container.extension(),
+                    None => movie_file
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .unwrap_or(embedder.container().extension()),
+                };
+                let path = output_path(
+                    movie_file,
+                    &self.embed_output_template,
+                    self.embed_output_dir.as_deref(),
+                    &LanguageCode::new(language),
+                    container,
+                )?;
+                let same = |a: &Path, b: &Path| match (a.canonicalize(), b.canonicalize()) {
+                    (Ok(a), Ok(b)) => a == b,
+                    _ => a == b,
+                };
+                if let Some(dir) = self.embed_output_dir.as_ref().filter(|dir| !dir.is_dir()) {
+                    bail!(filled(
+                        "error-not-a-directory",
+                        &[("path", &format!("{dir:?}"))]
+                    ));
+                }
+                if same(&path, movie_file) {
+                    bail!(text("error-embed-output-is-movie"));
+                }
+                if path.exists() && !self.force {
+                    bail!(filled(
+                        "error-output-exists",
+                        &[("path", &format!("{path:?}"))]
+                    ));
+                }
+                path
+            }
+            _ => movie_file.to_owned(),
+        };
+        // re-encoding takes long, its result isn't replaced by accident either
+        let burned_in = burn_in_output(movie_file);
+        if self.burn_in && burned_in.exists() && !self.force {
+            bail!(filled(
+                "error-output-exists",
+                &[("path", &format!("{burned_in:?}"))]
+            ));
+        }
+        Ok(PreparedEmbedding {
+            options: self,
+            embedder,
+            with_subtitles_name,
+            timeout,
+        })
+    }
+}
+
+impl PreparedEmbedding {
+    /// burns the subtitles in or offers to embed them, whatever was asked for. `downloaded` has
+    /// the files of every language
+    pub async fn embed(
+        self,
+        movie_file: &Path,
+        downloaded: &[Fetched],
+        movie_duration: Option<Timestamp>,
+        recorder: &Recorder,
+        timings: &Timings,
+        cleanup: &Cleanup,
+    ) -> Result<Option<Embedded>> {
+        let Self {
+            options:
+                Embedding {
+                    track_title,
+                    set_default,
+                    set_forced,
+                    burn_in,
+                    crf,
+                    preset,
+                    embed_in_place,
+                    backup,
+                    print_embed_command,
+                    force,
+                    replace_existing_track,
+                    skip_if_embedded,
+                    ..
+                },
+            embedder,
+            with_subtitles_name,
+            timeout: embed_timeout,
+        } = self;
+        if burn_in || embedder.is_some() {
+            for fetched in downloaded.iter().filter(|fetched| fetched.parts) {
+                info!(
+                    language = fetched.language,
+                    "subtitles split into parts are not embedded, --join-parts makes one file \
+                     of them"
+                );
+            }
+        }
+        // every file next to the language it's in
+        let subtitle_files = downloaded
+            .iter()
+            .filter(|fetched| !fetched.parts)
+            .flat_map(|fetched| {
+                embeddable(&fetched.files)
+                    .into_iter()
+                    .map(move |path| (path, fetched.language.as_str()))
+            })
+            .collect::<Vec<_>>();
+        if burn_in {
+            warn!("--burn-in re-encodes the whole movie, this takes long and loses some quality");
+            let (subtitle_file, _) = subtitle_files
+                .into_iter()
+                .find(|(subtitle_file, _)| !is_image_based(subtitle_file))
+                .ok_or_else(|| eyre!("none of the subtitle files can be burned in"))?;
+            let output = burn_in_output(movie_file);
+            let options = BurnIn {
+                crf,
+                preset,
+                overwrite: force,
+            };
+            let burned_in = self::burn_in(
+                movie_file,
+                &subtitle_file,
+                &output,
+                &options,
+                movie_duration,
+                embed_timeout,
+            );
+            let burned_in = cleanup.guard([output.clone()], burned_in);
+            timings.time("burn-in", burned_in).await?;
+            cleanup.completed(&output);
+            recorder.video(movie_file, output.clone(), None).await;
+            return Ok(Some(Embedded::BurnedIn(output)));
+        }
+        let Some(embedder) = embedder else {
+            return Ok(None);
+        };
+        let question = match embed_in_place {
+            true => filled(
+                "prompt-embed-in-place",
+                &[("movie", &format!("{movie_file:?}"))],
+            ),
+            false => filled(
+                "prompt-embed",
+                &[("movie", &format!("{with_subtitles_name:?}"))],
+            ),
+        };
+        let to_embed = match subtitle_files.as_slice() {
+            [] => vec![],
+            [subtitle_file] => {
+                let (yes, no) = (text("answer-yes"), text("answer-no"));
+                match prompt::select(&question, vec![yes, no]).unwrap_or(no) == yes {
+                    true => vec![subtitle_file.clone()],
+                    false => vec![],
+                }
+            }
+            // every chosen file becomes a track of its own, in one pass over the movie
+            subtitle_files => {
+                let options = subtitle_files
+                    .iter()
+                    .map(|(v, _)| v.display().to_string())
+                    .collect();
+                prompt::multi_select(&question, options)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|choice| {
+                        subtitle_files
+                            .iter()
+                            .find(|(v, _)| v.display().to_string() == choice)
+                            .cloned()
+                    })
+                    .collect()
+            }
+        };
+        let tracks = to_embed
+            .into_iter()
+            .filter(|(subtitle_file, _)| {
+                let container = embedder.container();
+                let unsupported = container.subtitle_codec(subtitle_file).is_none();
+                if unsupported {
+                    warn!(
+                        ?subtitle_file,
+                        "{container} can't carry the subtitles, --embed-container mkv remuxes \
+                         the movie"
+                    );
+                }
+                !unsupported
+            })
+            .map(|(path, language)| {
+                let language = LanguageCode::new(language);
+                // `--set-default` alone is for every track, `--set-default pol` for polish ones
+                let applies = |flag: &Option<Option<String>>| match flag {
+                    Some(Some(languages)) => languages.split(',').any(|code| {
+                        LanguageCode::new(code).container_tag() == language.container_tag()
+                    }),
+                    Some(None) => true,
+                    None => false,
+                };
+                Track {
+                    path,
+                    title: track_title.clone(),
+                    default: applies(&set_default),
+                    forced: applies(&set_forced),
+                    language,
+                }
+            })
+            .collect::<Vec<_>>();
+        if tracks.is_empty() {
+            return Ok(None);
+        }
+        let mut existing = Existing::of(movie_file).await?;
+        for stream in &existing.streams {
+            info!(
+                language = stream.language,
+                title = stream.title,
+                codec = stream.codec,
+                "the movie already has a subtitle track"
+            );
+        }
+        // repeated runs would otherwise pile up tracks in the same language
+        let mut new_tracks = vec![];
+        for track in tracks {
+            let embedded = existing.in_language(&track.language);
+            if embedded.is_empty() {
+                new_tracks.push(track);
+                continue;
+            }
+            let choice = match (replace_existing_track, skip_if_embedded) {
+                (true, _) => ExistingTrack::Replace,
+                (_, true) => ExistingTrack::Skip,
+                _ => prompt::select(
+                    &filled(
+                        "prompt-existing-track",
+                        &[
+                            (
+                                "language",
+                                &track.language.name().unwrap_or(track.language.as_str()),
+                            ),
+                            ("subtitle", &format!("{:?}", track.path)),
+                        ],
+                    ),
+                    vec![
+                        ExistingTrack::Skip,
+                        ExistingTrack::Add,
+                        ExistingTrack::Replace,
+                    ],
+                )
+                .unwrap_or(ExistingTrack::Skip),
+            };
+            match choice {
+                ExistingTrack::Skip => {
+                    info!(path = ?track.path, "not embedded, the language already has a track")
+                }
+                ExistingTrack::Add => new_tracks.push(track),
+                ExistingTrack::Replace => {
+                    existing.dropped.extend(embedded);
+                    new_tracks.push(track);
+                }
+            }
+        }
+        existing.dropped.sort_unstable();
+        existing.dropped.dedup();
+        if let Some(format) = print_embed_command.filter(|_| !new_tracks.is_empty()) {
+            let output = (!embed_in_place).then_some(with_subtitles_name.as_path());
+            let arguments = command_arguments(
+                embedder.as_ref(),
+                movie_file,
+                &existing,
+                &new_tracks,
+                output,
+            );
+            let line = format_command(&arguments, format)?;
+            return Ok(Some(Embedded::Command { arguments, line }));
+        }
+        // a movie embedded in place without a backup can't be taken back
+        let video = match (new_tracks.is_empty(), embed_in_place) {
+            (true, _) => None,
+            (false, true) => {
+                let embedded = self::embed_in_place(
+                    embedder.as_ref(),
+                    movie_file,
+                    &existing,
+                    &new_tracks,
+                    backup,
+                    embed_timeout,
+                    cleanup,
+                );
+                timings.time("embed", embedded).await?
+            }
+            .map(|backup| (movie_file.to_owned(), Some(backup))),
+            (false, false) => {
+                let embedded = embed(
+                    embedder.as_ref(),
+                    movie_file,
+                    &existing,
+                    &new_tracks,
+                    &with_subtitles_name,
+                    embed_timeout,
+                );
+                let embedded = cleanup.guard([with_subtitles_name.clone()], embedded);
+                timings.time("embed", embedded).await?;
+                cleanup.completed(&with_subtitles_name);
+                Some((with_subtitles_name, None))
+            }
+        };
+        if let Some((path, backup)) = video {
+            recorder.video(movie_file, path, backup).await;
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie(dir: &Path) -> PathBuf {
+        let movie_file = dir.join("movie.mkv");
+        std::fs::write(&movie_file, "the movie").unwrap();
+        movie_file
+    }
+
+    #[tokio::test]
+    async fn the_muxed_movie_takes_the_movies_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie_file = movie(dir.path());
+        let temporary = in_place_temporary(&movie_file);
+        std::fs::write(&temporary, "the movie with subtitles").unwrap();
+        let backup = replace(&movie_file, &temporary, true).await.unwrap();
+        let backup = backup.unwrap();
+        assert_eq!(backup, dir.path().join("movie.mkv.bak"));
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), "the movie");
+        let muxed = std::fs::read_to_string(&movie_file).unwrap();
+        assert_eq!(muxed, "the movie with subtitles");
+        assert!(!temporary.exists());
+    }
+
+    #[tokio::test]
+    async fn a_backed_up_movie_is_put_back_when_the_rename_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie_file = movie(dir.path());
+        // nothing to rename over the movie once it's backed up
+        let temporary = in_place_temporary(&movie_file);
+        let error = replace(&movie_file, &temporary, true).await.unwrap_err();
+        assert!(error.to_string().contains("replacing"), "{error}");
+        assert_eq!(std::fs::read_to_string(&movie_file).unwrap(), "the movie");
+        assert!(!dir.path().join("movie.mkv.bak").exists());
+    }
+}
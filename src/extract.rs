@@ -0,0 +1,220 @@
+//! `extract-subs`, subtitle streams of the movie written out as files next to it
+use crate::{
+    cleanup::Cleanup,
+    language::LanguageCode,
+    messages::text,
+    output,
+    probe::{self, SubtitleStream},
+    progress, prompt, tools,
+};
+use eyre::{bail, Result, WrapErr};
+use itertools::Itertools;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// a stream of the movie and the file it's written to
+#[derive(Debug, Clone)]
+pub struct Extraction {
+    /// position among the movie's subtitle streams, i.e. `s:N`
+    pub position: usize,
+    pub path: PathBuf,
+    /// `-c:s`
+    codec: &'static str,
+}
+
+/// the extension and ffmpeg encoder a stream is written with, an error says why ffmpeg can't
+/// write it on its own
+fn output_format(stream: &SubtitleStream) -> Result<(&'static str, &'static str)> {
+    Ok(match stream.codec.as_deref().unwrap_or_default() {
+        // ASS styling would be lost converting to SubRip
+        "ass" | "ssa" => ("ass", "copy"),
+        "subrip" | "srt" | "mov_text" | "webvtt" | "text" => ("srt", "srt"),
+        // images, players read the raw stream as it is
+        "hdmv_pgs_subtitle" => ("sup", "copy"),
+        "dvd_subtitle" => {
+            bail!("VobSub streams need an .idx next to them, mkvextract writes those")
+        }
+        "dvb_subtitle" => bail!("DVB subtitles have no file format of their own"),
+        "" => bail!("ffprobe doesn't know the stream's codec"),
+        codec => bail!("{codec} can't be extracted"),
+    })
+}
+
+/// `what it is`, for lists and logs
+pub fn describe(position: usize, stream: &SubtitleStream) -> String {
+    let mut description = format!(
+        "#{position} {} ({})",
+        stream.language.as_deref().unwrap_or("und"),
+        stream.codec.as_deref().unwrap_or("unknown")
+    );
+    if let Some(title) = &stream.title {
+        description.push_str(&format!(" \"{title}\""));
+    }
+    for (set, flag) in [(stream.default, "default"), (stream.forced, "forced")] {
+        if set {
+            description.push_str(&format!(" [{flag}]"));
+        }
+    }
+    description
+}
+
+/// `movie.<language>.<extension>` like downloaded subtitles, `movie.<language>.forced.<...>`
+/// for forced ones and numbered when a file of that name exists or was picked already
+fn file_name(
+    movie_file: &Path,
+    stream: &SubtitleStream,
+    extension: &str,
+    taken: &[PathBuf],
+) -> PathBuf {
+    let language = stream.language.as_deref().unwrap_or("und");
+    let forced = match stream.forced {
+        true => ".forced",
+        false => "",
+    };
+    (1..)
+        .map(|n| match n {
+            1 => format!("{language}{forced}.{extension}"),
+            n => format!("{language}{forced}.{n}.{extension}"),
+        })
+        .map(|extension| movie_file.with_extension(extension))
+        .find(|path| !taken.contains(path) && !path.exists())
+        .expect("infinite iterator")
+}
+
+/// where each of the chosen `positions` goes, streams that can't be extracted are left out
+/// with a warning
+pub fn plan(movie_file: &Path, streams: &[SubtitleStream], positions: &[usize]) -> Vec<Extraction> {
+    let mut extractions: Vec<Extraction> = vec![];
+    for position in positions {
+        let stream = &streams[*position];
+        let (extension, codec) = match output_format(stream) {
+            Ok(format) => format,
+            Err(message) => {
+                warn!(%message, stream = describe(*position, stream), "skipped");
+                continue;
+            }
+        };
+        if extension == "sup" {
+            info!(
+                stream = describe(*position, stream),
+                "PGS subtitles are images, written as .sup for players and OCR tools"
+            );
+        }
+        let taken = extractions
+            .iter()
+            .map(|extraction| extraction.path.clone())
+            .collect::<Vec<_>>();
+        extractions.push(Extraction {
+            position: *position,
+            path: file_name(movie_file, stream, extension, &taken),
+            codec,
+        });
+    }
+    extractions
+}
+
+/// writes every extraction in one pass over the movie, none of them are left behind when it
+/// fails
+pub async fn extract(
+    movie_file: &Path,
+    extractions: &[Extraction],
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let mut command = tools::command("ffmpeg");
+    // never overwrite, the names were picked to be free
+    command
+        .args(["-n", "-progress", "pipe:1", "-nostats", "-i"])
+        .arg(movie_file);
+    for extraction in extractions {
+        command
+            .arg("-map")
+            .arg(format!("0:s:{}", extraction.position))
+            .args(["-c:s", extraction.codec])
+            .arg(&extraction.path);
+    }
+    let duration = probe::duration(movie_file).await.ok().flatten();
+    let extracted = progress::run(command, "extracting", duration, timeout, |status| {
+        status.success()
+    })
+    .await;
+    if extracted.is_err() {
+        for extraction in extractions {
+            tokio::fs::remove_file(&extraction.path).await.ok();
+        }
+    }
+    extracted
+}
+
+/// `extract-subs`
+pub async fn extract_subs(
+    movie_file: &Path,
+    all: bool,
+    languages: Option<&str>,
+    timeout: Option<Duration>,
+    cleanup: &Cleanup,
+) -> Result<()> {
+    for program in ["ffmpeg", "ffprobe"] {
+        if !tools::available(program) {
+            bail!(
+                "extract-subs needs {program}: {}",
+                tools::install_hint(program)
+            );
+        }
+    }
+    let streams = probe::subtitle_streams(movie_file)
+        .await
+        .wrap_err("listing the movie's subtitle streams")?;
+    if streams.is_empty() {
+        bail!("{movie_file:?} has no subtitle streams");
+    }
+    let positions = match (all, languages) {
+        (true, _) => (0..streams.len()).collect::<Vec<_>>(),
+        (false, Some(languages)) => {
+            let languages = languages
+                .split(',')
+                .map(LanguageCode::new)
+                .collect::<Vec<_>>();
+            let positions = languages
+                .iter()
+                .flat_map(|language| probe::in_language(&streams, language))
+                .sorted()
+                .dedup()
+                .collect::<Vec<_>>();
+            if positions.is_empty() {
+                bail!(
+                    "{movie_file:?} has no subtitle streams in {}",
+                    languages.iter().join(", ")
+                );
+            }
+            positions
+        }
+        (false, None) => {
+            let options = streams
+                .iter()
+                .enumerate()
+                .map(|(position, stream)| describe(position, stream))
+                .collect::<Vec<_>>();
+            prompt::multi_select(text("prompt-extract-streams"), options.clone())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|choice| options.iter().position(|option| *option == choice))
+                .collect()
+        }
+    };
+    let extractions = plan(movie_file, &streams, &positions);
+    if extractions.is_empty() {
+        return Ok(());
+    }
+    let paths = extractions.iter().map(|extraction| extraction.path.clone());
+    cleanup
+        .guard(paths, extract(movie_file, &extractions, timeout))
+        .await?;
+    for extraction in &extractions {
+        cleanup.completed(&extraction.path);
+        println!("{}", output::quoted(&extraction.path));
+    }
+    Ok(())
+}
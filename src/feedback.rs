@@ -0,0 +1,116 @@
+//! `rate` and `report`, a vote for a downloaded subtitle or a flag on a bad one, sent
+//! through the XML-RPC api
+use crate::{
+    api::{Credentials, Session},
+    http::HttpFetch,
+    xmlrpc::Value,
+};
+use eyre::{bail, Result};
+use reqwest::Url;
+
+/// what's wrong with a reported subtitle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportReason {
+    OutOfSync,
+    WrongLanguage,
+    /// subtitles for another movie or episode
+    WrongMovie,
+    BadTranslation,
+    /// cues missing, or cut off before the end
+    Incomplete,
+    /// only what --comment says
+    Other,
+}
+
+impl ReportReason {
+    pub const ALL: [Self; 6] = [
+        Self::OutOfSync,
+        Self::WrongLanguage,
+        Self::WrongMovie,
+        Self::BadTranslation,
+        Self::Incomplete,
+        Self::Other,
+    ];
+}
+
+impl std::fmt::Display for ReportReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::OutOfSync => "out of sync",
+            Self::WrongLanguage => "wrong language",
+            Self::WrongMovie => "wrong movie",
+            Self::BadTranslation => "bad translation",
+            Self::Incomplete => "incomplete",
+            Self::Other => "other",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Feedback {
+    /// `stars` from 1 to 10, like the site's
+    Vote { subtitle_id: u64, stars: u8 },
+    /// a comment marking the subtitle bad
+    Report {
+        subtitle_id: u64,
+        reason: ReportReason,
+        comment: Option<String>,
+    },
+}
+
+impl Feedback {
+    fn call(&self) -> Result<(&'static str, Value)> {
+        match self {
+            Self::Vote { stars, .. } if !(1..=10).contains(stars) => {
+                bail!("a vote is 1 to 10 stars, not {stars}")
+            }
+            Self::Vote { subtitle_id, stars } => Ok((
+                "SubtitlesVote",
+                Value::members([
+                    ("idsubtitle", Value::string(subtitle_id.to_string())),
+                    ("score", Value::Int(i64::from(*stars))),
+                ]),
+            )),
+            Self::Report {
+                reason: ReportReason::Other,
+                comment: None,
+                ..
+            } => bail!("say what's wrong with --comment"),
+            Self::Report {
+                subtitle_id,
+                reason,
+                comment,
+            } => {
+                let comment = match (reason, comment) {
+                    (ReportReason::Other, Some(comment)) => comment.clone(),
+                    (reason, Some(comment)) => format!("{reason}: {comment}"),
+                    (reason, None) => reason.to_string(),
+                };
+                Ok((
+                    "AddComment",
+                    Value::members([
+                        ("idsubtitle", Value::string(subtitle_id.to_string())),
+                        ("comment", Value::string(comment)),
+                        ("badsubtitle", Value::Int(1)),
+                    ]),
+                ))
+            }
+        }
+    }
+}
+
+/// logged in as `credentials`, sends `feedback` and logs out. `user_agent` has to be one
+/// registered with the api
+pub async fn send(
+    http: &dyn HttpFetch,
+    url: &Url,
+    credentials: &Credentials,
+    user_agent: &str,
+    feedback: &Feedback,
+) -> Result<()> {
+    let (method, params) = feedback.call()?;
+    let session = Session::log_in(http, url, credentials, user_agent).await?;
+    let sent = session.call(method, vec![params]).await.map(|_| ());
+    session.log_out().await;
+    sent
+}
@@ -0,0 +1,172 @@
+//! the movie hash opensubtitles indexes files by: the file size plus the sums of the first and
+//! last 64 KiB as little endian words
+use eyre::{Result, WrapErr};
+use futures::{stream, StreamExt};
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+const HASH_BLK_SIZE: u64 = 65536;
+/// the first and last blocks must not overlap, the site never matches hashes of smaller files
+pub const MIN_SIZE: u64 = 2 * HASH_BLK_SIZE;
+/// files hashed at once, each is a couple of reads so a few keep a slow disk busy
+pub const HASH_CONCURRENCY: usize = 4;
+
+/// files the hash can't be computed for, found in the report's chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashError {
+    TooSmall { size: u64 },
+}
+
+impl HashError {
+    pub fn find(report: &eyre::Report) -> Option<Self> {
+        report
+            .chain()
+            .find_map(|e| e.downcast_ref::<Self>())
+            .copied()
+    }
+}
+
+impl std::fmt::Display for HashError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooSmall { size } => write!(
+                f,
+                "file too small to hash: {size} bytes, the hash needs at least {} KiB, \
+                 search by title with --query instead",
+                MIN_SIZE / 1024
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HashError {}
+
+/// how the two blocks are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// mapped into memory, falling back to reading where the filesystem refuses. only unix
+    /// maps, windows reads either way
+    Mmap,
+    /// `--no-mmap`, two seeks and reads
+    Read,
+}
+
+impl Default for Method {
+    /// mapping a whole movie only fits in a 64-bit address space, and there's no mapping on
+    /// windows yet
+    fn default() -> Self {
+        match cfg!(all(unix, target_pointer_width = "64")) {
+            true => Self::Mmap,
+            false => Self::Read,
+        }
+    }
+}
+
+/// `block` summed as little endian words
+fn words_sum(block: &[u8]) -> u64 {
+    block
+        .chunks_exact(8)
+        .map(|word| u64::from_le_bytes(word.try_into().expect("8 bytes")))
+        .fold(0u64, u64::wrapping_add)
+}
+
+/// the 64 KiB at `offset`, summed
+fn block_sum(file: &mut File, offset: u64, buf: &mut [u8]) -> Result<u64> {
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)?;
+    Ok(words_sum(buf))
+}
+
+/// seeded with the file size
+fn format_hash(fsize: u64, head: u64, tail: u64) -> String {
+    let hash_val = fsize.wrapping_add(head).wrapping_add(tail);
+    format!("{:01$x}", hash_val, 16)
+}
+
+fn create_hash(mut file: File, fsize: u64) -> Result<String> {
+    let mut buf = vec![0u8; HASH_BLK_SIZE as usize];
+    let head = block_sum(&mut file, 0, &mut buf)?;
+    let tail = block_sum(&mut file, fsize - HASH_BLK_SIZE, &mut buf)?;
+    Ok(format_hash(fsize, head, tail))
+}
+
+/// both blocks straight out of the page cache, `None` when the file can't be mapped
+#[cfg(all(unix, target_pointer_width = "64"))]
+fn mapped_hash(file: &File, fsize: u64) -> Option<String> {
+    use std::os::unix::io::AsRawFd;
+    let len = usize::try_from(fsize).ok()?;
+    // SAFETY: a private read-only mapping of the whole file, unmapped before returning. the
+    // slice is only read while mapped; a file truncated meanwhile would fault, like any mmap
+    unsafe {
+        let map = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if map == libc::MAP_FAILED {
+            return None;
+        }
+        let contents = std::slice::from_raw_parts(map as *const u8, len);
+        let block = HASH_BLK_SIZE as usize;
+        let hash = format_hash(
+            fsize,
+            words_sum(&contents[..block]),
+            words_sum(&contents[len - block..]),
+        );
+        libc::munmap(map, len);
+        Some(hash)
+    }
+}
+
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+fn mapped_hash(_file: &File, _fsize: u64) -> Option<String> {
+    None
+}
+
+/// the opensubtitles hash of the movie file, what searches by hash look for
+pub fn hash_for_file<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<String> {
+    hash_with(path, Method::default())
+}
+
+/// [`hash_for_file`] reading the blocks with `method`, the hash is the same either way
+pub fn hash_with<P: AsRef<Path> + std::fmt::Debug>(path: P, method: Method) -> Result<String> {
+    let file = std::fs::File::open(path).wrap_err("opening file")?;
+    // the size of the file opened, what's mapped is this handle's
+    let size = file.metadata().wrap_err("checking file size")?.len();
+    if size < MIN_SIZE {
+        return Err(HashError::TooSmall { size }.into());
+    }
+    match method {
+        Method::Mmap => match mapped_hash(&file, size) {
+            Some(hash) => Ok(hash),
+            None => create_hash(file, size),
+        },
+        Method::Read => create_hash(file, size),
+    }
+}
+
+/// [`hash_with`] off the async threads, the reads block for long on network storage
+pub async fn hash_file(path: PathBuf, method: Method) -> Result<String> {
+    tokio::task::spawn_blocking(move || hash_with(&path, method))
+        .await
+        .wrap_err("hashing")?
+}
+
+/// hashes of every file, in the order given, `concurrency` of them at a time
+pub async fn hash_files(
+    paths: Vec<PathBuf>,
+    method: Method,
+    concurrency: usize,
+) -> Vec<Result<String>> {
+    stream::iter(paths)
+        .map(|path| hash_file(path, method))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await
+}
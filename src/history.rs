@@ -0,0 +1,534 @@
+//! `history`, every subtitle file downloaded for a movie, kept as json in the user's data
+//! directory so a file can be traced back to the subtitle it came from
+use crate::{crawler::Candidate, output};
+use chrono::{DateTime, Utc};
+use eyre::{bail, eyre, Result, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// bumped with every change to what's stored, `MIGRATIONS` brings older files up to date
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// `MIGRATIONS[n - 1]` turns a version `n` file into a version `n + 1` one
+const MIGRATIONS: &[fn(&mut Value)] = &[runs_and_videos, selections];
+
+fn downloads_in(value: &mut Value) -> impl Iterator<Item = &mut serde_json::Map<String, Value>> {
+    value
+        .get_mut("downloads")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// version 2 groups the files of a run and remembers the movie with subtitles, every older
+/// download counts as a run of its own without one
+fn runs_and_videos(value: &mut Value) {
+    for download in downloads_in(value) {
+        let downloaded_at = download.get("downloaded_at").cloned().unwrap_or_default();
+        download.insert("run".to_string(), downloaded_at);
+        download.insert("video".to_string(), Value::Null);
+    }
+}
+
+/// version 3 remembers how the subtitle was picked, nobody knows for older downloads
+fn selections(value: &mut Value) {
+    for download in downloads_in(value) {
+        download.insert("selection".to_string(), Value::Null);
+    }
+}
+
+/// the site every download so far came from
+pub const PROVIDER: &str = "opensubtitles.org";
+
+/// what the subtitle was, as the search results listed it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    /// the release name closest to the movie's file name
+    pub release_name: Option<String>,
+    pub format: String,
+    pub uploaded_by: String,
+    pub rating: Option<f32>,
+    pub downloads: u32,
+}
+
+/// the movie with the subtitles embedded or burned in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Video {
+    pub path: PathBuf,
+    /// the movie as it was, when `--embed-in-place --backup` replaced it
+    pub backup: Option<PathBuf>,
+}
+
+/// how the subtitle was picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Selection {
+    /// `--auto`, or a `hook` or `daemon` run
+    Auto,
+    /// from the prompt
+    Asked,
+}
+
+/// one subtitle file written for a movie
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Download {
+    /// when the run that wrote it started, the same for every file of the run
+    pub run: DateTime<Utc>,
+    pub movie: PathBuf,
+    /// `None` when the movie was searched by title
+    pub movie_hash: Option<String>,
+    pub language: String,
+    pub provider: String,
+    pub subtitle_id: u64,
+    pub entry: Entry,
+    pub output: PathBuf,
+    pub downloaded_at: DateTime<Utc>,
+    /// sha-256 of the file as it was written
+    pub content_hash: String,
+    pub video: Option<Video>,
+    /// `None` for downloads recorded before it was
+    pub selection: Option<Selection>,
+}
+
+impl Download {
+    /// a record of `output`, which must be written already
+    pub fn new(
+        run: DateTime<Utc>,
+        movie: &Path,
+        movie_hash: Option<&str>,
+        language: &str,
+        candidate: &Candidate,
+        selection: Selection,
+        output: &Path,
+    ) -> Result<Self> {
+        let contents =
+            std::fs::read(output).wrap_err_with(|| format!("reading {output:?} back"))?;
+        let entry = &candidate.entry;
+        Ok(Self {
+            run,
+            movie: absolute(movie),
+            movie_hash: movie_hash.map(String::from),
+            language: language.to_string(),
+            provider: PROVIDER.to_string(),
+            subtitle_id: entry.subtitle_id,
+            entry: Entry {
+                name: entry.name.clone(),
+                release_name: candidate.release_name.clone(),
+                format: entry.format.to_string(),
+                uploaded_by: entry.uploaded_by.clone(),
+                rating: entry.rating,
+                downloads: entry.downloads,
+            },
+            output: absolute(output),
+            downloaded_at: Utc::now(),
+            content_hash: content_hash(&contents),
+            video: None,
+            selection: Some(selection),
+        })
+    }
+}
+
+/// sha-256 as lowercase hex
+pub fn content_hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// paths are stored absolute, the same movie is found from any working directory
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// the file as stored, `version` first so it can be read before the rest
+#[derive(Debug, Serialize, Deserialize)]
+struct Stored {
+    version: u32,
+    downloads: Vec<Download>,
+}
+
+/// brings the contents of a history file of any earlier version to the current one
+fn migrate(mut value: Value) -> Result<Vec<Download>> {
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| eyre!("no schema version"))? as u32;
+    if version > SCHEMA_VERSION {
+        bail!("written by a newer version of opensubtitlescli (schema {version})");
+    }
+    for migration in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+        migration(&mut value);
+    }
+    value["version"] = SCHEMA_VERSION.into();
+    serde_json::from_value::<Stored>(value)
+        .map(|stored| stored.downloads)
+        .wrap_err("reading the downloads")
+}
+
+/// how long a run waits for another to be done with the history
+const LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+/// a lock this old was left behind by a run that crashed, writing the history takes
+/// milliseconds
+const STALE_LOCK: Duration = Duration::from_secs(10);
+
+/// `history.json.lock`, held from reading the history to writing it back so runs at the same
+/// time don't drop each other's downloads. removed once dropped
+struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    async fn acquire(path: PathBuf) -> Result<Self> {
+        let started = Instant::now();
+        loop {
+            let created = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path);
+            match created {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e).wrap_err_with(|| format!("creating {path:?}")),
+            }
+            let stale = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > STALE_LOCK);
+            if stale {
+                warn!(?path, "removing the lock a crashed run left behind");
+                std::fs::remove_file(&path).ok();
+                continue;
+            }
+            if started.elapsed() > LOCK_TIMEOUT {
+                bail!("another run holds {path:?}, remove it if none is running");
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// the downloads recorded in one history file
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// `$XDG_DATA_HOME/opensubtitlescli/history.json`, `~/.local/share` without it and
+    /// `%APPDATA%` on windows
+    pub fn default_path() -> Option<PathBuf> {
+        let data = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| match cfg!(windows) {
+                true => std::env::var_os("APPDATA").map(PathBuf::from),
+                false => std::env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".local").join("share")),
+            })?;
+        Some(data.join("opensubtitlescli").join("history.json"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// oldest first, nothing when no download was recorded yet
+    pub fn load(&self) -> Result<Vec<Download>> {
+        let contents = match std::fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).wrap_err_with(|| format!("reading {:?}", self.path)),
+        };
+        serde_json::from_slice(&contents)
+            .map_err(eyre::Report::from)
+            .and_then(migrate)
+            .wrap_err_with(|| format!("reading the history in {:?}", self.path))
+    }
+
+    /// every change reads the history and writes it back under this
+    async fn lock(&self) -> Result<Lock> {
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .wrap_err_with(|| format!("creating {dir:?}"))?;
+        }
+        let mut path = self.path.clone().into_os_string();
+        path.push(".lock");
+        Lock::acquire(path.into()).await
+    }
+
+    /// written next to the history and renamed over it, readers never see half of it
+    async fn save(&self, downloads: Vec<Download>) -> Result<()> {
+        let stored = Stored {
+            version: SCHEMA_VERSION,
+            downloads,
+        };
+        output::write_atomic(&self.path, &serde_json::to_vec_pretty(&stored)?).await
+    }
+
+    pub async fn record(&self, downloads: Vec<Download>) -> Result<()> {
+        if downloads.is_empty() {
+            return Ok(());
+        }
+        let _lock = self.lock().await?;
+        let mut all = self.load()?;
+        all.extend(downloads);
+        self.save(all).await
+    }
+
+    /// downloads for the movie at `path`, or of the subtitle file at `path`
+    pub fn for_path(&self, path: &Path) -> Result<Vec<Download>> {
+        let path = absolute(path);
+        Ok(self
+            .load()?
+            .into_iter()
+            .filter(|download| download.movie == path || download.output == path)
+            .collect())
+    }
+
+    /// downloads of a file with these contents, by its [`content_hash`]. the same subtitles
+    /// come up again for a renamed copy of a movie
+    pub fn by_content_hash(&self, content_hash: &str) -> Result<Vec<Download>> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .filter(|download| download.content_hash == content_hash)
+            .collect())
+    }
+
+    /// the downloads of the latest run, for the movie at `movie` when given
+    pub fn last_run(&self, movie: Option<&Path>) -> Result<Vec<Download>> {
+        let movie = movie.map(absolute);
+        let downloads = self
+            .load()?
+            .into_iter()
+            .filter(|download| movie.as_ref().is_none_or(|movie| download.movie == *movie))
+            .collect::<Vec<_>>();
+        let Some(last) = downloads.iter().map(|download| download.run).max() else {
+            return Ok(vec![]);
+        };
+        Ok(downloads
+            .into_iter()
+            .filter(|download| download.run == last)
+            .collect())
+    }
+
+    /// the movie with subtitles the run made of `movie`
+    pub async fn record_video(&self, run: DateTime<Utc>, movie: &Path, video: Video) -> Result<()> {
+        let movie = absolute(movie);
+        let video = Video {
+            path: absolute(&video.path),
+            backup: video.backup.as_deref().map(absolute),
+        };
+        let _lock = self.lock().await?;
+        let mut downloads = self.load()?;
+        downloads
+            .iter_mut()
+            .filter(|download| download.run == run && download.movie == movie)
+            .for_each(|download| download.video = Some(video.clone()));
+        self.save(downloads).await
+    }
+
+    /// drops `forgotten` from the history
+    pub async fn forget(&self, forgotten: &[Download]) -> Result<()> {
+        let _lock = self.lock().await?;
+        let downloads = self
+            .load()?
+            .into_iter()
+            .filter(|download| !forgotten.contains(download))
+            .collect();
+        self.save(downloads).await
+    }
+
+    /// forgets every download, returns how many there were
+    pub async fn purge(&self) -> Result<usize> {
+        let _lock = self.lock().await?;
+        let count = self.load()?.len();
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(count),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e).wrap_err_with(|| format!("removing {:?}", self.path)),
+        }
+    }
+}
+
+/// one thing `undo` does to take a run back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    Remove(PathBuf),
+    /// moves the backup back over the file it was made of
+    Restore {
+        backup: PathBuf,
+        to: PathBuf,
+    },
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Remove(path) => write!(f, "remove {}", output::quoted(path)),
+            Self::Restore { backup, to } => write!(
+                f,
+                "restore {} from {}",
+                output::quoted(to),
+                output::quoted(backup)
+            ),
+        }
+    }
+}
+
+impl Step {
+    pub async fn apply(&self) -> Result<()> {
+        match self {
+            Self::Remove(path) => tokio::fs::remove_file(path)
+                .await
+                .wrap_err_with(|| format!("removing {path:?}")),
+            Self::Restore { backup, to } => tokio::fs::rename(backup, to)
+                .await
+                .wrap_err_with(|| format!("restoring {to:?} from {backup:?}")),
+        }
+    }
+}
+
+/// what takes `downloads` back, failing before anything is touched when a subtitle file was
+/// edited since. files already gone are left out, the movie with subtitles only goes with
+/// `remove_video`
+pub fn undo_steps(downloads: &[Download], remove_video: bool) -> Result<Vec<Step>> {
+    let mut steps = vec![];
+    for download in downloads {
+        let path = &download.output;
+        match std::fs::read(path) {
+            Ok(contents) if content_hash(&contents) != download.content_hash => {
+                bail!("{path:?} changed since it was downloaded, not removing it");
+            }
+            Ok(_) => steps.push(Step::Remove(path.clone())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).wrap_err_with(|| format!("reading {path:?}")),
+        }
+    }
+    let videos = downloads
+        .iter()
+        .filter_map(|download| download.video.as_ref())
+        .fold(vec![], |mut videos: Vec<&Video>, video| {
+            if !videos.contains(&video) {
+                videos.push(video);
+            }
+            videos
+        });
+    for video in videos {
+        match &video.backup {
+            Some(backup) if backup.exists() => steps.push(Step::Restore {
+                backup: backup.clone(),
+                to: video.path.clone(),
+            }),
+            Some(backup) => bail!(
+                "the backup {backup:?} is gone, can't restore {:?}",
+                video.path
+            ),
+            None if remove_video && video.path.exists() => {
+                steps.push(Step::Remove(video.path.clone()))
+            }
+            None => {}
+        }
+    }
+    Ok(steps)
+}
+
+/// totals and breakdowns of the downloads, for `stats`. every breakdown is largest first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    /// subtitle files, a run unpacking a season writes a file per episode
+    pub downloads: usize,
+    pub per_language: Vec<(String, usize)>,
+    pub per_provider: Vec<(String, usize)>,
+    /// `2024-01`, oldest first
+    pub per_month: Vec<(String, usize)>,
+    pub top_uploaders: Vec<(String, usize)>,
+    /// of the entries the site listed a rating for
+    pub average_rating: Option<f32>,
+    pub rated: usize,
+    pub auto: usize,
+    pub asked: usize,
+    /// recorded before the selection was
+    pub unknown_selection: usize,
+}
+
+/// how many uploaders [`Stats`] lists
+pub const TOP_UPLOADERS: usize = 10;
+
+/// `(key, count)` of `keys`, largest count first, ties in key order
+fn counted(keys: impl Iterator<Item = String>) -> Vec<(String, usize)> {
+    let mut counts = keys.counts().into_iter().collect::<Vec<_>>();
+    counts.sort_by(|(left, left_count), (right, right_count)| {
+        right_count.cmp(left_count).then_with(|| left.cmp(right))
+    });
+    counts
+}
+
+impl Stats {
+    /// of the downloads made since the start of `since`
+    pub fn of(downloads: &[Download], since: Option<chrono::NaiveDate>) -> Self {
+        let downloads = downloads
+            .iter()
+            .filter(|download| {
+                since.is_none_or(|since| download.downloaded_at.date_naive() >= since)
+            })
+            .collect::<Vec<_>>();
+        let ratings = downloads
+            .iter()
+            .filter_map(|download| download.entry.rating)
+            .collect::<Vec<_>>();
+        let selections = downloads.iter().map(|download| download.selection).counts();
+        let mut per_month = counted(
+            downloads
+                .iter()
+                .map(|download| download.downloaded_at.format("%Y-%m").to_string()),
+        );
+        per_month.sort();
+        let mut top_uploaders = counted(
+            downloads
+                .iter()
+                .map(|download| download.entry.uploaded_by.clone()),
+        );
+        top_uploaders.truncate(TOP_UPLOADERS);
+        Self {
+            downloads: downloads.len(),
+            per_language: counted(downloads.iter().map(|download| download.language.clone())),
+            per_provider: counted(downloads.iter().map(|download| download.provider.clone())),
+            per_month,
+            top_uploaders,
+            average_rating: match ratings.is_empty() {
+                true => None,
+                false => Some(ratings.iter().sum::<f32>() / ratings.len() as f32),
+            },
+            rated: ratings.len(),
+            auto: selections.get(&Some(Selection::Auto)).copied().unwrap_or(0),
+            asked: selections
+                .get(&Some(Selection::Asked))
+                .copied()
+                .unwrap_or(0),
+            unknown_selection: selections.get(&None).copied().unwrap_or(0),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! the commands reading the history: `history`, `stats` and `undo`, and the download `rate`
+//! and `report` are about
+use crate::{
+    history::{self, Download, History, Stats},
+    messages::{filled, text},
+    output::{self, print_table, ListFormat},
+    prompt,
+};
+use eyre::{bail, eyre, Result};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// `--history-file` or the default location
+pub fn at(history_file: Option<PathBuf>) -> Result<History> {
+    history_file
+        .or_else(History::default_path)
+        .map(History::new)
+        .ok_or_else(|| eyre!(text("error-no-history-dir")))
+}
+
+/// the download of the subtitle file at `path`, or the latest one for the movie there.
+/// `choose` asks which of the movie's downloads instead
+pub fn downloaded(history: &History, path: &Path, choose: bool) -> Result<Download> {
+    let mut downloads = history.for_path(path)?;
+    downloads.sort_by_key(|download| std::cmp::Reverse(download.downloaded_at));
+    if downloads.is_empty() {
+        bail!(filled(
+            "error-not-recorded",
+            &[
+                ("path", &output::quoted(path)),
+                ("history", &output::quoted(history.path()))
+            ]
+        ));
+    }
+    match choose && downloads.len() > 1 {
+        true => Ok(prompt::select(
+            text("prompt-which-download"),
+            downloads.into_iter().map(Chosen).collect(),
+        )?
+        .0),
+        false => Ok(downloads.swap_remove(0)),
+    }
+}
+
+/// a download as `rate` and `report` offer it
+struct Chosen(Download);
+
+impl std::fmt::Display for Chosen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let download = &self.0;
+        write!(
+            f,
+            "{} {} {}",
+            download.downloaded_at.format("%Y-%m-%d %H:%M"),
+            download.entry.name,
+            output::displayed(&download.output)
+        )
+    }
+}
+
+/// `history list`
+pub fn list(history: &History, format: ListFormat) -> Result<()> {
+    let downloads = history.load()?;
+    if let ListFormat::Json = format {
+        println!("{}", serde_json::to_string_pretty(&downloads)?);
+        return Ok(());
+    }
+    let header = [
+        "column-downloaded",
+        "column-language",
+        "column-subtitle",
+        "column-movie",
+        "column-output",
+    ]
+    .map(|key| text(key).to_string());
+    let rows = downloads
+        .iter()
+        .map(|download| {
+            [
+                download.downloaded_at.format("%Y-%m-%d %H:%M").to_string(),
+                download.language.clone(),
+                download.subtitle_id.to_string(),
+                download.movie.display().to_string(),
+                download.output.display().to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(header, &rows);
+    Ok(())
+}
+
+/// `history show`, the downloads for the movie or the subtitle file at `path`
+pub fn show(history: &History, path: &Path) -> Result<()> {
+    let downloads = history.for_path(path)?;
+    if downloads.is_empty() {
+        bail!(filled(
+            "error-nothing-downloaded-for",
+            &[("path", &format!("{path:?}"))]
+        ));
+    }
+    for download in downloads {
+        let entry = &download.entry;
+        println!(
+            "{}",
+            filled(
+                "show-download",
+                &[
+                    (
+                        "time",
+                        &download.downloaded_at.format("%Y-%m-%d %H:%M:%S UTC")
+                    ),
+                    ("language", &download.language),
+                    ("subtitle", &download.subtitle_id),
+                    ("provider", &download.provider),
+                ]
+            )
+        );
+        let rating = entry
+            .rating
+            .map_or_else(|| "-".to_string(), |rating| rating.to_string());
+        let fields = [
+            ("show-movie", Some(output::quoted(&download.movie))),
+            ("show-movie-hash", download.movie_hash.clone()),
+            ("show-name", Some(entry.name.clone())),
+            ("show-release", entry.release_name.clone()),
+            (
+                "show-format",
+                Some(filled(
+                    "show-entry",
+                    &[
+                        ("format", &entry.format),
+                        ("uploader", &entry.uploaded_by),
+                        ("downloads", &entry.downloads),
+                        ("rating", &rating),
+                    ],
+                )),
+            ),
+            ("show-output", Some(output::quoted(&download.output))),
+            ("show-content-hash", Some(download.content_hash.clone())),
+        ];
+        let width = fields
+            .iter()
+            .map(|(label, _)| text(label).chars().count() + 1)
+            .max()
+            .unwrap_or_default();
+        for (label, value) in fields {
+            if let Some(value) = value {
+                let label = format!("{}:", text(label));
+                println!("  {label:<width$}  {value}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `history purge`
+pub async fn purge(history: &History) -> Result<()> {
+    let count = history.purge().await?;
+    println!("{}", filled("history-purged", &[("count", &count)]));
+    Ok(())
+}
+
+/// `stats`
+pub fn stats(
+    history: &History,
+    since: Option<chrono::NaiveDate>,
+    format: ListFormat,
+) -> Result<()> {
+    let stats = Stats::of(&history.load()?, since);
+    if let ListFormat::Json = format {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+    let count = stats.downloads;
+    match since {
+        Some(day) => println!(
+            "{}",
+            filled("stats-downloads-since", &[("count", &count), ("day", &day)])
+        ),
+        None => println!("{}", filled("stats-downloads", &[("count", &count)])),
+    }
+    if stats.downloads == 0 {
+        return Ok(());
+    }
+    if let Some(rating) = stats.average_rating {
+        let rating = format!("{rating:.1}");
+        let rated = &stats.rated;
+        println!(
+            "{}",
+            filled("stats-rating", &[("rating", &rating), ("rated", rated)])
+        );
+    }
+    println!(
+        "{}",
+        filled(
+            "stats-selection",
+            &[
+                ("auto", &stats.auto),
+                ("asked", &stats.asked),
+                ("unknown", &stats.unknown_selection),
+            ]
+        )
+    );
+    let breakdowns = [
+        ("column-language", &stats.per_language),
+        ("column-provider", &stats.per_provider),
+        ("column-month", &stats.per_month),
+        ("column-uploader", &stats.top_uploaders),
+    ];
+    for (column, counts) in breakdowns {
+        println!();
+        let rows = counts
+            .iter()
+            .map(|(key, count)| [key.clone(), count.to_string()])
+            .collect::<Vec<_>>();
+        let header = [text(column), text("column-downloads")].map(String::from);
+        print_table(header, &rows);
+    }
+    Ok(())
+}
+
+/// `undo`, removes what the latest run for the movie wrote
+pub async fn undo(
+    history: &History,
+    movie_file: Option<&Path>,
+    remove_video: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let downloads = history.last_run(movie_file)?;
+    if downloads.is_empty() {
+        match movie_file {
+            Some(movie_file) => bail!(filled(
+                "error-nothing-downloaded-for",
+                &[("path", &format!("{movie_file:?}"))]
+            )),
+            None => bail!(text("error-nothing-downloaded")),
+        }
+    }
+    let steps = history::undo_steps(&downloads, remove_video)?;
+    if let Some(video) = downloads
+        .iter()
+        .filter_map(|download| download.video.as_ref())
+        .find(|video| video.backup.is_none() && !remove_video)
+    {
+        info!(path = ?video.path, "the movie with subtitles is kept, --remove-video removes it");
+    }
+    if steps.is_empty() {
+        println!("{}", text("undo-nothing"));
+    }
+    for step in &steps {
+        match dry_run {
+            true => println!("would {step}"),
+            false => {
+                step.apply().await?;
+                println!("{step}");
+            }
+        }
+    }
+    if !dry_run {
+        history.forget(&downloads).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+//! `hook`, what a torrent client runs once a download finished
+use crate::{
+    hash,
+    notify::{self, Notification},
+    release,
+    timings::Timings,
+    unattended::{Ahead, Outcome, Unattended},
+};
+use eyre::{bail, Result, WrapErr};
+use itertools::Itertools;
+use std::{
+    ffi::OsString,
+    fs,
+    future::Future,
+    path::{Path, PathBuf},
+};
+use tracing::{error, info};
+
+/// the torrent client running the hook, they pass the finished download differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TorrentClient {
+    /// the content path as an argument, `hook --client qbittorrent "%F"`
+    Qbittorrent,
+    /// `TR_TORRENT_DIR` and `TR_TORRENT_NAME`, unless a path is given
+    Transmission,
+}
+
+impl TorrentClient {
+    /// the finished download, a single file or the torrent's folder. `env` looks up the
+    /// client's variables
+    pub fn content_path(
+        self,
+        argument: Option<PathBuf>,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> Result<PathBuf> {
+        if let Some(path) = argument {
+            return Ok(path);
+        }
+        match self {
+            Self::Qbittorrent => {
+                bail!("qbittorrent passes the content path as an argument, add \"%F\" to the hook")
+            }
+            Self::Transmission => match (env("TR_TORRENT_DIR"), env("TR_TORRENT_NAME")) {
+                (Some(dir), Some(name)) => Ok(Path::new(&dir).join(name)),
+                _ => bail!(
+                    "TR_TORRENT_DIR and TR_TORRENT_NAME aren't set, is transmission running the \
+                     hook?"
+                ),
+            },
+        }
+    }
+}
+
+/// `sample.mkv`, `Movie.2019-sample.mkv` or anything in a `Samples` folder, `path` relative to
+/// the download so the torrent's own name doesn't count
+pub fn is_sample(path: &Path) -> bool {
+    path.iter()
+        .filter_map(|component| component.to_str())
+        .flat_map(release::tokens)
+        .any(|token| token == "sample" || token == "samples")
+}
+
+/// video files of the finished download, samples left out, sorted
+pub fn video_files(content_path: &Path) -> Result<Vec<PathBuf>> {
+    let metadata =
+        fs::metadata(content_path).wrap_err_with(|| format!("reading {content_path:?}"))?;
+    let (root, files) = match metadata.is_dir() {
+        true => (content_path, files_in(content_path)?),
+        false => (
+            content_path.parent().unwrap_or(Path::new("")),
+            vec![content_path.to_path_buf()],
+        ),
+    };
+    Ok(files
+        .into_iter()
+        .filter(|file| release::is_video(file))
+        .filter(|file| !is_sample(file.strip_prefix(root).unwrap_or(file)))
+        .sorted()
+        .collect())
+}
+
+/// every file under `dir`, symlinked folders aren't followed
+fn files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir).wrap_err_with(|| format!("listing {dir:?}"))? {
+            let entry = entry.wrap_err_with(|| format!("listing {dir:?}"))?;
+            match entry.file_type()?.is_dir() {
+                true => dirs.push(entry.path()),
+                false => files.push(entry.path()),
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// `hook`, every video of the download run by `run`, with the flags `unattended` gives it.
+/// a download without videos is nothing to do, not an error the torrent client would flag.
+/// `notify` is the language the whole download is notified about
+pub async fn run<C, F>(
+    content_path: &Path,
+    unattended: impl Fn(PathBuf) -> Result<Unattended<C>>,
+    run: impl Fn(C, Ahead) -> F,
+    hash_method: hash::Method,
+    notify: Option<&str>,
+    timings: &Timings,
+) -> Result<()>
+where
+    F: Future<Output = Result<Outcome>>,
+{
+    let videos = video_files(content_path)?;
+    if videos.is_empty() {
+        info!(?content_path, "no videos in the download, nothing to do");
+        return Ok(());
+    }
+    let movies = videos
+        .iter()
+        .map(|video| unattended(video.clone()))
+        .collect::<Vec<_>>();
+    // the audio first, a video skipped for it is never read
+    let mut audio = vec![];
+    for (video, movie) in videos.iter().zip(&movies) {
+        audio.push(match movie {
+            Ok(movie) => Ahead::audio(movie, video).await,
+            Err(_) => Ok(None),
+        });
+    }
+    let hashed = |movie: &Result<Unattended<C>>, audio: &Result<Option<String>>| matches!(movie, Ok(movie) if Ahead::hashes(movie, audio));
+    let to_hash = videos
+        .iter()
+        .zip(movies.iter().zip(&audio))
+        .filter(|(_, (movie, audio))| hashed(movie, audio))
+        .map(|(video, _)| video.clone())
+        .collect::<Vec<_>>();
+    // the reads of one video don't wait for the ones before it
+    let mut hashes = match to_hash.is_empty() {
+        true => vec![],
+        false => {
+            let hashing = hash::hash_files(to_hash, hash_method, hash::HASH_CONCURRENCY);
+            timings.time("hashing", hashing).await
+        }
+    }
+    .into_iter();
+    let (mut skipped, mut failed) = (vec![], vec![]);
+    for (video, (movie, audio)) in videos.iter().zip(movies.into_iter().zip(audio)) {
+        let ahead = Ahead {
+            hashed: match hashed(&movie, &audio) {
+                true => hashes.next(),
+                false => None,
+            },
+            audio,
+        };
+        let ran = async { run(movie?.run, ahead).await };
+        match Box::pin(ran).await {
+            Ok(Outcome::Done) => {}
+            Ok(Outcome::Skipped) => skipped.push(video.clone()),
+            Err(report) => {
+                error!(?video, ?report, "getting subtitles failed");
+                failed.push(video.clone());
+            }
+        }
+    }
+    if let Some(language) = notify {
+        let batch = Notification::batch(language, videos.len(), &skipped, &failed);
+        notify::send(batch).await;
+    }
+    match failed.len() {
+        0 => Ok(()),
+        failed => bail!("{failed} of {} videos got no subtitles", videos.len()),
+    }
+}
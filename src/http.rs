@@ -0,0 +1,353 @@
+//! the requests the crawler makes, behind a trait so tests can answer them without a network
+use eyre::{bail, eyre, Result, WrapErr};
+use futures::future::BoxFuture;
+use reqwest::Url;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+use tokio::time::Instant;
+
+/// `(name, value)` pairs sent along with a request
+pub type Headers = Vec<(String, String)>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub url: Url,
+    pub headers: Headers,
+    /// posted when there is one, a plain get otherwise
+    pub body: Option<Vec<u8>>,
+}
+
+impl Request {
+    pub fn get(url: Url) -> Self {
+        Self {
+            url,
+            headers: vec![],
+            body: None,
+        }
+    }
+
+    pub fn post(url: Url, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            body: Some(body.into()),
+            ..Self::get(url)
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// `body` is a `String` for pages and bytes for downloads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response<B> {
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// `Retry-After` in seconds, sent along with `429 Too Many Requests`
+    pub retry_after: Option<Duration>,
+    pub body: B,
+}
+
+/// a download larger than the limit it was made with, `.0` is the size seen so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLarge(pub u64);
+
+impl std::fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "response of {} bytes is too large", self.0)
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
+/// `429 Too Many Requests`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    pub retry_after: Option<Duration>,
+}
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.retry_after {
+            Some(retry_after) => write!(
+                f,
+                "too many requests, the site asks to retry after {}s",
+                retry_after.as_secs()
+            ),
+            None => f.write_str("too many requests"),
+        }
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// how pages and archives are fetched, and api calls posted
+pub trait HttpFetch: std::fmt::Debug + Send + Sync {
+    /// the body decoded as text, in the charset the response declares
+    fn get_text(&self, request: Request) -> BoxFuture<'_, Result<Response<String>>>;
+    /// the raw body, failing with [`TooLarge`] as soon as it grows past `max_size` bytes
+    fn get_bytes(
+        &self,
+        request: Request,
+        max_size: u64,
+    ) -> BoxFuture<'_, Result<Response<Vec<u8>>>>;
+}
+
+/// the real thing
+/// whether this build has a tls backend, without one https urls are refused
+pub const TLS: bool = cfg!(any(feature = "native-tls", feature = "rustls-tls"));
+
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestFetch {
+    client: reqwest::Client,
+}
+
+impl ReqwestFetch {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+
+    async fn send(&self, request: Request) -> Result<reqwest::Response> {
+        // reqwest's own error doesn't say it's the build
+        if request.url.scheme() == "https" && !TLS {
+            bail!(
+                "{} is served over https, this build has no tls. build it with the `api-backend` \
+                 feature",
+                request.url
+            );
+        }
+        let builder = match request.body {
+            Some(body) => self.client.post(request.url).body(body),
+            None => self.client.get(request.url),
+        };
+        request
+            .headers
+            .iter()
+            .fold(builder, |builder, (name, value)| {
+                builder.header(name, value)
+            })
+            .send()
+            .await
+            .wrap_err("fetching")
+    }
+}
+
+fn content_type(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+/// only the seconds form, servers rarely send an http date
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+impl HttpFetch for ReqwestFetch {
+    fn get_text(&self, request: Request) -> BoxFuture<'_, Result<Response<String>>> {
+        Box::pin(async move {
+            let response = self.send(request).await?;
+            let status = response.status().as_u16();
+            let content_type = content_type(&response);
+            let retry_after = retry_after(&response);
+            let body = response.text().await.wrap_err("parsing page string")?;
+            Ok(Response {
+                status,
+                content_type,
+                retry_after,
+                body,
+            })
+        })
+    }
+
+    fn get_bytes(
+        &self,
+        request: Request,
+        max_size: u64,
+    ) -> BoxFuture<'_, Result<Response<Vec<u8>>>> {
+        Box::pin(async move {
+            let mut response = self.send(request).await?;
+            let status = response.status().as_u16();
+            let content_type = content_type(&response);
+            let retry_after = retry_after(&response);
+            if let Some(size) = response.content_length().filter(|size| *size > max_size) {
+                return Err(TooLarge(size).into());
+            }
+            let mut body = vec![];
+            while let Some(chunk) = response.chunk().await.wrap_err("downloading")? {
+                body.extend_from_slice(&chunk);
+                if body.len() as u64 > max_size {
+                    return Err(TooLarge(body.len() as u64).into());
+                }
+            }
+            Ok(Response {
+                status,
+                content_type,
+                retry_after,
+                body,
+            })
+        })
+    }
+}
+
+/// what [`FakeHttp`] answers a request with
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub retry_after: Option<Duration>,
+    pub body: Vec<u8>,
+    /// waited out before answering
+    pub delay: Duration,
+    /// fails the request with this message instead
+    pub error: Option<String>,
+}
+
+impl Reply {
+    /// `200 OK` with `body`
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::status(200, body)
+    }
+
+    pub fn status(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            content_type: None,
+            retry_after: None,
+            body: body.into(),
+            delay: Duration::ZERO,
+            error: None,
+        }
+    }
+
+    /// a transport error, like a dropped connection
+    pub fn error(message: &str) -> Self {
+        Self {
+            error: Some(message.to_string()),
+            ..Self::status(0, vec![])
+        }
+    }
+
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// a request [`FakeHttp`] received
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub request: Request,
+    /// tokio's clock, so paused time in tests still orders and spaces them
+    pub at: Instant,
+}
+
+/// answers from replies scripted per url and remembers every request, for tests
+///
+/// replies to a url are used up in order, the last one keeps answering after that. urls
+/// nobody scripted fail the request
+#[derive(Debug, Default)]
+pub struct FakeHttp {
+    replies: Mutex<HashMap<Url, VecDeque<Reply>>>,
+    requests: Mutex<Vec<Recorded>>,
+}
+
+impl FakeHttp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// queues `reply` for `url`
+    pub fn reply(&self, url: &str, reply: Reply) -> &Self {
+        let url = url.parse::<Url>().expect("valid url");
+        self.replies
+            .lock()
+            .expect("not poisoned")
+            .entry(url)
+            .or_default()
+            .push_back(reply);
+        self
+    }
+
+    /// every request so far, oldest first
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.requests.lock().expect("not poisoned").clone()
+    }
+
+    async fn answer(&self, request: Request) -> Result<Response<Vec<u8>>> {
+        self.requests.lock().expect("not poisoned").push(Recorded {
+            request: request.clone(),
+            at: Instant::now(),
+        });
+        let reply = {
+            let mut replies = self.replies.lock().expect("not poisoned");
+            let queue = replies
+                .get_mut(&request.url)
+                .ok_or_else(|| eyre!("no reply scripted for {}", request.url))?;
+            match queue.len() {
+                1 => queue.front().cloned(),
+                _ => queue.pop_front(),
+            }
+            .ok_or_else(|| eyre!("no reply scripted for {}", request.url))?
+        };
+        tokio::time::sleep(reply.delay).await;
+        if let Some(message) = reply.error {
+            return Err(eyre!(message)).wrap_err("fetching");
+        }
+        Ok(Response {
+            status: reply.status,
+            content_type: reply.content_type,
+            retry_after: reply.retry_after,
+            body: reply.body,
+        })
+    }
+}
+
+impl HttpFetch for FakeHttp {
+    fn get_text(&self, request: Request) -> BoxFuture<'_, Result<Response<String>>> {
+        Box::pin(async move {
+            let response = self.answer(request).await?;
+            Ok(Response {
+                status: response.status,
+                content_type: response.content_type,
+                retry_after: response.retry_after,
+                body: String::from_utf8_lossy(&response.body).into_owned(),
+            })
+        })
+    }
+
+    fn get_bytes(
+        &self,
+        request: Request,
+        max_size: u64,
+    ) -> BoxFuture<'_, Result<Response<Vec<u8>>>> {
+        Box::pin(async move {
+            let response = self.answer(request).await?;
+            match response.body.len() as u64 > max_size {
+                true => Err(TooLarge(response.body.len() as u64).into()),
+                false => Ok(response),
+            }
+        })
+    }
+}
@@ -0,0 +1,215 @@
+//! guessing the language of cue text from its script and its most common words, good enough
+//! to catch an upload labeled with the wrong language
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// fewer words than this aren't worth guessing about
+const MIN_WORDS: usize = 80;
+/// share of the words that must be in one of the lists
+const MIN_HITS_RATIO: f64 = 0.08;
+/// how many times the runner-up's score the best guess needs
+const MIN_LEAD: f64 = 1.5;
+/// share of the letters a script needs to decide the language on its own
+const SCRIPT_RATIO: f64 = 0.5;
+
+/// opensubtitles codes languages are reported as, with frequent words in subtitles
+const STOPWORDS: &[(&str, &str)] = &[
+    (
+        "eng",
+        "the and you that what this is are have it's don't i'm with for not was your we he they know just",
+    ),
+    (
+        "pol",
+        "nie się jest że co jak tak ale na mnie jestem ty już czy tylko mi być wiem dobrze tu może był",
+    ),
+    (
+        "ger",
+        "und ich nicht das ist sie die der du ein es zu mir wir was ja mit den hier auf aber habe",
+    ),
+    (
+        "fre",
+        "je pas vous le la les est et que tu c'est une un de ne il qui pour moi dans du suis",
+    ),
+    (
+        "spa",
+        "que de no el la es y en lo un por qué me una te los se con para está pero eso",
+    ),
+    (
+        "ita",
+        "che non di il è e la un per mi sono ho ma cosa lo ti hai questo una qui bene sei",
+    ),
+    (
+        "por",
+        "que não de o é a um e você eu se para uma com me os isso está do em mas muito",
+    ),
+    (
+        "dut",
+        "de het een ik je is niet en dat van wat we in op ze maar zijn dit hij met naar heb",
+    ),
+    (
+        "cze",
+        "je to se že na co ne jsem v a jsi tak mi jak být tady ale ty já vás proč není",
+    ),
+    (
+        "slo",
+        "je to sa že na čo nie som v a si tak mi ako tu ale ty ja vás prečo už len",
+    ),
+    (
+        "hun",
+        "a az nem hogy és egy is meg ez van de mi csak már ne igen még jó kell mit vagy itt",
+    ),
+    (
+        "rum",
+        "nu să de că e este și în o un la ce pe mai am te ai bine asta tu cu eu",
+    ),
+    (
+        "swe",
+        "och det att jag är inte du som en på har vi med för vad så den han kan här om ska",
+    ),
+    (
+        "nor",
+        "og det er jeg ikke du en på har vi med for hva så den han kan her å til meg deg",
+    ),
+    (
+        "dan",
+        "og det er jeg ikke du en på har vi med for hvad så den han kan her at til mig dig",
+    ),
+    (
+        "fin",
+        "on ja ei se että en mitä olen sinä minä hän oli me mutta nyt tämä kun niin vain no jos täällä",
+    ),
+    (
+        "tur",
+        "bir ve bu ne ben değil mi sen çok için o da de var ama şey evet hayır misin beni seni burada",
+    ),
+    (
+        "scc",
+        "je da se ne to sam što i u ti mi si nije ali sve ovo kako šta ovdje ovde jesi će је шта сам ово",
+    ),
+    (
+        "slv",
+        "je da se ne to sem kaj in v ti mi si ni ampak vse kako tukaj bo že pa zdaj sva",
+    ),
+    (
+        "rus",
+        "не что я ты это в и на он мы с как да вы так мне все но нет она тебя здесь",
+    ),
+    (
+        "ukr",
+        "не що я ти це в і на він ми з як так ви мені все але ні вона тебе тут є",
+    ),
+    (
+        "bul",
+        "не да е и на се това ли за ти си съм какво ще много тук но от с той сме имам",
+    ),
+];
+
+/// languages told apart by their script alone
+fn script_language(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{0370}'..='\u{03ff}' => "gre",
+        '\u{0590}'..='\u{05ff}' => "heb",
+        '\u{0600}'..='\u{06ff}' => "ara",
+        '\u{0e00}'..='\u{0e7f}' => "tha",
+        '\u{3040}'..='\u{30ff}' => "jpn",
+        '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}' => "kor",
+        '\u{4e00}'..='\u{9fff}' => "chi",
+        _ => return None,
+    })
+}
+
+/// the code languages too close to tell apart are compared by, `None` for languages that
+/// can't be guessed at all
+pub fn family(code: &str) -> Option<&'static str> {
+    Some(match code.trim().to_lowercase().as_str() {
+        "eng" | "en" => "eng",
+        "pol" | "pl" => "pol",
+        "ger" | "deu" | "de" => "ger",
+        "fre" | "fra" | "fr" => "fre",
+        "spa" | "spn" | "es" => "spa",
+        "ita" | "it" => "ita",
+        "por" | "pob" | "pom" | "pb" | "pt" => "por",
+        "dut" | "nld" | "nl" => "dut",
+        "cze" | "ces" | "cs" => "cze",
+        "slo" | "slk" | "sk" => "slo",
+        "hun" | "hu" => "hun",
+        "rum" | "ron" | "ro" => "rum",
+        "swe" | "sv" => "swe",
+        // norwegian and danish subtitles share most of their words
+        "nor" | "nob" | "nno" | "no" | "dan" | "da" => "nor",
+        "fin" | "fi" => "fin",
+        "tur" | "tr" => "tur",
+        // bosnian, croatian and serbian can't be told apart by their words
+        "scc" | "srp" | "hrv" | "bos" | "sr" | "hr" | "bs" => "scc",
+        "slv" | "sl" => "slv",
+        "rus" | "ru" => "rus",
+        "ukr" | "uk" => "ukr",
+        "bul" | "bg" => "bul",
+        "gre" | "ell" | "el" => "gre",
+        "heb" | "he" => "heb",
+        "ara" | "ar" => "ara",
+        "tha" | "th" => "tha",
+        "jpn" | "ja" => "jpn",
+        "kor" | "ko" => "kor",
+        "chi" | "zho" | "zht" | "zhe" | "zh" => "chi",
+        _ => return None,
+    })
+}
+
+/// the language family of the text, `None` when there's too little of it or no guess stands
+/// out
+pub fn detect(text: &str) -> Option<&'static str> {
+    let letters = text
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .collect::<Vec<_>>();
+    let scripts = letters.iter().filter_map(|c| script_language(*c)).counts();
+    // kanji next to kana is still japanese
+    let script = match scripts.contains_key("jpn") {
+        true => Some(("jpn", scripts.values().sum::<usize>())),
+        false => scripts.into_iter().max_by_key(|(_, count)| *count),
+    };
+    if let Some((language, count)) = script {
+        if count as f64 >= letters.len() as f64 * SCRIPT_RATIO {
+            return Some(language);
+        }
+    }
+    let words = text
+        .split(|c: char| !c.is_alphabetic() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    if words.len() < MIN_WORDS {
+        return None;
+    }
+    let mut hits = 0;
+    let mut scores = HashMap::<&str, f64>::new();
+    for word in &words {
+        let families = STOPWORDS
+            .iter()
+            .filter(|(_, stopwords)| {
+                stopwords
+                    .split_whitespace()
+                    .any(|stopword| stopword == word)
+            })
+            .map(|(code, _)| family(code).expect("listed"))
+            .unique()
+            .collect::<Vec<_>>();
+        if !families.is_empty() {
+            hits += 1;
+        }
+        // words many languages share say little about which one it is
+        for family in &families {
+            *scores.entry(family).or_default() += 1.0 / families.len() as f64;
+        }
+    }
+    let scores = scores
+        .into_iter()
+        .sorted_by(|a, b| b.1.total_cmp(&a.1))
+        .collect::<Vec<_>>();
+    let (best, best_score) = *scores.first()?;
+    let runner_up = scores.get(1).map_or(0.0, |(_, score)| *score);
+    let confident =
+        hits as f64 >= words.len() as f64 * MIN_HITS_RATIO && best_score >= runner_up * MIN_LEAD;
+    confident.then_some(best)
+}
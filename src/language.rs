@@ -0,0 +1,276 @@
+//! language codes, in the forms opensubtitles, containers and people use them
+use serde::{Deserialize, Serialize};
+
+/// language code as used by opensubtitles, lowercase (`pol`, `eng`, `pb`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LanguageCode(String);
+
+impl LanguageCode {
+    pub fn new(code: &str) -> Self {
+        Self(code.trim().to_lowercase())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// ISO 639-2/B, what containers tag tracks with, from an opensubtitles code, an ISO
+    /// 639-1 one or an english name (`en`, `english`, `eng` all give `eng`)
+    pub fn iso639_2(&self) -> Option<&'static str> {
+        Some(match self.0.as_str() {
+            "eng" | "en" | "english" => "eng",
+            "pol" | "pl" | "polish" => "pol",
+            "ger" | "deu" | "de" | "german" => "ger",
+            "fre" | "fra" | "fr" | "french" => "fre",
+            "spa" | "spn" | "es" | "spanish" => "spa",
+            "ita" | "it" | "italian" => "ita",
+            "por" | "pob" | "pom" | "pb" | "pt" | "portuguese" => "por",
+            "dut" | "nld" | "nl" | "dutch" => "dut",
+            "cze" | "ces" | "cs" | "czech" => "cze",
+            "slo" | "slk" | "sk" | "slovak" => "slo",
+            "hun" | "hu" | "hungarian" => "hun",
+            "rum" | "ron" | "ro" | "romanian" => "rum",
+            "swe" | "sv" | "swedish" => "swe",
+            "nor" | "nob" | "nno" | "no" | "norwegian" => "nor",
+            "dan" | "da" | "danish" => "dan",
+            "fin" | "fi" | "finnish" => "fin",
+            "tur" | "tr" | "turkish" => "tur",
+            "hrv" | "hr" | "croatian" => "hrv",
+            "bos" | "bs" | "bosnian" => "bos",
+            // opensubtitles' code for serbian predates `srp`
+            "scc" | "srp" | "sr" | "serbian" => "srp",
+            "slv" | "sl" | "slovenian" => "slv",
+            "rus" | "ru" | "russian" => "rus",
+            "ukr" | "uk" | "ukrainian" => "ukr",
+            "bul" | "bg" | "bulgarian" => "bul",
+            "gre" | "ell" | "el" | "greek" => "gre",
+            "heb" | "he" | "hebrew" => "heb",
+            "ara" | "ar" | "arabic" => "ara",
+            "per" | "fas" | "fa" | "persian" => "per",
+            "hin" | "hi" | "hindi" => "hin",
+            "tha" | "th" | "thai" => "tha",
+            "vie" | "vi" | "vietnamese" => "vie",
+            "ind" | "id" | "indonesian" => "ind",
+            "may" | "msa" | "ms" | "malay" => "may",
+            "chi" | "zho" | "zht" | "zhe" | "zh" | "chinese" => "chi",
+            "jpn" | "ja" | "japanese" => "jpn",
+            "kor" | "ko" | "korean" => "kor",
+            "est" | "et" | "estonian" => "est",
+            "lav" | "lv" | "latvian" => "lav",
+            "lit" | "lt" | "lithuanian" => "lit",
+            "cat" | "ca" | "catalan" => "cat",
+            "ice" | "isl" | "is" | "icelandic" => "ice",
+            "mac" | "mkd" | "mk" | "macedonian" => "mac",
+            "alb" | "sqi" | "sq" | "albanian" => "alb",
+            _ => return None,
+        })
+    }
+
+    /// ISO 639-1, what translation services take, for the codes [`Self::iso639_2`] knows
+    pub fn iso639_1(&self) -> Option<&'static str> {
+        Some(match self.iso639_2()? {
+            "eng" => "en",
+            "pol" => "pl",
+            "ger" => "de",
+            "fre" => "fr",
+            "spa" => "es",
+            "ita" => "it",
+            "por" => "pt",
+            "dut" => "nl",
+            "cze" => "cs",
+            "slo" => "sk",
+            "hun" => "hu",
+            "rum" => "ro",
+            "swe" => "sv",
+            "nor" => "no",
+            "dan" => "da",
+            "fin" => "fi",
+            "tur" => "tr",
+            "hrv" => "hr",
+            "bos" => "bs",
+            "srp" => "sr",
+            "slv" => "sl",
+            "rus" => "ru",
+            "ukr" => "uk",
+            "bul" => "bg",
+            "gre" => "el",
+            "heb" => "he",
+            "ara" => "ar",
+            "per" => "fa",
+            "hin" => "hi",
+            "tha" => "th",
+            "vie" => "vi",
+            "ind" => "id",
+            "may" => "ms",
+            "chi" => "zh",
+            "jpn" => "ja",
+            "kor" => "ko",
+            "est" => "et",
+            "lav" => "lv",
+            "lit" => "lt",
+            "cat" => "ca",
+            "ice" => "is",
+            "mac" => "mk",
+            "alb" => "sq",
+            _ => return None,
+        })
+    }
+
+    /// the tag written into containers, codes nothing knows become `und` rather than a
+    /// tag players show as garbage
+    pub fn container_tag(&self) -> &str {
+        match self.iso639_2() {
+            Some(code) => code,
+            None if self.0.len() == 3 && self.0.chars().all(|c| c.is_ascii_lowercase()) => &self.0,
+            None => "und",
+        }
+    }
+
+    /// english name, for track names of embedded subtitles
+    pub fn name(&self) -> Option<&'static str> {
+        if matches!(self.0.as_str(), "pob" | "pb") {
+            return Some("Portuguese (Brazil)");
+        }
+        Some(match self.iso639_2()? {
+            "eng" => "English",
+            "pol" => "Polish",
+            "ger" => "German",
+            "fre" => "French",
+            "spa" => "Spanish",
+            "ita" => "Italian",
+            "por" => "Portuguese",
+            "dut" => "Dutch",
+            "cze" => "Czech",
+            "slo" => "Slovak",
+            "hun" => "Hungarian",
+            "rum" => "Romanian",
+            "swe" => "Swedish",
+            "nor" => "Norwegian",
+            "dan" => "Danish",
+            "fin" => "Finnish",
+            "tur" => "Turkish",
+            "hrv" => "Croatian",
+            "bos" => "Bosnian",
+            "srp" => "Serbian",
+            "slv" => "Slovenian",
+            "rus" => "Russian",
+            "ukr" => "Ukrainian",
+            "bul" => "Bulgarian",
+            "gre" => "Greek",
+            "heb" => "Hebrew",
+            "ara" => "Arabic",
+            "per" => "Persian",
+            "hin" => "Hindi",
+            "tha" => "Thai",
+            "vie" => "Vietnamese",
+            "ind" => "Indonesian",
+            "may" => "Malay",
+            "chi" => "Chinese",
+            "jpn" => "Japanese",
+            "kor" => "Korean",
+            "est" => "Estonian",
+            "lav" => "Latvian",
+            "lit" => "Lithuanian",
+            "cat" => "Catalan",
+            "ice" => "Icelandic",
+            "mac" => "Macedonian",
+            "alb" => "Albanian",
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for LanguageCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// every code `iso639_2` gives
+    const KNOWN: &[&str] = &[
+        "eng", "pol", "ger", "fre", "spa", "ita", "por", "dut", "cze", "slo", "hun", "rum", "swe",
+        "nor", "dan", "fin", "tur", "hrv", "bos", "srp", "slv", "rus", "ukr", "bul", "gre", "heb",
+        "ara", "per", "hin", "tha", "vie", "ind", "may", "chi", "jpn", "kor", "est", "lav", "lit",
+        "cat", "ice", "mac", "alb",
+    ];
+
+    fn iso639_2(code: &str) -> Option<&'static str> {
+        LanguageCode::new(code).iso639_2()
+    }
+
+    #[test]
+    fn every_form_comes_back_to_the_same_tag() {
+        for &tag in KNOWN {
+            let code = LanguageCode::new(tag);
+            assert_eq!(code.iso639_2(), Some(tag));
+            assert_eq!(code.container_tag(), tag);
+            let iso639_1 = code
+                .iso639_1()
+                .unwrap_or_else(|| panic!("{tag} has no 639-1"));
+            assert_eq!(iso639_2(iso639_1), Some(tag), "{tag} by {iso639_1}");
+            let name = code.name().unwrap_or_else(|| panic!("{tag} has no name"));
+            assert_eq!(iso639_2(name), Some(tag), "{tag} by {name}");
+            // what's written into a container reads back as the same language
+            assert_eq!(LanguageCode::new(code.container_tag()), code);
+        }
+    }
+
+    #[test]
+    fn terminology_and_opensubtitles_codes_give_the_bibliographic_tag() {
+        let cases = [
+            ("deu", "ger"),
+            ("fra", "fre"),
+            ("nld", "dut"),
+            ("ces", "cze"),
+            ("slk", "slo"),
+            ("ron", "rum"),
+            ("ell", "gre"),
+            ("fas", "per"),
+            ("msa", "may"),
+            ("zho", "chi"),
+            ("isl", "ice"),
+            ("mkd", "mac"),
+            ("sqi", "alb"),
+            ("scc", "srp"),
+            ("pob", "por"),
+            ("pb", "por"),
+            ("zht", "chi"),
+            ("spn", "spa"),
+        ];
+        for (code, tag) in cases {
+            assert_eq!(LanguageCode::new(code).container_tag(), tag, "{code}");
+        }
+        assert_eq!(LanguageCode::new("pob").name(), Some("Portuguese (Brazil)"));
+    }
+
+    #[test]
+    fn codes_are_compared_trimmed_and_lowercase() {
+        assert_eq!(LanguageCode::new(" POL "), LanguageCode::new("pol"));
+        assert_eq!(iso639_2("English"), Some("eng"));
+        assert_eq!(LanguageCode::new("PL").to_string(), "pl");
+    }
+
+    #[test]
+    fn unknown_codes() {
+        // three letters pass as they are, anything else players would show as garbage
+        assert_eq!(LanguageCode::new("epo").container_tag(), "epo");
+        assert_eq!(LanguageCode::new("xx").container_tag(), "und");
+        assert_eq!(LanguageCode::new("pt-br").container_tag(), "und");
+        assert_eq!(LanguageCode::new("k1x").container_tag(), "und");
+        assert_eq!(LanguageCode::new("epo").name(), None);
+        assert_eq!(LanguageCode::new("epo").iso639_1(), None);
+    }
+
+    #[test]
+    fn serialized_as_the_code() {
+        let code = LanguageCode::new("pob");
+        let json = serde_json::to_string(&code).unwrap();
+        assert_eq!(json, r#""pob""#);
+        assert_eq!(serde_json::from_str::<LanguageCode>(&json).unwrap(), code);
+    }
+}
@@ -0,0 +1,67 @@
+//! finds subtitles for a movie file on opensubtitles.org by its hash, and everything done to
+//! them afterwards: unpacking, cleaning, converting, synchronizing and embedding.
+//!
+//! [`Client`] searches and downloads, [`archive::open`] unpacks what it downloaded and
+//! [`output::SubtitleWriter`] cleans and writes the subtitles. the `opensubtitlescli` binary
+//! is these put together behind a command line
+pub mod api;
+pub mod archive;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod charset;
+pub mod check;
+pub mod clean;
+pub mod cleanup;
+pub mod client;
+pub mod clipboard;
+pub mod crawler;
+pub mod daemon;
+pub mod diff;
+pub mod dir_config;
+pub mod download;
+pub mod dump;
+pub mod editor;
+#[cfg(feature = "embed")]
+pub mod embed;
+pub mod extract;
+pub mod feedback;
+pub mod hash;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "history")]
+pub mod history_command;
+pub mod hook;
+pub mod http;
+pub mod langid;
+pub mod language;
+pub mod logging;
+pub mod markup;
+pub mod merge;
+pub mod messages;
+pub mod notify;
+pub mod output;
+pub mod postprocess;
+pub mod probe;
+pub mod progress;
+pub mod prompt;
+pub mod recorder;
+pub mod reflow;
+pub mod release;
+pub mod rename;
+pub mod results;
+pub mod sdh;
+pub mod srt;
+pub mod subtitle;
+pub mod sync;
+pub mod timings;
+pub mod tools;
+pub mod translate;
+pub mod unattended;
+#[cfg(feature = "self-update")]
+pub mod update;
+pub mod upload;
+pub mod xmlrpc;
+
+pub use client::{Client, ClientBuilder, ClientConfig};
+pub use crawler::{Candidate, Ranking, SubsEntry};
+pub use hash::hash_for_file;
@@ -0,0 +1,274 @@
+//! `--log-format` and `--log-file`, what the tracing events end up as
+use eyre::{Result, WrapErr};
+use serde_json::{Map, Value};
+use std::{
+    fmt,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::{
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    Event, Subscriber,
+};
+use tracing_subscriber::{
+    field::{MakeExt, RecordFields},
+    filter::{filter_fn, FilterExt},
+    fmt::{
+        format::{self, FmtSpan, Writer},
+        FmtContext, FormatEvent, FormatFields, FormattedFields, MakeWriter,
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer,
+};
+
+/// the span each movie's run happens in, only json shows it so the human formats stay as
+/// they always were
+pub const MOVIE_SPAN: &str = "movie";
+
+/// rotated logs kept next to `--log-file`, as `<name>.1` (the newest) to `<name>.3`
+const BACKUPS: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LogFormat {
+    /// a line per event with the spans it happened in
+    #[default]
+    Pretty,
+    /// shorter lines, without the span names
+    Compact,
+    /// an object per line, for journald, Loki and the like
+    Json,
+}
+
+/// event and span fields as json values, numbers and booleans stay what they are
+#[derive(Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+}
+
+/// span fields kept as a json object, so [`Json`] can nest them
+struct JsonFields;
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor::default();
+        fields.record(&mut visitor);
+        write!(writer, "{}", Value::Object(visitor.0))
+    }
+
+    /// fields recorded later, `hash` once it's computed
+    fn add_fields(
+        &self,
+        current: &mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor(serde_json::from_str(&current.fields).unwrap_or_default());
+        fields.record(&mut visitor);
+        current.fields = Value::Object(visitor.0).to_string();
+        Ok(())
+    }
+}
+
+/// `{"timestamp", "level", "target", "fields", "spans"}`, the spans outermost first
+struct Json;
+
+impl<S> FormatEvent<S, JsonFields> for Json
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+        let spans = ctx
+            .event_scope()
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+            .map(|span| {
+                let mut object = span
+                    .extensions()
+                    .get::<FormattedFields<JsonFields>>()
+                    .and_then(|fields| serde_json::from_str::<Map<_, _>>(&fields.fields).ok())
+                    .unwrap_or_default();
+                object.insert("name".to_string(), span.name().into());
+                Value::Object(object)
+            })
+            .collect::<Vec<_>>();
+        let line = serde_json::json!({
+            "timestamp": chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            "level": metadata.level().as_str(),
+            "target": metadata.target(),
+            "fields": fields.0,
+            "spans": spans,
+        });
+        writeln!(writer, "{line}")
+    }
+}
+
+/// appends to `path`, moving it aside once it grows past `max_size` bytes
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    /// the open file and how much it holds
+    file: Mutex<(File, u64)>,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_size: u64) -> Result<Self> {
+        let file = Self::append(path)?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_owned(),
+            max_size,
+            file: Mutex::new((file, size)),
+        })
+    }
+
+    fn append(path: &Path) -> Result<File> {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .wrap_err_with(|| format!("opening the log file {path:?}"))
+    }
+
+    fn backup(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        name.into()
+    }
+
+    /// `<name>.2` becomes `<name>.3` and so on, the oldest is dropped
+    fn rotate(&self) -> std::io::Result<File> {
+        for n in (1..BACKUPS).rev() {
+            let from = self.backup(n);
+            if from.exists() {
+                std::fs::rename(&from, self.backup(n + 1))?;
+            }
+        }
+        std::fs::rename(&self.path, self.backup(1))?;
+        Self::append(&self.path).map_err(std::io::Error::other)
+    }
+}
+
+impl Write for &RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if file.1 > 0 && file.1 + buf.len() as u64 > self.max_size {
+            *file = (self.rotate()?, 0);
+        }
+        let written = file.0.write(buf)?;
+        file.1 += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut file = self
+            .file
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        file.0.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RotatingFile {
+    type Writer = &'a RotatingFile;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self
+    }
+}
+
+/// `name=value` without colors. span fields are formatted once per field formatter type, a
+/// file sharing the terminal's would get its escape codes
+fn plain_fields() -> impl for<'writer> FormatFields<'writer> + Send + Sync + 'static {
+    format::debug_fn(|writer, field, value| match field.name() {
+        "message" => write!(writer, "{value:?}"),
+        name => write!(writer, "{name}={value:?}"),
+    })
+    .delimited(" ")
+}
+
+/// a layer for `writer` in `format`, events at info and above
+fn layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let human = filter_fn(|metadata| !(metadata.is_span() && metadata.name() == MOVIE_SPAN));
+    let human = LevelFilter::INFO.and(human);
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+    match (format, ansi) {
+        (LogFormat::Pretty, true) => layer.with_filter(human).boxed(),
+        (LogFormat::Compact, true) => layer.compact().with_filter(human).boxed(),
+        (LogFormat::Pretty, false) => layer.fmt_fields(plain_fields()).with_filter(human).boxed(),
+        (LogFormat::Compact, false) => layer
+            .compact()
+            .fmt_fields(plain_fields())
+            .with_filter(human)
+            .boxed(),
+        (LogFormat::Json, _) => layer
+            .with_ansi(false)
+            // closing spans carry what was recorded meanwhile, the status of a request
+            .with_span_events(FmtSpan::CLOSE)
+            .fmt_fields(JsonFields)
+            .event_format(Json)
+            .with_filter(LevelFilter::INFO)
+            .boxed(),
+    }
+}
+
+/// logs go to stderr and, with `log_file`, to that file as well
+pub fn init(format: LogFormat, log_file: Option<&Path>, max_size: u64) -> Result<()> {
+    let file = log_file
+        .map(|path| RotatingFile::open(path, max_size))
+        .transpose()?;
+    tracing_subscriber::registry()
+        .with(layer(format, std::io::stderr, true))
+        .with(file.map(|file| layer(format, file, false)))
+        .try_init()
+        .wrap_err("setting up logging")
+}
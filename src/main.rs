@@ -1,319 +1,1840 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 #[allow(unused_imports)]
 use eyre::{bail, eyre, Result, WrapErr};
-use itertools::Itertools;
 use reqwest::Url;
-use std::path::{Path, PathBuf};
 use std::{
-    fs::{self, File},
-    io::{BufReader, Read, Seek, SeekFrom},
-    mem,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
+use subtitle::{FormatPreference, SubtitleFormat};
 use tap::prelude::*;
-use tokio::process::Command;
 #[allow(unused_imports)]
-use tracing::{debug, error, info, instrument, trace, warn};
+use tracing::{debug, error, field, info, info_span, instrument, trace, warn, Instrument, Span};
 
-const HASH_BLK_SIZE: u64 = 65536;
+#[cfg(feature = "embed")]
+use opensubtitlescli::embed;
+#[cfg(feature = "self-update")]
+use opensubtitlescli::update;
+use opensubtitlescli::{
+    api, archive, charset, check, clean, cleanup, client, clipboard, crawler, daemon, dir_config,
+    download, editor, extract, hash, hook, logging, merge,
+    messages::{self, filled, text},
+    notify::{self, Notification},
+    output, postprocess, probe, progress, prompt, recorder, release, rename, results, sdh, srt,
+    subtitle, sync, timings, tools, translate,
+    unattended::{self, Ahead, Outcome},
+    upload, Client,
+};
+#[cfg(feature = "history")]
+use opensubtitlescli::{feedback, history_command};
+
+const MEGABYTE: u64 = 1024 * 1024;
 
 /// this automates subtitle search
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[derive(Clone, Parser)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Cli {
-    /// file path
+    #[command(subcommand)]
+    pub action: Option<Action>,
+    /// file path, a directory of episodes implies --season-pack
+    #[arg(short, long, required = true)]
+    pub movie_file: Option<PathBuf>,
+    /// search by this title instead of the movie's hash
     #[arg(short, long)]
-    pub movie_file: PathBuf,
+    pub query: Option<String>,
+    /// the subtitles' language, a list like `pol,eng` downloads subtitles in each
     #[arg(short, long, default_value = "eng")]
     pub language: String,
     /// you will be presented with top n values to choose from
     #[arg(short, long, default_value_t = 1)]
     pub top_n: usize,
+    /// skip subtitles reported as bad more than this many times
+    #[arg(long)]
+    pub max_bad_reports: Option<u32>,
+    /// keep the results in the site's order, featured rows on top, instead of ranking them
+    #[arg(long)]
+    pub include_featured_first: bool,
+    /// save every fetched page to this directory, to attach to bug reports
+    #[arg(long)]
+    pub dump_html: Option<PathBuf>,
+    /// hash the movie with plain reads instead of mapping it into memory. only 64-bit unix
+    /// maps it, windows and 32-bit builds always read
+    #[arg(long)]
+    pub no_mmap: bool,
+    /// how logs are written, json for journald, Loki and the like
+    #[arg(long, value_enum, default_value_t)]
+    pub log_format: logging::LogFormat,
+    /// language of the prompts and messages, the system locale's by default
+    #[arg(long, value_enum, env = "OPENSUBTITLESCLI_UI_LANGUAGE")]
+    pub ui_language: Option<messages::UiLanguage>,
+    /// also write the logs to this file, moved aside to `<file>.1` once it outgrows
+    /// --log-file-max-mb
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+    #[arg(long, default_value_t = 10, requires = "log_file")]
+    pub log_file_max_mb: u64,
+    /// print how long every stage took once done, as events with --log-format json. the
+    /// --json document has them too
+    #[arg(long)]
+    pub timings: bool,
+    /// a desktop notification once done, for every request of `daemon` and when a prompt
+    /// waits for an answer. without a notification daemon it's only logged
+    #[arg(long)]
+    pub notify: bool,
+    /// put the subtitles' paths on the clipboard as well, one per line
+    #[arg(long)]
+    pub copy_path: bool,
+    /// print one json document of what was downloaded and written once done, instead of the
+    /// paths
+    #[arg(long)]
+    pub json: bool,
+    /// don't look for a new release, plain runs in a terminal do once a day
+    #[cfg(feature = "self-update")]
+    #[arg(long, env = "OPENSUBTITLESCLI_NO_UPDATE_CHECK")]
+    pub no_update_check: bool,
+    /// don't record what was downloaded
+    #[cfg(feature = "history")]
+    #[arg(long)]
+    pub no_history: bool,
+    /// where downloads are recorded, `$XDG_DATA_HOME/opensubtitlescli/history.json` by default
+    #[cfg(feature = "history")]
+    #[arg(long, env = "OPENSUBTITLESCLI_HISTORY")]
+    pub history_file: Option<PathBuf>,
+    /// send every request through this proxy, `http://`, `https://` or `socks5://`
+    #[arg(long, env = "OPENSUBTITLESCLI_PROXY")]
+    pub proxy: Option<String>,
+    /// identify as this to the site
+    #[arg(long)]
+    pub user_agent: Option<String>,
+    /// don't verify the site's certificate, for proxies that intercept tls. anyone in between
+    /// can read and change what's downloaded, so there's no env variable for it
+    #[arg(long)]
+    pub insecure: bool,
+    /// give up on a request taking longer than this many seconds
+    #[arg(long)]
+    pub request_timeout: Option<u64>,
+    /// wait at least this many milliseconds between requests
+    #[arg(long, default_value_t = 0)]
+    pub request_interval_ms: u64,
+    /// a mirror of opensubtitles.org to search instead
+    #[arg(long, env = "OPENSUBTITLESCLI_BASE_URL", default_value = client::BASE_URL)]
+    pub base_url: String,
+    /// the XML-RPC api `upload`, `rate` and `report` talk to
+    #[arg(long, env = "OPENSUBTITLESCLI_API_URL", default_value = api::API_URL)]
+    pub api_url: String,
+    /// subtitle formats in the order you prefer them, e.g. `srt,ass,sub`
+    #[arg(long, value_delimiter = ',')]
+    pub format_preference: Vec<SubtitleFormat>,
+    /// drop subtitles in formats not listed in --format-preference
+    #[arg(long, requires = "format_preference")]
+    pub only_preferred_formats: bool,
+    /// pick the best subtitle and archive entry without asking, never embed
+    #[cfg(feature = "tui")]
+    #[arg(long)]
+    pub auto: bool,
+    /// with --auto, how many subtitles are tried when downloading, unpacking or writing them
+    /// fails. the prompt asks what to do instead
+    #[arg(long, default_value_t = 3)]
+    pub max_attempts: usize,
+    /// refuse downloads bigger than this, raise it for giant season packs
+    #[arg(long, default_value_t = 50)]
+    pub max_download_mb: u64,
+    /// refuse to extract archive entries bigger than this
+    #[arg(long, default_value_t = 20)]
+    pub max_entry_mb: u64,
+    /// entries compressed more than this many times over are treated as a zip bomb
+    #[arg(long, default_value_t = 1000)]
+    pub max_compression_ratio: u64,
+    /// encoding of zip entry names not marked as utf-8, e.g. `windows-1250` (default cp437)
+    #[arg(long, value_parser = parse_encoding)]
+    pub archive_codepage: Option<&'static encoding_rs::Encoding>,
+    /// also save the downloaded archive, as `movie.<language>.zip` unless a path is given
+    #[arg(long, num_args = 0..=1)]
+    pub keep_archive: Option<Option<PathBuf>>,
+    /// write every subtitle file in the archive instead of choosing one
+    #[arg(long)]
+    pub extract_all: bool,
+    /// map the entries of a whole-season archive to the episodes next to the movie file
+    #[arg(long)]
+    pub season_pack: bool,
+    /// archive entry extensions never offered
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = archive::DEFAULT_EXCLUDED.map(String::from)
+    )]
+    pub archive_exclude: Vec<String>,
+    /// only offer archive entries with a subtitle extension, `--subtitles-only false` to see all
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub subtitles_only: bool,
+    /// give the subtitles the movie file's access and modification times
+    #[arg(long)]
+    pub preserve_times: bool,
+    /// give the subtitles the movie file's permission bits (unix only)
+    #[arg(long)]
+    pub match_perms: bool,
+    /// an existing subtitle differing only in line endings counts as identical and is kept
+    #[arg(long)]
+    pub ignore_line_endings: bool,
+    #[command(flatten)]
+    pub processing: Processing,
+    /// rescale the timing for another frame rate, `25:23.976` for subtitles timed for 25 fps
+    #[arg(long, conflicts_with = "auto_retime")]
+    pub retime_fps: Option<srt::FrameRates>,
+    /// retime from the frame rate the uploader gave to the movie's, as told by ffprobe
+    #[arg(long)]
+    pub auto_retime: bool,
+    /// write subtitles split into parts (CD1, CD2) as one file, every part moved by the
+    /// lengths of the ones before it. the length of a part, once for every part but the last:
+    /// `--join-parts 00:52:10,500` for two parts
+    #[arg(long)]
+    pub join_parts: Vec<srt::Timestamp>,
+    /// line the subtitles up with the audio using alass or ffsubsync
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "auto"
+    )]
+    pub sync: Option<sync::SyncTool>,
+    /// give up on synchronizing after this many seconds
+    #[arg(long, default_value_t = 300, requires = "sync")]
+    pub sync_timeout: u64,
+    /// keep the subtitles from before --sync as `<name>.orig.srt`
+    #[arg(long, requires = "sync")]
+    pub keep_unsynced: bool,
+    #[command(flatten)]
+    pub translation: Translation,
+    /// open the subtitles in $VISUAL or $EDITOR once they're written and wait for it to close,
+    /// before they're embedded. not with --auto
+    #[arg(long)]
+    pub edit: bool,
+    /// the editor --edit runs instead, for ones that open a window and return at once unless
+    /// told to wait: `code --wait`, `subl -w`, `gedit --wait`. `{}` stands for the file, it's
+    /// put last otherwise
+    #[arg(long, requires = "edit")]
+    pub edit_wait_cmd: Option<String>,
+    /// pick another subtitle when the cues don't fit the movie's duration
+    #[arg(long)]
+    pub strict_duration: bool,
+    /// do nothing when the movie has an audio track in the language, untagged ones don't count
+    #[arg(long)]
+    pub skip_if_audio_matches: bool,
+    /// check the subtitles are in the language asked for, `strict` picks others when not
+    #[arg(long, value_enum, default_value_t)]
+    pub verify_language: check::CheckMode,
+    /// check the subtitles name the episode of the movie file, `warn` to only complain
+    #[arg(long, value_enum, default_value = "strict")]
+    pub verify_episode: check::CheckMode,
+    #[cfg(feature = "embed")]
+    #[command(flatten)]
+    pub embedding: embed::Embedding,
+    /// give up on embedding, burning in or extracting after this many seconds
+    #[arg(long)]
+    pub embed_timeout: Option<u64>,
+    /// ffmpeg to embed and burn in with when it isn't on PATH
+    #[arg(long, env = "OPENSUBTITLESCLI_FFMPEG")]
+    pub ffmpeg_path: Option<PathBuf>,
+    /// ffprobe to inspect the movie with when it isn't on PATH
+    #[arg(long, env = "OPENSUBTITLESCLI_FFPROBE")]
+    pub ffprobe_path: Option<PathBuf>,
+    /// what the flags were parsed from, read again with a `.opensubtitlescli.toml`
+    #[arg(skip)]
+    pub args: Vec<std::ffi::OsString>,
 }
 
-fn create_hash(file: File, fsize: u64) -> Result<String> {
-    let mut buf = [0u8; 8];
-    let mut word: u64;
+/// how subtitles are cleaned, shared by downloads and `clean`
+#[derive(Clone, clap::Args)]
+struct Processing {
+    /// write subtitles in the encoding they were uploaded in instead of converting to utf-8
+    #[arg(long)]
+    pub keep_encoding: bool,
+    /// convert from this encoding instead of guessing, e.g. `windows-1250`
+    #[arg(long, value_parser = parse_encoding, conflicts_with = "keep_encoding")]
+    pub encoding: Option<&'static encoding_rs::Encoding>,
+    /// utf-8 byte order mark of written subtitles
+    #[arg(long, value_enum, default_value_t)]
+    pub bom: postprocess::Bom,
+    /// line endings of written subtitles
+    #[arg(long, value_enum, default_value_t)]
+    pub line_endings: postprocess::LineEndings,
+    /// keep the advertising cues at the start and the end of the subtitles
+    #[arg(long)]
+    pub no_clean: bool,
+    /// more phrases or domains of advertising cues, on top of the built-in ones
+    #[arg(long, value_delimiter = ',', conflicts_with = "no_clean")]
+    pub ad_patterns: Vec<String>,
+    /// remove sound descriptions and speaker labels, `--strip-hi=aggressive` removes more
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "conservative"
+    )]
+    pub strip_hi: Option<sdh::StripHi>,
+    /// remove font and color tags, balance the ones that are kept
+    #[arg(long)]
+    pub strip_tags: bool,
+    /// tags --strip-tags keeps
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "i,b",
+        requires = "strip_tags"
+    )]
+    pub keep_tags: Vec<String>,
+    /// rewrap cues to lines of at most this many characters, splitting ones that need more
+    /// than two lines
+    #[arg(long)]
+    pub max_line_length: Option<usize>,
+    /// fix broken numbering, order, duplicates, overlapping and negative timing, `--repair false`
+    /// to keep them
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub repair: bool,
+    /// keep cues in the order they are in the file
+    #[arg(long)]
+    pub no_sort: bool,
+    /// keep cues with the same timing and text as an earlier one
+    #[arg(long)]
+    pub keep_duplicates: bool,
+    /// don't join neighbouring cues repeating the same text, e.g. for song lyrics
+    #[arg(long)]
+    pub no_merge_repeats: bool,
+    /// keep cues without text, like `<i></i>` or a lone dash
+    #[arg(long)]
+    pub keep_empty: bool,
+    /// move every cue by this many seconds (`-2.5`) or this long (`+00:00:02,300`)
+    #[arg(long, allow_hyphen_values = true)]
+    pub shift: Option<srt::Offset>,
+    /// frame rate of MicroDVD subtitles, instead of the one they state or the movie's
+    #[arg(long)]
+    pub fps: Option<f64>,
+    /// convert the subtitles, ASS and SSA ones lose their styling
+    #[arg(long, value_enum)]
+    pub convert_to: Option<subtitle::format::ConvertTo>,
+    /// also write the subtitles as they were before --convert-to
+    #[arg(long)]
+    pub keep_original: bool,
+}
 
-    let mut hash_val: u64 = fsize; // seed hash with file size
+/// `--translate-from`, machine translated subtitles when there are none in the language
+#[derive(Clone, clap::Args)]
+struct Translation {
+    /// when nothing is found in --language, translate subtitles in this one instead. they're
+    /// written as `movie.machine.srt`
+    #[arg(long)]
+    pub translate_from: Option<String>,
+    /// the service translating them
+    #[arg(long, value_enum, default_value_t, requires = "translate_from")]
+    pub translate_backend: translate::Backend,
+    /// the endpoint cues are posted to, for a LibreTranslate server of your own
+    #[arg(long, requires = "translate_from")]
+    pub translate_url: Option<Url>,
+    /// sent to the translation service, prefer the env variable over the command line
+    #[arg(
+        long,
+        env = "OPENSUBTITLESCLI_TRANSLATE_API_KEY",
+        hide_env_values = true
+    )]
+    pub translate_api_key: Option<String>,
+    /// cues sent in one request
+    #[arg(long, default_value_t = 50, requires = "translate_from")]
+    pub translate_batch_size: usize,
+    /// wait at least this many milliseconds between translation requests
+    #[arg(long, default_value_t = 1000, requires = "translate_from")]
+    pub translate_interval_ms: u64,
+}
 
-    let iterations = HASH_BLK_SIZE / 8;
+impl Translation {
+    /// the language translated from and what translates, `None` without --translate-from
+    fn translator(self) -> Option<(String, translate::Translator)> {
+        let from = self.translate_from?;
+        let translator = translate::Translator::new(self.translate_backend, self.translate_api_key);
+        Some((
+            from,
+            translate::Translator {
+                url: self.translate_url.unwrap_or(translator.url.clone()),
+                batch_size: self.translate_batch_size,
+                min_interval: std::time::Duration::from_millis(self.translate_interval_ms),
+                ..translator
+            },
+        ))
+    }
+}
 
-    let mut reader = BufReader::with_capacity(HASH_BLK_SIZE as usize, file);
+impl Processing {
+    fn writer(self, language: String) -> output::SubtitleWriter {
+        output::SubtitleWriter {
+            ignore_line_endings: false,
+            transcode: (!self.keep_encoding).then_some(charset::Transcode {
+                source: self.encoding,
+                language,
+            }),
+            postprocess: postprocess::PostProcess {
+                bom: self.bom,
+                line_endings: self.line_endings,
+                repair: self.repair.then_some(srt::RepairOptions {
+                    sort: !self.no_sort,
+                    drop_duplicates: !self.keep_duplicates,
+                    merge_repeats: !self.no_merge_repeats,
+                    drop_empty: !self.keep_empty,
+                    notes_are_empty: self.strip_hi.is_some(),
+                }),
+                remove_ads: !self.no_clean,
+                ad_patterns: self.ad_patterns,
+                strip_hi: self.strip_hi,
+                strip_tags: self.strip_tags.then(|| {
+                    self.keep_tags
+                        .iter()
+                        .map(|tag| tag.trim().to_lowercase())
+                        .collect()
+                }),
+                max_line_length: self.max_line_length,
+                linear: None,
+                shift: self.shift,
+            },
+            fps: self.fps,
+            movie_fps: None,
+            convert_to: self.convert_to,
+            keep_original: self.keep_original,
+            timings: Default::default(),
+            cleanup: Default::default(),
+            results: Default::default(),
+            #[cfg(feature = "history")]
+            history: None,
+        }
+    }
+}
 
-    for _ in 0..iterations {
-        reader.read_exact(&mut buf)?;
-        unsafe {
-            word = mem::transmute(buf);
+/// the opensubtitles.org account of `upload`, `rate` and `report`
+#[derive(Clone, clap::Args)]
+struct Account {
+    #[arg(long, env = "OPENSUBTITLESCLI_USERNAME")]
+    pub username: Option<String>,
+    /// asked for unless given
+    #[arg(long, env = "OPENSUBTITLESCLI_PASSWORD", hide_env_values = true)]
+    pub password: Option<String>,
+}
+
+impl Account {
+    /// `command` needs an account, it's what the error says without one
+    fn credentials(self, command: &str) -> Result<api::Credentials> {
+        let Some(username) = self.username else {
+            bail!(filled("error-needs-account", &[("command", &command)]));
         };
-        hash_val = hash_val.wrapping_add(word);
+        let password = match self.password {
+            Some(password) => password,
+            None => prompt::password(&filled(
+                "prompt-account-password",
+                &[("username", &username)],
+            ))
+            .wrap_err("reading the password")?,
+        };
+        Ok(api::Credentials { username, password })
     }
+}
 
-    reader.seek(SeekFrom::Start(fsize - HASH_BLK_SIZE))?;
+#[derive(Clone, clap::Subcommand)]
+enum Action {
+    /// report what the repair step would change in an srt file
+    Verify { subtitle_file: PathBuf },
+    /// retime an srt file in place
+    #[command(group(
+        clap::ArgGroup::new("adjustment")
+            .required(true)
+            .multiple(true)
+            .args(["shift", "anchor", "first_at", "last_at", "retime_fps"])
+    ))]
+    Adjust {
+        subtitle_file: PathBuf,
+        /// seconds (`-2.5`) or a signed timestamp (`+00:00:02,300`)
+        #[arg(long, allow_hyphen_values = true)]
+        shift: Option<srt::Offset>,
+        /// `<cue time>=<correct time>`, given twice to stretch the timing between them
+        #[arg(long, conflicts_with_all = ["first_at", "last_at"])]
+        anchor: Vec<srt::Anchor>,
+        /// where the first cue should start
+        #[arg(long)]
+        first_at: Option<srt::Timestamp>,
+        /// where the last cue should start
+        #[arg(long)]
+        last_at: Option<srt::Timestamp>,
+        /// `<from fps>:<to fps>`, rescale the timing for another frame rate
+        #[arg(long, conflicts_with_all = ["anchor", "first_at", "last_at"])]
+        retime_fps: Option<srt::FrameRates>,
+    },
+    /// combine two srt files in different languages, the secondary one under the primary
+    Merge {
+        primary: PathBuf,
+        secondary: PathBuf,
+        /// defaults to `<primary>.merged.srt`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// how far apart cues may start or end and still be shown together
+        #[arg(long, default_value_t = 500)]
+        tolerance_ms: i64,
+        /// italicize the secondary language
+        #[arg(long)]
+        italic: bool,
+        /// color of the secondary language, e.g. `#ffff00`
+        #[arg(long)]
+        color: Option<String>,
+    },
+    /// run subtitle files already on disk through the same cleaning as downloads
+    Clean {
+        /// subtitle files or directories of them
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// a directory, or a file when cleaning one subtitle, instead of writing in place
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// print what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// don't keep the file being overwritten as `<name>.bak`
+        #[arg(long)]
+        no_backup: bool,
+        /// language of the subtitles, narrows down guessing their encoding
+        #[arg(short, long, default_value = "eng")]
+        language: String,
+        #[command(flatten)]
+        processing: Processing,
+    },
+    /// rename the subtitle files of a directory not named after any of its videos after the
+    /// video their name is closest to
+    Rename {
+        dir: PathBuf,
+        /// the new name, `{stem}` of the video, `{lang}` of the subtitles (from their name or
+        /// their text) and `{ext}` of the subtitle file are filled in
+        #[arg(long, default_value = rename::DEFAULT_TEMPLATE)]
+        template: String,
+        /// rename without asking
+        #[arg(short, long)]
+        yes: bool,
+        /// replace files the new names are taken by
+        #[arg(long)]
+        force: bool,
+        /// print the renames without making them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// write the movie's embedded subtitles out as files next to it
+    #[cfg_attr(
+        not(feature = "tui"),
+        command(group(clap::ArgGroup::new("streams").required(true).args(["all", "language"])))
+    )]
+    ExtractSubs {
+        movie_file: PathBuf,
+        /// every subtitle stream, without asking
+        #[arg(long, conflicts_with = "language")]
+        all: bool,
+        /// streams in these languages (`pol,eng`), without asking
+        #[arg(short, long)]
+        language: Option<String>,
+    },
+    /// print the movie's video, audio and subtitle streams
+    ListTracks {
+        movie_file: PathBuf,
+        #[arg(long, value_enum, default_value_t)]
+        output_format: output::ListFormat,
+    },
+    /// for a torrent client to run once a download finished, gets subtitles for every video in
+    /// it without asking. errors go to --log-file, there's no terminal to show them
+    Hook {
+        #[arg(long, value_enum)]
+        client: hook::TorrentClient,
+        /// the finished download, qbittorrent's `%F`. transmission's is read from its variables
+        content_path: Option<PathBuf>,
+    },
+    /// take `{"action":"download","path":"...","language":"pol"}` requests on a local socket
+    /// and answer each with a line of json, like `send` does. requests share the client, so
+    /// --request-interval-ms spaces out all of them. SIGTERM waits for the ones running
+    Daemon {
+        /// the unix socket, `127.0.0.1:<port>` on windows
+        #[arg(long)]
+        socket: Option<String>,
+        /// how many requests run at once, the rest wait for their turn
+        #[arg(long, default_value_t = 1)]
+        jobs: usize,
+    },
+    /// ask a running daemon for the movie's subtitles and print its answer
+    Send {
+        path: PathBuf,
+        /// instead of the daemon's language
+        #[arg(short, long)]
+        language: Option<String>,
+        /// the daemon's --socket
+        #[arg(long)]
+        socket: Option<String>,
+    },
+    /// contribute a subtitle for the movie back to opensubtitles.org, in --language. needs
+    /// an account, the password is asked for unless given
+    Upload {
+        video: PathBuf,
+        subtitle: PathBuf,
+        /// `tt0133093`, needed when the site doesn't know the movie by its hash
+        #[arg(long)]
+        imdb: Option<String>,
+        /// instead of the video's file name
+        #[arg(long)]
+        release_name: Option<String>,
+        /// the subtitle describes sounds too
+        #[arg(long)]
+        hearing_impaired: bool,
+        /// only the foreign parts are subtitled
+        #[arg(long)]
+        forced: bool,
+        /// shown with the subtitle on the site
+        #[arg(long)]
+        comment: Option<String>,
+        #[command(flatten)]
+        account: Account,
+    },
+    /// vote for a downloaded subtitle, found by the movie or the subtitle file in the history.
+    /// without --stars the downloads for the movie and the stars are asked for
+    #[cfg(feature = "history")]
+    Rate {
+        path: PathBuf,
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=10))]
+        stars: Option<u8>,
+        #[command(flatten)]
+        account: Account,
+    },
+    /// flag a downloaded subtitle as bad, found like `rate` finds it. without --reason the
+    /// downloads for the movie and the reason are asked for
+    #[cfg(feature = "history")]
+    Report {
+        path: PathBuf,
+        #[arg(long, value_enum)]
+        reason: Option<feedback::ReportReason>,
+        /// what's wrong, along with the reason
+        #[arg(long)]
+        comment: Option<String>,
+        #[command(flatten)]
+        account: Account,
+    },
+    /// remove the subtitles the latest download wrote and restore the movie it replaced
+    #[cfg(feature = "history")]
+    Undo {
+        /// the latest download for this movie instead of the latest one
+        movie_file: Option<PathBuf>,
+        /// also remove the movie with subtitles
+        #[arg(long)]
+        remove_video: bool,
+        /// print what would be removed and restored without doing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// replace this binary with the latest release, for the target it was built for, once
+    /// its checksum matches
+    #[cfg(feature = "self-update")]
+    SelfUpdate {
+        /// only tell whether there's a newer release
+        #[arg(long)]
+        check_only: bool,
+    },
+    /// the subtitles downloaded so far
+    #[cfg(feature = "history")]
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// totals of the downloads so far, per language, provider, month and uploader
+    #[cfg(feature = "history")]
+    Stats {
+        /// only the downloads from this day on, `2024-01-01`
+        #[arg(long)]
+        since: Option<chrono::NaiveDate>,
+        #[arg(long, value_enum, default_value_t)]
+        output_format: output::ListFormat,
+    },
+}
 
-    for _ in 0..iterations {
-        reader.read_exact(&mut buf)?;
-        unsafe {
-            word = mem::transmute(buf);
-        };
-        hash_val = hash_val.wrapping_add(word);
+#[cfg(feature = "history")]
+#[derive(Clone, clap::Subcommand)]
+enum HistoryCommand {
+    /// every download, oldest first
+    List {
+        #[arg(long, value_enum, default_value_t)]
+        output_format: output::ListFormat,
+    },
+    /// the downloads for a movie, or the one that wrote a subtitle file
+    Show { path: PathBuf },
+    /// forget every download
+    Purge,
+}
+
+/// the stretch described by `--anchor` or `--first-at`/`--last-at`
+fn linear_retime(
+    srt: &srt::Srt,
+    anchors: &[srt::Anchor],
+    first_at: Option<srt::Timestamp>,
+    last_at: Option<srt::Timestamp>,
+) -> Result<Option<srt::Linear>> {
+    let linear = match (anchors, first_at, last_at) {
+        ([], None, None) => return Ok(None),
+        ([first, second], None, None) => srt::Linear::from_anchors(*first, *second)?,
+        ([], first_at, last_at) => {
+            let (first, last) = srt
+                .cues
+                .first()
+                .zip(srt.cues.last())
+                .ok_or_else(|| eyre!("the file has no cues"))?;
+            srt::Linear::from_anchors(
+                srt::Anchor {
+                    from: first.start,
+                    to: first_at.unwrap_or(first.start),
+                },
+                srt::Anchor {
+                    from: last.start,
+                    to: last_at.unwrap_or(last.start),
+                },
+            )?
+        }
+        _ => bail!(text("error-anchors")),
+    };
+    match srt::frame_rate_conversion(linear.scale) {
+        Some((from, to)) => println!(
+            "speed factor {:.6} (subtitles for {from} fps on a {to} fps video)",
+            linear.scale
+        ),
+        None => println!("speed factor {:.6}", linear.scale),
+    }
+    Ok(Some(linear))
+}
+
+/// `adjust`, runs the file through post-processing decided by its cues and writes it back
+async fn adjust(
+    subtitle_file: &Path,
+    postprocess: impl FnOnce(&srt::Srt) -> Result<postprocess::PostProcess>,
+) -> Result<()> {
+    let contents =
+        fs::read(subtitle_file).wrap_err_with(|| format!("reading {subtitle_file:?}"))?;
+    let is_srt = SubtitleFormat::from_path(subtitle_file) == Some(SubtitleFormat::Srt);
+    if !is_srt {
+        bail!("only srt files can be adjusted");
+    }
+    let text = std::str::from_utf8(&contents).wrap_err("only utf-8 files can be adjusted")?;
+    let postprocess = postprocess(&srt::parse_lenient(text).srt)?;
+    let adjusted = postprocess.apply(subtitle_file, contents);
+    output::write_atomic(subtitle_file, &adjusted).await?;
+    println!("{}", output::quoted(subtitle_file));
+    Ok(())
+}
+
+fn verify(subtitle_file: &Path) -> Result<()> {
+    let contents =
+        fs::read(subtitle_file).wrap_err_with(|| format!("reading {subtitle_file:?}"))?;
+    let (_, repairs) = srt::parse_lenient(&String::from_utf8_lossy(&contents))
+        .repair(srt::RepairOptions::default());
+    match repairs.is_empty() {
+        true => {
+            println!("{}: ok", output::quoted(subtitle_file));
+            Ok(())
+        }
+        false => bail!("{subtitle_file:?}: {repairs}"),
     }
+}
 
-    let hash_string = format!("{:01$x}", hash_val, 16);
+fn parse_encoding(label: &str) -> Result<&'static encoding_rs::Encoding> {
+    encoding_rs::Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| eyre!("unknown encoding [{label}]"))
+}
 
-    Ok(hash_string)
+/// what the search goes by
+enum SearchBy {
+    Hash(String),
+    Title(String),
 }
 
-fn hash_for_file<P: AsRef<Path> + std::fmt::Debug>(path: P) -> Result<String> {
-    let size = fs::metadata(&path).wrap_err("checking file size")?.len();
-    if size <= HASH_BLK_SIZE {
-        bail!("file too small");
+/// `--edit`, every srt file opened until the validation pass has nothing to say about it or
+/// the user keeps it as it is. other formats are opened once
+async fn edit_subtitles(
+    editor: &editor::Editor,
+    subtitle_files: &[PathBuf],
+    movie_duration: Option<srt::Timestamp>,
+    language: &str,
+) -> Result<()> {
+    for subtitle_file in subtitle_files {
+        loop {
+            if !editor.edit(subtitle_file).await? {
+                info!(?subtitle_file, "the subtitles weren't changed");
+            }
+            let problem = match SubtitleFormat::from_path(subtitle_file) {
+                Some(SubtitleFormat::Srt) => {
+                    edited_problem(subtitle_file, movie_duration, language)?
+                }
+                _ => None,
+            };
+            let Some(problem) = problem else {
+                break;
+            };
+            warn!(%problem, ?subtitle_file, "the edited subtitles have a problem");
+            if !prompt::confirm(&filled("prompt-edit-again", &[("problem", &problem)]), true)? {
+                break;
+            }
+        }
     }
-    create_hash(std::fs::File::open(path).wrap_err("opening file")?, size)
+    Ok(())
 }
-static BASE_URL: &str = "https://www.opensubtitles.org";
 
-fn url(lang: &str, hash: String) -> Result<Url> {
-    format!("{BASE_URL}/pl/search/sublanguageid-{lang}/moviehash-{hash}")
-        .parse()
-        .wrap_err("invalid url")
+/// what the repair step would change in an edited file, then what the checks against the
+/// movie say about it
+fn edited_problem(
+    subtitle_file: &Path,
+    movie_duration: Option<srt::Timestamp>,
+    language: &str,
+) -> Result<Option<String>> {
+    let contents =
+        fs::read(subtitle_file).wrap_err_with(|| format!("reading {subtitle_file:?}"))?;
+    let (srt, repairs) = srt::parse_lenient(&String::from_utf8_lossy(&contents))
+        .repair(srt::RepairOptions::default());
+    if !repairs.is_empty() {
+        return Ok(Some(repairs.to_string()));
+    }
+    Ok(movie_duration
+        .and_then(|duration| check::duration_mismatch(&srt, duration))
+        .or_else(|| check::language_mismatch(&srt, language)))
 }
 
-fn to_url_in_base(url: &str) -> Result<Url> {
-    let url = match url.starts_with(BASE_URL) {
-        true => url.to_string(),
-        false => format!("{BASE_URL}{url}"),
-    };
-    url.parse().wrap_err_with(|| format!("invalid url: {url}"))
+/// `--sync`, a failure leaves the subtitles as they were. returns the files `--keep-unsynced`
+/// kept
+async fn synchronize(
+    synchronizer: sync::Synchronizer,
+    movie_file: &Path,
+    subtitle_files: &[PathBuf],
+    timeout: std::time::Duration,
+    keep_unsynced: bool,
+    results: &results::Results,
+) -> Vec<PathBuf> {
+    let mut unsynced_files = vec![];
+    for subtitle_file in subtitle_files {
+        let format = SubtitleFormat::from_path(subtitle_file);
+        let is_text = matches!(
+            format,
+            Some(
+                SubtitleFormat::Srt
+                    | SubtitleFormat::Ass
+                    | SubtitleFormat::Ssa
+                    | SubtitleFormat::Vtt
+            )
+        );
+        if !is_text {
+            continue;
+        }
+        let synced = synchronizer
+            .sync(movie_file, subtitle_file, timeout, keep_unsynced)
+            .await;
+        let (offset, error) = match synced {
+            Ok(synced) => {
+                unsynced_files.extend(synced.unsynced);
+                (synced.offset, None)
+            }
+            Err(message) => {
+                warn!(
+                    ?message,
+                    ?subtitle_file,
+                    "keeping the unsynchronized subtitles"
+                );
+                (None, Some(format!("{message:#}")))
+            }
+        };
+        let synced = results::Synced {
+            tool: synchronizer.name().to_string(),
+            offset,
+            error,
+        };
+        results.synced(subtitle_file, synced);
+    }
+    unsynced_files
 }
 
-pub mod crawler {
-    use super::*;
-    use ordered_float::OrderedFloat;
-    use scraper::{ElementRef, Html, Selector};
+impl Cli {
+    fn limits(&self) -> archive::Limits {
+        archive::Limits {
+            max_download_size: self.max_download_mb * MEGABYTE,
+            max_entry_size: self.max_entry_mb * MEGABYTE,
+            max_compression_ratio: self.max_compression_ratio,
+        }
+    }
 
-    impl std::fmt::Display for SubsEntry {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            write!(f, "[{} (rating: {})]", self.download_url, self.rating)
+    /// `--no-mmap` reads the movie instead of mapping it
+    fn hash_method(&self) -> hash::Method {
+        match self.no_mmap {
+            true => hash::Method::Read,
+            false => hash::Method::default(),
         }
     }
-    #[derive(Debug, Clone)]
-    pub struct SubsEntry {
-        pub name: String,
-        pub flag: String,
-        pub cd: String,
-        pub sent: String,
-        pub download_url: Url,
-        pub rating: f32,
-        pub edits: i32,
-        pub imdb_rating: f32,
-        pub uploaded_by: String,
-    }
-
-    impl SubsEntry {
-        fn from_table_row_element(element: ElementRef<'_>) -> Result<Self> {
-            let tr_selector = Selector::parse("td").map_err(|e| eyre!("{e:?}"))?;
-            let a_selector = Selector::parse("a").map_err(|e| eyre!("{e:?}"))?;
-            let mut trs = element.select(&tr_selector);
-            let mut idx: i32 = -1;
-            let mut next = || {
-                idx += 1;
-                trs.next()
-                    .ok_or_else(|| eyre!("fetching entry number [{idx}]"))
-            };
-            Ok(Self {
-                name: next().map(|v| v.text().join(" "))?,
-                flag: next().map(|v| v.text().join(" "))?,
-                cd: next().map(|v| v.text().join(" "))?,
-                sent: next().map(|v| v.text().join(" "))?,
-                download_url: next().and_then(|tr| {
-                    tr.select(&a_selector)
-                        .next()
-                        .ok_or_else(|| eyre!("no a element"))
-                        .and_then(|v| {
-                            v.value()
-                                .attr("href")
-                                .ok_or_else(|| eyre!("no href element"))
-                                .and_then(to_url_in_base)
-                        })
-                        .wrap_err_with(|| format!("extracting download url from [{}]", tr.html()))
-                })?,
-                rating: next()
-                    .and_then(|v| v.text().join(" ").trim().parse().wrap_err("not a float"))?,
-                edits: next()
-                    .and_then(|v| v.text().join(" ").trim().parse().wrap_err("not an int"))?,
-                imdb_rating: next()
-                    .and_then(|v| v.text().join(" ").trim().parse().wrap_err("not a float"))?,
-                uploaded_by: next().map(|v| v.text().join(" "))?,
-            })
+
+    fn client(&self, timings: Arc<timings::Timings>) -> Result<Client> {
+        self.client_builder(timings).build()
+    }
+
+    fn client_builder(&self, timings: Arc<timings::Timings>) -> client::ClientBuilder {
+        if self.insecure {
+            eprintln!("{}", text("warning-insecure"));
         }
+        Client::builder()
+            .base_url(self.base_url.clone())
+            .api_url(self.api_url.clone())
+            .dump_html(self.dump_html.clone())
+            .limits(self.limits())
+            .proxy(self.proxy.clone())
+            .user_agent(self.user_agent.clone())
+            .insecure(self.insecure)
+            .timeout(self.request_timeout.map(std::time::Duration::from_secs))
+            .min_interval(std::time::Duration::from_millis(self.request_interval_ms))
+            .timings(timings)
     }
 
-    #[instrument(fields(url=%url))]
-    pub async fn get_page(url: Url) -> Result<String> {
-        info!("fetching page");
-        reqwest::get(url)
-            .await
-            .wrap_err("fetching")?
-            .text()
-            .await
-            .wrap_err("parsing page string")
-    }
-    pub fn top_rated_subs(page: String, top_n: usize) -> Result<Vec<SubsEntry>> {
-        let html = Html::parse_document(&page);
-        let tr_selector = Selector::parse("tr").map_err(|e| eyre!("{e:?}"))?;
-        let search_results_selector =
-            Selector::parse("table#search_results").map_err(|e| eyre!("{e:?}"))?;
-        html.select(&search_results_selector)
-            .next()
-            .ok_or_else(|| eyre!("no search result table"))
-            .map(|html| {
-                html.select(&tr_selector)
-                    .skip(1)
-                    .filter_map(|tr| {
-                        SubsEntry::from_table_row_element(tr)
-                            .wrap_err_with(|| format!("parsing tr:\n{}", tr.html()))
-                            .tap_err(|message| {
-                                warn!(?message, "parsing failed");
-                            })
-                            .ok()
-                    })
-                    .sorted_unstable_by_key(|v| OrderedFloat(-v.rating))
-                    .take(top_n)
-                    .collect::<Vec<_>>()
-            })
+    /// the flags of this run for the movie at `movie_file`, without prompts. `language` is
+    /// a daemon request's
+    fn unattended(
+        &self,
+        movie_file: PathBuf,
+        language: Option<String>,
+    ) -> Result<unattended::Unattended<Self>> {
+        let cli = self.clone().with_dir_config(&movie_file)?;
+        let cli = Self {
+            action: None,
+            movie_file: Some(movie_file),
+            #[cfg(feature = "tui")]
+            auto: true,
+            language: language.unwrap_or(cli.language),
+            ..cli
+        };
+        Ok(unattended::Unattended {
+            language: cli.language.clone(),
+            skip_if_audio_matches: cli.skip_if_audio_matches,
+            query: cli.query.is_some(),
+            hash_method: cli.hash_method(),
+            notify: cli.notify,
+            run: cli,
+        })
     }
 
-    pub fn sub_download_url(page: String) -> Result<Url> {
-        let html = Html::parse_document(&page);
-        let selector = Selector::parse("tr").map_err(|e| eyre!("{e:?}"))?;
+    /// the flags with the ones of the `.opensubtitlescli.toml` nearest to `movie_file` put
+    /// ahead of them, so the ones given still win
+    fn with_dir_config(self, movie_file: &Path) -> Result<Self> {
+        let Some(config) = dir_config::DirConfig::find(movie_file)? else {
+            return Ok(self);
+        };
+        for key in &config.refused {
+            warn!(path = ?config.path, key, "ignored, a per-directory config can't set it");
+        }
+        let command = Cli::command();
+        let given = command.clone().try_get_matches_from(&self.args)?;
+        let mut args = self.args.iter().take(1).cloned().collect::<Vec<_>>();
+        for (key, value) in &config.values {
+            // `auto` of a build without the prompts
+            let Some(arg) = command
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(key.as_str()))
+            else {
+                debug!(key, "not a flag of this build, ignored");
+                continue;
+            };
+            let source = given.value_source(arg.get_id().as_str());
+            if source == Some(clap::parser::ValueSource::CommandLine) {
+                continue;
+            }
+            match (arg.get_action(), value) {
+                (clap::ArgAction::SetTrue, dir_config::Value::Bool(true)) => {
+                    args.push(format!("--{key}").into())
+                }
+                (clap::ArgAction::SetTrue, dir_config::Value::Bool(false)) => {}
+                (clap::ArgAction::SetTrue, value) => {
+                    bail!("{key} in {:?} is true or false, not {value}", config.path)
+                }
+                (_, value) => args.push(format!("--{key}={value}").into()),
+            }
+        }
+        args.extend(self.args.iter().skip(1).cloned());
+        debug!(path = ?config.path, ?args, "read the per-directory config");
+        let mut cli = Cli::try_parse_from(&args)
+            .wrap_err_with(|| format!("taking the options of {:?}", config.path))?;
+        cli.args = self.args;
+        Ok(cli)
+    }
+}
 
-        html.select(&selector)
-            .next()
-            .ok_or_else(|| eyre!("no element on page"))
-            .and_then(|v| {
-                v.value()
-                    .attr("href")
-                    .ok_or_else(|| eyre!("no link present"))
-                    .and_then(to_url_in_base)
-            })
+/// `--copy-path`, the subtitles among what was written. they're printed already, copying adds
+/// nothing to stdout
+fn copy_subtitle_paths(written: &[PathBuf]) {
+    let subtitles = written
+        .iter()
+        .filter(|path| SubtitleFormat::from_path(path).is_some())
+        .map(|path| output::displayed(path))
+        .collect::<Vec<_>>();
+    if subtitles.is_empty() {
+        return;
+    }
+    match clipboard::copy(&subtitles.join("\n")) {
+        Some(copied) => debug!(?copied, "copied the paths"),
+        None => warn!("no clipboard program and no terminal for OSC 52, the paths weren't copied"),
     }
+}
 
-    pub async fn get_zip(url: Url) -> Result<Vec<u8>> {
-        reqwest::get(url)
-            .await
-            .wrap_err("fetching")?
-            .bytes()
-            .await
-            .wrap_err("parsing page string")
-            .map(|v| v.to_vec())
+/// `send`, prints the daemon's answer
+async fn send(path: PathBuf, language: Option<String>, socket: Option<String>) -> Result<()> {
+    // the daemon runs somewhere else, relative paths are from here
+    let path = std::path::absolute(&path).wrap_err_with(|| format!("resolving {path:?}"))?;
+    let address = socket.unwrap_or_else(daemon::default_address);
+    let response = daemon::send(&address, &daemon::Request::Download { path, language }).await?;
+    println!("{}", serde_json::to_string(&response)?);
+    match response.ok {
+        true => Ok(()),
+        false => bail!("the daemon couldn't get the subtitles"),
     }
 }
 
-fn prompt_unless_single<T: Clone + std::fmt::Display>(prompt: &str, values: Vec<T>) -> Result<T> {
-    match &values[..] {
-        [single] => Ok(single.clone()),
-        values => inquire::Select::new(prompt, values.to_vec())
-            .prompt()
-            .wrap_err("invalid selection"),
+/// how long the end of a run waits for the notice's answer
+#[cfg(feature = "self-update")]
+const UPDATE_NOTICE_WAIT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// once a day, the latest release looked up while a plain run goes on. only for someone at a
+/// terminal, scripts and torrent clients have nobody reading it
+#[cfg(feature = "self-update")]
+fn update_notice(cli: &Cli) -> Option<tokio::task::JoinHandle<Option<update::Release>>> {
+    use std::io::IsTerminal;
+    if cli.action.is_some() || cli.no_update_check || !std::io::stderr().is_terminal() {
+        return None;
     }
+    let last_check = update::LastCheck::new(update::LastCheck::default_path()?);
+    if !last_check.due(std::time::SystemTime::now()) {
+        return None;
+    }
+    let proxy = cli.proxy.clone();
+    Some(tokio::spawn(async move {
+        let newer = async {
+            let http = update::http(proxy.as_deref(), std::time::Duration::from_secs(10))?;
+            let newer = update::newer(&http, Url::parse(update::LATEST_RELEASE_URL)?).await?;
+            last_check.checked()?;
+            Ok::<_, eyre::Report>(newer)
+        };
+        newer
+            .await
+            .tap_err(|report| debug!(?report, "looking for a new release failed"))
+            .ok()
+            .flatten()
+    }))
+}
+
+/// `upload`, printing the subtitle's page
+async fn upload_subtitle(client: &Client, upload: &upload::Upload) -> Result<()> {
+    match client.upload(upload).await? {
+        upload::Uploaded::New(link) => println!("{}", filled("uploaded", &[("link", &link)])),
+        upload::Uploaded::AlreadyInDatabase(Some(link)) => {
+            println!("{}", filled("uploaded-already", &[("link", &link)]))
+        }
+        upload::Uploaded::AlreadyInDatabase(None) => {
+            println!("{}", text("uploaded-already-unlinked"))
+        }
+    }
+    Ok(())
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    let mut cli = Cli::parse();
+    cli.args = std::env::args_os().collect();
+    messages::set_ui_language(
+        cli.ui_language
+            .unwrap_or_else(|| messages::UiLanguage::from_locale(|name| std::env::var(name).ok())),
+    );
+    logging::init(
+        cli.log_format,
+        cli.log_file.as_deref(),
+        cli.log_file_max_mb * MEGABYTE,
+    )?;
+    let cli = match (&cli.action, cli.movie_file.clone()) {
+        (None, Some(movie_file)) => cli.with_dir_config(&movie_file)?,
+        _ => cli,
+    };
+    let (show_timings, log_format) = (cli.timings, cli.log_format);
+    if cli.notify {
+        prompt::notify_when_asking();
+    }
+    // `hook` and `daemon` notify about their movies on their own
+    let notified = match (&cli.action, &cli.movie_file) {
+        (None, Some(movie_file)) if cli.notify => Some((movie_file.clone(), cli.language.clone())),
+        _ => None,
+    };
+    let copy_path = cli.action.is_none() && cli.copy_path;
+    #[cfg(feature = "self-update")]
+    let update_notice = update_notice(&cli);
+    let timings = Arc::new(timings::Timings::new());
+    let cleanup = Arc::new(cleanup::Cleanup::new());
+    cleanup.cancel_on_ctrl_c();
+    let result = cleanup
+        .cancellable(run(cli, None, None, timings.clone(), cleanup.clone()))
+        .await;
+    if let Err(report) = &result {
+        if progress::Stopped::find(report) == Some(progress::Stopped::Interrupted) {
+            for path in cleanup.remove_partial() {
+                let path = output::quoted(&path);
+                eprintln!("{}", filled("interrupted-removed", &[("path", &path)]));
+            }
+            let completed = cleanup.completed_files();
+            if !completed.is_empty() {
+                eprintln!("{}", text("interrupted-written"));
+                completed
+                    .iter()
+                    .for_each(|path| eprintln!("  {}", output::quoted(path)));
+            }
+        }
+    }
+    if copy_path && result.is_ok() {
+        copy_subtitle_paths(&cleanup.completed_files());
+    }
+    if let Some((movie_file, language)) = notified {
+        let notification = match &result {
+            Ok(_) => Notification::downloaded(&movie_file, &language, &cleanup.completed_files()),
+            Err(report) => Notification::failed(&movie_file, &language, &report.to_string()),
+        };
+        notify::send(notification).await;
+    }
+    #[cfg(feature = "self-update")]
+    if let Some(notice) = update_notice {
+        // a slow GitHub doesn't hold up the subtitles
+        if let Ok(Ok(Some(release))) = tokio::time::timeout(UPDATE_NOTICE_WAIT, notice).await {
+            eprintln!("{}", filled("update-notice", &[("new", &release.tag_name)]));
+        }
+    }
+    if show_timings {
+        match log_format {
+            logging::LogFormat::Json => {
+                for total in timings.totals() {
+                    info!(
+                        stage = total.stage,
+                        count = total.count,
+                        duration_ms = total.duration.as_millis() as u64,
+                        "timing"
+                    );
+                }
+            }
+            _ => eprintln!("{}", timings.report()),
+        }
+    }
+    // scripts running `--auto` can tell protected and damaged archives apart by the exit code
+    // and so can a timed out or interrupted ffmpeg
+    result.map(|_| ()).map_err(|report| {
+        let exit_code = archive::ArchiveError::find(&report)
+            .map(archive::ArchiveError::exit_code)
+            .or_else(|| progress::Stopped::find(&report).map(progress::Stopped::exit_code));
+        match exit_code {
+            Some(exit_code) => {
+                eprintln!("Error: {report:?}");
+                std::process::exit(exit_code)
+            }
+            None => report,
+        }
+    })
+}
+
+/// `client` is shared by the runs of `hook` and `daemon`, every other run builds its own.
+/// they look at their movies ahead too, `ahead` is what they found
+async fn run(
+    cli: Cli,
+    client: Option<Arc<Client>>,
+    ahead: Option<Ahead>,
+    timings: Arc<timings::Timings>,
+    cleanup: Arc<cleanup::Cleanup>,
+) -> Result<Outcome> {
+    match cli.action.clone() {
+        Some(Action::Hook {
+            client,
+            content_path,
+        }) => {
+            // stderr goes nowhere when a torrent client runs it
+            return async {
+                let content_path =
+                    client.content_path(content_path, |name| std::env::var_os(name))?;
+                let shared = Arc::new(cli.client(timings.clone())?);
+                let run_movie = |cli, ahead| {
+                    let client = Some(shared.clone());
+                    run(cli, client, Some(ahead), timings.clone(), cleanup.clone())
+                };
+                let unattended = |video| cli.unattended(video, None);
+                let notify = cli.notify.then_some(cli.language.as_str());
+                let hashing = cli.hash_method();
+                hook::run(
+                    &content_path,
+                    unattended,
+                    run_movie,
+                    hashing,
+                    notify,
+                    &timings,
+                )
+                .await
+            }
+            .await
+            .map(|()| Outcome::Done)
+            .tap_err(|report| error!(?report, "the hook failed"));
+        }
+        Some(Action::Daemon { socket, jobs }) => {
+            let address = socket.unwrap_or_else(daemon::default_address);
+            let shared = Arc::new(cli.client(timings.clone())?);
+            let run_movie = |cli, ahead, cleanup| {
+                let client = Some(shared.clone());
+                run(cli, client, ahead, timings.clone(), cleanup)
+            };
+            let unattended = |path, language| cli.unattended(path, language);
+            return daemon::serve(&address, jobs, unattended, run_movie, &timings, &cleanup)
+                .await
+                .map(|()| Outcome::Done);
+        }
+        #[cfg(feature = "self-update")]
+        Some(Action::SelfUpdate { check_only }) => {
+            return update::self_update(cli.proxy.as_deref(), check_only)
+                .await
+                .map(|()| Outcome::Done);
+        }
+        _ => {}
+    }
+    // uploads, votes and reports log in to the api with the account
+    let account = match &cli.action {
+        Some(Action::Upload { account, .. }) => Some(("upload", account)),
+        #[cfg(feature = "history")]
+        Some(Action::Rate { account, .. }) => Some(("rate", account)),
+        #[cfg(feature = "history")]
+        Some(Action::Report { account, .. }) => Some(("report", account)),
+        _ => None,
+    };
+    // built up front as the flags are taken apart below, its errors wait for the download
+    let client = match (client, account) {
+        (Some(client), _) => Ok(client),
+        (None, Some((command, account))) => {
+            let built = account
+                .clone()
+                .credentials(command)
+                .and_then(|credentials| {
+                    let builder = cli.client_builder(timings.clone());
+                    builder
+                        .credentials(Some(credentials))
+                        .account_required(true)
+                        .build()
+                });
+            built.map(Arc::new)
+        }
+        (None, None) => cli.client(timings.clone()).map(Arc::new),
+    };
+    let limits = cli.limits();
+    let hashing = cli.hash_method();
     let Cli {
+        action,
         movie_file,
+        query,
         language,
         top_n,
-    } = Cli::parse();
-    info!(?movie_file, %language, "downloading");
-    let hash = hash_for_file(&movie_file)?;
-    let url = url(&language, hash)?;
-    let page = crawler::get_page(url).await?;
-    let link = crawler::top_rated_subs(page, top_n).and_then(|values| {
-        prompt_unless_single("which url do your want to download", values)
-            .wrap_err("selecting url to download")
-    })?;
-    let download_url = link.download_url;
-    let zip = crawler::get_zip(download_url).await?;
-    let mut zip_contents = std::io::Cursor::new(zip);
-    let mut zip_reader = ::zip::ZipArchive::new(&mut zip_contents).wrap_err("reading zip")?;
-    let files = zip_reader
-        .file_names()
-        .filter(|e| !e.to_lowercase().trim().ends_with(".nfo"))
-        .map(|v| v.to_string())
-        .sorted_unstable_by_key(|v| v.to_lowercase().ends_with(".srt"))
-        .rev()
+        max_bad_reports,
+        include_featured_first,
+        dump_html: _,
+        no_mmap: _,
+        log_format: _,
+        ui_language: _,
+        log_file: _,
+        log_file_max_mb: _,
+        timings: show_timings,
+        notify: _,
+        copy_path: _,
+        json,
+        #[cfg(feature = "self-update")]
+            no_update_check: _,
+        #[cfg(feature = "history")]
+        no_history,
+        #[cfg(feature = "history")]
+        history_file,
+        proxy: _,
+        user_agent: _,
+        insecure,
+        request_timeout: _,
+        request_interval_ms: _,
+        base_url: _,
+        api_url: _,
+        format_preference,
+        only_preferred_formats,
+        #[cfg(feature = "tui")]
+        auto,
+        max_attempts,
+        max_download_mb: _,
+        max_entry_mb: _,
+        max_compression_ratio: _,
+        archive_codepage,
+        keep_archive,
+        extract_all,
+        season_pack,
+        archive_exclude,
+        subtitles_only,
+        preserve_times,
+        match_perms,
+        ignore_line_endings,
+        processing,
+        retime_fps,
+        auto_retime,
+        join_parts,
+        sync,
+        sync_timeout,
+        keep_unsynced,
+        translation,
+        edit,
+        edit_wait_cmd,
+        strict_duration,
+        skip_if_audio_matches,
+        verify_language,
+        verify_episode,
+        #[cfg(feature = "embed")]
+        embedding,
+        embed_timeout,
+        ffmpeg_path,
+        ffprobe_path,
+        args: _,
+    } = cli;
+    // nobody to ask without the prompts
+    #[cfg(not(feature = "tui"))]
+    let auto = true;
+    let embed_timeout = embed_timeout.map(std::time::Duration::from_secs);
+    tools::configure(
+        [("ffmpeg", ffmpeg_path), ("ffprobe", ffprobe_path)]
+            .into_iter()
+            .filter_map(|(program, path)| Some((program, path?))),
+    );
+    // a command is done once it did what it does, the rest is the download
+    if action.is_some() {
+        let done = async move {
+            match action {
+                Some(Action::Verify { subtitle_file }) => verify(&subtitle_file),
+                Some(Action::Adjust {
+                    subtitle_file,
+                    shift,
+                    anchor,
+                    first_at,
+                    last_at,
+                    retime_fps,
+                }) => {
+                    adjust(&subtitle_file, |srt| {
+                        Ok(postprocess::PostProcess {
+                            linear: match retime_fps {
+                                Some(rates) => download::frame_rate_retime(rates),
+                                None => linear_retime(srt, &anchor, first_at, last_at)?,
+                            },
+                            shift,
+                            ..Default::default()
+                        })
+                    })
+                    .await
+                }
+                Some(Action::Merge {
+                    primary,
+                    secondary,
+                    output,
+                    tolerance_ms,
+                    italic,
+                    color,
+                }) => {
+                    let style = merge::SecondaryStyle { italic, color };
+                    merge::merge_files(&primary, &secondary, output, tolerance_ms, &style).await
+                }
+                Some(Action::Clean {
+                    files,
+                    output,
+                    dry_run,
+                    no_backup,
+                    language,
+                    processing,
+                }) => {
+                    let target = match dry_run {
+                        true => clean::Target::DryRun,
+                        false => clean::Target::Write {
+                            output,
+                            backup: !no_backup,
+                        },
+                    };
+                    clean::clean(&files, target, &processing.writer(language)).await
+                }
+                Some(Action::Rename {
+                    dir,
+                    template,
+                    yes,
+                    force,
+                    dry_run,
+                }) => rename::rename_orphans(&dir, &template, yes, force, dry_run),
+                Some(Action::ExtractSubs {
+                    movie_file,
+                    all,
+                    language,
+                }) => {
+                    let languages = language.as_deref();
+                    extract::extract_subs(&movie_file, all, languages, embed_timeout, &cleanup)
+                        .await
+                }
+                Some(Action::ListTracks {
+                    movie_file,
+                    output_format,
+                }) => probe::list_tracks(&movie_file, output_format).await,
+                Some(Action::Hook { .. } | Action::Daemon { .. }) => unreachable!("handled before"),
+                Some(Action::Send {
+                    path,
+                    language,
+                    socket,
+                }) => send(path, language, socket).await,
+                Some(Action::Upload {
+                    video,
+                    subtitle,
+                    imdb,
+                    release_name,
+                    hearing_impaired,
+                    forced,
+                    comment,
+                    account: _,
+                }) => {
+                    let upload = upload::Upload {
+                        imdb_id: imdb,
+                        hearing_impaired,
+                        forced,
+                        comment,
+                        ..upload::Upload::from_files(&video, &subtitle, &language)?
+                    };
+                    let upload = match release_name {
+                        Some(release_name) => upload::Upload {
+                            release_name,
+                            ..upload
+                        },
+                        None => upload,
+                    };
+                    upload_subtitle(&*client?, &upload).await
+                }
+                #[cfg(feature = "history")]
+                Some(Action::Rate {
+                    path,
+                    stars,
+                    account: _,
+                }) => {
+                    let history = history_command::at(history_file)?;
+                    let download = history_command::downloaded(&history, &path, stars.is_none())?;
+                    let stars = match stars {
+                        Some(stars) => stars,
+                        None => prompt::select(text("prompt-stars"), (1..=10).rev().collect())?,
+                    };
+                    let feedback = feedback::Feedback::Vote {
+                        subtitle_id: download.subtitle_id,
+                        stars,
+                    };
+                    client?.feedback(&feedback).await?;
+                    let subtitle = download.subtitle_id;
+                    println!(
+                        "{}",
+                        filled("rated", &[("subtitle", &subtitle), ("stars", &stars)])
+                    );
+                    Ok(())
+                }
+                #[cfg(feature = "history")]
+                Some(Action::Report {
+                    path,
+                    reason,
+                    comment,
+                    account: _,
+                }) => {
+                    let history = history_command::at(history_file)?;
+                    let download = history_command::downloaded(&history, &path, reason.is_none())?;
+                    let reason = match reason {
+                        Some(reason) => reason,
+                        None => prompt::select(
+                            text("prompt-report-reason"),
+                            feedback::ReportReason::ALL.to_vec(),
+                        )?,
+                    };
+                    let feedback = feedback::Feedback::Report {
+                        subtitle_id: download.subtitle_id,
+                        reason,
+                        comment,
+                    };
+                    client?.feedback(&feedback).await?;
+                    let subtitle = download.subtitle_id;
+                    println!(
+                        "{}",
+                        filled("reported", &[("subtitle", &subtitle), ("reason", &reason)])
+                    );
+                    Ok(())
+                }
+                #[cfg(feature = "history")]
+                Some(Action::Undo {
+                    movie_file,
+                    remove_video,
+                    dry_run,
+                }) => {
+                    let history = history_command::at(history_file)?;
+                    let movie_file = movie_file.as_deref();
+                    history_command::undo(&history, movie_file, remove_video, dry_run).await
+                }
+                #[cfg(feature = "history")]
+                Some(Action::History { command }) => {
+                    let history = history_command::at(history_file)?;
+                    match command {
+                        HistoryCommand::List { output_format } => {
+                            history_command::list(&history, output_format)
+                        }
+                        HistoryCommand::Show { path } => history_command::show(&history, &path),
+                        HistoryCommand::Purge => history_command::purge(&history).await,
+                    }
+                }
+                #[cfg(feature = "history")]
+                Some(Action::Stats {
+                    since,
+                    output_format,
+                }) => {
+                    let history = history_command::at(history_file)?;
+                    history_command::stats(&history, since, output_format)
+                }
+                #[cfg(feature = "self-update")]
+                Some(Action::SelfUpdate { .. }) => unreachable!("handled before"),
+                None => unreachable!("downloads aren't commands"),
+            }
+        };
+        return done.await.map(|()| Outcome::Done);
+    }
+    #[cfg(feature = "history")]
+    let recorder = recorder::Recorder::new(
+        match no_history {
+            true => None,
+            false => Some(history_command::at(history_file)?),
+        },
+        auto,
+    );
+    #[cfg(not(feature = "history"))]
+    let recorder = recorder::Recorder::default();
+    let movie_file = movie_file.ok_or_else(|| eyre!("--movie-file is required"))?;
+    let editor = match (edit, auto) {
+        (true, true) => bail!(text("error-edit-auto")),
+        (true, false) => Some(editor::Editor::find(edit_wait_cmd.as_deref(), |name| {
+            std::env::var(name).ok()
+        })?),
+        (false, _) => None,
+    };
+    let languages = language
+        .split(',')
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(str::to_string)
         .collect::<Vec<_>>();
-    info!(?files, "found files");
-
-    let file = prompt_unless_single("Select the subtitle file", files)
-        .wrap_err("choosing subtitle file")?;
-
-    let extension = file
-        .split('.')
-        .last()
-        .ok_or_else(|| eyre!("this file has no extension"))?;
-
-    let file = zip_reader
-        .by_name(&file)
-        .wrap_err_with(|| format!("extracting {file} from the archive"))?
-        .bytes()
-        .map(|v| v.wrap_err("invalid byte"))
-        .collect::<Result<Vec<_>>>()?;
-    let subtitle_file = movie_file.with_extension(extension);
-    tokio::fs::write(&subtitle_file, &file)
-        .await
-        .wrap_err_with(|| format!("writing subtitle file to {subtitle_file:?}"))?;
-    println!("{subtitle_file:?}");
-    let with_subtitles_name = movie_file
-        .extension()
-        .and_then(|e| e.to_str())
-        .ok_or_else(|| eyre!("file has no extension"))
-        .map(|extension| format!("with-subs.{extension}"))
-        .map(|extension| movie_file.with_extension(extension))
-        .wrap_err_with(|| format!("generating a with-subs file name for [{movie_file:?}]"))?;
-
-    match inquire::Select::new(
-        &format!("soft-embed subtitles into [{with_subtitles_name:?}]?"),
-        vec![true, false],
-    )
-    .prompt()
-    .unwrap_or_default()
-    {
-        true => {
-            info!(?with_subtitles_name, "saving video with subs to new path");
-            Command::new("ffmpeg")
-                .arg("-i")
-                .arg(movie_file.as_os_str())
-                .arg("-i")
-                .arg(subtitle_file.as_os_str())
-                .args([
-                    "-map",
-                    "0",
-                    "-map",
-                    "1",
-                    "-c",
-                    "copy",
-                    "-c:s",
-                    "mov_text",
-                    "-metadata:s:s:1",
-                ])
-                .arg(format!("language={language}"))
-                .arg(&with_subtitles_name)
-                .status()
-                .await
-                .wrap_err("embedding the subtitles")
-                .and_then(|status| {
-                    status
-                        .success()
-                        .then_some(())
-                        .ok_or_else(|| eyre!("bad status code: [{status:?}]"))
-                })
-                .tap_ok(move |_| {
-                    info!("file with subtitles available at {with_subtitles_name:?}");
-                })
+    // subtitles of a list of languages are named after theirs
+    let several = match languages.as_slice() {
+        [] => bail!("--language names no language"),
+        [_] => false,
+        _ => true,
+    };
+    let episodes = match season_pack || movie_file.is_dir() {
+        true => match movie_file.is_dir() {
+            true => download::episode_files(&movie_file)?,
+            false => download::episode_files(movie_file.parent().unwrap_or(Path::new(".")))?,
+        }
+        .pipe(Some),
+        false => None,
+    };
+    if episodes.is_some() && several {
+        bail!("season packs are downloaded one language at a time");
+    }
+    // the search goes by the hash of a single episode
+    let movie_file = match (&episodes, movie_file.is_dir()) {
+        (Some(episodes), true) => episodes
+            .first()
+            .cloned()
+            .ok_or_else(|| eyre!("no video files in {movie_file:?}"))?,
+        _ => movie_file,
+    };
+    let shared = Arc::new(results::Results::new(json, &movie_file, &language));
+    let results = shared.as_ref();
+    let (audio, hashed) = match ahead {
+        Some(Ahead { audio, hashed }) => (audio?, hashed),
+        None if skip_if_audio_matches => {
+            let audio = probe::audio_in_language(&movie_file, &language).await?;
+            (audio, None)
+        }
+        None => (None, None),
+    };
+    if let Some(audio) = audio {
+        info!(
+            ?movie_file,
+            audio, "the movie already has audio in the language, skipped"
+        );
+        results.skipped(results::Skipped::AudioMatches);
+        results.finish()?;
+        return Ok(Outcome::Skipped);
+    }
+    let synchronizer = sync.map(sync::SyncTool::synchronizer).transpose()?;
+    let translator = translation.translator();
+    #[cfg(feature = "embed")]
+    let embedding = embedding
+        .prepare(&movie_file, &languages[0], auto, embed_timeout)
+        .await?;
+    let mut writer = output::SubtitleWriter {
+        ignore_line_endings,
+        timings: timings.clone(),
+        cleanup: cleanup.clone(),
+        results: shared.clone(),
+        #[cfg(feature = "history")]
+        history: recorder.history().cloned(),
+        ..processing.writer(language.clone())
+    };
+    let copy_metadata = output::CopyMetadata {
+        times: preserve_times,
+        permissions: match_perms,
+    };
+    let archive_options = archive::Options {
+        limits,
+        legacy_encoding: archive_codepage,
+        filter: archive::EntryFilter {
+            excluded: archive_exclude,
+            subtitles_only,
+        },
+    };
+    let format_preference = FormatPreference(format_preference);
+    // archives are searched for srt files unless told otherwise
+    let entry_preference = match format_preference.is_empty() {
+        true => FormatPreference(vec![SubtitleFormat::Srt]),
+        false => format_preference.clone(),
+    };
+    let client = client?;
+    // everything from here on is about this one movie, its logs carry the file and the hash
+    let span = info_span!(
+        "movie",
+        file = ?movie_file,
+        %language,
+        hash = field::Empty,
+        insecure = field::Empty
+    );
+    // only runs that skipped the certificate checks say so
+    if insecure {
+        span.record("insecure", true);
+        results.insecure();
+    }
+    // the stages of parts and season packs too, their downloads end early
+    let timed = show_timings.then(|| timings.clone());
+    async move {
+        info!(?movie_file, %language, "downloading");
+        // `hook` and `daemon` hashed the movie already
+        let hashed = async {
+            match hashed {
+                Some(hashed) => hashed,
+                None => {
+                    let hashing = hash::hash_file(movie_file.clone(), hashing);
+                    timings.time("hashing", hashing).await
+                }
+            }
+        };
+        // a file too small to hash is most likely a sample, its title still finds the movie
+        let search = match query {
+            Some(query) => SearchBy::Title(query),
+            None => match hashed.await {
+                Ok(hash) => {
+                    Span::current().record("hash", hash.as_str());
+                    SearchBy::Hash(hash)
+                }
+                Err(report) if hash::HashError::find(&report).is_some() => {
+                    let stem = movie_file.file_stem().unwrap_or_default().to_string_lossy();
+                    let title = release::title(&stem);
+                    if title.is_empty() {
+                        return Err(report);
+                    }
+                    warn!(%report, %title, "searching by title instead");
+                    SearchBy::Title(title)
+                }
+                Err(report) => return Err(report),
+            },
+        };
+        let ranking = crawler::Ranking {
+            top_n,
+            max_bad_reports,
+            featured_first: include_featured_first,
+            format_preference,
+            only_preferred_formats,
+            // picking one of these by accident is a common trap
+            exclude_foreign_parts_only: auto,
+        };
+        let movie_hash = match &search {
+            SearchBy::Hash(hash) => Some(hash.as_str()),
+            SearchBy::Title(_) => None,
+        };
+        let search_in = |language: String| {
+            let (client, search, ranking) = (&client, &search, &ranking);
+            async move {
+                match search {
+                    SearchBy::Hash(hash) => client.search_by_hash(&language, hash, ranking).await,
+                    SearchBy::Title(title) => {
+                        client.search_by_query(&language, title, ranking).await
+                    }
+                }
+            }
+        };
+        let movie_duration = probe::duration(&movie_file)
+            .await
+            .tap_err(|message| debug!(?message, "probing the movie's duration failed"))
+            .ok()
+            .flatten();
+        if strict_duration && movie_duration.is_none() {
+            warn!("the movie's duration is unknown, --strict-duration can't check the subtitles");
+        }
+        // season packs pair entries and episodes on their own
+        let movie_episode = movie_file
+            .file_name()
+            .and_then(|v| v.to_str())
+            .and_then(release::episode)
+            .filter(|_| episodes.is_none() && verify_episode != check::CheckMode::Off);
+        // the languages one after another, their subtitles are embedded together
+        let mut downloaded = vec![];
+        for language in &languages {
+            let candidates = search_in(language.clone()).await?;
+            // nothing in the language, subtitles in another one are translated instead
+            let (candidates, translation) = match (candidates.is_empty(), translator.clone()) {
+                (true, Some((from, translator))) => {
+                    info!(%from, "no subtitles in the language, looking for some to translate");
+                    let translation = download::MachineTranslation {
+                        from: from.clone(),
+                        to: language.clone(),
+                        translator,
+                        http: client.http().clone(),
+                        timings: timings.clone(),
+                        cleanup: cleanup.clone(),
+                    };
+                    (search_in(from).await?, Some(translation))
+                }
+                _ => (candidates, None),
+            };
+            let translation = translation.as_ref();
+            // the subtitles are in this one until they're translated
+            let subtitle_language = translation.map_or(language, |translation| &translation.from);
+            if let Some(transcode) = &mut writer.transcode {
+                transcode.language = subtitle_language.clone();
+            }
+            let candidates = candidates
+                .into_iter()
+                .map(|candidate| candidate.for_movie(&movie_file))
+                .collect::<Vec<_>>();
+            let download = download::Download {
+                client: &client,
+                movie_file: &movie_file,
+                language,
+                subtitle_language,
+                named_by_language: several,
+                auto,
+                max_attempts,
+                episodes: episodes.as_deref(),
+                movie_episode,
+                movie_duration,
+                verify_episode,
+                verify_language,
+                strict_duration,
+                retime_fps,
+                join_parts: (!join_parts.is_empty()).then_some(&join_parts[..]),
+                auto_retime,
+                entry_preference: &entry_preference,
+                archive_options: &archive_options,
+                keep_archive: keep_archive.as_ref().map(Option::as_deref),
+                extract_all,
+                copy_metadata,
+                translation,
+                results,
+                cleanup: &cleanup,
+            };
+            let (link, subtitle_files) = match download.run(candidates, &mut writer).await? {
+                download::Downloaded::Files { link, files } => (link, files),
+                download::Downloaded::Parts { link, files } => {
+                    if let Some(editor) = &editor {
+                        edit_subtitles(editor, &files, movie_duration, language).await?;
+                    }
+                    for path in &files {
+                        copy_metadata.apply(&movie_file, path);
+                        results.written(path);
+                    }
+                    recorder
+                        .downloads(&movie_file, movie_hash, language, &link, &files)
+                        .await;
+                    downloaded.push(download::Fetched {
+                        language: language.clone(),
+                        files,
+                        parts: true,
+                    });
+                    continue;
+                }
+                download::Downloaded::SeasonPack { link, episodes } => {
+                    if editor.is_some() {
+                        warn!("--edit doesn't open the subtitles of a season pack");
+                    }
+                    for (episode, subtitle_files) in episodes {
+                        // the hash searched by is the first episode's
+                        let hash = movie_hash.filter(|_| episode == movie_file);
+                        recorder
+                            .downloads(&episode, hash, language, &link, &subtitle_files)
+                            .await;
+                    }
+                    return Ok(());
+                }
+            };
+            let unsynced_files = match synchronizer {
+                Some(synchronizer) => {
+                    let timeout = std::time::Duration::from_secs(sync_timeout);
+                    let synchronized = synchronize(
+                        synchronizer,
+                        &movie_file,
+                        &subtitle_files,
+                        timeout,
+                        keep_unsynced,
+                        results,
+                    );
+                    timings.time("sync", synchronized).await
+                }
+                None => vec![],
+            };
+            if let Some(editor) = &editor {
+                edit_subtitles(editor, &subtitle_files, movie_duration, language).await?;
+            }
+            for subtitle_file in subtitle_files.iter().chain(&unsynced_files) {
+                copy_metadata.apply(&movie_file, subtitle_file);
+                results.written(subtitle_file);
+            }
+            // after --sync, the recorded content is what ends up on disk
+            let written = subtitle_files
+                .iter()
+                .chain(&unsynced_files)
+                .cloned()
+                .collect::<Vec<_>>();
+            recorder
+                .downloads(&movie_file, movie_hash, language, &link, &written)
+                .await;
+            downloaded.push(download::Fetched {
+                language: language.clone(),
+                files: subtitle_files,
+                parts: false,
+            });
         }
-        false => Ok(()),
+        #[cfg(feature = "embed")]
+        let embedded = embedding
+            .embed(
+                &movie_file,
+                &downloaded,
+                movie_duration,
+                &recorder,
+                &timings,
+                &cleanup,
+            )
+            .await?;
+        #[cfg(feature = "embed")]
+        match embedded {
+            Some(embed::Embedded::BurnedIn(burned_in)) => results.written(&burned_in),
+            Some(embed::Embedded::Command { arguments, line }) => {
+                results.embed_command(arguments, &line)
+            }
+            None => {}
+        }
+        Ok(())
+    }
+    .instrument(span)
+    .await?;
+    if let Some(timings) = timed {
+        results.timings(&timings);
     }
+    results.finish().map(|()| Outcome::Done)
 }
@@ -0,0 +1,353 @@
+//! html-ish markup in cue text: entities players show literally, `<font>` soup and tags
+//! left open or closed twice
+use crate::srt::{Cue, Srt};
+
+/// `&amp;`, `&#39;`, `&#x27;`... unknown ones are left as they are
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        "hellip" => Some('…'),
+        "ndash" => Some('–'),
+        "mdash" => Some('—'),
+        "lsquo" => Some('‘'),
+        "rsquo" => Some('’'),
+        "ldquo" => Some('“'),
+        "rdquo" => Some('”'),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => number.parse().ok(),
+            }
+            .and_then(char::from_u32)
+        }
+    }
+}
+
+pub(crate) fn decode_entities(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .split_once(';')
+            .filter(|(name, _)| name.len() <= 8)
+            .and_then(|(name, after)| entity(name).map(|c| (c, after)));
+        match decoded {
+            Some((c, after)) => {
+                output.push(c);
+                rest = after;
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// tags carry their lowercase name and the text they were written as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token<'a> {
+    Text(&'a str),
+    Open(String, &'a str),
+    Close(String, &'a str),
+}
+
+/// `<name ...>` or `</name>`, a `<` not starting one of them (`<3`, `a < b`) is text
+fn tag(text: &str) -> Option<(Token<'_>, usize)> {
+    let inner = text.strip_prefix('<')?;
+    let length = inner.find(['>', '<'])?;
+    if !inner[length..].starts_with('>') {
+        return None;
+    }
+    // `a < b > c` is no tag
+    let tag = &inner[..length];
+    let (closing, tag) = match tag.strip_prefix('/') {
+        Some(tag) => (true, tag),
+        None => (false, tag),
+    };
+    let name = tag
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let is_name = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric());
+    if !is_name {
+        return None;
+    }
+    let raw = &text[..length + 2];
+    let token = match closing {
+        true => Token::Close(name, raw),
+        false => Token::Open(name, raw),
+    };
+    Some((token, length + 2))
+}
+
+pub fn tokens(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = line;
+    let mut text_start = 0;
+    let mut idx = 0;
+    while let Some(offset) = rest[idx..].find('<') {
+        let start = idx + offset;
+        match tag(&rest[start..]) {
+            Some((token, length)) => {
+                if text_start < start {
+                    tokens.push(Token::Text(&rest[text_start..start]));
+                }
+                tokens.push(token);
+                rest = &rest[start + length..];
+                text_start = 0;
+                idx = 0;
+            }
+            None => idx = start + 1,
+        }
+    }
+    if !rest[text_start..].is_empty() {
+        tokens.push(Token::Text(&rest[text_start..]));
+    }
+    tokens
+}
+
+/// only the `keep` tags survive, balanced within the cue: closers without an opener are
+/// dropped, tags closed out of order are closed and reopened around the one being closed
+/// and whatever is open is closed at the end of each line and reopened on the next
+fn strip_cue(lines: &[String], keep: &[String]) -> Vec<String> {
+    let mut open: Vec<String> = vec![];
+    let mut output = vec![];
+    for line in lines {
+        let mut text = String::with_capacity(line.len());
+        if !output.is_empty() {
+            open.iter()
+                .for_each(|name| text.push_str(&format!("<{name}>")));
+        }
+        for token in tokens(line) {
+            match token {
+                Token::Text(part) => text.push_str(part),
+                Token::Open(name, _) if keep.contains(&name) && !open.contains(&name) => {
+                    text.push_str(&format!("<{name}>"));
+                    open.push(name);
+                }
+                Token::Close(name, _) if open.contains(&name) => {
+                    let idx = open.iter().position(|v| *v == name).expect("checked");
+                    let reopened = open.split_off(idx + 1);
+                    open.pop();
+                    reopened
+                        .iter()
+                        .rev()
+                        .for_each(|name| text.push_str(&format!("</{name}>")));
+                    text.push_str(&format!("</{name}>"));
+                    reopened
+                        .iter()
+                        .for_each(|name| text.push_str(&format!("<{name}>")));
+                    open.extend(reopened);
+                }
+                Token::Open(..) | Token::Close(..) => {}
+            }
+        }
+        // some players reset the styling on every line
+        open.iter()
+            .rev()
+            .for_each(|name| text.push_str(&format!("</{name}>")));
+        output.push(remove_empty_pairs(&text).trim().to_string());
+    }
+    output.retain(|line| !line.is_empty());
+    output
+}
+
+/// `<i></i>` left behind by tags around removed text
+fn remove_empty_pairs(line: &str) -> String {
+    let mut line = line.to_string();
+    loop {
+        let tokens = tokens(&line);
+        let empty = tokens.windows(2).position(|pair| match pair {
+            [Token::Open(open, _), Token::Close(close, _)] => open == close,
+            _ => false,
+        });
+        let Some(idx) = empty else {
+            return line;
+        };
+        line = tokens
+            .iter()
+            .enumerate()
+            .filter(|(position, _)| *position != idx && *position != idx + 1)
+            .map(|(_, token)| match token {
+                Token::Text(text) | Token::Open(_, text) | Token::Close(_, text) => *text,
+            })
+            .collect();
+    }
+}
+
+/// length of the text a player shows, in characters
+pub fn visible_len(line: &str) -> usize {
+    tokens(line)
+        .iter()
+        .map(|token| match token {
+            Token::Text(text) => text.chars().count(),
+            Token::Open(..) | Token::Close(..) => 0,
+        })
+        .sum()
+}
+
+/// closes the tags left open at the end of each line and reopens them on the next one, so
+/// any of the lines can be shown on its own
+pub fn balance_lines(lines: &[String]) -> Vec<String> {
+    let mut open: Vec<(String, String)> = vec![];
+    lines
+        .iter()
+        .map(|line| {
+            let mut text = open.iter().map(|(_, raw)| raw.as_str()).collect::<String>();
+            for token in tokens(line) {
+                match token {
+                    Token::Text(part) => text.push_str(part),
+                    Token::Open(name, raw) => {
+                        text.push_str(raw);
+                        open.push((name, raw.to_string()));
+                    }
+                    Token::Close(name, raw) => {
+                        text.push_str(raw);
+                        if let Some(idx) = open.iter().rposition(|(open, _)| *open == name) {
+                            open.remove(idx);
+                        }
+                    }
+                }
+            }
+            open.iter()
+                .rev()
+                .for_each(|(name, _)| text.push_str(&format!("</{name}>")));
+            remove_empty_pairs(&text)
+        })
+        .collect()
+}
+
+/// returns how many cues changed, cues left without text are dropped
+pub fn strip_tags(srt: &mut Srt, keep: &[String]) -> usize {
+    let mut changed = 0;
+    srt.cues = srt
+        .cues
+        .iter()
+        .filter_map(|cue| {
+            let stripped = Cue {
+                lines: strip_cue(&cue.lines, keep),
+                ..cue.clone()
+            };
+            if stripped != *cue {
+                changed += 1;
+            }
+            (!stripped.lines.is_empty()).then_some(stripped)
+        })
+        .collect();
+    changed
+}
+
+/// returns how many cues had entities decoded
+pub fn decode(srt: &mut Srt) -> usize {
+    let mut changed = 0;
+    for cue in &mut srt.cues {
+        let lines = cue
+            .lines
+            .iter()
+            .map(|line| decode_entities(line))
+            .collect::<Vec<_>>();
+        if lines != cue.lines {
+            cue.lines = lines;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stripped(lines: &[&str]) -> Vec<String> {
+        let lines = lines.iter().map(ToString::to_string).collect::<Vec<_>>();
+        strip_cue(&lines, &["i".to_string(), "b".to_string()])
+    }
+
+    #[test]
+    fn crossed_tags_are_closed_in_order() {
+        assert_eq!(stripped(&["<i><b>text</i></b>"]), ["<i><b>text</b></i>"]);
+        assert_eq!(
+            stripped(&["<b><i>bold</b> italic</i>"]),
+            ["<b><i>bold</i></b><i> italic</i>"]
+        );
+    }
+
+    #[test]
+    fn stray_and_doubled_tags() {
+        assert_eq!(stripped(&["</i>stray"]), ["stray"]);
+        assert_eq!(stripped(&["<i><i>twice</i></i>"]), ["<i>twice</i>"]);
+        assert_eq!(stripped(&["<I>loud</I>"]), ["<i>loud</i>"]);
+        assert_eq!(stripped(&["<i>never closed"]), ["<i>never closed</i>"]);
+    }
+
+    #[test]
+    fn font_soup_goes() {
+        assert_eq!(
+            stripped(&["<font color=\"#ffff00\"><i>Hey!</font></i>"]),
+            ["<i>Hey!</i>"]
+        );
+        assert_eq!(stripped(&["<font face=Arial><u>x</u></font>"]), ["x"]);
+        let deep = format!("{}deep{}", "<font>".repeat(200), "</font>".repeat(200));
+        assert_eq!(stripped(&[&deep]), ["deep"]);
+    }
+
+    #[test]
+    fn tags_open_across_lines_are_reopened() {
+        assert_eq!(
+            stripped(&["<i>first", "second</i>", "third"]),
+            ["<i>first</i>", "<i>second</i>", "third"]
+        );
+        // a line left with nothing but tags goes
+        assert_eq!(stripped(&["<i>", "text</i>"]), ["<i>text</i>"]);
+    }
+
+    #[test]
+    fn angle_brackets_that_arent_tags_stay() {
+        assert_eq!(stripped(&["<i>a < b</i>"]), ["<i>a < b</i>"]);
+        assert_eq!(stripped(&["I <3 you"]), ["I <3 you"]);
+        assert_eq!(stripped(&["<<i>>"]), ["<<i>></i>"]);
+        assert_eq!(stripped(&["< i >"]), ["< i >"]);
+    }
+
+    #[test]
+    fn cues_left_empty_are_dropped() {
+        let cue = |lines: &[&str]| Cue {
+            start: crate::srt::Timestamp(0),
+            end: crate::srt::Timestamp(1000),
+            lines: lines.iter().map(ToString::to_string).collect(),
+        };
+        let mut srt = Srt {
+            cues: vec![cue(&["<i></i>", "<font> </font>"]), cue(&["<i>kept</i>"])],
+        };
+        assert_eq!(strip_tags(&mut srt, &["i".to_string()]), 1);
+        assert_eq!(srt.cues, [cue(&["<i>kept</i>"])]);
+    }
+
+    #[test]
+    fn entities_are_decoded_once() {
+        assert_eq!(decode_entities("&amp;lt;"), "&lt;");
+        assert_eq!(
+            decode_entities("it&#39;s &#x27;it&#X27;s&apos;"),
+            "it's 'it's'"
+        );
+        assert_eq!(decode_entities("AT&T &bogus; &"), "AT&T &bogus; &");
+        // surrogates and numbers too big for a char
+        assert_eq!(
+            decode_entities("&#xD800; &#99999999999;"),
+            "&#xD800; &#99999999999;"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! two languages in one file, for learning one of them
+use crate::{
+    output,
+    srt::{self, Cue, Srt},
+};
+use eyre::{Result, WrapErr};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct SecondaryStyle {
+    pub italic: bool,
+    /// `#ffff00` or a color name, wrapped in `<font color>`
+    pub color: Option<String>,
+}
+
+impl SecondaryStyle {
+    fn apply(&self, line: &str) -> String {
+        let line = match self.italic {
+            true => format!("<i>{line}</i>"),
+            false => line.to_string(),
+        };
+        match &self.color {
+            Some(color) => format!("<font color=\"{color}\">{line}</font>"),
+            None => line,
+        }
+    }
+}
+
+/// how long the two cues share the screen, the primary one stretched by `tolerance` at both
+/// ends since translations are rarely timed to the millisecond
+fn overlap(primary: &Cue, secondary: &Cue, tolerance: i64) -> i64 {
+    let start = (primary.start.0 - tolerance).max(secondary.start.0);
+    let end = (primary.end.0 + tolerance).min(secondary.end.0);
+    end - start
+}
+
+/// every secondary cue is put under the primary cue it overlaps the most, the ones
+/// overlapping none (and primary cues nothing was put under) pass through unchanged
+pub fn merge(primary: &Srt, secondary: &Srt, tolerance: i64, style: &SecondaryStyle) -> Srt {
+    let mut merged = primary.cues.clone();
+    let mut unmatched = vec![];
+    for cue in &secondary.cues {
+        let best = primary
+            .cues
+            .iter()
+            .enumerate()
+            .map(|(idx, primary)| (idx, overlap(primary, cue, tolerance)))
+            .filter(|(_, overlap)| *overlap > 0)
+            .max_by_key(|(_, overlap)| *overlap);
+        match best {
+            Some((idx, _)) => merged[idx]
+                .lines
+                .extend(cue.lines.iter().map(|line| style.apply(line))),
+            None => unmatched.push(cue.clone()),
+        }
+    }
+    merged.extend(unmatched);
+    merged.sort_by_key(|cue| cue.start);
+    Srt { cues: merged }
+}
+
+/// `merge`, the two files merged into `output`, `<primary>.merged.srt` unless given
+pub async fn merge_files(
+    primary: &Path,
+    secondary: &Path,
+    output: Option<PathBuf>,
+    tolerance: i64,
+    style: &SecondaryStyle,
+) -> Result<()> {
+    let read = |path: &Path| {
+        fs::read(path)
+            .wrap_err_with(|| format!("reading {path:?}"))
+            .and_then(|contents| {
+                String::from_utf8(contents).wrap_err("only utf-8 files can be merged")
+            })
+            .map(|text| {
+                srt::parse_lenient(&text)
+                    .repair(srt::RepairOptions::default())
+                    .0
+            })
+    };
+    let merged = merge(&read(primary)?, &read(secondary)?, tolerance, style);
+    let output = output.unwrap_or_else(|| primary.with_extension("merged.srt"));
+    output::write_atomic(&output, merged.to_string().as_bytes()).await?;
+    println!("{}", output::quoted(&output));
+    Ok(())
+}
@@ -0,0 +1,442 @@
+//! what the binary says to whoever runs it: prompts, tables and the hints of its errors, in
+//! english or polish. logs stay english, they're for bug reports
+//!
+//! every text is found by its key, `{name}` in it is filled in by [`filled`]
+use std::{fmt::Display, sync::OnceLock};
+
+/// `--ui-language`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UiLanguage {
+    En,
+    Pl,
+}
+
+impl UiLanguage {
+    pub const ALL: [Self; 2] = [Self::En, Self::Pl];
+
+    /// the locale of `LC_ALL`, `LC_MESSAGES` or `LANG`, the first one set. english for
+    /// anything but polish
+    pub fn from_locale(env: impl Fn(&str) -> Option<String>) -> Self {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(env)
+            .find(|locale| !locale.is_empty())
+            .unwrap_or_default();
+        match locale.starts_with("pl") {
+            true => Self::Pl,
+            false => Self::En,
+        }
+    }
+
+    /// `(key, text)` of every message
+    pub fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::En => EN,
+            Self::Pl => PL,
+        }
+    }
+}
+
+static UI_LANGUAGE: OnceLock<UiLanguage> = OnceLock::new();
+
+/// set once at startup, english until then
+pub fn set_ui_language(language: UiLanguage) {
+    UI_LANGUAGE.get_or_init(|| language);
+}
+
+pub fn ui_language() -> UiLanguage {
+    UI_LANGUAGE.get().copied().unwrap_or(UiLanguage::En)
+}
+
+/// the message in the ui language, the english one when it's missing there and the key itself
+/// when it's missing everywhere
+pub fn text(key: &str) -> &str {
+    let find = |language: UiLanguage| {
+        language
+            .catalog()
+            .iter()
+            .find(|(found, _)| *found == key)
+            .map(|(_, text)| *text)
+    };
+    find(ui_language())
+        .or_else(|| find(UiLanguage::En))
+        .unwrap_or(key)
+}
+
+/// [`text`] with every `{name}` of `values` filled in
+pub fn filled(key: &str, values: &[(&str, &dyn Display)]) -> String {
+    fill(text(key), values)
+}
+
+pub fn fill(text: &str, values: &[(&str, &dyn Display)]) -> String {
+    values.iter().fold(text.to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), &value.to_string())
+    })
+}
+
+/// the `{name}`s of `text`
+pub fn placeholders(text: &str) -> Vec<&str> {
+    text.split('{')
+        .skip(1)
+        .filter_map(|rest| rest.split_once('}'))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+static EN: &[(&str, &str)] = &[
+    // prompts
+    (
+        "prompt-which-subtitle",
+        "which subtitle do you want to download?",
+    ),
+    ("prompt-which-file", "which subtitle file?"),
+    ("prompt-extract-streams", "extract which subtitle streams?"),
+    ("prompt-which-download", "which download?"),
+    ("prompt-stars", "how many stars?"),
+    ("prompt-report-reason", "what's wrong with it?"),
+    (
+        "prompt-account-password",
+        "opensubtitles.org password of {username}:",
+    ),
+    (
+        "prompt-archive-password",
+        "the archive is password protected, password:",
+    ),
+    ("prompt-download-again", "{error}, download it again?"),
+    ("prompt-edit-again", "{problem}, edit the subtitles again?"),
+    ("prompt-recover", "{error}, what now?"),
+    ("recovery-retry", "retry the same subtitle"),
+    ("recovery-another", "pick another subtitle"),
+    ("recovery-abort", "abort"),
+    ("prompt-rename", "rename {count} subtitle files?"),
+    ("prompt-embed", "soft-embed subtitles into [{movie}]?"),
+    (
+        "prompt-embed-in-place",
+        "soft-embed subtitles into [{movie}] in place?",
+    ),
+    (
+        "prompt-existing-track",
+        "the movie already has {language} subtitles, what about [{subtitle}]?",
+    ),
+    ("answer-yes", "yes"),
+    ("answer-no", "no"),
+    // tables
+    ("column-type", "type"),
+    ("column-codec", "codec"),
+    ("column-language", "language"),
+    ("column-title", "title"),
+    ("column-flags", "flags"),
+    ("column-downloaded", "downloaded"),
+    ("column-subtitle", "subtitle"),
+    ("column-movie", "movie"),
+    ("column-output", "output"),
+    ("column-provider", "provider"),
+    ("column-month", "month"),
+    ("column-uploader", "uploader"),
+    ("column-downloads", "downloads"),
+    // `history show`
+    (
+        "show-download",
+        "{time} {language} subtitle {subtitle} from {provider}",
+    ),
+    ("show-movie", "movie"),
+    ("show-movie-hash", "movie hash"),
+    ("show-name", "name"),
+    ("show-release", "release"),
+    ("show-format", "format"),
+    (
+        "show-entry",
+        "{format}, uploaded by {uploader}, {downloads} downloads, rated {rating}",
+    ),
+    ("show-output", "output"),
+    ("show-content-hash", "sha-256"),
+    ("history-purged", "forgot {count} downloads"),
+    // `stats`
+    ("stats-downloads", "{count} downloads"),
+    ("stats-downloads-since", "{count} downloads since {day}"),
+    (
+        "stats-rating",
+        "rated {rating} on average, of {rated} rated",
+    ),
+    (
+        "stats-selection",
+        "picked {auto} without asking, {asked} from the prompt, {unknown} unknown",
+    ),
+    // the end of a run
+    (
+        "season-unmatched-entries",
+        "subtitle files without an episode:",
+    ),
+    ("season-unmatched-episodes", "episodes without subtitles:"),
+    (
+        "rename-nothing",
+        "every subtitle file in {dir} is named after a video already",
+    ),
+    (
+        "rename-unmatched",
+        "subtitle files no video's name is close to:",
+    ),
+    ("renamed", "renamed {count} subtitle files"),
+    (
+        "interrupted",
+        "interrupted, cleaning up, Ctrl-C again to exit right away",
+    ),
+    ("interrupted-removed", "removed the partial {path}"),
+    ("interrupted-written", "written before the interruption:"),
+    (
+        "undo-nothing",
+        "the files are gone already, nothing to undo",
+    ),
+    ("uploaded", "uploaded, {link}"),
+    (
+        "uploaded-already",
+        "the site has this subtitle already, {link}",
+    ),
+    (
+        "uploaded-already-unlinked",
+        "the site has this subtitle already",
+    ),
+    ("rated", "rated subtitle {subtitle} {stars}/10"),
+    ("reported", "reported subtitle {subtitle} as {reason}"),
+    ("update-latest", "opensubtitlescli {version} is the latest"),
+    (
+        "update-available",
+        "opensubtitlescli {new} is available, this is {version}",
+    ),
+    (
+        "update-notice",
+        "opensubtitlescli {new} is available, `opensubtitlescli self-update` installs it",
+    ),
+    (
+        "update-done",
+        "opensubtitlescli updated {version} -> {new}, {path}",
+    ),
+    // warnings
+    (
+        "warning-insecure",
+        "warning: --insecure, the site's certificate isn't verified. anyone between here and the \
+         site can read and change what's downloaded",
+    ),
+    // errors
+    (
+        "error-needs-account",
+        "`{command}` needs a logged in opensubtitles.org account, pass --username or set \
+         OPENSUBTITLESCLI_USERNAME",
+    ),
+    (
+        "error-no-history-dir",
+        "no data directory for the history, --history-file sets one",
+    ),
+    (
+        "error-not-recorded",
+        "no download of {path} is recorded in {history}, it was made before the history was \
+         kept or with --no-history",
+    ),
+    (
+        "error-nothing-downloaded-for",
+        "nothing downloaded for {path}",
+    ),
+    ("error-nothing-downloaded", "nothing downloaded yet"),
+    (
+        "error-output-exists",
+        "{path} already exists, --force overwrites it",
+    ),
+    (
+        "error-not-a-directory",
+        "--embed-output-dir {path} is not a directory",
+    ),
+    (
+        "error-embed-output-is-movie",
+        "--embed-output-template names the movie itself, --embed-in-place replaces it",
+    ),
+    ("error-anchors", "--anchor has to be given exactly twice"),
+    (
+        "error-edit-auto",
+        "--edit waits for an editor to close, it can't go with --auto",
+    ),
+    ("error-gave-up", "gave up after {attempts} subtitles failed"),
+    (
+        "error-retime-subtitle-fps",
+        "refusing to --auto-retime, the subtitle's frame rate is unknown",
+    ),
+    (
+        "error-retime-movie-fps",
+        "refusing to --auto-retime, the movie's frame rate is unknown",
+    ),
+];
+
+static PL: &[(&str, &str)] = &[
+    // prompts
+    ("prompt-which-subtitle", "które napisy pobrać?"),
+    ("prompt-which-file", "który plik napisów?"),
+    (
+        "prompt-extract-streams",
+        "które ścieżki napisów wyodrębnić?",
+    ),
+    ("prompt-which-download", "które pobranie?"),
+    ("prompt-stars", "ile gwiazdek?"),
+    ("prompt-report-reason", "co jest z nimi nie tak?"),
+    (
+        "prompt-account-password",
+        "hasło konta {username} na opensubtitles.org:",
+    ),
+    (
+        "prompt-archive-password",
+        "archiwum jest chronione hasłem, hasło:",
+    ),
+    ("prompt-download-again", "{error}, pobrać je jeszcze raz?"),
+    (
+        "prompt-edit-again",
+        "{problem}, edytować napisy jeszcze raz?",
+    ),
+    ("prompt-recover", "{error}, co teraz?"),
+    (
+        "recovery-retry",
+        "spróbować tych samych napisów jeszcze raz",
+    ),
+    ("recovery-another", "wybrać inne napisy"),
+    ("recovery-abort", "przerwać"),
+    ("prompt-rename", "zmienić nazwy {count} plików napisów?"),
+    ("prompt-embed", "osadzić napisy w [{movie}]?"),
+    ("prompt-embed-in-place", "osadzić napisy w samym [{movie}]?"),
+    (
+        "prompt-existing-track",
+        "film ma już napisy ({language}), co zrobić z [{subtitle}]?",
+    ),
+    ("answer-yes", "tak"),
+    ("answer-no", "nie"),
+    // tables
+    ("column-type", "typ"),
+    ("column-codec", "kodek"),
+    ("column-language", "język"),
+    ("column-title", "tytuł"),
+    ("column-flags", "flagi"),
+    ("column-downloaded", "pobrano"),
+    ("column-subtitle", "napisy"),
+    ("column-movie", "film"),
+    ("column-output", "plik"),
+    ("column-provider", "serwis"),
+    ("column-month", "miesiąc"),
+    ("column-uploader", "autor"),
+    ("column-downloads", "pobrania"),
+    // `history show`
+    (
+        "show-download",
+        "{time} {language} napisy {subtitle} z {provider}",
+    ),
+    ("show-movie", "film"),
+    ("show-movie-hash", "hash filmu"),
+    ("show-name", "nazwa"),
+    ("show-release", "wydanie"),
+    ("show-format", "format"),
+    (
+        "show-entry",
+        "{format}, dodane przez {uploader}, pobrania: {downloads}, ocena {rating}",
+    ),
+    ("show-output", "plik"),
+    ("show-content-hash", "sha-256"),
+    ("history-purged", "zapomniane pobrania: {count}"),
+    // `stats`
+    ("stats-downloads", "pobrania: {count}"),
+    ("stats-downloads-since", "pobrania od {day}: {count}"),
+    (
+        "stats-rating",
+        "średnia ocena {rating}, z ocenionych: {rated}",
+    ),
+    (
+        "stats-selection",
+        "wybrane bez pytania: {auto}, z listy: {asked}, nie wiadomo jak: {unknown}",
+    ),
+    // the end of a run
+    ("season-unmatched-entries", "pliki napisów bez odcinka:"),
+    ("season-unmatched-episodes", "odcinki bez napisów:"),
+    (
+        "rename-nothing",
+        "każdy plik napisów w {dir} ma już nazwę po filmie",
+    ),
+    (
+        "rename-unmatched",
+        "pliki napisów, których nazwa nie przypomina żadnego filmu:",
+    ),
+    ("renamed", "zmieniono nazwy {count} plików napisów"),
+    (
+        "interrupted",
+        "przerwano, sprzątanie, ponowne Ctrl-C kończy od razu",
+    ),
+    ("interrupted-removed", "usunięto niedokończony {path}"),
+    ("interrupted-written", "zapisane przed przerwaniem:"),
+    ("undo-nothing", "plików już nie ma, nie ma czego cofać"),
+    ("uploaded", "wysłano, {link}"),
+    ("uploaded-already", "serwis ma już te napisy, {link}"),
+    ("uploaded-already-unlinked", "serwis ma już te napisy"),
+    ("rated", "oceniono napisy {subtitle} na {stars}/10"),
+    ("reported", "zgłoszono napisy {subtitle}: {reason}"),
+    (
+        "update-latest",
+        "opensubtitlescli {version} to najnowsza wersja",
+    ),
+    (
+        "update-available",
+        "jest opensubtitlescli {new}, ta wersja to {version}",
+    ),
+    (
+        "update-notice",
+        "jest opensubtitlescli {new}, `opensubtitlescli self-update` ją zainstaluje",
+    ),
+    (
+        "update-done",
+        "opensubtitlescli zaktualizowany {version} -> {new}, {path}",
+    ),
+    // warnings
+    (
+        "warning-insecure",
+        "uwaga: --insecure, certyfikat serwisu nie jest sprawdzany. każdy pomiędzy może czytać \
+         i zmieniać to, co jest pobierane",
+    ),
+    // errors
+    (
+        "error-needs-account",
+        "`{command}` wymaga zalogowania na konto opensubtitles.org, podaj --username albo \
+         ustaw OPENSUBTITLESCLI_USERNAME",
+    ),
+    (
+        "error-no-history-dir",
+        "nie ma katalogu danych na historię, wskaż plik przez --history-file",
+    ),
+    (
+        "error-not-recorded",
+        "w {history} nie ma pobrania {path}, zrobiono je przed prowadzeniem historii albo z \
+         --no-history",
+    ),
+    ("error-nothing-downloaded-for", "nic nie pobrano dla {path}"),
+    ("error-nothing-downloaded", "jeszcze nic nie pobrano"),
+    (
+        "error-output-exists",
+        "{path} już istnieje, --force go nadpisze",
+    ),
+    (
+        "error-not-a-directory",
+        "--embed-output-dir {path} nie jest katalogiem",
+    ),
+    (
+        "error-embed-output-is-movie",
+        "--embed-output-template wskazuje sam film, do tego jest --embed-in-place",
+    ),
+    ("error-anchors", "--anchor trzeba podać dokładnie dwa razy"),
+    (
+        "error-edit-auto",
+        "--edit czeka na zamknięcie edytora, nie da się go użyć z --auto",
+    ),
+    (
+        "error-gave-up",
+        "poddano się po {attempts} nieudanych napisach",
+    ),
+    (
+        "error-retime-subtitle-fps",
+        "--auto-retime nie zadziała, nie wiadomo, ile klatek na sekundę mają napisy",
+    ),
+    (
+        "error-retime-movie-fps",
+        "--auto-retime nie zadziała, nie wiadomo, ile klatek na sekundę ma film",
+    ),
+];
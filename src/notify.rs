@@ -0,0 +1,167 @@
+//! `--notify`, desktop notifications through `notify-send` on linux, `osascript` on macos and
+//! powershell on windows. without the program, or a notification daemon to show them,
+//! they're logged instead
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+use tracing::{debug, info};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub summary: String,
+    pub body: String,
+}
+
+/// `Movie.2019.1080p` for `Movie.2019.1080p.mkv`, a season's folder keeps its whole name
+pub fn movie_name(movie: &Path) -> String {
+    let name = match movie.is_dir() {
+        true => movie.file_name(),
+        false => movie.file_stem(),
+    };
+    name.unwrap_or(movie.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn file_names(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| {
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Notification {
+    /// subtitles in `language` were written for `movie`
+    pub fn downloaded(movie: &Path, language: &str, written: &[PathBuf]) -> Self {
+        Self {
+            summary: format!("{language} subtitles for {}", movie_name(movie)),
+            body: match written.is_empty() {
+                true => "nothing had to be written".to_string(),
+                false => file_names(written),
+            },
+        }
+    }
+
+    pub fn failed(movie: &Path, language: &str, error: &str) -> Self {
+        Self {
+            summary: format!("no {language} subtitles for {}", movie_name(movie)),
+            body: error.to_string(),
+        }
+    }
+
+    /// a batch done, `skipped` needed no subtitles and `failed` are the movies that got none
+    pub fn batch(language: &str, movies: usize, skipped: &[PathBuf], failed: &[PathBuf]) -> Self {
+        let names = |movies: &[PathBuf]| {
+            let names = movies.iter().map(|movie| movie_name(movie));
+            names.collect::<Vec<_>>().join(", ")
+        };
+        let body = [("skipped", skipped), ("failed", failed)]
+            .into_iter()
+            .filter(|(_, movies)| !movies.is_empty())
+            .map(|(label, movies)| format!("{label}: {}", names(movies)))
+            .collect::<Vec<_>>();
+        Self {
+            summary: format!(
+                "{language} subtitles for {} of {movies} videos",
+                movies - skipped.len() - failed.len()
+            ),
+            body: match body.is_empty() {
+                true => "all done".to_string(),
+                false => body.join("\n"),
+            },
+        }
+    }
+
+    /// a prompt is waiting for an answer
+    pub fn asking(prompt: &str) -> Self {
+        Self {
+            summary: "opensubtitlescli is waiting for an answer".to_string(),
+            body: prompt.to_string(),
+        }
+    }
+}
+
+/// the texts are passed as arguments or variables, none of them is quoted for a shell
+fn command(notification: &Notification) -> Command {
+    let Notification { summary, body } = notification;
+    match std::env::consts::OS {
+        "macos" => {
+            let mut command = Command::new("osascript");
+            command
+                .args([
+                    "-e",
+                    "on run argv",
+                    "-e",
+                    "display notification (item 2 of argv) with title (item 1 of argv)",
+                    "-e",
+                    "end run",
+                ])
+                .args([summary, body]);
+            command
+        }
+        "windows" => {
+            let mut command = Command::new("powershell");
+            command
+                .args([
+                    "-NoProfile",
+                    "-Command",
+                    "Add-Type -AssemblyName System.Windows.Forms; \
+                     $icon = New-Object System.Windows.Forms.NotifyIcon; \
+                     $icon.Icon = [System.Drawing.SystemIcons]::Information; \
+                     $icon.Visible = $true; \
+                     $icon.ShowBalloonTip(10000, $env:NOTIFY_SUMMARY, $env:NOTIFY_BODY, 'Info'); \
+                     Start-Sleep -Seconds 10; $icon.Dispose()",
+                ])
+                .env("NOTIFY_SUMMARY", summary)
+                .env("NOTIFY_BODY", body);
+            command
+        }
+        _ => {
+            let mut command = Command::new("notify-send");
+            command
+                .arg("--app-name=opensubtitlescli")
+                .arg(summary)
+                .arg(body);
+            command
+        }
+    }
+}
+
+/// shows `notification` and waits until it's handed over, failing to only logs it
+pub fn show(notification: &Notification) {
+    let mut command = command(notification);
+    let program = command.get_program().to_string_lossy().into_owned();
+    // the balloon lives as long as powershell does, nobody waits for that
+    let shown = match std::env::consts::OS {
+        "windows" => command.spawn().map(|_| Ok(())),
+        _ => command
+            .output()
+            .map(|output| match output.status.success() {
+                true => Ok(()),
+                false => Err(String::from_utf8_lossy(&output.stderr).trim().to_string()),
+            }),
+    };
+    let Notification { summary, body } = notification;
+    match shown {
+        Ok(Ok(())) => debug!(%summary, "notified"),
+        Ok(Err(message)) => info!(%summary, %body, %message, "{program} showed no notification"),
+        Err(message) => info!(%summary, %body, %message, "{program} can't notify"),
+    }
+}
+
+/// [`show`] on a thread of its own, for a notification about to be followed by something
+/// blocking this one
+pub fn show_in_background(notification: Notification) {
+    std::thread::spawn(move || show(&notification));
+}
+
+/// [`show`] from async code
+pub async fn send(notification: Notification) {
+    let _ = tokio::task::spawn_blocking(move || show(&notification)).await;
+}
@@ -0,0 +1,378 @@
+//! writing results next to the movie, a crash mid-write never leaves a truncated file behind
+use crate::{
+    charset::{self, Transcode, UTF8_BOM},
+    cleanup::Cleanup,
+    postprocess::{self, PostProcess},
+    results::Results,
+    srt,
+    subtitle::{
+        format::{self, ConvertTo},
+        SubtitleFormat,
+    },
+    timings::Timings,
+};
+use eyre::{eyre, Result, WrapErr};
+use itertools::Itertools;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// `.<name>.tmp` in the same directory, so the rename stays on one filesystem
+pub fn temporary_path(path: &Path) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .and_then(|v| v.to_str())
+        .ok_or_else(|| eyre!("{path:?} has no file name"))?;
+    Ok(path.with_file_name(format!(".{file_name}.tmp")))
+}
+
+async fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::File::create(path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await
+}
+
+/// `path` without the `\\?\` prefix windows puts in front of long and canonical paths
+pub fn displayed(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    match path.strip_prefix(r"\\?\") {
+        Some(unc) if unc.starts_with("UNC\\") => format!(r"\\{}", &unc[4..]),
+        Some(local) => local.to_string(),
+        None => path.into_owned(),
+    }
+}
+
+/// `path` in quotes for the results printed on stdout, [`displayed`] and without doubling
+/// its backslashes
+pub fn quoted(path: &Path) -> String {
+    format!("\"{}\"", displayed(path))
+}
+
+/// how the listing commands print what they found
+#[derive(Debug, Clone, Copy, Default, clap::ValueEnum)]
+pub enum ListFormat {
+    #[default]
+    Table,
+    /// the records as they are stored
+    Json,
+}
+
+/// left aligned columns as wide as their widest cell
+pub fn print_table<const N: usize>(header: [String; N], rows: &[[String; N]]) {
+    let widths = (0..N)
+        .map(|column| {
+            std::iter::once(&header)
+                .chain(rows)
+                .map(|row| row[column].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .join("  ");
+        println!("{}", line.trim_end());
+    }
+}
+
+/// writes to a temporary file, syncs it and renames it over `path`
+pub async fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let temporary = temporary_path(path)?;
+    let written = match write_synced(&temporary, contents).await {
+        Ok(()) => tokio::fs::rename(&temporary, path).await,
+        Err(e) => Err(e),
+    };
+    if written.is_err() {
+        tokio::fs::remove_file(&temporary).await.ok();
+    }
+    written.wrap_err_with(|| format!("writing {path:?}"))
+}
+
+/// `--preserve-times` / `--match-perms`, copies the movie's metadata onto its subtitle
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CopyMetadata {
+    pub times: bool,
+    pub permissions: bool,
+}
+
+impl CopyMetadata {
+    /// some filesystems refuse either, that's not worth failing over
+    pub fn apply(self, movie_file: &Path, subtitle_file: &Path) {
+        if let Err(message) = self.try_apply(movie_file, subtitle_file) {
+            warn!(
+                ?message,
+                ?subtitle_file,
+                "copying the movie file's metadata failed"
+            );
+        }
+    }
+
+    fn try_apply(self, movie_file: &Path, subtitle_file: &Path) -> Result<()> {
+        if !self.times && !self.permissions {
+            return Ok(());
+        }
+        let metadata = std::fs::metadata(movie_file)
+            .wrap_err_with(|| format!("reading metadata of {movie_file:?}"))?;
+        if self.times {
+            let times = std::fs::FileTimes::new()
+                .set_accessed(metadata.accessed()?)
+                .set_modified(metadata.modified()?);
+            std::fs::File::options()
+                .write(true)
+                .open(subtitle_file)
+                .and_then(|file| file.set_times(times))
+                .wrap_err("setting file times")?;
+        }
+        #[cfg(unix)]
+        if self.permissions {
+            std::fs::set_permissions(subtitle_file, metadata.permissions())
+                .wrap_err("setting permissions")?;
+        }
+        Ok(())
+    }
+}
+
+/// writes extracted subtitles, leaving an identical existing file alone
+#[derive(Debug, Clone, Default)]
+pub struct SubtitleWriter {
+    /// `\r\n` and `\n` files with otherwise equal contents count as identical
+    pub ignore_line_endings: bool,
+    /// converts text subtitles to utf-8, `None` writes them as they came
+    pub transcode: Option<Transcode>,
+    pub postprocess: PostProcess,
+    /// `--fps`, for converting MicroDVD frames
+    pub fps: Option<f64>,
+    /// the movie's frame rate, when neither `--fps` nor the MicroDVD file gives one
+    pub movie_fps: Option<f64>,
+    pub convert_to: Option<ConvertTo>,
+    /// also write the file subtitles were converted from
+    pub keep_original: bool,
+    /// `--timings`, how long cleaning and converting every file took
+    pub timings: Arc<Timings>,
+    /// half written files are removed on Ctrl-C
+    pub cleanup: Arc<Cleanup>,
+    /// `--json`, the encoding every file was decoded as
+    pub results: Arc<Results>,
+    /// where subtitles written before are looked up, `None` with `--no-history`
+    #[cfg(feature = "history")]
+    pub history: Option<crate::history::History>,
+}
+
+impl SubtitleWriter {
+    fn normalized<'a>(&self, contents: &'a [u8]) -> std::borrow::Cow<'a, [u8]> {
+        match self.ignore_line_endings && contents.contains(&b'\r') {
+            true => postprocess::crlf_to_lf(contents).into(),
+            false => contents.into(),
+        }
+    }
+
+    pub async fn is_identical(&self, path: &Path, contents: &[u8]) -> bool {
+        match tokio::fs::read(path).await {
+            Ok(existing) => self.normalized(&existing) == self.normalized(contents),
+            Err(_) => false,
+        }
+    }
+
+    /// MicroDVD `.sub` files always become `.srt`, the rest only with `--convert-to`. cleaning
+    /// works on SubRip, other formats are rendered from its result. `None` when there's
+    /// nothing to convert or converting failed
+    fn convert(&self, path: &Path, text: &[u8]) -> Option<(PathBuf, Vec<u8>)> {
+        let source = SubtitleFormat::from_path(path)?;
+        let to = format::target(&source, self.convert_to)?;
+        let utf8 = std::str::from_utf8(text).ok()?;
+        let srt = match format::parse(&source, utf8, self.fps, self.movie_fps)? {
+            Ok(srt) => srt,
+            Err(message) => {
+                warn!(
+                    ?message,
+                    ?path,
+                    "converting {source} failed, writing it as it is"
+                );
+                return None;
+            }
+        };
+        if source != to.format() {
+            info!(?path, "converted {source} to {}", to.format());
+        }
+        let bom = match text.starts_with(UTF8_BOM) {
+            true => UTF8_BOM,
+            false => &[],
+        };
+        let srt = self.postprocess.apply(
+            &path.with_extension("srt"),
+            [bom, srt.to_string().as_bytes()].concat(),
+        );
+        let converted_path = path.with_extension(to.format().name());
+        let contents = match to {
+            ConvertTo::Srt => srt,
+            ConvertTo::Vtt | ConvertTo::Ass => {
+                let srt = srt::parse_lenient(&String::from_utf8_lossy(&srt)).srt;
+                let rendered = [bom, format::render(&srt, to).as_bytes()].concat();
+                self.postprocess.apply(&converted_path, rendered)
+            }
+        };
+        Some((converted_path, contents))
+    }
+
+    /// the files subtitles are written as, conversions change the extension and
+    /// `--keep-original` writes the file they were converted from too
+    pub fn process(&self, path: &Path, contents: &[u8]) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+        if !charset::is_text(contents) {
+            return Ok(vec![(path.to_path_buf(), contents.to_vec())]);
+        }
+        let text = match &self.transcode {
+            Some(transcode) => transcode
+                .to_utf8(contents)
+                .wrap_err_with(|| format!("converting {path:?} to utf-8"))?,
+            None => contents.to_vec(),
+        };
+        let converted = self.convert(path, &text);
+        let original = match &converted {
+            // cleaned through SubRip cues, written back in the same format
+            Some((converted_path, _)) if converted_path == path => None,
+            Some(_) if !self.keep_original => None,
+            _ => Some((path.to_path_buf(), self.postprocess.apply(path, text))),
+        };
+        Ok(converted.into_iter().chain(original).collect())
+    }
+
+    /// cleans and converts the subtitles in memory, nothing is written until
+    /// [`SubtitleWriter::write_prepared`]
+    pub async fn prepare(&self, path: &Path, contents: &[u8]) -> Result<Prepared> {
+        let subject = Some(path.display().to_string());
+        let prepare = async {
+            let encoding = self
+                .transcode
+                .as_ref()
+                .filter(|_| charset::is_text(contents))
+                .map(|transcode| transcode.source_encoding(contents).name());
+            let files = self.process(path, contents)?;
+            Ok(Prepared { files, encoding })
+        };
+        self.timings
+            .time_of("post-processing", subject, prepare)
+            .await
+    }
+
+    /// returns where the subtitles ended up
+    pub async fn write(&self, path: &Path, contents: &[u8]) -> Result<Vec<PathBuf>> {
+        let prepared = self.prepare(path, contents).await?;
+        self.write_prepared(prepared).await
+    }
+
+    /// says which movie the same subtitles were written for before, most likely a copy of
+    /// this one under another name. they're written all the same
+    #[cfg(feature = "history")]
+    fn downloaded_before(&self, path: &Path, contents: &[u8]) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_owned());
+        let downloads = match history.by_content_hash(&crate::history::content_hash(contents)) {
+            Ok(downloads) => downloads,
+            Err(report) => {
+                warn!(?report, "looking the subtitles up in the history failed");
+                return;
+            }
+        };
+        let earlier = downloads
+            .into_iter()
+            .filter(|download| download.output != path)
+            .max_by_key(|download| download.downloaded_at);
+        if let Some(earlier) = earlier {
+            info!(
+                ?path,
+                movie = ?earlier.movie,
+                "the same subtitles were downloaded for another movie before"
+            );
+            self.results.downloaded_before(&path, &earlier.movie);
+        }
+    }
+
+    pub async fn write_prepared(&self, prepared: Prepared) -> Result<Vec<PathBuf>> {
+        let Prepared { files, encoding } = prepared;
+        let mut written = vec![];
+        for (path, contents) in files {
+            if self.is_identical(&path, &contents).await {
+                info!(?path, "identical, skipped");
+            } else {
+                #[cfg(feature = "history")]
+                self.downloaded_before(&path, &contents);
+                let temporary = temporary_path(&path)?;
+                self.cleanup
+                    .guard([temporary], write_atomic(&path, &contents))
+                    .await
+                    .wrap_err_with(|| format!("writing subtitle file to {path:?}"))?;
+            }
+            self.cleanup.completed(&path);
+            if let Some(encoding) = encoding {
+                self.results.decoded(&path, encoding);
+            }
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/// subtitles as they're going to be written, checked before anything on disk changes
+#[derive(Debug, Clone)]
+pub struct Prepared {
+    pub files: Vec<(PathBuf, Vec<u8>)>,
+    /// the encoding the subtitles were decoded as
+    encoding: Option<&'static str>,
+}
+
+impl Prepared {
+    /// the files `pick` is true for, the rest are left to be written
+    pub fn take(&mut self, pick: impl Fn(&Path) -> bool) -> Vec<(PathBuf, Vec<u8>)> {
+        let (taken, kept) = std::mem::take(&mut self.files)
+            .into_iter()
+            .partition(|(path, _)| pick(path));
+        self.files = kept;
+        taken
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn replaces_the_file_in_one_go() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("movie.srt");
+        std::fs::write(&path, b"old").unwrap();
+        write_atomic(&path, b"new").await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert!(!temporary_path(&path).unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn a_failed_rename_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        // a folder in the way, with something in it, can't be renamed over
+        let path = dir.path().join("movie.srt");
+        std::fs::create_dir(&path).unwrap();
+        std::fs::write(path.join("kept.txt"), b"kept").unwrap();
+        let report = write_atomic(&path, b"new").await.unwrap_err();
+        assert!(format!("{report:?}").contains("movie.srt"), "{report:?}");
+        assert!(!temporary_path(&path).unwrap().exists());
+        assert!(path.is_dir());
+        assert_eq!(std::fs::read(path.join("kept.txt")).unwrap(), b"kept");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_failed_write_leaves_nothing_behind() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("movie.srt");
+        assert!(write_atomic(&path, b"new").await.is_err());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
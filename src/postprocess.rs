@@ -0,0 +1,419 @@
+//! changes applied to subtitle text after it's been converted to utf-8, the defaults leave
+//! the bytes untouched
+use crate::{
+    charset::UTF8_BOM,
+    markup, reflow,
+    sdh::{self, StripHi},
+    srt::{self, Cue, Linear, Offset, RepairOptions, Repairs, Srt},
+};
+use clap::ValueEnum;
+use std::path::Path;
+use tracing::{info, warn};
+
+/// `--bom`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Bom {
+    #[default]
+    Keep,
+    Add,
+    Strip,
+}
+
+/// `--line-endings`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum LineEndings {
+    #[default]
+    Keep,
+    Crlf,
+    Lf,
+}
+
+/// `\r\n` to `\n`, lone `\r`s are left alone
+pub fn crlf_to_lf(contents: &[u8]) -> Vec<u8> {
+    contents
+        .iter()
+        .enumerate()
+        .filter(|(idx, byte)| !(**byte == b'\r' && contents.get(idx + 1) == Some(&b'\n')))
+        .map(|(_, byte)| *byte)
+        .collect()
+}
+
+/// phrases and domains of the cues the site, ad sellers and uploaders put around the
+/// subtitles, lowercase
+const AD_PATTERNS: &[&str] = &[
+    "opensubtitles",
+    "osdb.link",
+    "advertise your product",
+    "become vip member",
+    "subtitles by",
+    "subtitle by",
+    "synced by",
+    "sync by",
+    "ripped by",
+    "corrected by",
+    "nordvpn",
+    "expressvpn",
+    "surfshark",
+    "addic7ed.com",
+    "subscene.com",
+    "podnapisi.net",
+    "yts.mx",
+];
+
+/// only this many cues at either end are checked for ads
+const AD_CUES: usize = 3;
+
+/// `pattern` in `text` as whole words, `www.opensubtitles.org` has `opensubtitles` but
+/// `subscene.community` has no `subscene.com`
+fn contains_words(text: &str, pattern: &str) -> bool {
+    let word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    text.match_indices(pattern).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + pattern.len()..].chars().next();
+        !word(before) && !word(after)
+    })
+}
+
+/// `extra` is `--ad-patterns`, on top of the built-in ones
+fn is_ad(cue: &Cue, extra: &[String]) -> bool {
+    let text = cue.text().to_lowercase();
+    AD_PATTERNS
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+        .any(|pattern| contains_words(&text, &pattern.to_lowercase()))
+}
+
+/// drops ads from the first and last few cues, returns how many were removed
+pub fn remove_ads(srt: &mut Srt, extra: &[String]) -> usize {
+    let count = srt.cues.len();
+    let before = count;
+    let mut idx = 0;
+    srt.cues.retain(|cue| {
+        let near_edge = idx < AD_CUES || idx + AD_CUES >= count;
+        idx += 1;
+        !(near_edge && is_ad(cue, extra))
+    });
+    before - srt.cues.len()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PostProcess {
+    pub bom: Bom,
+    pub line_endings: LineEndings,
+    /// fix numbering, order, timing and garbage, `None` with `--repair false`
+    pub repair: Option<RepairOptions>,
+    /// off with `--no-clean`
+    pub remove_ads: bool,
+    /// `--ad-patterns`
+    pub ad_patterns: Vec<String>,
+    pub strip_hi: Option<StripHi>,
+    /// tags `--strip-tags` keeps, `None` leaves the markup alone
+    pub strip_tags: Option<Vec<String>>,
+    /// `--max-line-length`
+    pub max_line_length: Option<usize>,
+    pub linear: Option<Linear>,
+    pub shift: Option<Offset>,
+}
+
+impl PostProcess {
+    pub fn apply(&self, path: &Path, contents: Vec<u8>) -> Vec<u8> {
+        let is_srt = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("srt"));
+        let contents = match is_srt {
+            true => self.edit_srt(contents),
+            false => contents,
+        };
+        let contents = self.line_endings(contents);
+        self.bom(contents)
+    }
+
+    /// the file is only rewritten when something changed, byte order mark and line endings
+    /// are carried over
+    fn edit_srt(&self, contents: Vec<u8>) -> Vec<u8> {
+        if self.repair.is_none()
+            && !self.remove_ads
+            && self.strip_hi.is_none()
+            && self.strip_tags.is_none()
+            && self.max_line_length.is_none()
+            && self.linear.is_none()
+            && self.shift.is_none()
+        {
+            return contents;
+        }
+        let Ok(text) = std::str::from_utf8(&contents) else {
+            return contents;
+        };
+        let (mut srt, repairs) = match self.repair {
+            Some(options) => srt::parse_lenient(text).repair(options),
+            None => match srt::parse(text) {
+                Ok(srt) => (srt, Repairs::default()),
+                Err(message) => {
+                    warn!(?message, "not cleaning subtitles that failed to parse");
+                    return contents;
+                }
+            },
+        };
+        if !repairs.is_empty() {
+            info!(%repairs, "repaired subtitles");
+        }
+        let removed = match self.remove_ads {
+            true => remove_ads(&mut srt, &self.ad_patterns),
+            false => 0,
+        };
+        if removed > 0 {
+            info!(removed, "removed advertising cues");
+        }
+        let decoded = match self.remove_ads {
+            true => markup::decode(&mut srt),
+            false => 0,
+        };
+        if decoded > 0 {
+            info!(decoded, "decoded html entities");
+        }
+        let untagged = match &self.strip_tags {
+            Some(keep) => markup::strip_tags(&mut srt, keep),
+            None => 0,
+        };
+        if untagged > 0 {
+            info!(untagged, "stripped tags");
+        }
+        let stripped = match self.strip_hi {
+            Some(level) => sdh::strip(&mut srt, level),
+            None => 0,
+        };
+        if stripped > 0 {
+            info!(stripped, "removed hearing impaired annotations");
+        }
+        let reflowed = match self.max_line_length {
+            Some(max_line_length) => reflow::reflow(&mut srt, max_line_length),
+            None => 0,
+        };
+        if reflowed > 0 {
+            info!(reflowed, "rewrapped long cues");
+        }
+        if let Some(linear) = self.linear {
+            srt.retime(linear);
+            info!(
+                scale = linear.scale,
+                offset_ms = linear.offset,
+                "retimed subtitles"
+            );
+        }
+        if let Some(offset) = self.shift {
+            srt.shift(offset);
+            info!(offset_ms = offset.0, "shifted subtitles");
+        }
+        let retimed = self.linear.is_some() || self.shift.is_some();
+        let edited = removed + decoded + untagged + stripped + reflowed;
+        if repairs.is_empty() && edited == 0 && !retimed {
+            return contents;
+        }
+        let bom = match contents.starts_with(UTF8_BOM) {
+            true => UTF8_BOM,
+            false => &[],
+        };
+        let text = match text.contains("\r\n") {
+            true => srt.to_string().replace('\n', "\r\n"),
+            false => srt.to_string(),
+        };
+        [bom, text.as_bytes()].concat()
+    }
+
+    fn line_endings(&self, contents: Vec<u8>) -> Vec<u8> {
+        let lf = || crlf_to_lf(&contents);
+        match self.line_endings {
+            LineEndings::Keep => contents,
+            LineEndings::Lf => lf(),
+            LineEndings::Crlf => lf().into_iter().fold(vec![], |mut acc, byte| {
+                if byte == b'\n' {
+                    acc.push(b'\r');
+                }
+                acc.push(byte);
+                acc
+            }),
+        }
+    }
+
+    /// only utf-8 text gets a utf-8 byte order mark, `--keep-encoding` can leave anything here
+    fn bom(&self, contents: Vec<u8>) -> Vec<u8> {
+        let has_bom = contents.starts_with(UTF8_BOM);
+        match self.bom {
+            Bom::Keep => contents,
+            Bom::Strip if has_bom => contents[UTF8_BOM.len()..].to_vec(),
+            Bom::Add if !has_bom && std::str::from_utf8(&contents).is_ok() => {
+                [UTF8_BOM, &contents].concat()
+            }
+            Bom::Strip | Bom::Add => contents,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LF: &str =
+        "1\n00:00:01,000 --> 00:00:02,000\nHi\n\n2\n00:00:03,000 --> 00:00:04,000\nBye\n";
+
+    /// `\r\n` endings and bare `\n` ones in the output
+    fn line_endings(contents: &[u8]) -> (usize, usize) {
+        let crlf = contents.windows(2).filter(|pair| pair == b"\r\n").count();
+        let newlines = contents.iter().filter(|byte| **byte == b'\n').count();
+        (crlf, newlines - crlf)
+    }
+
+    /// the same cues with and without a byte order mark, with unix, windows and mixed line
+    /// endings
+    fn inputs() -> Vec<Vec<u8>> {
+        let crlf = LF.replace('\n', "\r\n");
+        let mixed = LF.replacen('\n', "\r\n", 3);
+        [LF.to_string(), crlf, mixed]
+            .into_iter()
+            .flat_map(|text| {
+                [
+                    text.clone().into_bytes(),
+                    [UTF8_BOM, text.as_bytes()].concat(),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn defaults_leave_the_bytes_alone() {
+        for contents in inputs() {
+            for path in ["movie.srt", "movie.ass"] {
+                let applied = PostProcess::default().apply(Path::new(path), contents.clone());
+                assert_eq!(applied, contents, "{path}");
+            }
+        }
+    }
+
+    #[test]
+    fn every_bom_and_line_ending_combination() {
+        let newlines = LF.matches('\n').count();
+        for contents in inputs() {
+            let had_bom = contents.starts_with(UTF8_BOM);
+            let before = line_endings(&contents);
+            for bom in [Bom::Keep, Bom::Add, Bom::Strip] {
+                for endings in [LineEndings::Keep, LineEndings::Crlf, LineEndings::Lf] {
+                    let process = PostProcess {
+                        bom,
+                        line_endings: endings,
+                        ..Default::default()
+                    };
+                    let applied = process.apply(Path::new("movie.srt"), contents.clone());
+                    let case = format!(
+                        "{bom:?} {endings:?} of {:?}",
+                        String::from_utf8_lossy(&contents)
+                    );
+                    let has_bom = applied.starts_with(UTF8_BOM);
+                    match bom {
+                        Bom::Keep => assert_eq!(has_bom, had_bom, "{case}"),
+                        Bom::Add => assert!(has_bom, "{case}"),
+                        Bom::Strip => assert!(!has_bom, "{case}"),
+                    }
+                    let expected = match endings {
+                        LineEndings::Keep => before,
+                        LineEndings::Crlf => (newlines, 0),
+                        LineEndings::Lf => (0, newlines),
+                    };
+                    assert_eq!(line_endings(&applied), expected, "{case}");
+                    // only the framing changes
+                    let text = String::from_utf8(applied).unwrap();
+                    assert_eq!(
+                        text.trim_start_matches('\u{feff}').replace("\r\n", "\n"),
+                        LF,
+                        "{case}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// a cue a second for each of `texts`
+    fn cues(texts: &[&str]) -> Srt {
+        let text = texts
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| {
+                let second = idx + 1;
+                format!("{second}\n00:00:{second:02},000 --> 00:00:{second:02},500\n{text}\n")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        srt::parse(&text).unwrap()
+    }
+
+    #[test]
+    fn removes_ads_at_the_ends_only() {
+        let mut srt = cues(&[
+            "Advertise your product or brand here\ncontact www.OpenSubtitles.org today",
+            "Hello.",
+            "Where were we?",
+            // the middle is dialogue, whatever it says
+            "Subtitles by someone",
+            "Go on.",
+            "Support us, get NordVPN.com",
+            "Bye.",
+            "The end.",
+        ]);
+        assert_eq!(remove_ads(&mut srt, &[]), 2);
+        let texts = srt.cues.iter().map(|cue| cue.text()).collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            [
+                "Hello.",
+                "Where were we?",
+                "Subtitles by someone",
+                "Go on.",
+                "Bye.",
+                "The end."
+            ]
+        );
+    }
+
+    #[test]
+    fn dialogue_mentioning_a_vpn_or_a_website_stays() {
+        let mut srt = cues(&[
+            "I'm on the VPN, give me a second.",
+            "Go to www.example.com and log in.",
+            "The vpnd process died again.",
+            "Is that subscene.community?",
+        ]);
+        assert_eq!(remove_ads(&mut srt, &[]), 0);
+        assert_eq!(srt.cues.len(), 4);
+    }
+
+    #[test]
+    fn ad_patterns_add_to_the_built_in_ones() {
+        let mut srt = cues(&["Visit Example-Subs.net", "Hello.", "Subtitles by someone"]);
+        let extra = ["example-subs.net".to_string()];
+        assert_eq!(remove_ads(&mut srt, &extra), 2);
+        assert_eq!(srt.cues[0].text(), "Hello.");
+    }
+
+    #[test]
+    fn lone_carriage_returns_stay() {
+        let process = PostProcess {
+            line_endings: LineEndings::Lf,
+            ..Default::default()
+        };
+        let applied = process.apply(Path::new("movie.srt"), b"a\rb\r\nc".to_vec());
+        assert_eq!(applied, b"a\rb\nc");
+    }
+
+    #[test]
+    fn no_bom_is_added_to_text_that_isnt_utf8() {
+        let process = PostProcess {
+            bom: Bom::Add,
+            ..Default::default()
+        };
+        let latin2 = b"1\r\n00:00:01,000 --> 00:00:02,000\r\nZa\xbf\xf3\xb3\xe6\r\n".to_vec();
+        assert_eq!(
+            process.apply(Path::new("movie.srt"), latin2.clone()),
+            latin2
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! what ffprobe knows about the movie file
+use crate::{
+    language::LanguageCode,
+    messages::text,
+    output::{print_table, ListFormat},
+    srt::Timestamp,
+    tools,
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// stdout of ffprobe run on `movie_file` with `args`
+async fn ffprobe(args: &[&str], movie_file: &Path) -> Result<String> {
+    let output = tools::command("ffprobe")
+        .args(["-v", "error"])
+        .args(args)
+        .arg(movie_file.as_os_str())
+        .output()
+        .await
+        .wrap_err("running ffprobe")?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `24000/1001` or `25`
+fn parse_rate(rate: &str) -> Option<f64> {
+    let rate = match rate.split_once('/') {
+        Some((numerator, denominator)) => {
+            numerator.trim().parse::<f64>().ok()? / denominator.trim().parse::<f64>().ok()?
+        }
+        None => rate.trim().parse().ok()?,
+    };
+    (rate.is_finite() && rate > 0.0).then_some(rate)
+}
+
+/// frame rate of the first video stream, `None` when it has none or ffprobe can't tell
+pub async fn frame_rate(movie_file: &Path) -> Result<Option<f64>> {
+    let output = ffprobe(
+        &[
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=avg_frame_rate,r_frame_rate",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ],
+        movie_file,
+    )
+    .await?;
+    Ok(parse_frame_rate(&output))
+}
+
+/// `avg_frame_rate` or else `r_frame_rate` of ffprobe's output, the first one that's a rate
+pub fn parse_frame_rate(output: &str) -> Option<f64> {
+    output.lines().find_map(parse_rate)
+}
+
+/// length of the movie according to its container, `None` when it doesn't say
+pub async fn duration(movie_file: &Path) -> Result<Option<Timestamp>> {
+    let output = ffprobe(
+        &[
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ],
+        movie_file,
+    )
+    .await?;
+    Ok(parse_duration(&output))
+}
+
+/// ffprobe's `format=duration` in seconds, `N/A` when the container doesn't say
+pub fn parse_duration(output: &str) -> Option<Timestamp> {
+    output
+        .lines()
+        .find_map(|line| line.trim().parse::<f64>().ok())
+        .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+        .map(|seconds| Timestamp((seconds * 1000.0).round() as i64))
+}
+
+#[derive(Debug, Deserialize)]
+struct Streams {
+    #[serde(default)]
+    streams: Vec<Stream>,
+}
+
+/// a stream as `ffprobe -of json` describes it, only what the rest of the program looks at
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Stream {
+    pub index: usize,
+    /// `video`, `audio`, `subtitle`...
+    pub codec_type: Option<String>,
+    pub codec_name: Option<String>,
+    #[serde(default)]
+    pub disposition: Disposition,
+    #[serde(default)]
+    pub tags: Tags,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Disposition {
+    #[serde(default)]
+    pub default: u8,
+    #[serde(default)]
+    pub forced: u8,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Tags {
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+/// a subtitle stream of the movie file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleStream {
+    /// among all of the file's streams, what mkvmerge calls the track id
+    pub index: usize,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+}
+
+/// positions of the streams in `language`, `ger` and `deu` are the same
+pub fn in_language(streams: &[SubtitleStream], language: &LanguageCode) -> Vec<usize> {
+    streams
+        .iter()
+        .enumerate()
+        .filter(|(_, stream)| {
+            stream.language.as_deref().is_some_and(|code| {
+                LanguageCode::new(code).container_tag() == language.container_tag()
+            })
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// the streams of ffprobe's json output
+pub fn parse_streams(json: &str) -> Result<Vec<Stream>> {
+    let streams: Streams =
+        serde_json::from_str(json).wrap_err("parsing the streams ffprobe listed")?;
+    Ok(streams.streams)
+}
+
+/// every stream of the movie file, in the order of their indices
+pub async fn streams(movie_file: &Path) -> Result<Vec<Stream>> {
+    let output = ffprobe(
+        &[
+            "-show_entries",
+            "stream=index,codec_type,codec_name:stream_disposition=default,forced:\
+             stream_tags=language,title",
+            "-of",
+            "json",
+        ],
+        movie_file,
+    )
+    .await?;
+    parse_streams(&output)
+}
+
+/// subtitle streams in the order ffmpeg numbers them (`s:0`, `s:1`...)
+pub async fn subtitle_streams(movie_file: &Path) -> Result<Vec<SubtitleStream>> {
+    Ok(subtitles(streams(movie_file).await?))
+}
+
+/// the subtitle streams among `streams`, in their order
+pub fn subtitles(streams: Vec<Stream>) -> Vec<SubtitleStream> {
+    streams
+        .into_iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("subtitle"))
+        .map(|stream| SubtitleStream {
+            index: stream.index,
+            codec: stream.codec_name,
+            language: stream.tags.language,
+            title: stream.tags.title,
+            default: stream.disposition.default != 0,
+            forced: stream.disposition.forced != 0,
+        })
+        .collect()
+}
+
+/// `--skip-if-audio-matches`, the language of the first audio stream in one of `languages`
+/// (`pol,eng`)
+pub async fn audio_in_language(movie_file: &Path, languages: &str) -> Result<Option<String>> {
+    if !tools::available("ffprobe") {
+        bail!(
+            "--skip-if-audio-matches needs ffprobe: {}",
+            tools::install_hint("ffprobe")
+        );
+    }
+    let languages = languages
+        .split(',')
+        .map(|code| LanguageCode::new(code).container_tag().to_string())
+        .collect::<Vec<_>>();
+    Ok(streams(movie_file)
+        .await
+        .wrap_err("listing the movie's audio streams")?
+        .into_iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("audio"))
+        .filter_map(|stream| stream.tags.language)
+        .find(|code| {
+            let tag = LanguageCode::new(code);
+            tag.container_tag() != "und"
+                && languages
+                    .iter()
+                    .any(|language| language == tag.container_tag())
+        }))
+}
+
+/// `list-tracks`
+pub async fn list_tracks(movie_file: &Path, format: ListFormat) -> Result<()> {
+    if !tools::available("ffprobe") {
+        bail!(
+            "list-tracks needs ffprobe: {}",
+            tools::install_hint("ffprobe")
+        );
+    }
+    let streams = streams(movie_file).await?;
+    if let ListFormat::Json = format {
+        println!("{}", serde_json::to_string_pretty(&streams)?);
+        return Ok(());
+    }
+    let header = [
+        "#",
+        text("column-type"),
+        text("column-codec"),
+        text("column-language"),
+        text("column-title"),
+        text("column-flags"),
+    ]
+    .map(String::from);
+    let rows = streams
+        .iter()
+        .map(|stream| {
+            let flags = [
+                (stream.disposition.default != 0, "default"),
+                (stream.disposition.forced != 0, "forced"),
+            ]
+            .into_iter()
+            .filter_map(|(set, flag)| set.then_some(flag))
+            .join(",");
+            [
+                stream.index.to_string(),
+                stream.codec_type.clone().unwrap_or_default(),
+                stream.codec_name.clone().unwrap_or_default(),
+                stream.tags.language.clone().unwrap_or_default(),
+                stream.tags.title.clone().unwrap_or_default(),
+                flags,
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(header, &rows);
+    Ok(())
+}
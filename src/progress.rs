@@ -0,0 +1,358 @@
+//! ffmpeg's `-progress pipe:1` output, drawn as a bar on terminals and logged otherwise
+use crate::srt::Timestamp;
+use eyre::{Report, Result, WrapErr};
+use std::{
+    collections::VecDeque,
+    io::{IsTerminal, Write},
+    process::{ExitStatus, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    process::Command,
+};
+use tracing::info;
+
+/// lines of output kept for the error when the program fails
+const TAIL_LINES: usize = 10;
+/// characters of the bar between the brackets
+const BAR_WIDTH: usize = 30;
+/// how long a program interrupted along with this one gets to exit on its own
+const GRACE: Duration = Duration::from_secs(5);
+
+/// how far ffmpeg got, one per block of `key=value` lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Update {
+    pub done: Timestamp,
+    /// times realtime, `None` while ffmpeg says `N/A`
+    pub speed: Option<f64>,
+    /// `progress=end`, the last block
+    pub finished: bool,
+}
+
+/// collects `key=value` lines until the `progress=continue` or `progress=end` closing each
+/// block
+#[derive(Debug, Default)]
+pub struct Parser {
+    done: Option<Timestamp>,
+    speed: Option<f64>,
+}
+
+impl Parser {
+    /// the update a line completes, lines that aren't progress are ignored
+    pub fn feed(&mut self, line: &str) -> Option<Update> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+        match key {
+            // microseconds, whatever the key says
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(micros) = value.parse::<i64>() {
+                    self.done = Some(Timestamp(micros.max(0) / 1000));
+                }
+            }
+            "speed" => {
+                self.speed = value
+                    .trim_end_matches('x')
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|speed| speed.is_finite());
+            }
+            "progress" => {
+                return Some(Update {
+                    done: self.done.unwrap_or(Timestamp(0)),
+                    speed: self.speed,
+                    finished: value == "end",
+                })
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+/// `00:12:34`, the bar has no room for milliseconds
+fn clock(timestamp: Timestamp) -> String {
+    let seconds = timestamp.0.max(0) / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// `duration` turns the progress into a percentage
+pub struct Bar {
+    label: &'static str,
+    duration: Option<Timestamp>,
+    terminal: bool,
+    /// percent, or minutes without a duration, last logged
+    reported: i64,
+    drawn: bool,
+}
+
+impl Bar {
+    pub fn new(label: &'static str, duration: Option<Timestamp>) -> Self {
+        Self {
+            label,
+            duration: duration.filter(|duration| duration.0 > 0),
+            terminal: std::io::stderr().is_terminal(),
+            reported: 0,
+            drawn: false,
+        }
+    }
+
+    fn percent(&self, done: Timestamp) -> Option<i64> {
+        self.duration
+            .map(|duration| (done.0 * 100 / duration.0).clamp(0, 100))
+    }
+
+    pub fn show(&mut self, update: &Update) {
+        let speed = update
+            .speed
+            .map(|speed| format!(" {speed:.1}x"))
+            .unwrap_or_default();
+        let percent = self.percent(update.done);
+        match self.terminal {
+            true => {
+                let line = match (percent, self.duration) {
+                    (Some(percent), Some(duration)) => {
+                        let filled = BAR_WIDTH * percent as usize / 100;
+                        format!(
+                            "{} [{}{}] {percent:>3}% {} / {}{speed}",
+                            self.label,
+                            "#".repeat(filled),
+                            "-".repeat(BAR_WIDTH - filled),
+                            clock(update.done),
+                            clock(duration),
+                        )
+                    }
+                    _ => format!("{} {}{speed}", self.label, clock(update.done)),
+                };
+                let mut stderr = std::io::stderr();
+                write!(stderr, "\r{line}\x1b[K").ok();
+                stderr.flush().ok();
+                self.drawn = true;
+            }
+            // every few percent, or every minute of the movie
+            false => match percent {
+                Some(percent) if percent >= self.reported + 5 => {
+                    self.reported = percent;
+                    info!(percent, done = %update.done, speed = update.speed, "{}", self.label);
+                }
+                None if update.done.0 / 60_000 > self.reported => {
+                    self.reported = update.done.0 / 60_000;
+                    info!(done = %update.done, speed = update.speed, "{}", self.label);
+                }
+                _ => {}
+            },
+        }
+    }
+
+    /// moves past the bar, so what's logged next starts on a line of its own
+    pub fn finish(&mut self) {
+        if self.drawn {
+            eprintln!();
+            self.drawn = false;
+        }
+    }
+}
+
+/// the last `TAIL_LINES` lines of `output` that `keep` lets through
+async fn tail(output: impl AsyncRead + Unpin, mut keep: impl FnMut(&str) -> bool) -> Vec<String> {
+    let mut lines = BufReader::new(output).lines();
+    let mut tail = VecDeque::with_capacity(TAIL_LINES);
+    while let Ok(Some(line)) = lines.next_line().await {
+        if !keep(&line) || line.trim().is_empty() {
+            continue;
+        }
+        if tail.len() == TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+    tail.into()
+}
+
+/// why a program didn't finish, scripts tell them apart by the exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stopped {
+    TimedOut,
+    /// Ctrl-C
+    Interrupted,
+    /// SIGTERM
+    Terminated,
+    /// by a signal nobody here sent, the OOM killer for one
+    Killed(i32),
+    /// exited on its own with an error
+    Failed,
+}
+
+impl Stopped {
+    pub fn find(report: &Report) -> Option<Self> {
+        report
+            .chain()
+            .find_map(|e| e.downcast_ref::<Self>())
+            .copied()
+    }
+
+    /// what `timeout` and shells exit with in the same situations
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::TimedOut => 124,
+            Self::Interrupted => 130,
+            Self::Terminated => 143,
+            Self::Killed(signal) => 128 + signal,
+            Self::Failed => 5,
+        }
+    }
+}
+
+impl std::fmt::Display for Stopped {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TimedOut => f.write_str("timed out"),
+            Self::Interrupted => f.write_str("interrupted"),
+            Self::Terminated => f.write_str("terminated"),
+            Self::Killed(signal) => write!(f, "killed by signal {signal}"),
+            Self::Failed => f.write_str("exited with an error"),
+        }
+    }
+}
+
+impl std::error::Error for Stopped {}
+
+/// set by the daemon, which lets what's running finish when it's terminated
+static FINISH_ON_TERMINATE: AtomicBool = AtomicBool::new(false);
+
+/// SIGTERM no longer stops the programs [`run`] runs, whoever set this stops when they're done
+pub fn finish_on_terminate() {
+    FINISH_ON_TERMINATE.store(true, Ordering::Relaxed);
+}
+
+/// resolves once this program is asked to stop
+async fn stop_requested() -> Stopped {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let (Ok(mut interrupt), Ok(mut terminate)) = (
+            signal(SignalKind::interrupt()),
+            signal(SignalKind::terminate()),
+        ) {
+            loop {
+                tokio::select! {
+                    _ = interrupt.recv() => return Stopped::Interrupted,
+                    _ = terminate.recv() => match FINISH_ON_TERMINATE.load(Ordering::Relaxed) {
+                        true => continue,
+                        false => return Stopped::Terminated,
+                    },
+                }
+            }
+        }
+    }
+    match tokio::signal::ctrl_c().await {
+        Ok(()) => Stopped::Interrupted,
+        Err(_) => std::future::pending().await,
+    }
+}
+
+/// the signal that ended the program, `None` when it exited
+fn signal(status: ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+/// runs `command`, drawing the progress it reports on stdout. a run `succeeded` doesn't
+/// accept fails with the end of its output, which is where ffmpeg and mkvmerge say why.
+/// the program is killed after `timeout` or when this one is interrupted, its output is
+/// for the caller to clean up
+pub async fn run(
+    mut command: Command,
+    label: &'static str,
+    duration: Option<Timestamp>,
+    timeout: Option<Duration>,
+    succeeded: impl Fn(ExitStatus) -> bool,
+) -> Result<()> {
+    let program = command
+        .as_std()
+        .get_program()
+        .to_string_lossy()
+        .into_owned();
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .wrap_err_with(|| format!("running {program}"))?;
+    let (stdout, stderr) = (
+        child.stdout.take().expect("piped"),
+        child.stderr.take().expect("piped"),
+    );
+    let mut bar = Bar::new(label, duration);
+    let mut parser = Parser::default();
+    // mkvmerge reports on stdout, including its errors
+    let stdout = tail(stdout, |line| match parser.feed(line) {
+        Some(update) => {
+            bar.show(&update);
+            false
+        }
+        None => !line.contains('=') && !line.starts_with("Progress:"),
+    });
+    let timed_out = async {
+        match timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    let output = tokio::select! {
+        output = async { tokio::join!(stdout, tail(stderr, |_| true)) } => Ok(output),
+        () = timed_out => Err(Stopped::TimedOut),
+        stopped = stop_requested() => Err(stopped),
+    };
+    let (stdout, stderr) = match output {
+        Ok(output) => output,
+        Err(stopped) => {
+            // the terminal sends Ctrl-C to the program too, it gets a moment to exit cleanly
+            let grace = match stopped {
+                Stopped::Interrupted => GRACE,
+                _ => Duration::ZERO,
+            };
+            if tokio::time::timeout(grace, child.wait()).await.is_err() {
+                child.start_kill().ok();
+            }
+            child.wait().await.ok();
+            bar.finish();
+            let message = match (stopped, timeout) {
+                (Stopped::TimedOut, Some(timeout)) => {
+                    format!("{program} took longer than {timeout:?}")
+                }
+                _ => format!("{program} was stopped"),
+            };
+            return Err(stopped).wrap_err(message);
+        }
+    };
+    let status = child
+        .wait()
+        .await
+        .wrap_err_with(|| format!("running {program}"))?;
+    bar.finish();
+    if !succeeded(status) {
+        let output = stderr.into_iter().chain(stdout).collect::<Vec<_>>();
+        let stopped = signal(status).map_or(Stopped::Failed, Stopped::Killed);
+        return Err(stopped).wrap_err(format!(
+            "{program} failed ({status}):\n{}",
+            output.join("\n")
+        ));
+    }
+    Ok(())
+}
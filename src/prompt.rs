@@ -0,0 +1,75 @@
+//! asking the user, with the `tui` feature. builds without it act as `--auto` and never get
+//! here, the prompts fail if they do
+use eyre::Result;
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// `--notify`, set once at startup
+static NOTIFY: AtomicBool = AtomicBool::new(false);
+
+/// a notification says so whenever a prompt waits for an answer
+pub fn notify_when_asking() {
+    NOTIFY.store(true, Ordering::Relaxed);
+}
+
+#[cfg(feature = "tui")]
+fn asking(prompt: &str) {
+    if NOTIFY.load(Ordering::Relaxed) {
+        crate::notify::show_in_background(crate::notify::Notification::asking(prompt));
+    }
+}
+
+#[cfg(feature = "tui")]
+pub fn select<T: Display>(prompt: &str, options: Vec<T>) -> Result<T> {
+    asking(prompt);
+    Ok(inquire::Select::new(prompt, options).prompt()?)
+}
+
+#[cfg(feature = "tui")]
+pub fn multi_select<T: Display>(prompt: &str, options: Vec<T>) -> Result<Vec<T>> {
+    asking(prompt);
+    Ok(inquire::MultiSelect::new(prompt, options).prompt()?)
+}
+
+#[cfg(feature = "tui")]
+pub fn password(prompt: &str) -> Result<String> {
+    asking(prompt);
+    Ok(inquire::Password::new(prompt)
+        .without_confirmation()
+        .prompt()?)
+}
+
+#[cfg(feature = "tui")]
+pub fn confirm(prompt: &str, default: bool) -> Result<bool> {
+    asking(prompt);
+    Ok(inquire::Confirm::new(prompt)
+        .with_default(default)
+        .prompt()?)
+}
+
+#[cfg(not(feature = "tui"))]
+fn unavailable(prompt: &str) -> eyre::Report {
+    eyre::eyre!("can't ask [{prompt}], this build was made without the `tui` feature")
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn select<T: Display>(prompt: &str, _options: Vec<T>) -> Result<T> {
+    Err(unavailable(prompt))
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn multi_select<T: Display>(prompt: &str, _options: Vec<T>) -> Result<Vec<T>> {
+    Err(unavailable(prompt))
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn password(prompt: &str) -> Result<String> {
+    Err(unavailable(prompt))
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn confirm(prompt: &str, _default: bool) -> Result<bool> {
+    Err(unavailable(prompt))
+}
@@ -0,0 +1,100 @@
+//! what a download run wrote, recorded in the history so `undo`, `rate` and `report` find it
+use crate::crawler;
+#[cfg(feature = "history")]
+use crate::history;
+#[cfg(feature = "history")]
+use eyre::Result;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "history")]
+use tracing::warn;
+
+/// where a run records the files it wrote, nowhere with `--no-history` or without the
+/// `history` feature
+#[derive(Default)]
+pub struct Recorder {
+    #[cfg(feature = "history")]
+    history: Option<history::History>,
+    /// every file of a run is recorded under the time it started
+    #[cfg(feature = "history")]
+    run: chrono::DateTime<chrono::Utc>,
+    /// the subtitles were picked without asking
+    #[cfg(feature = "history")]
+    auto: bool,
+}
+
+#[cfg(feature = "history")]
+impl Recorder {
+    pub fn new(history: Option<history::History>, auto: bool) -> Self {
+        Self {
+            history,
+            run: chrono::Utc::now(),
+            auto,
+        }
+    }
+
+    /// the history written to, none with `--no-history`
+    pub fn history(&self) -> Option<&history::History> {
+        self.history.as_ref()
+    }
+
+    /// records the files written for `movie_file`, a history that can't be written only warns
+    pub async fn downloads(
+        &self,
+        movie_file: &Path,
+        movie_hash: Option<&str>,
+        language: &str,
+        link: &crawler::Candidate,
+        written: &[PathBuf],
+    ) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let selection = match self.auto {
+            true => history::Selection::Auto,
+            false => history::Selection::Asked,
+        };
+        let downloads = written
+            .iter()
+            .map(|path| {
+                history::Download::new(
+                    self.run, movie_file, movie_hash, language, link, selection, path,
+                )
+            })
+            .collect::<Result<Vec<_>>>();
+        let recorded = match downloads {
+            Ok(downloads) => history.record(downloads).await,
+            Err(report) => Err(report),
+        };
+        if let Err(report) = recorded {
+            warn!(?report, path = ?history.path(), "recording the download failed");
+        }
+    }
+
+    /// records the movie with subtitles, so `undo` can take it back too
+    #[cfg(feature = "embed")]
+    pub async fn video(&self, movie_file: &Path, path: PathBuf, backup: Option<PathBuf>) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let video = history::Video { path, backup };
+        if let Err(report) = history.record_video(self.run, movie_file, video).await {
+            warn!(?report, path = ?history.path(), "recording the movie with subtitles failed");
+        }
+    }
+}
+
+#[cfg(not(feature = "history"))]
+impl Recorder {
+    pub async fn downloads(
+        &self,
+        _: &Path,
+        _: Option<&str>,
+        _: &str,
+        _: &crawler::Candidate,
+        _: &[PathBuf],
+    ) {
+    }
+
+    #[cfg(feature = "embed")]
+    pub async fn video(&self, _: &Path, _: PathBuf, _: Option<PathBuf>) {}
+}
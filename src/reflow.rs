@@ -0,0 +1,294 @@
+//! `--max-line-length`, rewraps cues written for cinema screens
+use crate::{
+    markup::{self, Token},
+    srt::{Cue, Srt, Timestamp},
+};
+
+/// lines of a cue before the rest goes to a cue of its own
+const MAX_LINES: usize = 2;
+
+#[derive(Debug, Clone)]
+struct Word {
+    text: String,
+    /// false for the pieces of a word cut up to fit, CJK text has no spaces to break at
+    spaced: bool,
+}
+
+/// a word longer than `max` cut into pieces of `max` characters, tags don't count
+fn cut(word: &str, max: usize) -> Vec<Word> {
+    if markup::visible_len(word) <= max {
+        return vec![Word {
+            text: word.to_string(),
+            spaced: true,
+        }];
+    }
+    let mut pieces = vec![String::new()];
+    let mut length = 0;
+    for token in markup::tokens(word) {
+        match token {
+            Token::Text(text) => {
+                for c in text.chars() {
+                    if length == max {
+                        pieces.push(String::new());
+                        length = 0;
+                    }
+                    pieces.last_mut().expect("never empty").push(c);
+                    length += 1;
+                }
+            }
+            Token::Open(_, raw) | Token::Close(_, raw) => {
+                pieces.last_mut().expect("never empty").push_str(raw)
+            }
+        }
+    }
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(idx, text)| Word {
+            text,
+            spaced: idx == 0,
+        })
+        .collect()
+}
+
+fn join(words: &[Word]) -> String {
+    words
+        .iter()
+        .enumerate()
+        .fold(String::new(), |mut line, (idx, word)| {
+            if idx > 0 && word.spaced {
+                line.push(' ');
+            }
+            line.push_str(&word.text);
+            line
+        })
+}
+
+/// greedily, text that fits on two lines is split where they come out about as long
+fn wrap(words: &[Word], max: usize) -> Vec<String> {
+    let fits = |words: &[Word]| markup::visible_len(&join(words)) <= max;
+    let mut lines: Vec<Vec<Word>> = vec![];
+    for word in words {
+        match lines.last_mut() {
+            Some(line) if fits(&[line.as_slice(), std::slice::from_ref(word)].concat()) => {
+                line.push(word.clone())
+            }
+            _ => lines.push(vec![word.clone()]),
+        }
+    }
+    if lines.len() == 2 {
+        let words = lines.concat();
+        let balanced = (1..words.len())
+            .map(|idx| (join(&words[..idx]), join(&words[idx..])))
+            .filter(|(first, second)| {
+                markup::visible_len(first) <= max && markup::visible_len(second) <= max
+            })
+            .min_by_key(|(first, second)| {
+                markup::visible_len(first).max(markup::visible_len(second))
+            });
+        if let Some((first, second)) = balanced {
+            return vec![first, second];
+        }
+    }
+    lines.iter().map(|line| join(line)).collect()
+}
+
+/// dialogue lines starting with a dash stay on lines of their own
+fn paragraphs(lines: &[String]) -> Vec<String> {
+    lines
+        .iter()
+        .fold(vec![], |mut paragraphs: Vec<String>, line| {
+            let is_dialogue = markup::tokens(line)
+                .iter()
+                .find_map(|token| match token {
+                    Token::Text(text) if !text.trim().is_empty() => Some(text.trim_start()),
+                    _ => None,
+                })
+                .is_some_and(|text| text.starts_with('-'));
+            match paragraphs.last_mut() {
+                Some(paragraph) if !is_dialogue => {
+                    paragraph.push(' ');
+                    paragraph.push_str(line);
+                }
+                _ => paragraphs.push(line.clone()),
+            }
+            paragraphs
+        })
+}
+
+/// the cue's text rewrapped, split into cues sharing its time by how much text each shows
+fn reflow_cue(cue: &Cue, max: usize) -> Vec<Cue> {
+    let lines = paragraphs(&cue.lines)
+        .iter()
+        .flat_map(|paragraph| {
+            let words = paragraph
+                .split_whitespace()
+                .flat_map(|word| cut(word, max))
+                .collect::<Vec<_>>();
+            wrap(&words, max)
+        })
+        .collect::<Vec<_>>();
+    let lines = markup::balance_lines(&lines);
+    let chunks = lines.chunks(MAX_LINES).collect::<Vec<_>>();
+    let weights = chunks
+        .iter()
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|line| markup::visible_len(line))
+                .sum::<usize>()
+                .max(1)
+        })
+        .collect::<Vec<_>>();
+    let total = weights.iter().sum::<usize>() as i64;
+    let duration = cue.end.0 - cue.start.0;
+    let mut shown = 0;
+    chunks
+        .iter()
+        .zip(weights)
+        .map(|(chunk, weight)| {
+            let start = cue.start.0 + duration * shown / total;
+            shown += weight as i64;
+            Cue {
+                start: Timestamp(start),
+                end: Timestamp(cue.start.0 + duration * shown / total),
+                lines: chunk.to_vec(),
+            }
+        })
+        .collect()
+}
+
+/// returns how many cues were rewrapped
+pub fn reflow(srt: &mut Srt, max_line_length: usize) -> usize {
+    let max = max_line_length.max(1);
+    let mut changed = 0;
+    srt.cues = srt
+        .cues
+        .iter()
+        .flat_map(|cue| {
+            let too_long = cue.lines.len() > MAX_LINES
+                || cue.lines.iter().any(|line| markup::visible_len(line) > max);
+            if !too_long {
+                return vec![cue.clone()];
+            }
+            let reflowed = reflow_cue(cue, max);
+            if reflowed != [cue.clone()] {
+                changed += 1;
+            }
+            reflowed
+        })
+        .collect();
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start: i64, end: i64, lines: &[&str]) -> Cue {
+        Cue {
+            start: Timestamp(start),
+            end: Timestamp(end),
+            lines: lines.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn reflowed(cue: Cue, max: usize) -> Vec<Cue> {
+        let mut srt = Srt { cues: vec![cue] };
+        reflow(&mut srt, max);
+        srt.cues
+    }
+
+    #[test]
+    fn cjk_is_counted_in_characters() {
+        // 12 characters, 36 bytes
+        let short = cue(0, 1000, &["今日はとても良い天気ですね"]);
+        assert_eq!(reflowed(short.clone(), 16), [short]);
+        let long = "今日はとても良い天気ですね。散歩に行きましょう。";
+        let lines = reflowed(cue(0, 1000, &[long]), 16)
+            .into_iter()
+            .flat_map(|cue| cue.lines)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            ["今日はとても良い天気ですね。散歩", "に行きましょう。"]
+        );
+        // no spaces where there were none
+        assert_eq!(lines.concat(), long);
+    }
+
+    #[test]
+    fn cjk_italics_are_closed_and_reopened_at_the_cut() {
+        let line = format!("<i>{}{}</i>", "一".repeat(10), "二".repeat(10));
+        assert_eq!(
+            reflowed(cue(0, 1000, &[&line]), 10),
+            [cue(
+                0,
+                1000,
+                &[
+                    &format!("<i>{}</i>", "一".repeat(10)),
+                    &format!("<i>{}</i>", "二".repeat(10))
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn two_lines_come_out_about_as_long() {
+        assert_eq!(
+            reflowed(cue(0, 1000, &["<i>one two three four five six</i>"]), 20),
+            [cue(
+                0,
+                1000,
+                &["<i>one two three</i>", "<i>four five six</i>"]
+            )]
+        );
+    }
+
+    #[test]
+    fn italics_spanning_the_split_go_to_both_cues() {
+        let long = cue(
+            0,
+            4400,
+            &["<i>This is a rather long line that goes on and on</i>"],
+        );
+        // the time is shared by how much text each cue shows, 35 and 9 characters
+        assert_eq!(
+            reflowed(long, 20),
+            [
+                cue(
+                    0,
+                    3500,
+                    &["<i>This is a rather</i>", "<i>long line that goes</i>"]
+                ),
+                cue(3500, 4400, &["<i>on and on</i>"]),
+            ]
+        );
+        let opened_midway = cue(
+            0,
+            3800,
+            &["He said <i>never, not once,", "not ever</i> again."],
+        );
+        assert_eq!(
+            reflowed(opened_midway, 16),
+            [
+                cue(0, 2700, &["He said <i>never,</i>", "<i>not once, not</i>"]),
+                cue(2700, 3800, &["<i>ever</i> again."]),
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_that_fit_are_left_alone() {
+        let fits = cue(
+            0,
+            1000,
+            &["<font color=\"red\">Short</font>", "- Dialogue."],
+        );
+        let mut srt = Srt {
+            cues: vec![fits.clone()],
+        };
+        assert_eq!(reflow(&mut srt, 12), 0);
+        assert_eq!(srt.cues, [fits]);
+    }
+}
@@ -0,0 +1,108 @@
+//! release names of movies and subtitles, compared token by token
+use itertools::Itertools;
+use std::path::Path;
+
+pub const VIDEO_EXTENSIONS: &[&str] = &["mkv", "mp4", "avi", "m4v", "mov", "wmv", "webm", "ts"];
+
+/// by the extension, whatever the case
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|v| v.to_str())
+        .is_some_and(|extension| VIDEO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+/// lowercase alphanumeric tokens of a release name (`The.Movie.2019.1080p-GRP` ->
+/// `the movie 2019 1080p grp`)
+pub fn tokens(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .unique()
+        .collect()
+}
+
+/// share of tokens two release names have in common, from 0.0 to 1.0
+pub fn similarity(left: &str, right: &str) -> f32 {
+    let left = tokens(left);
+    let right = tokens(right);
+    let common = left.iter().filter(|token| right.contains(token)).count();
+    let total = left.len() + right.len() - common;
+    match total {
+        0 => 0.0,
+        total => common as f32 / total as f32,
+    }
+}
+
+/// `Movie.2019.CD2.DVDRip` -> `2`
+pub fn part_number(name: &str) -> Option<u8> {
+    tokens(name).iter().find_map(|token| {
+        token
+            .strip_prefix("cd")
+            .or_else(|| token.strip_prefix("part"))
+            .and_then(|number| number.parse().ok())
+    })
+}
+
+/// tokens of the release name with the part marker dropped
+pub fn without_part_number(name: &str) -> String {
+    tokens(name)
+        .into_iter()
+        .filter(|token| part_number(token).is_none())
+        .join(" ")
+}
+
+/// tokens from where a release name stops naming the movie
+const NOT_TITLE: &[&str] = &[
+    "480p", "576p", "720p", "1080p", "1080i", "2160p", "4k", "bluray", "bdrip", "brrip", "webrip",
+    "web", "webdl", "hdtv", "dvdrip", "dvd", "hdrip", "x264", "x265", "h264", "h265", "hevc",
+    "xvid", "remux", "proper", "repack", "extended", "unrated",
+];
+
+/// `The.Movie.2019.1080p-GRP` -> `the movie`, what a search by title goes by
+pub fn title(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .enumerate()
+        .take_while(|(idx, token)| {
+            // a year right at the start is the title, `1917` or `2012`
+            let year = *idx > 0
+                && token.len() == 4
+                && token
+                    .parse::<u32>()
+                    .is_ok_and(|year| (1900..2100).contains(&year));
+            !year
+                && !NOT_TITLE.contains(&token.as_str())
+                && episode(token).is_none()
+                && part_number(token).is_none()
+        })
+        .map(|(_, token)| token)
+        .join(" ")
+}
+
+/// `Show.S01E02.720p` -> `(1, 2)`
+pub fn episode(name: &str) -> Option<(u32, u32)> {
+    tokens(name).iter().find_map(|token| {
+        let (season, episode) = token.strip_prefix('s')?.split_once('e')?;
+        Some((season.parse().ok()?, episode.parse().ok()?))
+    })
+}
+
+/// similarity of a subtitle file name to the movie file name, a matching episode counts
+/// as much as every other token together and a different one rules the file out
+pub fn file_similarity(file_name: &str, movie_name: &str) -> f32 {
+    let episode_bonus = match (episode(file_name), episode(movie_name)) {
+        (Some(left), Some(right)) if left == right => 1.0,
+        (Some(_), Some(_)) => -1.0,
+        _ => 0.0,
+    };
+    similarity(file_name, movie_name) + episode_bonus
+}
+
+/// release name closest to the given (movie file) name
+pub fn best_match<'a>(release_names: &'a [String], target: &str) -> Option<&'a str> {
+    release_names
+        .iter()
+        .max_by_key(|name| ordered_float::OrderedFloat(similarity(name, target)))
+        .map(|name| name.as_str())
+}
@@ -0,0 +1,280 @@
+//! `rename`, subtitle files that came under names of their own paired with the videos next to
+//! them and renamed after them. the names are compared token by token, an episode marker
+//! (`s01e02`) has to agree when both have one
+use crate::{
+    charset::{self, Transcode},
+    langid,
+    language::LanguageCode,
+    markup::{self, Token},
+    messages::{filled, text},
+    output::print_table,
+    prompt, release, srt,
+    subtitle::SubtitleFormat,
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use itertools::Itertools;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::warn;
+
+/// `--template` when none is given
+pub const DEFAULT_TEMPLATE: &str = "{stem}.{lang}.{ext}";
+
+/// `{lang}` of subtitles neither the name nor the text tell the language of
+pub const UNDETERMINED: &str = "und";
+
+/// the least [`release::file_similarity`] a subtitle file is paired with a video at
+const MIN_SIMILARITY: f32 = 0.2;
+
+/// a subtitle file and the name it gets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub subtitle: PathBuf,
+    pub video: PathBuf,
+    /// from the file name, guessed from the text without one
+    pub language: Option<String>,
+    pub target: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    /// by the subtitle file
+    pub renames: Vec<Rename>,
+    /// orphans no video is close enough to
+    pub unmatched: Vec<PathBuf>,
+}
+
+/// the renames of the orphan subtitles in `dir`, the ones not named after any video there.
+/// the closest pairs go first, every subtitle is renamed once and no two to the same name
+pub fn plan(dir: &Path, template: &str) -> Result<Plan> {
+    let files = fs::read_dir(dir)
+        .wrap_err_with(|| format!("listing {dir:?}"))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .sorted()
+        .collect::<Vec<_>>();
+    let videos = files
+        .iter()
+        .filter(|path| release::is_video(path))
+        .collect::<Vec<_>>();
+    let orphans = files
+        .iter()
+        .filter(|path| is_subtitle(path))
+        .filter(|path| !videos.iter().any(|video| named_after(path, video)))
+        .map(|path| {
+            let contents = fs::read(path).wrap_err_with(|| format!("reading {path:?}"))?;
+            Ok((path, language(path, &contents)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let pairs = orphans
+        .iter()
+        .cartesian_product(&videos)
+        .map(|(orphan, video)| {
+            let score = release::file_similarity(&stem(orphan.0), &stem(video));
+            (orphan, video, score)
+        })
+        .filter(|(_, _, score)| *score >= MIN_SIMILARITY)
+        .sorted_by(|left, right| right.2.total_cmp(&left.2));
+    let mut plan = Plan::default();
+    for ((subtitle, language), video, _) in pairs {
+        if plan
+            .renames
+            .iter()
+            .any(|rename| rename.subtitle == **subtitle)
+        {
+            continue;
+        }
+        let target = target_path(video, template, language.as_deref(), &extension(subtitle))?;
+        if target == **subtitle || plan.renames.iter().any(|rename| rename.target == target) {
+            continue;
+        }
+        plan.renames.push(Rename {
+            subtitle: subtitle.to_path_buf(),
+            video: video.to_path_buf(),
+            language: language.clone(),
+            target,
+        });
+    }
+    plan.renames
+        .sort_by(|left, right| left.subtitle.cmp(&right.subtitle));
+    plan.unmatched = orphans
+        .into_iter()
+        .map(|(path, _)| path.clone())
+        .filter(|path| !plan.renames.iter().any(|rename| rename.subtitle == *path))
+        .collect();
+    Ok(plan)
+}
+
+fn is_subtitle(path: &Path) -> bool {
+    matches!(
+        SubtitleFormat::from_path(path),
+        Some(
+            SubtitleFormat::Srt
+                | SubtitleFormat::Sub
+                | SubtitleFormat::Ass
+                | SubtitleFormat::Ssa
+                | SubtitleFormat::Vtt
+        )
+    )
+}
+
+/// `movie.srt` and `movie.pol.srt` are the subtitles of `movie.mkv` already
+fn named_after(subtitle: &Path, video: &Path) -> bool {
+    let name = subtitle.file_name().unwrap_or_default().to_string_lossy();
+    name.strip_prefix(stem(video).as_str())
+        .is_some_and(|rest| rest.starts_with('.'))
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_lowercase()
+}
+
+/// the language the name ends with (`movie.en.srt`, `movie_polish.srt`), the one the text
+/// reads like without it
+pub fn language(path: &Path, contents: &[u8]) -> Option<String> {
+    let stem = stem(path);
+    let named = stem
+        .rsplit(|c: char| !c.is_alphanumeric())
+        .next()
+        .filter(|last| *last != stem)
+        .and_then(|last| LanguageCode::new(last).iso639_2());
+    named.map(String::from).or_else(|| {
+        let transcode = Transcode {
+            source: None,
+            language: String::new(),
+        };
+        let text = charset::is_text(contents)
+            .then(|| transcode.to_utf8(contents).ok())
+            .flatten()
+            .and_then(|text| String::from_utf8(text).ok())?;
+        let text = match SubtitleFormat::from_path(path) {
+            Some(SubtitleFormat::Srt) => srt::parse_lenient(&text)
+                .srt
+                .cues
+                .iter()
+                .flat_map(|cue| &cue.lines)
+                .flat_map(|line| markup::tokens(line))
+                .filter_map(|token| match token {
+                    Token::Text(text) => Some(text),
+                    Token::Open(..) | Token::Close(..) => None,
+                })
+                .join(" "),
+            _ => text,
+        };
+        langid::detect(&text).map(String::from)
+    })
+}
+
+/// `template` filled in for the subtitles of `video`, next to it. `{stem}` is the video's file
+/// name without its extension, `{lang}` the language and `{ext}` the subtitle's extension
+pub fn target_path(
+    video: &Path,
+    template: &str,
+    language: Option<&str>,
+    extension: &str,
+) -> Result<PathBuf> {
+    if video.file_stem().is_none() {
+        return Err(eyre!("{video:?} has no file name"));
+    }
+    let stem = stem(video);
+    let rendered = [
+        ("{stem}", stem.as_str()),
+        ("{lang}", language.unwrap_or(UNDETERMINED)),
+        ("{ext}", extension),
+    ]
+    .into_iter()
+    .fold(template.to_string(), |rendered, (placeholder, value)| {
+        rendered.replace(placeholder, value)
+    });
+    if rendered.contains(['{', '}']) {
+        bail!("{template:?} has placeholders other than {{stem}}, {{lang}} and {{ext}}");
+    }
+    Ok(video.parent().unwrap_or(Path::new("")).join(rendered))
+}
+
+/// `rename`, asks before renaming unless `yes`. names that are taken are left alone without
+/// `force`
+pub fn rename_orphans(
+    dir: &Path,
+    template: &str,
+    yes: bool,
+    force: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let plan = plan(dir, template)?;
+    let name = |path: &Path| {
+        path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    };
+    match plan.renames.is_empty() {
+        true if plan.unmatched.is_empty() => println!(
+            "{}",
+            filled("rename-nothing", &[("dir", &format!("{dir:?}"))])
+        ),
+        false => print_table(
+            [
+                text("column-subtitle").to_string(),
+                text("column-movie").to_string(),
+                text("column-language").to_string(),
+                text("column-output").to_string(),
+            ],
+            &plan
+                .renames
+                .iter()
+                .map(|rename| {
+                    [
+                        name(&rename.subtitle),
+                        name(&rename.video),
+                        rename
+                            .language
+                            .clone()
+                            .unwrap_or_else(|| UNDETERMINED.to_string()),
+                        name(&rename.target),
+                    ]
+                })
+                .collect::<Vec<_>>(),
+        ),
+        true => {}
+    }
+    if !plan.unmatched.is_empty() {
+        println!("{}", text("rename-unmatched"));
+        plan.unmatched
+            .iter()
+            .for_each(|path| println!("  {}", name(path)));
+    }
+    let (renames, taken): (Vec<_>, Vec<_>) = plan
+        .renames
+        .into_iter()
+        .partition(|rename| force || !rename.target.exists());
+    for rename in &taken {
+        let path = format!("{:?}", rename.target);
+        warn!("{}", filled("error-output-exists", &[("path", &path)]));
+    }
+    if renames.is_empty() || dry_run {
+        return Ok(());
+    }
+    let count = renames.len();
+    if !yes && !prompt::confirm(&filled("prompt-rename", &[("count", &count)]), true)? {
+        return Ok(());
+    }
+    for rename in &renames {
+        fs::rename(&rename.subtitle, &rename.target)
+            .wrap_err_with(|| format!("renaming {:?} to {:?}", rename.subtitle, rename.target))?;
+    }
+    println!("{}", filled("renamed", &[("count", &count)]));
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! what a download run prints on stdout, the paths it wrote one per line or with `--json` one
+//! document of everything it did once it's done
+use crate::{check::DurationCheck, crawler::SubsEntry, output, timings::Timings};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// `--json`, printed once the run is done
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Document {
+    pub movie_file: PathBuf,
+    pub language: String,
+    /// the row the subtitles came from, `None` when the run ended before picking one
+    pub subtitle: Option<SubsEntry>,
+    /// every file written, in the order they were
+    pub written: Vec<PathBuf>,
+    /// `--keep-archive`, where the downloaded archive was saved
+    pub archive: Option<PathBuf>,
+    /// the encoding every subtitle file was taken for before it became utf-8, for telling
+    /// misdetections apart
+    pub encodings: BTreeMap<PathBuf, String>,
+    /// the movie each subtitle file was downloaded for before, when the history has the same
+    /// subtitles for another one
+    pub downloaded_before: BTreeMap<PathBuf, PathBuf>,
+    /// `--sync`, every subtitle file it ran on, empty when it didn't
+    pub synced: BTreeMap<PathBuf, Synced>,
+    /// the last subtitles checked against the movie's duration, `None` when it's unknown
+    pub duration_check: Option<DurationChecked>,
+    /// `--print-embed-command`, the program and its arguments to run as they are
+    pub embed_command: Option<Vec<String>>,
+    /// `--timings`, how long every stage took
+    pub timings: Option<TimingsReport>,
+    /// `--insecure`, the site's certificate wasn't verified
+    pub insecure: bool,
+    /// why nothing was downloaded, `None` when the run didn't skip the movie
+    pub skipped: Option<Skipped>,
+}
+
+/// a movie left alone on purpose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Skipped {
+    /// `--skip-if-audio-matches`, the movie has audio in the language
+    AudioMatches,
+}
+
+/// the stages in the order they ran, then added up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimingsReport {
+    pub stages: Vec<StageTiming>,
+    pub totals: Vec<StageTotal>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageTiming {
+    pub stage: String,
+    /// the file written or the url fetched
+    pub subject: Option<String>,
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StageTotal {
+    pub stage: String,
+    pub count: usize,
+    pub duration_ms: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DurationChecked {
+    #[serde(flatten)]
+    pub check: DurationCheck,
+    /// what's wrong with the timing, `None` when nothing is
+    pub mismatch: Option<String>,
+}
+
+/// how `--sync` went for a subtitle file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Synced {
+    pub tool: String,
+    /// seconds the subtitles were moved by, when the tool says
+    pub offset: Option<f64>,
+    /// why the subtitles were left as they were
+    pub error: Option<String>,
+}
+
+/// collects what a run did as it goes
+#[derive(Debug, Default)]
+pub struct Results {
+    json: bool,
+    document: Mutex<Document>,
+}
+
+impl Results {
+    pub fn new(json: bool, movie_file: &Path, language: &str) -> Self {
+        Self {
+            json,
+            document: Mutex::new(Document {
+                movie_file: movie_file.to_owned(),
+                language: language.to_string(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn update(&self, update: impl FnOnce(&mut Document)) {
+        update(
+            &mut self
+                .document
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        );
+    }
+
+    /// the subtitles were settled on, the last one picked wins when others failed before
+    pub fn chosen(&self, entry: &SubsEntry) {
+        self.update(|document| document.subtitle = Some(entry.clone()));
+    }
+
+    /// prints the path right away without `--json`
+    pub fn written(&self, path: &Path) {
+        if !self.json {
+            println!("{}", output::quoted(path));
+        }
+        self.update(|document| document.written.push(path.to_owned()));
+    }
+
+    /// `--keep-archive` saved the download, printed like a written file without `--json`
+    pub fn archive(&self, path: &Path) {
+        if !self.json {
+            println!("{}", output::quoted(path));
+        }
+        self.update(|document| document.archive = Some(path.to_owned()));
+    }
+
+    pub fn decoded(&self, path: &Path, encoding: &str) {
+        self.update(|document| {
+            document
+                .encodings
+                .insert(path.to_owned(), encoding.to_string());
+        });
+    }
+
+    pub fn downloaded_before(&self, path: &Path, movie: &Path) {
+        self.update(|document| {
+            document
+                .downloaded_before
+                .insert(path.to_owned(), movie.to_owned());
+        });
+    }
+
+    pub fn synced(&self, path: &Path, synced: Synced) {
+        self.update(|document| {
+            document.synced.insert(path.to_owned(), synced);
+        });
+    }
+
+    pub fn insecure(&self) {
+        self.update(|document| document.insecure = true);
+    }
+
+    pub fn duration_checked(&self, check: DurationCheck) {
+        let checked = DurationChecked {
+            check,
+            mismatch: check.mismatch(),
+        };
+        self.update(|document| document.duration_check = Some(checked));
+    }
+
+    pub fn skipped(&self, skipped: Skipped) {
+        self.update(|document| document.skipped = Some(skipped));
+    }
+
+    /// `--print-embed-command`, `line` is printed without `--json`
+    pub fn embed_command(&self, arguments: Vec<String>, line: &str) {
+        if !self.json {
+            println!("{line}");
+        }
+        self.update(|document| document.embed_command = Some(arguments));
+    }
+
+    /// `--timings` of a run that's done, the ones that ran so far
+    pub fn timings(&self, timings: &Timings) {
+        let millis = |duration: std::time::Duration| duration.as_secs_f64() * 1000.0;
+        let stages = timings.timings().into_iter().map(|timing| StageTiming {
+            stage: timing.stage.to_string(),
+            subject: timing.subject,
+            duration_ms: millis(timing.duration),
+        });
+        let totals = timings.totals().into_iter().map(|total| StageTotal {
+            stage: total.stage.to_string(),
+            count: total.count,
+            duration_ms: millis(total.duration),
+        });
+        let report = TimingsReport {
+            stages: stages.collect(),
+            totals: totals.collect(),
+        };
+        self.update(|document| document.timings = Some(report));
+    }
+
+    /// a line for whoever reads the results, on stderr with `--json` as stdout is the document's
+    pub fn line(&self, line: &str) {
+        match self.json {
+            true => eprintln!("{line}"),
+            false => println!("{line}"),
+        }
+    }
+
+    pub fn document(&self) -> Document {
+        self.document
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// prints the document with `--json`, nothing is left to print without it
+    pub fn finish(&self) -> Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&self.document())?);
+        }
+        Ok(())
+    }
+}
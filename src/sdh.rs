@@ -0,0 +1,242 @@
+//! `--strip-hi`, removes the hearing impaired annotations of SDH subtitles
+use crate::srt::{Cue, Srt};
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StripHi {
+    /// sound descriptions, short parenthesized asides, uppercase speaker labels, music notes
+    Conservative,
+    /// also any parenthesized text, title case speaker labels and sung lines
+    Aggressive,
+}
+
+/// parenthesized text longer than this is more likely dialogue than a sound description
+const MAX_ANNOTATION_WORDS: usize = 3;
+pub const MUSIC_NOTES: &[char] = &['♪', '♫', '#'];
+
+/// removes every `open`...`close` span `remove` agrees to, unbalanced ones are kept
+fn remove_enclosed(line: &str, open: char, close: char, remove: impl Fn(&str) -> bool) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find(open) {
+        let Some(length) = rest[start..].find(close) else {
+            break;
+        };
+        let (enclosed, after) = rest[start..].split_at(length + close.len_utf8());
+        output.push_str(&rest[..start]);
+        if !remove(&enclosed[open.len_utf8()..length]) {
+            output.push_str(enclosed);
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+    output
+}
+
+/// `JOHN:` or `MAN #2:` at the start of the line, `John:` too when aggressive
+fn strip_speaker_label(line: &str, level: StripHi) -> &str {
+    let Some((label, rest)) = line.split_once(':') else {
+        return line;
+    };
+    let label = label.trim_start_matches(['-', ' ']);
+    let words = label.split_whitespace().collect::<Vec<_>>();
+    let is_label = !words.is_empty()
+        && words.len() <= MAX_ANNOTATION_WORDS
+        && label.chars().any(char::is_alphabetic)
+        && match level {
+            StripHi::Conservative => label
+                .chars()
+                .all(|c| !c.is_lowercase() && (c.is_alphanumeric() || " #'.".contains(c))),
+            StripHi::Aggressive => words
+                .iter()
+                .all(|word| word.chars().next().is_some_and(|c| !c.is_lowercase())),
+        };
+    // `10:30` is a time
+    match is_label && !rest.starts_with(|c: char| c.is_ascii_digit()) {
+        true => rest.trim_start(),
+        false => line,
+    }
+}
+
+/// the line without annotations, untouched when there were none
+fn strip_line(original: &str, level: StripHi) -> String {
+    let line = remove_enclosed(original, '[', ']', |_| true);
+    let line = remove_enclosed(&line, '(', ')', |text| {
+        level == StripHi::Aggressive || text.split_whitespace().count() <= MAX_ANNOTATION_WORDS
+    });
+    let dash = line.trim_start().starts_with('-');
+    let line = strip_speaker_label(line.trim(), level).trim();
+    let only_notes = line
+        .chars()
+        .all(|c| c.is_whitespace() || MUSIC_NOTES.contains(&c));
+    let sung = line.starts_with(MUSIC_NOTES) && line.ends_with(MUSIC_NOTES);
+    if only_notes || (level == StripHi::Aggressive && sung) {
+        return String::new();
+    }
+    if line == original.trim() {
+        return original.to_string();
+    }
+    let line = line.trim_start_matches('-').trim();
+    match line.is_empty() {
+        true => String::new(),
+        // dialogue dashes stay with the line they introduce
+        false if dash => format!("- {line}"),
+        false => line.to_string(),
+    }
+}
+
+/// whether the last `open` on the line isn't closed on it
+fn unclosed(line: &str, open: char, close: char) -> bool {
+    line.rfind(open) > line.rfind(close)
+}
+
+/// the lines with an annotation spanning several, `[door` and `creaks]`, joined by `\n`
+fn logical_lines(lines: &[String]) -> Vec<String> {
+    let mut joined = Vec::<String>::with_capacity(lines.len());
+    let mut open = false;
+    for line in lines {
+        match (open, joined.last_mut()) {
+            (true, Some(last)) => {
+                last.push('\n');
+                last.push_str(line);
+            }
+            _ => joined.push(line.clone()),
+        }
+        let last = joined.last().map(String::as_str).unwrap_or_default();
+        open = unclosed(last, '[', ']') || unclosed(last, '(', ')');
+    }
+    joined
+}
+
+fn strip_cue(cue: &Cue, level: StripHi) -> Cue {
+    Cue {
+        lines: logical_lines(&cue.lines)
+            .iter()
+            .flat_map(|line| match line.contains('\n') {
+                // the joined lines go back to the lines they were, whatever is left of them
+                true => strip_line(line, level)
+                    .split('\n')
+                    .map(|line| strip_line(line, level))
+                    .collect::<Vec<_>>(),
+                false => vec![strip_line(line, level)],
+            })
+            .filter(|line| !line.is_empty())
+            .collect(),
+        ..cue.clone()
+    }
+}
+
+/// returns how many cues changed, cues left empty are dropped
+pub fn strip(srt: &mut Srt, level: StripHi) -> usize {
+    let mut changed = 0;
+    srt.cues = srt
+        .cues
+        .iter()
+        .filter_map(|cue| {
+            let stripped = strip_cue(cue, level);
+            if stripped != *cue {
+                changed += 1;
+            }
+            (!stripped.lines.is_empty()).then_some(stripped)
+        })
+        .collect();
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::Timestamp;
+
+    fn cue(lines: &[&str]) -> Cue {
+        Cue {
+            start: Timestamp(1_000),
+            end: Timestamp(2_000),
+            lines: lines.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn stripped(lines: &[&str], level: StripHi) -> Vec<String> {
+        strip_cue(&cue(lines), level).lines
+    }
+
+    #[test]
+    fn only_the_annotated_line_goes() {
+        let level = StripHi::Conservative;
+        assert_eq!(
+            stripped(&["[door creaks]", "Who's there?"], level),
+            ["Who's there?"]
+        );
+        assert_eq!(
+            stripped(&["Come here.", "(whispering)"], level),
+            ["Come here."]
+        );
+        assert_eq!(stripped(&["♪ ♪", "Hello."], level), ["Hello."]);
+        assert_eq!(
+            stripped(&["JOHN: Run!", "Where to?"], level),
+            ["Run!", "Where to?"]
+        );
+    }
+
+    #[test]
+    fn dialogue_dashes_stay() {
+        assert_eq!(
+            stripped(
+                &["- (sighs) I know.", "- JOHN: You don't."],
+                StripHi::Conservative
+            ),
+            ["- I know.", "- You don't."]
+        );
+        assert_eq!(
+            stripped(&["- [gunshot]", "- Get down!"], StripHi::Conservative),
+            ["- Get down!"]
+        );
+    }
+
+    #[test]
+    fn untouched_lines_keep_their_spacing() {
+        assert_eq!(
+            stripped(&["[thunder]", "  Quite  a storm. "], StripHi::Conservative),
+            ["  Quite  a storm. "]
+        );
+    }
+
+    #[test]
+    fn annotations_spanning_lines() {
+        let level = StripHi::Conservative;
+        assert_eq!(
+            stripped(&["[door", "creaks]", "Who's there?"], level),
+            ["Who's there?"]
+        );
+        assert_eq!(stripped(&["(sighs", "heavily) Fine."], level), ["Fine."]);
+        assert_eq!(stripped(&["Wait. [phone", "ringing]"], level), ["Wait."]);
+        // never closed, nothing to strip
+        assert_eq!(
+            stripped(&["[door creaks", "Who's there?"], level),
+            ["[door creaks", "Who's there?"]
+        );
+    }
+
+    #[test]
+    fn long_asides_only_go_when_aggressive() {
+        let lines = ["(and I mean it, every word)", "Go home."];
+        assert_eq!(stripped(&lines, StripHi::Conservative), lines);
+        assert_eq!(stripped(&lines, StripHi::Aggressive), ["Go home."]);
+        let sung = ["♪ la la la ♪", "Stop singing."];
+        assert_eq!(stripped(&sung, StripHi::Conservative), sung);
+        assert_eq!(stripped(&sung, StripHi::Aggressive), ["Stop singing."]);
+    }
+
+    #[test]
+    fn emptied_cues_are_dropped() {
+        let mut srt = Srt {
+            cues: vec![
+                cue(&["Hello."]),
+                cue(&["[door creaks]", "(footsteps)"]),
+                cue(&["MAN #2: Who's there?", "Me."]),
+            ],
+        };
+        assert_eq!(strip(&mut srt, StripHi::Conservative), 2);
+        assert_eq!(srt.cues, [cue(&["Hello."]), cue(&["Who's there?", "Me."])]);
+    }
+}
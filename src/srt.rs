@@ -0,0 +1,525 @@
+//! SubRip, the format nearly every download comes in
+use crate::{markup, sdh};
+use eyre::{bail, eyre, Result, WrapErr};
+use itertools::Itertools;
+use tap::Pipe;
+
+/// milliseconds since the start of the movie
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp(pub i64);
+
+impl std::str::FromStr for Timestamp {
+    type Err = eyre::Report;
+
+    /// `01:02:03,456`, some tools write a `.` instead of the comma
+    fn from_str(s: &str) -> Result<Self> {
+        let (time, millis) = s
+            .trim()
+            .split_once([',', '.'])
+            .ok_or_else(|| eyre!("no milliseconds in [{s}]"))?;
+        let parts = time
+            .split(':')
+            .map(|part| part.trim().parse::<i64>())
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err_with(|| format!("invalid timestamp [{s}]"))?;
+        let [hours, minutes, seconds] = parts[..] else {
+            bail!("invalid timestamp [{s}]");
+        };
+        // `,5` is half a second
+        let millis = millis
+            .chars()
+            .chain(std::iter::repeat('0'))
+            .take(3)
+            .collect::<String>()
+            .parse::<i64>()
+            .wrap_err_with(|| format!("invalid milliseconds in [{s}]"))?;
+        // a number too long for a timestamp is as invalid as one that isn't a number
+        hours
+            .checked_mul(60)
+            .and_then(|v| v.checked_add(minutes)?.checked_mul(60))
+            .and_then(|v| v.checked_add(seconds)?.checked_mul(1000))
+            .and_then(|v| v.checked_add(millis))
+            .map(Self)
+            .ok_or_else(|| eyre!("timestamp out of range [{s}]"))
+    }
+}
+
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let millis = self.0.max(0);
+        write!(
+            f,
+            "{:02}:{:02}:{:02},{:03}",
+            millis / 3_600_000,
+            millis / 60_000 % 60,
+            millis / 1000 % 60,
+            millis % 1000
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Cue {
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub lines: Vec<String>,
+}
+
+impl Cue {
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+/// cues in file order, numbered from 1 when written
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Srt {
+    pub cues: Vec<Cue>,
+}
+
+/// what a lenient parse skipped over or found out of place
+#[derive(Debug, Clone, Default)]
+pub struct Parsed {
+    pub srt: Srt,
+    /// blocks without a valid timing line
+    pub garbage: Vec<String>,
+    /// cues not numbered by their position
+    pub misnumbered: usize,
+}
+
+/// lines up to the timing line are the number (and garbage before it), the rest is text
+fn parse_block(block: &[String]) -> Result<(Option<&str>, Cue)> {
+    let timing = block
+        .iter()
+        .position(|line| line.contains("-->"))
+        .ok_or_else(|| eyre!("no timing line"))?;
+    let (start, end) = block[timing]
+        .split_once("-->")
+        .ok_or_else(|| eyre!("invalid timing line [{}]", block[timing]))?;
+    let cue = Cue {
+        start: start.parse()?,
+        // positioning may follow the end timestamp
+        end: end
+            .split_whitespace()
+            .next()
+            .ok_or_else(|| eyre!("no end timestamp in [{}]", block[timing]))?
+            .parse()?,
+        lines: block[timing + 1..].to_vec(),
+    };
+    let number = timing.checked_sub(1).map(|idx| block[idx].trim());
+    Ok((number, cue))
+}
+
+/// blocks separated by blank lines: an optional number, `start --> end` and the text.
+/// blocks that aren't cues are collected instead of failing the whole file
+pub fn parse_lenient(text: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    // byte order marks turn up in the middle of concatenated files too
+    let mut lines = text
+        .lines()
+        .map(|line| line.trim_end_matches('\r').replace('\u{feff}', ""))
+        .peekable();
+    while lines.peek().is_some() {
+        let block = lines
+            .by_ref()
+            .skip_while(|line| line.trim().is_empty())
+            .take_while(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        if block.is_empty() {
+            continue;
+        }
+        match parse_block(&block) {
+            Ok((number, cue)) => {
+                let expected = parsed.srt.cues.len() + 1;
+                if number.and_then(|v| v.parse::<usize>().ok()) != Some(expected) {
+                    parsed.misnumbered += 1;
+                }
+                parsed.srt.cues.push(cue);
+            }
+            Err(_) => parsed.garbage.push(block.join("\n")),
+        }
+    }
+    parsed
+}
+
+pub fn parse(text: &str) -> Result<Srt> {
+    let parsed = parse_lenient(text);
+    match parsed.garbage.first() {
+        Some(block) => bail!("no valid timing line in cue [{block}]"),
+        None => Ok(parsed.srt),
+    }
+}
+
+/// how long a cue ending before or as it starts is shown for, unless the next one comes sooner
+const REPAIRED_DURATION: i64 = 2000;
+
+/// what `Srt::repair` changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Repairs {
+    pub garbage: usize,
+    pub misnumbered: usize,
+    pub reordered: usize,
+    pub duplicates: usize,
+    pub merged: usize,
+    pub negative_durations: usize,
+    pub overlaps: usize,
+    pub empty: usize,
+}
+
+/// the fixes `Parsed::repair` can be told to leave out, repeated lyrics are often meant as
+/// separate cues
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepairOptions {
+    /// put cues in order of their start
+    pub sort: bool,
+    /// drop cues with the same timing and text as an earlier one
+    pub drop_duplicates: bool,
+    /// join neighbouring cues with the same text that touch or overlap
+    pub merge_repeats: bool,
+    /// drop cues without text, some players flicker on them
+    pub drop_empty: bool,
+    /// cues of nothing but music notes count as empty, `--strip-hi` would remove them anyway
+    pub notes_are_empty: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            sort: true,
+            drop_duplicates: true,
+            merge_repeats: true,
+            drop_empty: true,
+            notes_are_empty: false,
+        }
+    }
+}
+
+/// nothing a player would show: blank lines, a lone dash or only tags like `<i></i>`
+fn is_empty(cue: &Cue, notes_are_empty: bool) -> bool {
+    cue.lines
+        .iter()
+        .flat_map(|line| markup::tokens(line))
+        .all(|token| match token {
+            markup::Token::Text(text) => text.chars().all(|c| {
+                c.is_whitespace() || c == '-' || (notes_are_empty && sdh::MUSIC_NOTES.contains(&c))
+            }),
+            markup::Token::Open(..) | markup::Token::Close(..) => true,
+        })
+}
+
+impl Repairs {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::fmt::Display for Repairs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            garbage,
+            misnumbered,
+            reordered,
+            duplicates,
+            merged,
+            negative_durations,
+            overlaps,
+            empty,
+        } = self;
+        [
+            (*garbage, "garbage blocks dropped"),
+            (*misnumbered, "cues renumbered"),
+            (*reordered, "cues put back in order"),
+            (*duplicates, "duplicate cues dropped"),
+            (*merged, "repeated cues merged"),
+            (
+                *negative_durations,
+                "cues ending before or as they start fixed",
+            ),
+            (*overlaps, "overlapping cues trimmed"),
+            (*empty, "empty cues dropped"),
+        ]
+        .into_iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, what)| format!("{count} {what}"))
+        .join(", ")
+        .pipe(|text| f.write_str(&text))
+    }
+}
+
+impl Parsed {
+    /// drops empty cues, puts the rest in order and fixes their timing, numbering is fixed
+    /// by writing the file
+    pub fn repair(self, options: RepairOptions) -> (Srt, Repairs) {
+        let Self {
+            mut srt,
+            garbage,
+            misnumbered,
+        } = self;
+        let mut repairs = Repairs {
+            garbage: garbage.len(),
+            misnumbered,
+            ..Default::default()
+        };
+        if options.drop_empty {
+            let count = srt.cues.len();
+            srt.cues
+                .retain(|cue| !is_empty(cue, options.notes_are_empty));
+            repairs.empty = count - srt.cues.len();
+        }
+        if options.sort && !srt.cues.is_sorted_by_key(|cue| cue.start) {
+            let unsorted = srt.cues.clone();
+            // stable, cues starting together keep their order
+            srt.cues.sort_by_key(|cue| cue.start);
+            repairs.reordered = unsorted
+                .iter()
+                .zip(&srt.cues)
+                .filter(|(before, after)| before != after)
+                .count();
+        }
+        if options.drop_duplicates {
+            let count = srt.cues.len();
+            let mut seen = std::collections::HashSet::new();
+            srt.cues.retain(|cue| seen.insert(cue.clone()));
+            repairs.duplicates = count - srt.cues.len();
+        }
+        if options.merge_repeats {
+            let count = srt.cues.len();
+            srt.cues = srt
+                .cues
+                .into_iter()
+                .coalesce(|previous, cue| {
+                    match previous.lines == cue.lines && cue.start <= previous.end {
+                        true => Ok(Cue {
+                            end: previous.end.max(cue.end),
+                            ..previous
+                        }),
+                        false => Err((previous, cue)),
+                    }
+                })
+                .collect();
+            repairs.merged = count - srt.cues.len();
+        }
+        let starts = srt
+            .cues
+            .iter()
+            .skip(1)
+            .map(|cue| Some(cue.start))
+            .chain([None])
+            .collect::<Vec<_>>();
+        for (cue, next_start) in srt.cues.iter_mut().zip(starts) {
+            // a cue ending as it starts is never shown either
+            if cue.end <= cue.start {
+                let end = cue.start.0.saturating_add(REPAIRED_DURATION);
+                let end = Timestamp(next_start.map_or(end, |next| end.min(next.0))).max(cue.start);
+                if end != cue.end {
+                    cue.end = end;
+                    repairs.negative_durations += 1;
+                }
+            }
+            if let Some(next_start) = next_start.filter(|next| cue.end > *next) {
+                cue.end = next_start;
+                repairs.overlaps += 1;
+            }
+        }
+        (srt, repairs)
+    }
+}
+
+impl std::fmt::Display for Srt {
+    /// renumbered, `\n` line endings
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, cue) in self.cues.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{}", idx + 1)?;
+            writeln!(f, "{} --> {}", cue.start, cue.end)?;
+            for line in &cue.lines {
+                writeln!(f, "{line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `--shift`, seconds (`-2.5`) or a signed timestamp (`+00:00:02,300`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Offset(pub i64);
+
+impl std::str::FromStr for Offset {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (sign, magnitude) = match s.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, s.trim_start_matches('+')),
+        };
+        let millis = match magnitude.contains(':') {
+            true => magnitude.parse::<Timestamp>()?.0,
+            false => magnitude
+                .parse::<f64>()
+                .map(|seconds| (seconds * 1000.0).round() as i64)
+                .wrap_err_with(|| format!("invalid offset [{s}]"))?,
+        };
+        millis
+            .checked_mul(sign)
+            .map(Self)
+            .ok_or_else(|| eyre!("offset out of range [{s}]"))
+    }
+}
+
+impl Srt {
+    /// moves every cue by the offset, cues pushed entirely before the start are dropped and
+    /// the ones across it start at zero
+    pub fn shift(&mut self, offset: Offset) {
+        self.retime(Linear {
+            scale: 1.0,
+            offset: offset.0 as f64,
+        });
+    }
+}
+
+/// `--anchor 00:10:00,000=00:10:02,500`, where a cue is and where it should be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Anchor {
+    pub from: Timestamp,
+    pub to: Timestamp,
+}
+
+impl std::str::FromStr for Anchor {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| eyre!("expected <cue time>=<correct time>, got [{s}]"))?;
+        Ok(Self {
+            from: from.parse()?,
+            to: to.parse()?,
+        })
+    }
+}
+
+/// `time * scale + offset`, for subtitles made for a different cut or frame rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Linear {
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl Linear {
+    pub fn from_anchors(first: Anchor, second: Anchor) -> Result<Self> {
+        let (first, second) = match first.from <= second.from {
+            true => (first, second),
+            false => (second, first),
+        };
+        if first.from == second.from || first.to >= second.to {
+            bail!("anchors must be distinct and keep the cues in order");
+        }
+        let scale = (second.to.0 - first.to.0) as f64 / (second.from.0 - first.from.0) as f64;
+        Ok(Self {
+            scale,
+            offset: first.to.0 as f64 - first.from.0 as f64 * scale,
+        })
+    }
+
+    fn apply(self, timestamp: Timestamp) -> i64 {
+        (timestamp.0 as f64 * self.scale + self.offset).round() as i64
+    }
+}
+
+impl Srt {
+    /// like `shift`, cues ending up before the start are dropped or start at zero
+    pub fn retime(&mut self, linear: Linear) {
+        self.cues.retain_mut(|cue| {
+            cue.start = Timestamp(linear.apply(cue.start).max(0));
+            cue.end = Timestamp(linear.apply(cue.end));
+            cue.end.0 > 0
+        });
+    }
+}
+
+impl Srt {
+    /// the parts of a movie split across CDs one after another, each part retimed by the
+    /// lengths of the ones before it. `lengths` has one for every part but the last
+    pub fn concatenate(parts: Vec<Srt>, lengths: &[Timestamp]) -> Result<Srt> {
+        if lengths.len() + 1 != parts.len() {
+            bail!(
+                "{} parts need the length of {}, got {}",
+                parts.len(),
+                parts.len().saturating_sub(1),
+                lengths.len()
+            );
+        }
+        let offsets = std::iter::once(0).chain(lengths.iter().scan(0, |offset, length| {
+            *offset += length.0;
+            Some(*offset)
+        }));
+        let mut joined = Srt::default();
+        for (mut part, offset) in parts.into_iter().zip(offsets) {
+            part.retime(Linear {
+                scale: 1.0,
+                offset: offset as f64,
+            });
+            joined.cues.extend(part.cues);
+        }
+        Ok(joined)
+    }
+}
+
+const KNOWN_FRAME_RATES: &[f64] = &[23.976, 24.0, 25.0, 29.97, 30.0];
+
+/// `(from, to)` frame rates a scale factor converts between, if it's a common one
+pub fn frame_rate_conversion(scale: f64) -> Option<(f64, f64)> {
+    KNOWN_FRAME_RATES
+        .iter()
+        .cartesian_product(KNOWN_FRAME_RATES)
+        .filter(|(from, to)| from != to)
+        .find(|(from, to)| (*from / *to - scale).abs() < 0.0005)
+        .map(|(from, to)| (*from, *to))
+}
+
+/// `--retime-fps 25:23.976`, the frame rate the subtitles were timed for and the video's
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameRates {
+    pub from: f64,
+    pub to: f64,
+}
+
+impl std::str::FromStr for FrameRates {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (from, to) = s
+            .split_once(':')
+            .ok_or_else(|| eyre!("expected <from fps>:<to fps>, got [{s}]"))?;
+        let parse = |fps: &str| {
+            fps.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|fps| *fps > 0.0)
+                .ok_or_else(|| eyre!("invalid frame rate [{fps}]"))
+        };
+        Ok(Self {
+            from: parse(from)?,
+            to: parse(to)?,
+        })
+    }
+}
+
+impl FrameRates {
+    /// frames keep their number, so a cue at 1000 frames moves from `1000 / from` to
+    /// `1000 / to` seconds
+    pub fn linear(self) -> Linear {
+        Linear {
+            scale: self.from / self.to,
+            offset: 0.0,
+        }
+    }
+
+    /// close enough that a retime would move cues less than a frame over two hours
+    pub fn is_same(self) -> bool {
+        (self.from / self.to - 1.0).abs() < 0.0001
+    }
+}
@@ -0,0 +1,87 @@
+//! subtitle file formats
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub mod format;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    /// MicroDVD, frame based
+    Sub,
+    Ass,
+    Ssa,
+    Vtt,
+    Other(String),
+}
+
+impl SubtitleFormat {
+    /// format of a file judging by its extension
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        Self::from_path(Path::new(file_name))
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()
+            .map(|extension| Self::from_name(&extension.to_string_lossy()))
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_lowercase().as_str() {
+            "srt" => Self::Srt,
+            "sub" => Self::Sub,
+            "ass" => Self::Ass,
+            "ssa" => Self::Ssa,
+            "vtt" | "webvtt" => Self::Vtt,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Srt => "srt",
+            Self::Sub => "sub",
+            Self::Ass => "ass",
+            Self::Ssa => "ssa",
+            Self::Vtt => "vtt",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+impl std::str::FromStr for SubtitleFormat {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_name(s))
+    }
+}
+
+impl std::fmt::Display for SubtitleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// formats in the order they are preferred, `--format-preference srt,ass,sub`
+#[derive(Debug, Clone, Default)]
+pub struct FormatPreference(pub Vec<SubtitleFormat>);
+
+impl FormatPreference {
+    /// position in the preference list, formats not listed rank last
+    pub fn rank(&self, format: &SubtitleFormat) -> usize {
+        self.0
+            .iter()
+            .position(|preferred| preferred == format)
+            .unwrap_or(self.0.len())
+    }
+
+    pub fn contains(&self, format: &SubtitleFormat) -> bool {
+        self.0.contains(format)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
@@ -0,0 +1,159 @@
+//! converting between text subtitle formats, everything goes through SubRip cues
+use super::SubtitleFormat;
+use crate::srt::{self, Srt};
+use eyre::Result;
+
+pub mod ass;
+pub mod microdvd;
+pub mod vtt;
+
+/// `--convert-to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ConvertTo {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl ConvertTo {
+    pub fn format(self) -> SubtitleFormat {
+        match self {
+            Self::Srt => SubtitleFormat::Srt,
+            Self::Vtt => SubtitleFormat::Vtt,
+            Self::Ass => SubtitleFormat::Ass,
+        }
+    }
+}
+
+/// what a file in `format` is converted to. MicroDVD always is since few players read it,
+/// WebVTT goes through SubRip cues and back so it gets cleaned like SubRip
+pub fn target(format: &SubtitleFormat, convert_to: Option<ConvertTo>) -> Option<ConvertTo> {
+    match (format, convert_to) {
+        (SubtitleFormat::Srt, Some(ConvertTo::Srt)) => None,
+        // the styling would be lost for nothing
+        (SubtitleFormat::Ass, Some(ConvertTo::Ass)) => None,
+        (SubtitleFormat::Sub, None) => Some(ConvertTo::Srt),
+        (SubtitleFormat::Vtt, None) => Some(ConvertTo::Vtt),
+        (_, to) => to,
+    }
+}
+
+/// cues of a file in a format we can read, `None` for the rest (and VobSub `.sub` files).
+/// `fps` and `movie_fps` are only needed for MicroDVD, see `microdvd::to_srt`
+pub fn parse(
+    format: &SubtitleFormat,
+    text: &str,
+    fps: Option<f64>,
+    movie_fps: Option<f64>,
+) -> Option<Result<Srt>> {
+    match format {
+        SubtitleFormat::Srt => Some(Ok(srt::parse_lenient(text).srt)),
+        SubtitleFormat::Sub if microdvd::is_microdvd(text) => {
+            Some(microdvd::to_srt(text, fps, movie_fps))
+        }
+        SubtitleFormat::Ass | SubtitleFormat::Ssa => Some(ass::to_srt(text)),
+        SubtitleFormat::Vtt => Some(vtt::to_srt(text)),
+        SubtitleFormat::Sub | SubtitleFormat::Other(_) => None,
+    }
+}
+
+pub fn render(srt: &Srt, to: ConvertTo) -> String {
+    match to {
+        ConvertTo::Srt => srt.to_string(),
+        ConvertTo::Vtt => vtt::render(srt),
+        ConvertTo::Ass => ass::render(srt),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::srt::Cue;
+
+    const STYLED: &str = include_str!("../../tests/fixtures/styled.ass");
+
+    fn cue(start: &str, end: &str, lines: &[&str]) -> Cue {
+        Cue {
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+            lines: lines.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn parsed(format: SubtitleFormat, text: &str) -> Srt {
+        parse(&format, text, None, None).unwrap().unwrap()
+    }
+
+    #[test]
+    fn styled_ass_keeps_its_text_and_timing() {
+        assert_eq!(
+            parsed(SubtitleFormat::Ass, STYLED).cues,
+            [
+                cue(
+                    "0:00:01.00",
+                    "0:00:03.25",
+                    &["Hello there,", "little <i>bird</i>."]
+                ),
+                cue(
+                    "0:00:03.50",
+                    "0:00:05.00",
+                    &["- Where are you going?", "- Away, far away."]
+                ),
+                cue("0:00:05.50", "0:00:07.00", &["Later that day..."]),
+                cue("0:01:02.34", "0:01:04.00", &["<b>Run, <u>now</u>!</b>"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ass_to_srt_to_ass_round_trips() {
+        let srt = parsed(SubtitleFormat::Ass, STYLED);
+        let written = render(&srt, ConvertTo::Srt);
+        let srt_again = parsed(SubtitleFormat::Srt, &written);
+        assert_eq!(srt_again, srt);
+        let ass = render(&srt_again, ConvertTo::Ass);
+        let hello = "Dialogue: 0,0:00:01.00,0:00:03.25,Default,,0,0,0,,\
+                     Hello there,\\Nlittle {\\i1}bird{\\i0}.\n";
+        assert!(ass.contains(hello), "{ass}");
+        let run = "Dialogue: 0,0:01:02.34,0:01:04.00,Default,,0,0,0,,\
+                   {\\b1}Run, {\\u1}now{\\u0}!{\\b0}\n";
+        assert!(ass.contains(run), "{ass}");
+        assert_eq!(parsed(SubtitleFormat::Ass, &ass), srt);
+        // twice through changes nothing more
+        assert_eq!(
+            render(&parsed(SubtitleFormat::Ass, &ass), ConvertTo::Ass),
+            ass
+        );
+    }
+
+    #[test]
+    fn srt_to_ass_drops_what_ass_has_no_toggle_for() {
+        let srt = Srt {
+            cues: vec![cue(
+                "00:00:01,005",
+                "01:00:00,999",
+                &["<font color=\"red\">Red</font> and <s>struck</s>"],
+            )],
+        };
+        let ass = render(&srt, ConvertTo::Ass);
+        assert!(
+            ass.ends_with(
+                "Dialogue: 0,0:00:01.00,1:00:00.99,Default,,0,0,0,,Red and {\\s1}struck{\\s0}\n"
+            ),
+            "{ass}"
+        );
+    }
+
+    #[test]
+    fn ass_stays_as_it_is() {
+        assert_eq!(target(&SubtitleFormat::Ass, Some(ConvertTo::Ass)), None);
+        assert_eq!(
+            target(&SubtitleFormat::Ssa, Some(ConvertTo::Ass)),
+            Some(ConvertTo::Ass)
+        );
+        assert_eq!(
+            target(&SubtitleFormat::Ass, Some(ConvertTo::Srt)),
+            Some(ConvertTo::Srt)
+        );
+    }
+}
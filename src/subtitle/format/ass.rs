@@ -0,0 +1,206 @@
+//! Advanced SubStation Alpha (and the older SSA), only the dialogue survives the conversion
+use crate::{
+    markup::{self, Token},
+    srt::{Cue, Srt, Timestamp},
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use itertools::Itertools;
+
+/// fields of the `Format:` line of the events section, lowercase
+fn event_format(line: &str) -> Vec<String> {
+    line.split(',')
+        .map(|field| field.trim().to_lowercase())
+        .collect()
+}
+
+/// `\i1`, `\b0`... toggles mapped to SubRip tags, everything else in the braces is dropped
+/// along with the text drawn by `\p1` vector drawings
+fn dialogue_text(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut open: Vec<char> = vec![];
+    let mut drawing = false;
+    let mut rest = text;
+    loop {
+        let (visible, after) = match rest.find('{') {
+            Some(start) => (&rest[..start], Some(&rest[start + 1..])),
+            None => (rest, None),
+        };
+        if !drawing {
+            output.push_str(visible);
+        }
+        let Some((block, after)) = after.and_then(|after| after.split_once('}')) else {
+            break;
+        };
+        for tag in block.split('\\').map(str::trim) {
+            if let Some(level) = tag.strip_prefix('p').filter(|v| !v.is_empty()) {
+                drawing = level.parse::<u32>().is_ok_and(|level| level > 0);
+                continue;
+            }
+            let mut chars = tag.chars();
+            let (Some(style @ ('i' | 'b' | 'u' | 's')), Some(state), None) =
+                (chars.next(), chars.next(), chars.next())
+            else {
+                continue;
+            };
+            match (state, open.contains(&style)) {
+                ('1', false) => {
+                    output.push_str(&format!("<{style}>"));
+                    open.push(style);
+                }
+                ('0', true) => {
+                    output.push_str(&format!("</{style}>"));
+                    open.retain(|v| *v != style);
+                }
+                _ => {}
+            }
+        }
+        rest = after;
+    }
+    for style in open.iter().rev() {
+        output.push_str(&format!("</{style}>"));
+    }
+    output
+        .replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+}
+
+/// the SubRip tags with an ASS toggle, `\i1` and `\i0` for `<i>` and `</i>`
+const STYLES: &[&str] = &["i", "b", "u", "s"];
+
+/// `0:01:02.34`, hundredths of a second
+fn timestamp(text: &str) -> Result<Timestamp> {
+    text.trim()
+        .parse()
+        .wrap_err_with(|| format!("invalid ASS time [{text}]"))
+}
+
+/// `Dialogue:` events of the `[Events]` section in start order, lines shown together (the
+/// same text on several layers, speakers of a two line exchange) become one cue
+pub fn to_srt(text: &str) -> Result<Srt> {
+    let mut in_events = false;
+    let mut format = vec![];
+    let mut events = vec![];
+    for line in text.lines() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        let Some((kind, value)) = line.split_once(':').filter(|_| in_events) else {
+            continue;
+        };
+        match kind.trim().to_lowercase().as_str() {
+            "format" => format = event_format(value),
+            "dialogue" => {
+                if format.is_empty() {
+                    bail!("dialogue before the events format line");
+                }
+                let fields = value.splitn(format.len(), ',').collect::<Vec<_>>();
+                let field = |name: &str| {
+                    format
+                        .iter()
+                        .position(|field| field == name)
+                        .and_then(|idx| fields.get(idx))
+                        .ok_or_else(|| eyre!("no {name} in [{line}]"))
+                };
+                let lines = dialogue_text(field("text")?)
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                if !lines.is_empty() {
+                    events.push(Cue {
+                        start: timestamp(field("start")?)?,
+                        end: timestamp(field("end")?)?,
+                        lines,
+                    });
+                }
+            }
+            // comments, pictures, sounds and the rest aren't dialogue
+            _ => {}
+        }
+    }
+    if format.is_empty() {
+        bail!("no [Events] section");
+    }
+    events.sort_by_key(|cue| cue.start);
+    let mut cues: Vec<Cue> = vec![];
+    for event in events {
+        match cues.last_mut() {
+            Some(last) if last.start == event.start && last.end == event.end => {
+                for line in event.lines {
+                    if !last.lines.contains(&line) {
+                        last.lines.push(line);
+                    }
+                }
+            }
+            _ => cues.push(event),
+        }
+    }
+    Ok(Srt { cues })
+}
+
+/// a plain default style for `render`, players style the rest themselves
+const HEADER: &str = "[Script Info]
+ScriptType: v4.00+
+
+[V4+ Styles]
+Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, \
+Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, \
+Alignment, MarginL, MarginR, MarginV, Encoding
+Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,2,2,\
+10,10,10,1
+
+[Events]
+Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+";
+
+/// `0:01:02.34`, the milliseconds past the hundredth are dropped
+fn render_timestamp(timestamp: Timestamp) -> String {
+    let centis = timestamp.0.max(0) / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        centis / 6000 % 60,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+/// SubRip tags ASS has a toggle for become it, the other tags are dropped
+fn event_text(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            markup::tokens(line)
+                .into_iter()
+                .map(|token| match token {
+                    Token::Text(text) => text.to_string(),
+                    Token::Open(name, _) if STYLES.contains(&name.as_str()) => {
+                        format!("{{\\{name}1}}")
+                    }
+                    Token::Close(name, _) if STYLES.contains(&name.as_str()) => {
+                        format!("{{\\{name}0}}")
+                    }
+                    Token::Open(..) | Token::Close(..) => String::new(),
+                })
+                .collect::<String>()
+        })
+        .join("\\N")
+}
+
+/// every cue a `Dialogue:` event in the default style
+pub fn render(srt: &Srt) -> String {
+    let mut output = String::from(HEADER);
+    for cue in &srt.cues {
+        output.push_str(&format!(
+            "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+            render_timestamp(cue.start),
+            render_timestamp(cue.end),
+            event_text(&cue.lines)
+        ));
+    }
+    output
+}
@@ -0,0 +1,184 @@
+//! MicroDVD, `{start frame}{end frame}text` with `|` between lines, converted to SubRip
+use crate::srt::{Cue, Srt, Timestamp};
+use eyre::{bail, eyre, Result};
+
+/// how long a cue without an end frame is shown for, unless the next one comes sooner
+const OPEN_ENDED_DURATION: i64 = 2000;
+
+/// the first line is a `{frame}{frame}` or a `{DEFAULT}` one
+pub fn is_microdvd(text: &str) -> bool {
+    text.trim_start_matches('\u{feff}')
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .and_then(|line| frames(line.trim()))
+        .is_some_and(|(start, _, _)| {
+            start == "DEFAULT" || start.chars().all(|c| c.is_ascii_digit())
+        })
+}
+
+/// `{12}{34}rest` -> `("12", "34", "rest")`
+fn frames(line: &str) -> Option<(&str, &str, &str)> {
+    let (start, rest) = line.strip_prefix('{')?.split_once('}')?;
+    let (end, rest) = rest.strip_prefix('{')?.split_once('}')?;
+    (!start.is_empty()).then_some((start, end, rest))
+}
+
+/// `{y:i}` and `{c:$BBGGRR}`, the lowercase ones apply to a line and the uppercase ones to the
+/// whole cue
+#[derive(Debug, Clone, Default)]
+struct Style {
+    tags: Vec<char>,
+    color: Option<String>,
+}
+
+impl Style {
+    /// unknown codes (fonts, sizes, positions) have no SubRip counterpart and are dropped
+    fn apply(&mut self, key: char, value: &str) {
+        match key.to_ascii_lowercase() {
+            'y' => self.tags.extend(
+                value
+                    .split(',')
+                    .filter_map(|style| style.trim().chars().next())
+                    .map(|style| style.to_ascii_lowercase())
+                    .filter(|style| "ibus".contains(*style)),
+            ),
+            'c' => {
+                let bgr = value.trim().trim_start_matches('$');
+                if bgr.len() == 6 && bgr.chars().all(|c| c.is_ascii_hexdigit()) {
+                    self.color = Some(format!("#{}{}{}", &bgr[4..], &bgr[2..4], &bgr[..2]));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn merged(&self, other: &Style) -> Style {
+        Style {
+            tags: self.tags.iter().chain(&other.tags).copied().collect(),
+            color: other.color.clone().or_else(|| self.color.clone()),
+        }
+    }
+
+    fn wrap(&self, text: &str) -> String {
+        let text = self.tags.iter().rev().fold(text.to_string(), |text, tag| {
+            format!("<{tag}>{text}</{tag}>")
+        });
+        match &self.color {
+            Some(color) => format!("<font color=\"{color}\">{text}</font>"),
+            None => text,
+        }
+    }
+}
+
+/// takes the control codes out of a line, into `cue` or `line` depending on their case
+fn control_codes(line: &str, cue: &mut Style, style: &mut Style) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('{') {
+        let code = rest[start + 1..]
+            .split_once('}')
+            .and_then(|(code, after)| code.split_once(':').map(|code| (code, after)));
+        let Some(((key, value), after)) = code.filter(|((key, _), _)| key.chars().count() == 1)
+        else {
+            text.push_str(&rest[..=start]);
+            rest = &rest[start + 1..];
+            continue;
+        };
+        text.push_str(&rest[..start]);
+        let key = key.chars().next().expect("one character");
+        match key.is_uppercase() {
+            true => cue.apply(key, value),
+            false => style.apply(key, value),
+        }
+        rest = after;
+    }
+    text.push_str(rest);
+    text
+}
+
+/// `fps` (`--fps`) wins over the `{1}{1}23.976` header some files start with, the movie's frame
+/// rate is the last resort
+pub fn to_srt(text: &str, fps: Option<f64>, movie_fps: Option<f64>) -> Result<Srt> {
+    let mut default = Style::default();
+    let mut header_fps = None;
+    let mut cues: Vec<(i64, Option<i64>, Vec<String>)> = vec![];
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() {
+            continue;
+        }
+        let (start, end, rest) =
+            frames(line).ok_or_else(|| eyre!("line {} isn't MicroDVD: [{line}]", idx + 1))?;
+        if start == "DEFAULT" {
+            control_codes(rest, &mut Style::default(), &mut default);
+            continue;
+        }
+        let start = start
+            .parse::<i64>()
+            .map_err(|_| eyre!("invalid start frame on line {}: [{line}]", idx + 1))?;
+        let end = match end.trim() {
+            "" => None,
+            end => Some(
+                end.parse::<i64>()
+                    .map_err(|_| eyre!("invalid end frame on line {}: [{line}]", idx + 1))?,
+            ),
+        };
+        if cues.is_empty() && header_fps.is_none() && start <= 1 && end.is_some_and(|end| end <= 1)
+        {
+            if let Ok(rate) = rest.trim().replace(',', ".").parse::<f64>() {
+                header_fps = Some(rate).filter(|rate| *rate > 0.0);
+                continue;
+            }
+        }
+        let mut cue_style = default.clone();
+        let lines = rest
+            .split('|')
+            .map(|line| {
+                let mut style = Style::default();
+                let text = control_codes(line, &mut cue_style, &mut style);
+                let text = match text.trim().strip_prefix('/') {
+                    Some(italic) => {
+                        style.tags.push('i');
+                        italic.trim().to_string()
+                    }
+                    None => text.trim().to_string(),
+                };
+                (text, style)
+            })
+            .collect::<Vec<_>>();
+        let lines = lines
+            .into_iter()
+            .filter(|(text, _)| !text.is_empty())
+            .map(|(text, style)| cue_style.merged(&style).wrap(&text))
+            .collect();
+        cues.push((start, end, lines));
+    }
+    let Some(fps) = fps.or(header_fps).or(movie_fps) else {
+        bail!("the frame rate of the MicroDVD subtitles is unknown, pass --fps");
+    };
+    let millis = |frame: i64| (frame as f64 * 1000.0 / fps).round() as i64;
+    let starts = cues
+        .iter()
+        .skip(1)
+        .map(|(start, _, _)| Some(millis(*start)))
+        .chain([None])
+        .collect::<Vec<_>>();
+    Ok(Srt {
+        cues: cues
+            .into_iter()
+            .zip(starts)
+            .map(|((start, end, lines), next_start)| {
+                let start = millis(start);
+                let end = end.map(millis).unwrap_or_else(|| {
+                    let end = start + OPEN_ENDED_DURATION;
+                    next_start.map_or(end, |next| end.min(next))
+                });
+                Cue {
+                    start: Timestamp(start),
+                    end: Timestamp(end),
+                    lines,
+                }
+            })
+            .collect(),
+    })
+}
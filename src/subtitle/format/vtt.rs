@@ -0,0 +1,151 @@
+//! WebVTT, what browsers and Plex web play
+use crate::srt::{Cue, Srt, Timestamp};
+use eyre::{bail, eyre, Result, WrapErr};
+
+/// tags SubRip and WebVTT share
+const SHARED_TAGS: &[&str] = &["i", "b", "u"];
+
+/// `01:02:03.456` or `02:03.456`, the hours are optional
+fn timestamp(text: &str) -> Result<Timestamp> {
+    let text = text.trim();
+    match text.matches(':').count() {
+        1 => format!("00:{text}").parse(),
+        _ => text.parse(),
+    }
+    .wrap_err_with(|| format!("invalid WebVTT timestamp [{text}]"))
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&lrm;", "\u{200e}")
+        .replace("&rlm;", "\u{200f}")
+        .replace("&amp;", "&")
+}
+
+/// keeps the shared tags, drops voice, class, ruby and timestamp ones
+fn srt_text(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(start) = rest.find('<') {
+        output.push_str(&rest[..start]);
+        let Some(length) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + length];
+        let name = tag
+            .trim_start_matches('/')
+            .split(['.', ' '])
+            .next()
+            .unwrap_or_default();
+        if SHARED_TAGS.contains(&name) {
+            let slash = match tag.starts_with('/') {
+                true => "/",
+                false => "",
+            };
+            output.push_str(&format!("<{slash}{name}>"));
+        }
+        rest = &rest[start + length + 1..];
+    }
+    output.push_str(rest);
+    unescape(&output)
+}
+
+/// cue blocks after the `WEBVTT` header, `NOTE`, `STYLE` and `REGION` blocks are skipped
+pub fn to_srt(text: &str) -> Result<Srt> {
+    let text = text.trim_start_matches('\u{feff}');
+    if !text.starts_with("WEBVTT") {
+        bail!("no WEBVTT header");
+    }
+    let lines = text
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .collect::<Vec<_>>();
+    let mut cues = vec![];
+    for block in lines.split(|line| line.trim().is_empty()).skip(1) {
+        let Some(first) = block.first() else {
+            continue;
+        };
+        // the keyword alone or followed by a space or tab, `NOTEWORTHY` is a cue identifier
+        let is_keyword = |keyword: &str| {
+            first
+                .strip_prefix(keyword)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+        };
+        if ["NOTE", "STYLE", "REGION"].into_iter().any(is_keyword) {
+            continue;
+        }
+        // the identifier line is optional
+        let timing = block
+            .iter()
+            .take(2)
+            .position(|line| line.contains("-->"))
+            .ok_or_else(|| eyre!("no timing line in [{}]", block.join("\n")))?;
+        let (start, end) = block[timing]
+            .split_once("-->")
+            .expect("checked for the arrow");
+        cues.push(Cue {
+            start: timestamp(start)?,
+            // cue settings follow the end timestamp
+            end: timestamp(end.split_whitespace().next().unwrap_or_default())?,
+            lines: block[timing + 1..]
+                .iter()
+                .map(|line| srt_text(line))
+                .collect(),
+        });
+    }
+    Ok(Srt { cues })
+}
+
+/// `<` and `&` escaped unless they start a shared tag, `<font>` has no WebVTT counterpart
+fn escape(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(idx) = rest.find(['<', '&']) {
+        output.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        if rest.starts_with('&') {
+            output.push_str("&amp;");
+            rest = &rest[1..];
+            continue;
+        }
+        let tag = rest[1..].split_once('>').map(|(tag, _)| tag);
+        let name = tag.map(|tag| tag.trim_start_matches('/').trim().to_lowercase());
+        match name.as_deref() {
+            Some(name) if SHARED_TAGS.contains(&name) => {
+                let tag = tag.expect("has a name");
+                output.push_str(&format!("<{}>", tag.trim().to_lowercase()));
+                rest = &rest[tag.len() + 2..];
+            }
+            Some(name) if name == "font" || name.starts_with("font ") => {
+                rest = &rest[tag.expect("has a name").len() + 2..];
+            }
+            _ => {
+                output.push_str("&lt;");
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    // an arrow in the text would read as a timing line
+    output.replace("-->", "--&gt;")
+}
+
+/// no cue identifiers, players don't need them
+pub fn render(srt: &Srt) -> String {
+    let mut output = String::from("WEBVTT\n");
+    for cue in &srt.cues {
+        output.push('\n');
+        output.push_str(&format!(
+            "{} --> {}\n",
+            cue.start.to_string().replace(',', "."),
+            cue.end.to_string().replace(',', ".")
+        ));
+        for line in &cue.lines {
+            output.push_str(&escape(line));
+            output.push('\n');
+        }
+    }
+    output
+}
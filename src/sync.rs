@@ -0,0 +1,144 @@
+//! `--sync`, lines the subtitles up with the movie's audio using alass or ffsubsync
+use crate::tools::on_path;
+use eyre::{bail, eyre, Result, WrapErr};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::process::Command;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SyncTool {
+    /// alass, or ffsubsync when alass isn't installed
+    Auto,
+    Alass,
+    Ffsubsync,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronizer {
+    Alass(&'static str),
+    Ffsubsync,
+}
+
+impl SyncTool {
+    /// checked before downloading anything, so a missing tool doesn't waste the run
+    pub fn synchronizer(self) -> Result<Synchronizer> {
+        // the binary is `alass-cli` in the release archives and `alass` in most packages
+        let alass = || {
+            ["alass", "alass-cli"]
+                .into_iter()
+                .find(|program| on_path(program))
+                .map(Synchronizer::Alass)
+        };
+        let ffsubsync = || on_path("ffsubsync").then_some(Synchronizer::Ffsubsync);
+        match self {
+            Self::Auto => alass()
+                .or_else(ffsubsync)
+                .ok_or_else(|| eyre!("--sync needs alass or ffsubsync, neither was found on PATH")),
+            Self::Alass => alass().ok_or_else(|| eyre!("alass was not found on PATH")),
+            Self::Ffsubsync => ffsubsync().ok_or_else(|| eyre!("ffsubsync was not found on PATH")),
+        }
+    }
+}
+
+/// what a synchronizer did to a subtitle file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Synced {
+    /// `--keep-unsynced`, where the subtitles from before are
+    pub unsynced: Option<PathBuf>,
+    /// seconds the subtitles were moved by, when the tool says
+    pub offset: Option<f64>,
+}
+
+/// `offset seconds: 1.234` as ffsubsync logs it
+fn reported_offset(output: &str) -> Option<f64> {
+    output.lines().find_map(|line| {
+        let line = line.to_lowercase();
+        let (_, offset) = line.split_once("offset seconds:")?;
+        offset.trim().parse().ok()
+    })
+}
+
+impl Synchronizer {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Alass(program) => program,
+            Self::Ffsubsync => "ffsubsync",
+        }
+    }
+
+    /// replaces the subtitle file with the synchronized one, the original becomes
+    /// `<name>.orig.srt` when `keep_unsynced`
+    pub async fn sync(
+        self,
+        movie_file: &Path,
+        subtitle_file: &Path,
+        timeout: Duration,
+        keep_unsynced: bool,
+    ) -> Result<Synced> {
+        let extension = subtitle_file
+            .extension()
+            .and_then(|v| v.to_str())
+            .unwrap_or("srt");
+        let synced_file = subtitle_file.with_extension(format!("synced.{extension}"));
+        let mut command = match self {
+            Self::Alass(program) => {
+                let mut command = Command::new(program);
+                command.arg(movie_file).arg(subtitle_file).arg(&synced_file);
+                command
+            }
+            Self::Ffsubsync => {
+                let mut command = Command::new("ffsubsync");
+                command
+                    .arg(movie_file)
+                    .arg("-i")
+                    .arg(subtitle_file)
+                    .arg("-o")
+                    .arg(&synced_file);
+                command
+            }
+        };
+        info!(
+            tool = self.name(),
+            ?subtitle_file,
+            "synchronizing subtitles"
+        );
+        let output = tokio::time::timeout(timeout, command.kill_on_drop(true).output())
+            .await
+            .map_err(|_| eyre!("{} took longer than {timeout:?}", self.name()))?
+            .wrap_err_with(|| format!("running {}", self.name()));
+        let output = match output {
+            Ok(output) if output.status.success() && synced_file.is_file() => output,
+            result => {
+                tokio::fs::remove_file(&synced_file).await.ok();
+                let output = result?;
+                bail!(
+                    "{} failed ({}): {}",
+                    self.name(),
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+        };
+        let unsynced = match keep_unsynced {
+            true => {
+                let unsynced = subtitle_file.with_extension(format!("orig.{extension}"));
+                tokio::fs::copy(subtitle_file, &unsynced)
+                    .await
+                    .wrap_err_with(|| format!("keeping the unsynced subtitles as {unsynced:?}"))?;
+                Some(unsynced)
+            }
+            false => None,
+        };
+        tokio::fs::rename(&synced_file, subtitle_file)
+            .await
+            .wrap_err_with(|| format!("replacing {subtitle_file:?}"))?;
+        let offset = [&output.stdout, &output.stderr]
+            .into_iter()
+            .find_map(|output| reported_offset(&String::from_utf8_lossy(output)));
+        info!(tool = self.name(), offset, "synchronized subtitles");
+        Ok(Synced { unsynced, offset })
+    }
+}
@@ -0,0 +1,136 @@
+//! `--timings`, how long each stage of a run took
+use itertools::Itertools;
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// a stage that ran, once for every time it did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub stage: &'static str,
+    /// what the stage worked on, the file written or the url fetched
+    pub subject: Option<String>,
+    pub duration: Duration,
+}
+
+/// every stage added up, in the order they first ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Total {
+    pub stage: &'static str,
+    pub count: usize,
+    pub duration: Duration,
+}
+
+/// collects the stages a run goes through, any stage timed shows up in the report
+#[derive(Debug, Default)]
+pub struct Timings {
+    timings: Mutex<Vec<Timing>>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, stage: &'static str, subject: Option<String>, started: Instant) {
+        self.timings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(Timing {
+                stage,
+                subject,
+                duration: started.elapsed(),
+            });
+    }
+
+    /// runs `future` as `stage`, failed attempts count too
+    pub async fn time<F: Future>(&self, stage: &'static str, future: F) -> F::Output {
+        self.time_of(stage, None, future).await
+    }
+
+    pub async fn time_of<F: Future>(
+        &self,
+        stage: &'static str,
+        subject: Option<String>,
+        future: F,
+    ) -> F::Output {
+        let started = Instant::now();
+        let output = future.await;
+        self.push(stage, subject, started);
+        output
+    }
+
+    /// the blocking counterpart of [`Timings::time`]
+    pub fn time_blocking<T>(&self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let output = f();
+        self.push(stage, None, started);
+        output
+    }
+
+    /// oldest first
+    pub fn timings(&self) -> Vec<Timing> {
+        self.timings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn totals(&self) -> Vec<Total> {
+        let mut totals: Vec<Total> = vec![];
+        for timing in self.timings() {
+            match totals.iter_mut().find(|total| total.stage == timing.stage) {
+                Some(total) => {
+                    total.count += 1;
+                    total.duration += timing.duration;
+                }
+                None => totals.push(Total {
+                    stage: timing.stage,
+                    count: 1,
+                    duration: timing.duration,
+                }),
+            }
+        }
+        totals
+    }
+
+    /// a line per stage that ran, then the totals
+    pub fn report(&self) -> String {
+        let millis = |duration: Duration| format!("{:.1} ms", duration.as_secs_f64() * 1000.0);
+        let timings = self.timings();
+        let width = timings
+            .iter()
+            .map(|timing| timing.stage.len())
+            .chain(["all stages".len()])
+            .max()
+            .unwrap_or_default();
+        let stages = timings.iter().map(|timing| {
+            let line = format!(
+                "  {:<width$}  {:>10}",
+                timing.stage,
+                millis(timing.duration)
+            );
+            match &timing.subject {
+                Some(subject) => format!("{line}  {subject}"),
+                None => line,
+            }
+        });
+        let totals = self.totals().into_iter().map(|total| {
+            format!(
+                "  {:<width$}  {:>10}  {}x",
+                total.stage,
+                millis(total.duration),
+                total.count
+            )
+        });
+        let total = timings.iter().map(|timing| timing.duration).sum();
+        std::iter::once("timings:".to_string())
+            .chain(stages)
+            .chain(["totals:".to_string()])
+            .chain(totals)
+            .chain([format!("  {:<width$}  {:>10}", "all stages", millis(total))])
+            .join("\n")
+    }
+}
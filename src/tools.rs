@@ -0,0 +1,144 @@
+//! external programs the subtitles are handed to
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+use tokio::process::Command;
+use tracing::debug;
+
+/// `--ffmpeg-path` / `--ffprobe-path`, set once at startup
+static PATHS: OnceLock<Vec<(&'static str, PathBuf)>> = OnceLock::new();
+
+/// where `program` is run from instead of `PATH`. surrounding quotes, as pasted from a
+/// windows shell, are dropped
+pub fn configure(paths: impl IntoIterator<Item = (&'static str, PathBuf)>) {
+    let paths = paths
+        .into_iter()
+        .map(|(program, path)| {
+            let unquoted = path.to_string_lossy().trim().trim_matches('"').to_string();
+            (program, PathBuf::from(unquoted))
+        })
+        .collect();
+    PATHS.set(paths).ok();
+}
+
+fn configured(program: &str) -> Option<&'static Path> {
+    PATHS
+        .get()?
+        .iter()
+        .find(|(name, _)| *name == program)
+        .map(|(_, path)| path.as_path())
+}
+
+/// `path` itself or with `.exe`, windows users tend to leave the suffix out
+fn executable(path: &Path) -> Option<PathBuf> {
+    [path.to_path_buf(), path.with_extension("exe")]
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+}
+
+/// `program` in one of the `PATH` directories
+pub fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| {
+        std::env::split_paths(&path).any(|dir| executable(&dir.join(program)).is_some())
+    })
+}
+
+/// `program` at its configured path or on `PATH`
+pub fn available(program: &str) -> bool {
+    match configured(program) {
+        Some(path) => executable(path).is_some(),
+        None => on_path(program),
+    }
+}
+
+/// runs `program` from its configured path, arguments with spaces need no quoting as no
+/// shell is involved
+pub fn command(program: &str) -> Command {
+    match configured(program).and_then(executable) {
+        Some(path) => Command::new(path),
+        None => Command::new(program),
+    }
+}
+
+/// how to get `program`, for errors about it missing
+pub fn install_hint(program: &str) -> String {
+    let (site, flag) = match program {
+        "mkvmerge" => ("https://mkvtoolnix.download", None),
+        "ffprobe" => ("https://ffmpeg.org/download.html", Some("--ffprobe-path")),
+        _ => ("https://ffmpeg.org/download.html", Some("--ffmpeg-path")),
+    };
+    let found = match configured(program) {
+        Some(path) => format!("{program} was not found at {path:?}"),
+        None => format!("{program} was not found on PATH"),
+    };
+    match flag {
+        Some(flag) => format!("{found}, install it from {site} or point {flag} at it"),
+        None => format!("{found}, install it from {site}"),
+    }
+}
+
+/// logs the first line of `program -version` (`--version` for mkvmerge), so bug reports say
+/// which build was used
+pub async fn log_version(program: &str) {
+    let flag = match program {
+        "mkvmerge" => "--version",
+        _ => "-version",
+    };
+    let version = command(program)
+        .arg(flag)
+        .output()
+        .await
+        .ok()
+        .and_then(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .next()
+                .map(str::to_string)
+        });
+    debug!(program, ?version, "found");
+}
+
+/// bytes available to unprivileged users on the filesystem of `dir`, from `df`. `None` when
+/// it can't tell, e.g. on windows
+pub async fn free_space(dir: &Path) -> Option<u64> {
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())?;
+    // `Filesystem 1024-blocks Used Available Capacity Mounted on`
+    let available = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .nth(1)?
+        .split_whitespace()
+        .nth(3)?
+        .parse::<u64>()
+        .ok()?;
+    Some(available * 1024)
+}
+
+/// the program and arguments `command` runs, as they'd be passed to it
+pub fn arguments(command: &Command) -> Vec<String> {
+    let command = command.as_std();
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|argument| argument.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// `argument` quoted for the shell of the platform, single quotes for POSIX shells and
+/// double quotes for windows
+pub fn shell_quote(argument: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c);
+    match (
+        argument.chars().all(plain) && !argument.is_empty(),
+        cfg!(windows),
+    ) {
+        (true, _) => argument.to_string(),
+        (false, true) => format!("\"{}\"", argument.replace('"', "\\\"")),
+        (false, false) => format!("'{}'", argument.replace('\'', r"'\''")),
+    }
+}
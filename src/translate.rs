@@ -0,0 +1,386 @@
+//! `--translate-from`, subtitles machine translated from another language when the one asked
+//! for has none. cue texts go to DeepL or a LibreTranslate server in batches as html, so the
+//! tags come back where they were, and the timing is never sent at all
+use crate::{
+    http::{HttpFetch, RateLimited, Request},
+    language::LanguageCode,
+    markup::{self, Token},
+    srt::Srt,
+};
+use eyre::{eyre, Result, WrapErr};
+use reqwest::Url;
+use serde_json::json;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// the files written from a translation are `movie.machine.srt`
+pub const INFIX: &str = "machine";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Backend {
+    #[default]
+    #[value(name = "deepl")]
+    DeepL,
+    #[value(name = "libretranslate")]
+    LibreTranslate,
+}
+
+impl Backend {
+    /// DeepL's free keys end in `:fx` and only work with the free api
+    pub fn default_url(self, api_key: Option<&str>) -> &'static str {
+        match self {
+            Self::DeepL => match api_key.is_some_and(|key| key.ends_with(":fx")) {
+                true => "https://api-free.deepl.com/v2/translate",
+                false => "https://api.deepl.com/v2/translate",
+            },
+            Self::LibreTranslate => "https://libretranslate.com/translate",
+        }
+    }
+
+    /// the code the service knows `language` by, DeepL wants a variant of english and
+    /// portuguese when translating into them
+    pub fn code(self, language: &str, target: bool) -> Option<String> {
+        let language = LanguageCode::new(language);
+        let code = language.iso639_1()?;
+        Some(match self {
+            Self::LibreTranslate => code.to_string(),
+            Self::DeepL => match (code, target) {
+                ("en", true) => "EN-US".to_string(),
+                ("pt", true) if matches!(language.as_str(), "pob" | "pb") => "PT-BR".to_string(),
+                ("pt", true) => "PT-PT".to_string(),
+                (code, _) => code.to_uppercase(),
+            },
+        })
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::DeepL => "DeepL",
+            Self::LibreTranslate => "LibreTranslate",
+        })
+    }
+}
+
+/// a translation the service refused, not worth retrying
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranslateError {
+    /// `401` or `403`, a wrong or missing api key
+    Refused(u16),
+    /// DeepL's `456`, the characters of the month are used up
+    QuotaExceeded,
+    /// a language the service can't be asked for by code
+    UnknownLanguage(String),
+}
+
+impl TranslateError {
+    pub fn find(report: &eyre::Report) -> Option<&Self> {
+        report.chain().find_map(|e| e.downcast_ref::<Self>())
+    }
+}
+
+impl std::fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Refused(status) => write!(
+                f,
+                "the translation was refused ({status}), is --translate-api-key right?"
+            ),
+            Self::QuotaExceeded => f.write_str("the translation quota is used up"),
+            Self::UnknownLanguage(language) => {
+                write!(f, "{language} has no code translation services know")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TranslateError {}
+
+/// where and how often cues are sent
+#[derive(Debug, Clone)]
+pub struct Translator {
+    pub backend: Backend,
+    /// the endpoint the cues are posted to
+    pub url: Url,
+    pub api_key: Option<String>,
+    /// cues sent in one request
+    pub batch_size: usize,
+    /// waited between requests
+    pub min_interval: Duration,
+    /// attempts of a failed batch after the first one
+    pub retries: u32,
+    /// waited before the first retry, doubled for every one after it. a `Retry-After` wins
+    pub backoff: Duration,
+}
+
+impl Translator {
+    pub fn new(backend: Backend, api_key: Option<String>) -> Self {
+        let url = backend
+            .default_url(api_key.as_deref())
+            .parse()
+            .expect("valid url");
+        Self {
+            backend,
+            url,
+            api_key,
+            batch_size: 50,
+            min_interval: Duration::from_secs(1),
+            retries: 3,
+            backoff: Duration::from_secs(2),
+        }
+    }
+
+    /// translates the cues of `srt` from `from` into `to`. cues of batches that failed even
+    /// after retrying keep their text, nothing translated at all is an error
+    pub async fn translate(
+        &self,
+        http: &dyn HttpFetch,
+        srt: &mut Srt,
+        from: &str,
+        to: &str,
+    ) -> Result<Translated> {
+        let code = |language: &str, target| {
+            self.backend
+                .code(language, target)
+                .ok_or_else(|| TranslateError::UnknownLanguage(language.to_string()))
+        };
+        let (source, target) = (code(from, false)?, code(to, true)?);
+        let mut translated = Translated {
+            cues: srt.cues.len(),
+            failed: vec![],
+        };
+        let mut last_request = None;
+        let mut last_error = None;
+        let batches = srt.cues.chunks_mut(self.batch_size.max(1)).enumerate();
+        for (batch, cues) in batches {
+            let first = batch * self.batch_size.max(1);
+            let texts = cues
+                .iter()
+                .map(|cue| Html::of(&cue.lines))
+                .collect::<Vec<_>>();
+            let sent = texts.iter().map(|html| html.text.clone()).collect();
+            match self
+                .with_retries(http, sent, &source, &target, &mut last_request)
+                .await
+            {
+                Ok(answers) => {
+                    for (cue, (html, answer)) in cues.iter_mut().zip(texts.iter().zip(answers)) {
+                        cue.lines = html.lines(&answer);
+                    }
+                }
+                Err(report) if TranslateError::find(&report).is_some() => return Err(report),
+                Err(report) => {
+                    warn!(
+                        ?report,
+                        cues = %format!("{}-{}", first + 1, first + cues.len()),
+                        "translating the cues failed, they keep their text"
+                    );
+                    translated.failed.extend(first + 1..=first + cues.len());
+                    last_error = Some(report);
+                }
+            }
+        }
+        match (translated.failed.len() == translated.cues, last_error) {
+            (true, Some(report)) => Err(report.wrap_err("translating the subtitles")),
+            _ => Ok(translated),
+        }
+    }
+
+    async fn with_retries(
+        &self,
+        http: &dyn HttpFetch,
+        texts: Vec<String>,
+        source: &str,
+        target: &str,
+        last_request: &mut Option<Instant>,
+    ) -> Result<Vec<String>> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            if let Some(last_request) = *last_request {
+                tokio::time::sleep_until(last_request + self.min_interval).await;
+            }
+            *last_request = Some(Instant::now());
+            let report = match self.post(http, &texts, source, target).await {
+                Ok(answers) => return Ok(answers),
+                Err(report) if TranslateError::find(&report).is_some() => return Err(report),
+                Err(report) if attempt >= self.retries => return Err(report),
+                Err(report) => report,
+            };
+            let wait = report
+                .downcast_ref::<RateLimited>()
+                .and_then(|limited| limited.retry_after)
+                .unwrap_or(backoff);
+            attempt += 1;
+            debug!(?report, ?wait, attempt, "retrying the translation");
+            tokio::time::sleep(wait).await;
+            backoff *= 2;
+        }
+    }
+
+    async fn post(
+        &self,
+        http: &dyn HttpFetch,
+        texts: &[String],
+        source: &str,
+        target: &str,
+    ) -> Result<Vec<String>> {
+        let body = match self.backend {
+            Backend::DeepL => json!({
+                "text": texts,
+                "source_lang": source,
+                "target_lang": target,
+                "tag_handling": "html",
+            }),
+            Backend::LibreTranslate => json!({
+                "q": texts,
+                "source": source,
+                "target": target,
+                "format": "html",
+                "api_key": self.api_key.as_deref().unwrap_or_default(),
+            }),
+        };
+        let mut request = Request::post(self.url.clone(), body.to_string())
+            .header("Content-Type", "application/json");
+        if let (Backend::DeepL, Some(key)) = (self.backend, &self.api_key) {
+            request = request.header("Authorization", &format!("DeepL-Auth-Key {key}"));
+        }
+        let response = http
+            .get_text(request)
+            .await
+            .wrap_err_with(|| format!("posting to {}", self.backend))?;
+        match response.status {
+            200 => {}
+            429 => {
+                let retry_after = response.retry_after;
+                return Err(RateLimited { retry_after }.into());
+            }
+            401 | 403 => return Err(TranslateError::Refused(response.status).into()),
+            456 => return Err(TranslateError::QuotaExceeded.into()),
+            status => return Err(eyre!("{} answered {status}", self.backend)),
+        }
+        let answer = serde_json::from_str::<serde_json::Value>(&response.body)
+            .wrap_err_with(|| format!("reading the answer of {}", self.backend))?;
+        let answers = match self.backend {
+            Backend::DeepL => answer["translations"]
+                .as_array()
+                .map(|translations| {
+                    translations
+                        .iter()
+                        .filter_map(|translation| translation["text"].as_str())
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default(),
+            Backend::LibreTranslate => answer["translatedText"]
+                .as_array()
+                .map(|texts| {
+                    texts
+                        .iter()
+                        .filter_map(|text| text.as_str())
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default(),
+        };
+        match answers.len() == texts.len() {
+            true => Ok(answers),
+            false => Err(eyre!(
+                "{} answered {} texts for {}",
+                self.backend,
+                answers.len(),
+                texts.len()
+            )),
+        }
+    }
+}
+
+/// how a translation went, failed cues are still in the language translated from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Translated {
+    pub cues: usize,
+    /// numbers of the cues that kept their text, from 1
+    pub failed: Vec<usize>,
+}
+
+/// a cue as the html sent for it, lines joined by `<br>`. override blocks like `{\an8}`
+/// opening a line mean nothing to the services and are put back in front of the line
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Html {
+    text: String,
+    overrides: Vec<String>,
+}
+
+impl Html {
+    fn of(lines: &[String]) -> Self {
+        let (overrides, lines): (Vec<_>, Vec<_>) = lines
+            .iter()
+            .map(|line| {
+                let rest = leading_overrides(line);
+                (line[..line.len() - rest.len()].to_string(), rest)
+            })
+            .unzip();
+        let text = lines
+            .iter()
+            .map(|line| {
+                markup::tokens(line)
+                    .into_iter()
+                    .map(|token| match token {
+                        Token::Text(text) => escaped(text),
+                        Token::Open(_, raw) | Token::Close(_, raw) => raw.to_string(),
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("<br>");
+        Self { text, overrides }
+    }
+
+    /// the lines of a translated cue, more lines than were sent go without overrides
+    fn lines(&self, translated: &str) -> Vec<String> {
+        translated
+            .replace(['\r', '\n'], " ")
+            .split("<br>")
+            .flat_map(|line| line.split("<br/>"))
+            .flat_map(|line| line.split("<br />"))
+            .enumerate()
+            .map(|(index, line)| {
+                let overrides = self.overrides.get(index).map(String::as_str);
+                let line = markup::decode_entities(line.trim()).replace('\u{a0}', " ");
+                format!("{}{line}", overrides.unwrap_or_default())
+            })
+            .collect()
+    }
+}
+
+/// `line` after the `{...}` blocks it starts with
+fn leading_overrides(line: &str) -> &str {
+    let mut rest = line;
+    while let Some(end) = rest
+        .strip_prefix('{')
+        .and_then(|inner| inner.find('}'))
+        .map(|end| end + 2)
+    {
+        rest = &rest[end..];
+    }
+    rest
+}
+
+fn escaped(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// `movie.srt` as `movie.machine.srt`
+pub fn machine_path(path: &Path) -> PathBuf {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => path.with_extension(format!("{INFIX}.{extension}")),
+        None => path.with_extension(INFIX),
+    }
+}
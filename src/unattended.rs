@@ -0,0 +1,62 @@
+//! runs nobody answers prompts for, `hook` and `daemon` starting one per movie. they look
+//! at their movies ahead of the runs
+use crate::{hash, probe, timings::Timings};
+use eyre::Result;
+use std::path::Path;
+
+/// how a run that didn't fail ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// the subtitles were written, or the command did what it does
+    Done,
+    /// `--skip-if-audio-matches`, the movie needs no subtitles
+    Skipped,
+}
+
+/// a movie's run and the flags looked at ahead of it
+pub struct Unattended<C> {
+    /// what the run goes by
+    pub run: C,
+    /// `--language`, the audio is checked against it and notifications name it
+    pub language: String,
+    pub skip_if_audio_matches: bool,
+    /// `--query`, the movie is searched by its title and never hashed
+    pub query: bool,
+    pub hash_method: hash::Method,
+    pub notify: bool,
+}
+
+/// what `hook` and `daemon` found out about a movie ahead of its run
+pub struct Ahead {
+    /// `--skip-if-audio-matches`, the language of the audio when it's in one of `--language`
+    pub audio: Result<Option<String>>,
+    /// none for a movie skipped for its audio or searched by `--query`
+    pub hashed: Option<Result<String>>,
+}
+
+impl Ahead {
+    pub async fn audio<C>(movie: &Unattended<C>, movie_file: &Path) -> Result<Option<String>> {
+        match movie.skip_if_audio_matches {
+            true => probe::audio_in_language(movie_file, &movie.language).await,
+            false => Ok(None),
+        }
+    }
+
+    /// a movie not skipped for its `audio` is searched by its hash, unless by `--query`
+    pub fn hashes<C>(movie: &Unattended<C>, audio: &Result<Option<String>>) -> bool {
+        !movie.query && matches!(audio, Ok(None))
+    }
+
+    /// the audio first, a movie skipped for it is never read
+    pub async fn of<C>(movie: &Unattended<C>, movie_file: &Path, timings: &Timings) -> Self {
+        let audio = Self::audio(movie, movie_file).await;
+        let hashed = match Self::hashes(movie, &audio) {
+            true => {
+                let hashing = hash::hash_file(movie_file.to_owned(), movie.hash_method);
+                Some(timings.time("hashing", hashing).await)
+            }
+            false => None,
+        };
+        Self { audio, hashed }
+    }
+}
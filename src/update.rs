@@ -0,0 +1,324 @@
+//! `self-update`, the latest GitHub release replacing the binary that runs, with the
+//! `self-update` feature
+//!
+//! a release carries a binary per target, `opensubtitlescli-<target>` (`.exe` on windows) or
+//! the same in a `.zip`, and `SHA256SUMS` listing them in the `sha256sum` format
+use crate::{
+    http::{HttpFetch, Request, ReqwestFetch},
+    messages::filled,
+    output,
+};
+use eyre::{bail, eyre, Result, WrapErr};
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::warn;
+
+pub static LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/Niedzwiedzw/opensubtitlescli/releases/latest";
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// the target triple this was built for
+pub const TARGET: &str = env!("TARGET");
+/// how often the notice about a new version looks for one
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// nothing near it is an opensubtitlescli binary
+const MAX_ASSET_SIZE: u64 = 200 * 1024 * 1024;
+
+/// a release that can't update this binary, found in the report's chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateError {
+    /// nothing was built for the target
+    NoAsset(String),
+    /// `SHA256SUMS` is missing or doesn't list the asset
+    NoChecksum(String),
+    ChecksumMismatch(String),
+}
+
+impl UpdateError {
+    pub fn find(report: &eyre::Report) -> Option<&Self> {
+        report.chain().find_map(|e| e.downcast_ref::<Self>())
+    }
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoAsset(target) => write!(f, "the latest release has no binary for {target}"),
+            Self::NoChecksum(name) => write!(f, "SHA256SUMS of the latest release lacks {name}"),
+            Self::ChecksumMismatch(name) => {
+                write!(f, "{name} doesn't match its checksum, it was not installed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UpdateError {}
+
+/// what the GitHub api says about a release, the parts used here
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Release {
+    /// `v0.3.0`
+    pub tag_name: String,
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: Url,
+}
+
+impl Release {
+    pub fn version(&self) -> Result<semver::Version> {
+        let tag = self.tag_name.trim_start_matches('v');
+        semver::Version::parse(tag)
+            .wrap_err_with(|| format!("the release tag {} isn't a version", self.tag_name))
+    }
+
+    /// later than the `current` version
+    pub fn is_newer_than(&self, current: &str) -> Result<bool> {
+        Ok(self.version()? > semver::Version::parse(current)?)
+    }
+
+    /// the binary built for `target`, bare or zipped
+    pub fn asset_for(&self, target: &str) -> Option<&Asset> {
+        let binary = format!("opensubtitlescli-{target}");
+        [
+            binary.clone(),
+            format!("{binary}.exe"),
+            format!("{binary}.zip"),
+        ]
+        .iter()
+        .find_map(|name| self.assets.iter().find(|asset| &asset.name == name))
+    }
+
+    fn checksums(&self) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == "SHA256SUMS")
+    }
+}
+
+/// what talks to GitHub, through the proxy the site is reached with. --insecure is meant for
+/// that site's proxy, a binary about to be installed is never downloaded without tls
+pub fn http(proxy: Option<&str>, timeout: Duration) -> Result<ReqwestFetch> {
+    let mut builder = reqwest::Client::builder().timeout(timeout);
+    #[cfg(feature = "rustls-tls")]
+    {
+        builder = builder.use_rustls_tls();
+    }
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).wrap_err("invalid --proxy")?);
+    }
+    builder
+        .build()
+        .map(ReqwestFetch::new)
+        .wrap_err("setting up the http client")
+}
+
+/// GitHub refuses requests without a user agent
+fn request(url: Url) -> Request {
+    Request::get(url)
+        .header("User-Agent", &format!("opensubtitlescli/{VERSION}"))
+        .header("Accept", "application/vnd.github+json")
+}
+
+/// the latest release, pre-releases aren't
+pub async fn latest(http: &dyn HttpFetch, url: Url) -> Result<Release> {
+    let response = http
+        .get_text(request(url))
+        .await
+        .wrap_err("asking GitHub for the latest release")?;
+    if response.status != 200 {
+        bail!(
+            "asking GitHub for the latest release failed ({})",
+            response.status
+        );
+    }
+    serde_json::from_str(&response.body).wrap_err("reading the latest release")
+}
+
+/// the latest release when it's newer than this binary
+pub async fn newer(http: &dyn HttpFetch, url: Url) -> Result<Option<Release>> {
+    let release = latest(http, url).await?;
+    Ok(release.is_newer_than(VERSION)?.then_some(release))
+}
+
+async fn download(http: &dyn HttpFetch, asset: &Asset) -> Result<Vec<u8>> {
+    let response = http
+        .get_bytes(request(asset.browser_download_url.clone()), MAX_ASSET_SIZE)
+        .await
+        .wrap_err_with(|| format!("downloading {}", asset.name))?;
+    match response.status {
+        200 => Ok(response.body),
+        status => bail!("downloading {} failed ({status})", asset.name),
+    }
+}
+
+/// the checksum `sums` lists `name` with, `<hex>  <name>` or `<hex> *<name>` per line
+pub fn checksum_for<'a>(sums: &'a str, name: &str) -> Option<&'a str> {
+    sums.lines().find_map(|line| {
+        let (checksum, file) = line.split_once(char::is_whitespace)?;
+        let file = file.trim_start().trim_start_matches('*').trim_end();
+        (file == name).then_some(checksum)
+    })
+}
+
+/// the binary in the asset, verified against `SHA256SUMS`
+pub async fn download_binary(
+    http: &dyn HttpFetch,
+    release: &Release,
+    target: &str,
+) -> Result<Vec<u8>> {
+    let asset = release
+        .asset_for(target)
+        .ok_or_else(|| UpdateError::NoAsset(target.to_string()))?;
+    let checksums = release
+        .checksums()
+        .ok_or_else(|| UpdateError::NoChecksum(asset.name.clone()))?;
+    let sums = String::from_utf8_lossy(&download(http, checksums).await?).into_owned();
+    let expected = checksum_for(&sums, &asset.name)
+        .ok_or_else(|| UpdateError::NoChecksum(asset.name.clone()))?;
+    let bytes = download(http, asset).await?;
+    let actual = format!("{:x}", Sha256::digest(&bytes));
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(UpdateError::ChecksumMismatch(asset.name.clone()).into());
+    }
+    match asset.name.ends_with(".zip") {
+        true => unzipped_binary(bytes),
+        false => Ok(bytes),
+    }
+}
+
+/// the only `opensubtitlescli` file in the archive, whatever folder it's in
+fn unzipped_binary(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    let index = (0..archive.len())
+        .find(|index| {
+            archive.by_index(*index).is_ok_and(|file| {
+                let name = file
+                    .enclosed_name()
+                    .and_then(|path| path.file_stem().map(|v| v.to_owned()));
+                file.is_file() && name.is_some_and(|name| name == "opensubtitlescli")
+            })
+        })
+        .ok_or_else(|| eyre!("the release archive has no opensubtitlescli in it"))?;
+    let mut binary = vec![];
+    archive.by_index(index)?.read_to_end(&mut binary)?;
+    Ok(binary)
+}
+
+/// `binary` in place of `exe`, all at once. windows won't replace a running program, it's
+/// moved aside to `<name>.old` first, which the next update removes
+pub fn replace(exe: &Path, binary: &[u8]) -> Result<()> {
+    let dir = exe
+        .parent()
+        .ok_or_else(|| eyre!("{exe:?} isn't in a directory"))?;
+    let mut temporary = tempfile::Builder::new()
+        .prefix(".opensubtitlescli-update")
+        .tempfile_in(dir)
+        .wrap_err_with(|| format!("writing next to {exe:?}, is it writable?"))?;
+    temporary.write_all(binary)?;
+    temporary.as_file().sync_all()?;
+    let permissions = std::fs::metadata(exe)?.permissions();
+    std::fs::set_permissions(temporary.path(), permissions)?;
+    let old = match cfg!(windows) {
+        true => {
+            let old = exe.with_extension("old");
+            let _ = std::fs::remove_file(&old);
+            std::fs::rename(exe, &old).wrap_err_with(|| format!("moving {exe:?} aside"))?;
+            Some(old)
+        }
+        false => None,
+    };
+    if let Err(e) = temporary.persist(exe) {
+        // the program moved aside goes back, a failed update doesn't leave no program at all
+        if let Some(old) = &old {
+            if let Err(restoring) = std::fs::rename(old, exe) {
+                warn!(?old, %restoring, "putting the old program back failed");
+            }
+        }
+        return Err(e.error).wrap_err_with(|| format!("replacing {exe:?}"));
+    }
+    Ok(())
+}
+
+/// when the notice last looked for a new version, the time a file was written
+#[derive(Debug, Clone)]
+pub struct LastCheck {
+    path: PathBuf,
+}
+
+impl LastCheck {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// `$XDG_CACHE_HOME/opensubtitlescli/update-check`, `~/.cache` without it and
+    /// `%LOCALAPPDATA%` on windows
+    pub fn default_path() -> Option<PathBuf> {
+        let cache = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| match cfg!(windows) {
+                true => std::env::var_os("LOCALAPPDATA").map(PathBuf::from),
+                false => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")),
+            })?;
+        Some(cache.join("opensubtitlescli").join("update-check"))
+    }
+
+    /// never checked, or longer than [`CHECK_INTERVAL`] ago
+    pub fn due(&self, now: SystemTime) -> bool {
+        std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|checked| now.duration_since(checked).ok())
+            .is_none_or(|since| since >= CHECK_INTERVAL)
+    }
+
+    pub fn checked(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&self.path, VERSION).wrap_err_with(|| format!("writing {:?}", self.path))
+    }
+}
+
+/// `self-update`, `--check-only` only tells
+pub async fn self_update(proxy: Option<&str>, check_only: bool) -> Result<()> {
+    let http = http(proxy, std::time::Duration::from_secs(300))?;
+    let url = Url::parse(LATEST_RELEASE_URL)?;
+    let Some(release) = newer(&http, url).await? else {
+        println!("{}", filled("update-latest", &[("version", &VERSION)]));
+        return Ok(());
+    };
+    if check_only {
+        println!(
+            "{}",
+            filled(
+                "update-available",
+                &[("new", &release.tag_name), ("version", &VERSION)]
+            )
+        );
+        return Ok(());
+    }
+    let exe = std::env::current_exe().wrap_err("finding this binary")?;
+    let exe = exe.canonicalize().unwrap_or(exe);
+    let binary = download_binary(&http, &release, TARGET).await?;
+    replace(&exe, &binary)?;
+    println!(
+        "{}",
+        filled(
+            "update-done",
+            &[
+                ("version", &VERSION),
+                ("new", &release.tag_name),
+                ("path", &output::quoted(&exe)),
+            ]
+        )
+    );
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! `upload`, a subtitle contributed back through the XML-RPC api: logging in,
+//! `TryUploadSubtitles` to learn whether the site has it already, then `UploadSubtitles`
+use crate::{
+    api::{Credentials, Session},
+    hash,
+    http::HttpFetch,
+    xmlrpc::Value,
+};
+use base64::Engine;
+use eyre::{Result, WrapErr};
+use reqwest::Url;
+use std::{io::Write, path::Path};
+
+/// a subtitle and what the site files it under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upload {
+    pub movie_hash: String,
+    pub movie_size: u64,
+    pub movie_file_name: String,
+    pub subtitle_file_name: String,
+    pub subtitle: Vec<u8>,
+    /// the site's three letter code, `pol`
+    pub language: String,
+    /// `tt0133093`, the site looks it up by the hash without it
+    pub imdb_id: Option<String>,
+    pub release_name: String,
+    pub hearing_impaired: bool,
+    /// only the foreign parts are subtitled
+    pub forced: bool,
+    pub comment: Option<String>,
+}
+
+impl Upload {
+    /// the hash, size and names read from the files, the release name is the movie's file
+    /// name without its extension
+    pub fn from_files(movie: &Path, subtitle: &Path, language: &str) -> Result<Self> {
+        let name = |path: &Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .ok_or_else(|| eyre::eyre!("{path:?} isn't a file"))
+        };
+        Ok(Self {
+            movie_hash: hash::hash_for_file(movie)?,
+            movie_size: std::fs::metadata(movie)
+                .wrap_err_with(|| format!("reading {movie:?}"))?
+                .len(),
+            movie_file_name: name(movie)?,
+            subtitle_file_name: name(subtitle)?,
+            subtitle: std::fs::read(subtitle).wrap_err_with(|| format!("reading {subtitle:?}"))?,
+            language: language.to_string(),
+            imdb_id: None,
+            release_name: movie
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            hearing_impaired: false,
+            forced: false,
+            comment: None,
+        })
+    }
+
+    /// the file as both calls describe it, the content only goes along with the upload
+    fn cd1(&self, with_content: bool) -> Result<Value> {
+        let mut members = vec![
+            ("subhash", Value::string(subtitle_hash(&self.subtitle))),
+            ("subfilename", Value::string(&self.subtitle_file_name)),
+            ("moviehash", Value::string(&self.movie_hash)),
+            ("moviebytesize", Value::string(self.movie_size.to_string())),
+            ("moviefilename", Value::string(&self.movie_file_name)),
+        ];
+        if with_content {
+            members.push(("subcontent", Value::string(gzipped_base64(&self.subtitle)?)));
+        }
+        Ok(Value::members(members))
+    }
+
+    fn base_info(&self, imdb_id: &str) -> Value {
+        let flag = |set: bool| {
+            Value::string(match set {
+                true => "1",
+                false => "0",
+            })
+        };
+        let mut members = vec![
+            (
+                "idmovieimdb",
+                Value::string(imdb_id.trim_start_matches("tt")),
+            ),
+            ("sublanguageid", Value::string(&self.language)),
+            ("moviereleasename", Value::string(&self.release_name)),
+            ("hearingimpaired", flag(self.hearing_impaired)),
+            ("foreignpartsonly", flag(self.forced)),
+        ];
+        if let Some(comment) = &self.comment {
+            members.push(("subauthorcomment", Value::string(comment)));
+        }
+        Value::members(members)
+    }
+}
+
+/// what became of the upload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Uploaded {
+    /// the subtitle's page on the site
+    New(String),
+    /// someone uploaded the same file before, with its page when the api said
+    AlreadyInDatabase(Option<String>),
+}
+
+/// an upload the api can't take, found in the report's chain. refused calls are
+/// [`crate::api::StatusError`]s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadError {
+    /// the movie's hash isn't known to the site and no imdb id was given
+    UnknownMovie,
+}
+
+impl UploadError {
+    pub fn find(report: &eyre::Report) -> Option<&Self> {
+        report.chain().find_map(|e| e.downcast_ref::<Self>())
+    }
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownMovie => f.write_str(
+                "the site doesn't know the movie by its hash, pass its imdb id with --imdb",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+/// the md5 of the subtitle, in hex, what the api tells uploads apart by
+pub fn subtitle_hash(bytes: &[u8]) -> String {
+    md5(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn md5(bytes: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 64] = [
+        7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5,
+        9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10,
+        15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+    ];
+    let constants: Vec<u32> = (0..64)
+        .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+        .collect();
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((bytes.len() as u64).wrapping_mul(8)).to_le_bytes());
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in message.chunks(64) {
+        let words: Vec<u32> = chunk
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(constants[i])
+                .wrapping_add(words[g])
+                .rotate_left(SHIFTS[i]);
+            (a, d, c) = (d, c, b);
+            b = b.wrapping_add(rotated);
+        }
+        for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+    let mut digest = [0; 16];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+/// how the api takes the content
+fn gzipped_base64(bytes: &[u8]) -> Result<String> {
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(bytes)?;
+    let gzipped = encoder.finish()?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(gzipped))
+}
+
+/// the first entry of `data` with a non-empty `field`
+fn first_of<'a>(value: &'a Value, field: &str) -> Option<&'a str> {
+    let entries = match value.get("data")? {
+        Value::Array(entries) => entries.iter().collect::<Vec<_>>(),
+        entry => vec![entry],
+    };
+    entries
+        .into_iter()
+        .filter_map(|entry| entry.get(field)?.as_str())
+        .find(|value| !value.is_empty() && *value != "0")
+}
+
+/// logged in as `credentials`, uploads `upload` unless the site has it and logs out.
+/// `user_agent` has to be one registered with the api
+pub async fn upload(
+    http: &dyn HttpFetch,
+    url: &Url,
+    credentials: &Credentials,
+    user_agent: &str,
+    upload: &Upload,
+) -> Result<Uploaded> {
+    let session = Session::log_in(http, url, credentials, user_agent).await?;
+    let uploaded = upload_as(&session, upload).await;
+    session.log_out().await;
+    uploaded
+}
+
+async fn upload_as(session: &Session<'_>, upload: &Upload) -> Result<Uploaded> {
+    let tried = session
+        .call(
+            "TryUploadSubtitles",
+            vec![Value::members([("cd1", upload.cd1(false)?)])],
+        )
+        .await?;
+    if tried.get("alreadyindb").and_then(Value::as_int) == Some(1) {
+        let link = first_of(&tried, "SubtitlesLink")
+            .map(str::to_string)
+            .or_else(|| {
+                first_of(&tried, "IDSubtitle")
+                    .map(|id| format!("https://www.opensubtitles.org/subtitles/{id}"))
+            });
+        return Ok(Uploaded::AlreadyInDatabase(link));
+    }
+    let imdb_id = upload
+        .imdb_id
+        .as_deref()
+        .or_else(|| first_of(&tried, "IDMovieImdb"))
+        .ok_or(UploadError::UnknownMovie)?;
+    let uploaded = session
+        .call(
+            "UploadSubtitles",
+            vec![Value::members([
+                ("baseinfo", upload.base_info(imdb_id)),
+                ("cd1", upload.cd1(true)?),
+            ])],
+        )
+        .await?;
+    let link = uploaded
+        .get("data")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    Ok(Uploaded::New(link.to_string()))
+}
@@ -0,0 +1,297 @@
+//! the little of XML-RPC the opensubtitles api needs: calls written out and responses read
+//! back, without dates or base64 values
+use eyre::{bail, eyre, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Int(i64),
+    Bool(bool),
+    Double(f64),
+    Struct(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Nil,
+}
+
+impl Value {
+    pub fn string(value: impl Into<String>) -> Self {
+        Self::String(value.into())
+    }
+
+    /// `(name, value)` members
+    pub fn members<'a>(members: impl IntoIterator<Item = (&'a str, Value)>) -> Self {
+        Self::Struct(
+            members
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        )
+    }
+
+    /// the member called `name` of a struct
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Self::Struct(members) => members
+                .iter()
+                .find(|(member, _)| member == name)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// the api sends flags as `1`, `"1"` or a boolean, depending on the method
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            Self::Bool(value) => Some(*value as i64),
+            Self::String(value) => value.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        out.push_str("<value>");
+        match self {
+            Self::String(value) => {
+                out.push_str("<string>");
+                out.push_str(&escape(value));
+                out.push_str("</string>");
+            }
+            Self::Int(value) => out.push_str(&format!("<int>{value}</int>")),
+            Self::Bool(value) => out.push_str(&format!("<boolean>{}</boolean>", *value as u8)),
+            Self::Double(value) => out.push_str(&format!("<double>{value}</double>")),
+            Self::Struct(members) => {
+                out.push_str("<struct>");
+                for (name, value) in members {
+                    out.push_str("<member><name>");
+                    out.push_str(&escape(name));
+                    out.push_str("</name>");
+                    value.write(out);
+                    out.push_str("</member>");
+                }
+                out.push_str("</struct>");
+            }
+            Self::Array(values) => {
+                out.push_str("<array><data>");
+                values.iter().for_each(|value| value.write(out));
+                out.push_str("</data></array>");
+            }
+            Self::Nil => out.push_str("<nil/>"),
+        }
+        out.push_str("</value>");
+    }
+}
+
+/// a `<fault>` answered instead of a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fault {
+    pub code: i64,
+    pub message: String,
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the api failed the call ({}): {}",
+            self.code, self.message
+        )
+    }
+}
+
+impl std::error::Error for Fault {}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// the body posted for calling `method` with `params`
+pub fn call(method: &str, params: &[Value]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\"?><methodCall><methodName>");
+    out.push_str(&escape(method));
+    out.push_str("</methodName><params>");
+    for param in params {
+        out.push_str("<param>");
+        param.write(&mut out);
+        out.push_str("</param>");
+    }
+    out.push_str("</params></methodCall>");
+    out
+}
+
+/// the value a `<methodResponse>` carries, a [`Fault`] in the chain when it's one
+pub fn response(body: &str) -> Result<Value> {
+    let mut parser = Parser { rest: body };
+    parser.skip_declaration();
+    parser.open("methodResponse")?;
+    match parser.peek_tag().as_deref() {
+        Some("fault") => {
+            parser.open("fault")?;
+            let fault = parser.value()?;
+            let code = fault.get("faultCode").and_then(Value::as_int).unwrap_or(0);
+            let message = fault
+                .get("faultString")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            Err(Fault { code, message }.into())
+        }
+        _ => {
+            parser.open("params")?;
+            parser.open("param")?;
+            parser.value()
+        }
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn skip_declaration(&mut self) {
+        self.skip_whitespace();
+        if self.rest.starts_with("<?") {
+            if let Some(end) = self.rest.find("?>") {
+                self.rest = &self.rest[end + 2..];
+            }
+        }
+    }
+
+    /// the name of the next tag, `/name` for a closing one
+    fn peek_tag(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let tag = self.rest.strip_prefix('<')?;
+        let (closing, name) = match tag.strip_prefix('/') {
+            Some(name) => ("/", name),
+            None => ("", tag),
+        };
+        let end = name.find(|c: char| c == '>' || c == '/' || c.is_whitespace())?;
+        Some(format!("{closing}{}", &name[..end]))
+    }
+
+    /// `<name>`, `true` when it was `<name/>` and there's nothing inside
+    fn open(&mut self, name: &str) -> Result<bool> {
+        self.skip_whitespace();
+        let tag = self
+            .rest
+            .strip_prefix('<')
+            .and_then(|rest| rest.strip_prefix(name))
+            .ok_or_else(|| eyre!("expected <{name}> at {}", self.context()))?;
+        let end = tag
+            .find('>')
+            .ok_or_else(|| eyre!("unterminated <{name}>"))?;
+        let empty = tag[..end].trim_end().ends_with('/');
+        self.rest = &tag[end + 1..];
+        Ok(empty)
+    }
+
+    fn close(&mut self, name: &str) -> Result<()> {
+        self.skip_whitespace();
+        self.rest = self
+            .rest
+            .strip_prefix("</")
+            .and_then(|rest| rest.strip_prefix(name))
+            .map(str::trim_start)
+            .and_then(|rest| rest.strip_prefix('>'))
+            .ok_or_else(|| eyre!("expected </{name}> at {}", self.context()))?;
+        Ok(())
+    }
+
+    /// the text up to the next tag, unescaped
+    fn text(&mut self) -> String {
+        let end = self.rest.find('<').unwrap_or(self.rest.len());
+        let text = unescape(&self.rest[..end]);
+        self.rest = &self.rest[end..];
+        text
+    }
+
+    fn context(&self) -> String {
+        self.rest.chars().take(40).collect()
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        if self.open("value")? {
+            return Ok(Value::String(String::new()));
+        }
+        // a value without a type is a string
+        let untyped = self.text();
+        let value = match self.peek_tag().as_deref() {
+            Some("/value") => Value::String(untyped),
+            Some(tag) => {
+                let tag = tag.to_string();
+                let empty = self.open(&tag)?;
+                let value = match (tag.as_str(), empty) {
+                    ("nil", _) => Value::Nil,
+                    ("string", true) => Value::String(String::new()),
+                    ("struct", true) => Value::Struct(vec![]),
+                    ("string", false) => Value::String(self.text()),
+                    ("int" | "i4" | "i8", false) => Value::Int(
+                        self.text()
+                            .trim()
+                            .parse()
+                            .map_err(|_| eyre!("invalid <{tag}>"))?,
+                    ),
+                    ("boolean", false) => Value::Bool(self.text().trim() == "1"),
+                    ("double", false) => Value::Double(
+                        self.text()
+                            .trim()
+                            .parse()
+                            .map_err(|_| eyre!("invalid <double>"))?,
+                    ),
+                    ("struct", false) => {
+                        let mut members = vec![];
+                        while self.peek_tag().as_deref() == Some("member") {
+                            self.open("member")?;
+                            self.open("name")?;
+                            let name = self.text();
+                            self.close("name")?;
+                            members.push((name, self.value()?));
+                            self.close("member")?;
+                        }
+                        Value::Struct(members)
+                    }
+                    ("array", false) => {
+                        let mut values = vec![];
+                        if !self.open("data")? {
+                            while self.peek_tag().as_deref() == Some("value") {
+                                values.push(self.value()?);
+                            }
+                            self.close("data")?;
+                        }
+                        Value::Array(values)
+                    }
+                    (tag, _) => bail!("unsupported value <{tag}>"),
+                };
+                if !empty {
+                    self.close(&tag)?;
+                }
+                value
+            }
+            None => bail!("unterminated <value>"),
+        };
+        self.close("value")?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,61 @@
+//! the blocking client from plain threads, with no runtime around it
+#![cfg(feature = "blocking")]
+use opensubtitlescli::{
+    archive, blocking,
+    http::{FakeHttp, Reply},
+    Ranking,
+};
+use std::sync::Arc;
+
+const HASH: &str = "33930e90499aa99c";
+
+fn http() -> Arc<FakeHttp> {
+    let http = FakeHttp::new();
+    http.reply(
+        &format!("https://www.opensubtitles.org/pl/search/sublanguageid-pol/moviehash-{HASH}"),
+        Reply::ok(include_str!("fixtures/search.html")),
+    )
+    .reply(
+        "https://www.opensubtitles.org/download/sub/1000001",
+        Reply {
+            content_type: Some("application/zip".to_string()),
+            ..Reply::ok(include_bytes!("fixtures/subtitles.zip").to_vec())
+        },
+    );
+    Arc::new(http)
+}
+
+#[test]
+fn searches_and_downloads_without_a_runtime() {
+    let client = blocking::Client::builder()
+        .http(http())
+        .build_blocking()
+        .unwrap();
+    let candidates = client
+        .search_by_hash("pol", HASH, &Ranking::default())
+        .unwrap();
+    assert_eq!(candidates.len(), 1);
+    let bytes = client.download_entry(&candidates[0].entry).unwrap();
+    let archive = archive::open(bytes, &archive::Options::default()).unwrap();
+    assert!(!archive.file_names().is_empty());
+}
+
+#[test]
+fn errors_are_the_async_clients() {
+    let client = blocking::Client::builder()
+        .http(http())
+        .build_blocking()
+        .unwrap();
+    let report = client
+        .search_by_hash("eng", HASH, &Ranking::default())
+        .unwrap_err();
+    let async_client = opensubtitlescli::Client::builder()
+        .http(http())
+        .build()
+        .unwrap();
+    let expected = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async_client.search_by_hash("eng", HASH, &Ranking::default()))
+        .unwrap_err();
+    assert_eq!(format!("{report:?}"), format!("{expected:?}"));
+}
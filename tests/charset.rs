@@ -0,0 +1,114 @@
+//! subtitles in legacy codepages come out as utf-8, `tests/fixtures/charset` has the same
+//! cues in the encodings they're usually uploaded in
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::read_fixture;
+use opensubtitlescli::charset::{Transcode, UTF8_BOM};
+
+const POLISH: &str = "1\r\n00:00:01,000 --> 00:00:01,900\r\nZażółć gęślą jaźń.\r\n\r\n2\r\n00:00:02,000 --> 00:00:02,900\r\nŹle się dzieje, Świętą prawdę mówiąc.\r\n\r\n3\r\n00:00:03,000 --> 00:00:03,900\r\nĄż do końca świata.\r\n";
+const CZECH: &str = "1\r\n00:00:01,000 --> 00:00:01,900\r\nPříliš žluťoučký kůň úpěl ďábelské ódy.\r\n\r\n2\r\n00:00:02,000 --> 00:00:02,900\r\nŘekni mi, proč tady ještě čekáš?\r\n";
+const RUSSIAN: &str = "1\r\n00:00:01,000 --> 00:00:01,900\r\nСъешь же ещё этих мягких французских булок.\r\n\r\n2\r\n00:00:02,000 --> 00:00:02,900\r\nДа выпей чаю, пожалуйста.\r\n";
+const GREEK: &str = "1\r\n00:00:01,000 --> 00:00:01,900\r\nΓαζέες καί μυρτιές δέν θά βρώ πιά στό χρυσαφί ξέφωτο.\r\n\r\n2\r\n00:00:02,000 --> 00:00:02,900\r\nΠού είσαι;\r\n";
+
+fn transcode(language: &str) -> Transcode {
+    Transcode {
+        source: None,
+        language: language.to_string(),
+    }
+}
+
+/// the fixture as utf-8 and the encoding it was taken for
+fn decoded(name: &str, language: &str) -> (String, &'static str) {
+    let contents = read_fixture(&format!("charset/{name}"));
+    let transcode = transcode(language);
+    let encoding = transcode.source_encoding(&contents).name();
+    let text = String::from_utf8(transcode.to_utf8(&contents).unwrap()).unwrap();
+    (text, encoding)
+}
+
+#[test]
+fn central_european_codepages() {
+    assert_eq!(
+        decoded("pol.windows-1250.srt", "pol"),
+        (POLISH.to_string(), "windows-1250")
+    );
+    assert_eq!(
+        decoded("pol.iso-8859-2.srt", "pol"),
+        (POLISH.to_string(), "ISO-8859-2")
+    );
+    assert_eq!(
+        decoded("cze.windows-1250.srt", "cze"),
+        (CZECH.to_string(), "windows-1250")
+    );
+}
+
+#[test]
+fn cyrillic_codepages() {
+    assert_eq!(
+        decoded("rus.windows-1251.srt", "rus"),
+        (RUSSIAN.to_string(), "windows-1251")
+    );
+    assert_eq!(
+        decoded("rus.koi8-r.srt", "rus"),
+        (RUSSIAN.to_string(), "KOI8-R")
+    );
+}
+
+#[test]
+fn greek_codepage() {
+    assert_eq!(
+        decoded("gre.windows-1253.srt", "gre"),
+        (GREEK.to_string(), "windows-1253")
+    );
+}
+
+#[test]
+fn languages_without_candidates_are_left_to_the_detector() {
+    let (text, encoding) = decoded("rus.windows-1251.srt", "eng");
+    assert_eq!((text.as_str(), encoding), (RUSSIAN, "windows-1251"));
+}
+
+#[test]
+fn utf8_stays_as_it_is_bom_and_all() {
+    let contents = read_fixture("charset/pol.utf-8-bom.srt");
+    let utf8 = transcode("pol").to_utf8(&contents).unwrap();
+    assert_eq!(utf8, contents);
+    assert!(utf8.starts_with(UTF8_BOM));
+    assert_eq!(&utf8[UTF8_BOM.len()..], POLISH.as_bytes());
+}
+
+#[test]
+fn unmappable_bytes_fail_the_conversion() {
+    let contents = read_fixture("charset/pol.windows-1250.srt");
+    let forced = Transcode {
+        source: Some(encoding_rs::UTF_8),
+        ..transcode("pol")
+    };
+    let error = forced.to_utf8(&contents).unwrap_err().to_string();
+    // `ż` of the first cue, after the 36 bytes before it
+    assert!(error.contains("aren't valid UTF-8"), "{error}");
+    assert!(error.contains("offset 36"), "{error}");
+}
+
+#[test]
+fn short_files_get_the_languages_codepage() {
+    let cue = |text: &str| format!("1\r\n00:00:01,000 --> 00:00:02,000\r\n{text}\r\n");
+    let cases = [
+        ("pol", "Świętą", encoding_rs::ISO_8859_2),
+        ("pol", "Świętą", encoding_rs::WINDOWS_1250),
+        ("rus", "Привет", encoding_rs::KOI8_R),
+        ("rus", "Привет", encoding_rs::WINDOWS_1251),
+    ];
+    for (language, text, encoding) in cases {
+        let cue = cue(text);
+        let (contents, _, _) = encoding.encode(&cue);
+        assert_eq!(
+            transcode(language).source_encoding(&contents),
+            encoding,
+            "{text} in {}",
+            encoding.name()
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! a cancelled run leaves nothing half written behind
+use opensubtitlescli::{cleanup::Cleanup, progress::Stopped};
+use std::time::Duration;
+
+#[tokio::test]
+async fn removes_what_was_being_written_when_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let (done, partial) = (dir.path().join("done.srt"), dir.path().join("partial.mkv"));
+    let cleanup = Cleanup::new();
+    let run = async {
+        std::fs::write(&done, b"done").unwrap();
+        cleanup.guard([done.clone()], async {}).await;
+        cleanup.completed(&done);
+        cleanup
+            .guard([partial.clone()], async {
+                std::fs::write(&partial, b"half").unwrap();
+                cleanup.token().cancel();
+                tokio::time::sleep(Duration::from_secs(60)).await;
+            })
+            .await;
+        Ok(())
+    };
+    let report = cleanup.cancellable(run).await.unwrap_err();
+    assert_eq!(Stopped::find(&report), Some(Stopped::Interrupted));
+    assert_eq!(cleanup.remove_partial(), vec![partial.clone()]);
+    assert!(!partial.exists() && done.exists());
+    assert_eq!(cleanup.completed_files(), vec![done]);
+}
@@ -0,0 +1,78 @@
+//! `--copy-path`, through a clipboard program or the terminal
+// the helpers only the library tests use go unused here
+#[cfg(all(target_os = "linux", feature = "history", feature = "tui"))]
+#[allow(dead_code)]
+mod common;
+
+use opensubtitlescli::clipboard::osc52;
+
+#[test]
+fn osc52_carries_the_text_in_base64() {
+    assert_eq!(
+        osc52("/movies/ą.srt", false),
+        "\x1b]52;c;L21vdmllcy/EhS5zcnQ=\x07"
+    );
+    // tmux passes it on to the terminal, escapes doubled
+    assert_eq!(osc52("a", true), "\x1bPtmux;\x1b\x1b]52;c;YQ==\x07\x1b\\");
+}
+
+#[cfg(all(target_os = "linux", feature = "history", feature = "tui"))]
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn copies_what_was_printed() {
+    use common::{read_fixture, MockServer};
+    use std::os::unix::fs::PermissionsExt;
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let movie_file = dir.path().join("Big.Buck.Bunny.2008.1080p.BluRay.x264.mkv");
+    let contents = (0..200_000u32).map(|n| (n % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&movie_file, contents).unwrap();
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    server.route(
+        &format!("/pl/search/sublanguageid-pol/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("search.html"),
+    );
+    server.route(
+        "/download/sub/1000001",
+        200,
+        &[("Content-Type", "application/zip")],
+        read_fixture("subtitles.zip"),
+    );
+    let bin = tempfile::tempdir().unwrap();
+    let copied = bin.path().join("copied");
+    let xclip = bin.path().join("xclip");
+    std::fs::write(&xclip, format!("#!/bin/sh\n/bin/cat > {copied:?}\n")).unwrap();
+    std::fs::set_permissions(&xclip, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args([
+            "--copy-path",
+            "--no-history",
+            "--auto",
+            "-l",
+            "pol",
+            "--base-url",
+        ])
+        .arg(server.base_url.as_str())
+        .arg("-m")
+        .arg(&movie_file)
+        .env("PATH", bin.path())
+        .env("DISPLAY", ":0")
+        .env_remove("WAYLAND_DISPLAY")
+        .env_remove("SSH_TTY")
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let subtitles = movie_file.with_extension("srt");
+    assert_eq!(
+        std::fs::read_to_string(copied).unwrap(),
+        subtitles.to_str().unwrap()
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("\"{}\"\n", subtitles.display())
+    );
+}
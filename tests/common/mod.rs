@@ -0,0 +1,118 @@
+//! a tiny http server answering with canned responses, standing in for opensubtitles.org
+use reqwest::Url;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+#[derive(Debug, Clone)]
+struct Route {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockServer {
+    pub base_url: Url,
+    routes: Arc<Mutex<HashMap<String, Route>>>,
+    hits: Arc<Mutex<Vec<String>>>,
+}
+
+pub fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+pub fn read_fixture(name: &str) -> Vec<u8> {
+    std::fs::read(fixture(name)).expect("fixture exists")
+}
+
+/// the path and query of the request line, `None` once the client hung up
+async fn request_target(stream: &mut TcpStream) -> Option<String> {
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    String::from_utf8_lossy(&request)
+        .lines()
+        .next()?
+        .split(' ')
+        .nth(1)
+        .map(|target| target.to_string())
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("binding");
+        let address = listener.local_addr().expect("bound");
+        let server = Self {
+            base_url: format!("http://{address}").parse().expect("valid url"),
+            routes: Default::default(),
+            hits: Default::default(),
+        };
+        let (routes, hits) = (server.routes.clone(), server.hits.clone());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (routes, hits) = (routes.clone(), hits.clone());
+                tokio::spawn(async move {
+                    let Some(target) = request_target(&mut stream).await else {
+                        return;
+                    };
+                    hits.lock().unwrap().push(target.clone());
+                    let route = routes.lock().unwrap().get(&target).cloned();
+                    let route = route.unwrap_or_else(|| Route {
+                        status: 404,
+                        headers: vec![],
+                        body: b"not found".to_vec(),
+                    });
+                    let mut response = format!(
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        route.status,
+                        route.body.len()
+                    );
+                    for (name, value) in &route.headers {
+                        response.push_str(&format!("{name}: {value}\r\n"));
+                    }
+                    response.push_str("\r\n");
+                    stream.write_all(response.as_bytes()).await.ok();
+                    stream.write_all(&route.body).await.ok();
+                    stream.shutdown().await.ok();
+                });
+            }
+        });
+        server
+    }
+
+    /// answers requests for `target`, a path with its query
+    pub fn route(&self, target: &str, status: u16, headers: &[(&str, &str)], body: Vec<u8>) {
+        let route = Route {
+            status,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            body,
+        };
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(target.to_string(), route);
+    }
+
+    /// the targets requested so far, oldest first
+    pub fn hits(&self) -> Vec<String> {
+        self.hits.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,262 @@
+//! search result pages read into candidates, from the pages in `tests/fixtures`
+use chrono::NaiveDate;
+use opensubtitlescli::{
+    crawler::{self, Ranking},
+    subtitle::SubtitleFormat,
+};
+use std::path::Path;
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+    )
+    .unwrap()
+}
+
+#[test]
+fn keeps_every_release_name_of_a_row() {
+    let candidates =
+        crawler::top_rated_subs(&fixture("release_names.html"), &Ranking::default()).unwrap();
+    assert_eq!(
+        candidates[0].entry.release_names,
+        [
+            "Big.Buck.Bunny.2008.1080p.BluRay.x264-GRP",
+            "Big.Buck.Bunny.2008.720p.WEB-DL",
+            "Big.Buck.Bunny.2008.2160p.UHD.BluRay.x265.HDR-SOMEVERYLONGGROUP",
+        ]
+    );
+}
+
+#[test]
+fn shows_the_best_match_in_the_prompt_and_every_name_in_the_details() {
+    let candidate = crawler::top_rated_subs(&fixture("release_names.html"), &Ranking::default())
+        .unwrap()
+        .remove(0)
+        .for_movie(Path::new("/movies/Big.Buck.Bunny.2008.720p.WEB-DL.mkv"));
+    assert_eq!(
+        candidate.to_string(),
+        "[Big.Buck.Bunny.2008.720p.WEB-DL (rating: 9.5)] srt"
+    );
+    assert_eq!(
+        candidate.details().to_string(),
+        "Big Buck Bunny (2008)\n\
+         \x20   Big.Buck.Bunny.2008.1080p.BluRay.x264-GRP\n\
+         \x20 * Big.Buck.Bunny.2008.720p.WEB-DL\n\
+         \x20   Big.Buck.Bunny.2008.2160p.UHD.BluRay.x265.HDR-SOMEVERYLONGGROUP\n\
+         \x20 pol srt, 1523 downloads, uploaded by uploader on 2020-05-01, rated 9.5"
+    );
+}
+
+#[test]
+fn counts_only_the_bad_report_markers() {
+    let ranking = Ranking {
+        top_n: 10,
+        ..Ranking::default()
+    };
+    let candidates = crawler::top_rated_subs(&fixture("reported.html"), &ranking).unwrap();
+    let reports = candidates
+        .iter()
+        .map(|candidate| (candidate.entry.subtitle_id, candidate.entry.bad_reports))
+        .collect::<Vec<_>>();
+    // the 9.5 reported four times falls behind the ones nobody reported
+    assert_eq!(reports, [(1000002, 0), (1000003, 0), (1000001, 4)]);
+    assert_eq!(
+        candidates[2]
+            .entry
+            .detail_url
+            .as_ref()
+            .map(|url| url.path()),
+        Some("/pl/subtitles/1000001/big-buck-bunny-pl")
+    );
+    assert_eq!(candidates[1].entry.detail_url, None);
+    assert_eq!(candidates[2].entry.edits, 3);
+}
+
+#[test]
+fn reads_reports_and_comments_off_the_subtitle_page() {
+    let details = crawler::Details::parse(&fixture("subtitle_page.html")).unwrap();
+    assert_eq!(
+        details,
+        crawler::Details {
+            bad_reports: Some(6),
+            comments: Some(3),
+        }
+    );
+    assert_eq!(
+        crawler::Details::parse("<html><body>nothing here</body></html>").unwrap(),
+        crawler::Details::default()
+    );
+}
+
+#[test]
+fn huge_report_counts_add_up_to_the_most_there_is() {
+    let page = r#"<html><body>
+        <img title="reported as bad 4000000000 times"><img title="reported as bad 4000000000 times">
+        </body></html>"#;
+    let details = crawler::Details::parse(page).unwrap();
+    assert_eq!(details.bad_reports, Some(u32::MAX));
+}
+
+#[test]
+fn reads_typed_fields_out_of_the_cells() {
+    let ranking = Ranking {
+        top_n: 10,
+        ..Ranking::default()
+    };
+    let candidates = crawler::top_rated_subs(&fixture("typed.html"), &ranking).unwrap();
+    let [rated, unrated] = &candidates[..] else {
+        panic!("{candidates:?}");
+    };
+    let rated = &rated.entry;
+    assert_eq!(rated.subtitle_id, 3004005);
+    assert_eq!(rated.language.as_str(), "en");
+    assert_eq!(rated.cd_count, 2);
+    assert_eq!(rated.uploaded_at, NaiveDate::from_ymd_opt(2020, 5, 1));
+    assert_eq!(rated.downloads, 12345);
+    assert_eq!(rated.format, SubtitleFormat::Sub);
+    assert_eq!(rated.rating, Some(8.5));
+    assert_eq!(rated.edits, 1024);
+    assert_eq!(rated.imdb_rating, Some(7.2));
+
+    let unrated = &unrated.entry;
+    // the link says more than the flag
+    assert_eq!(unrated.language.as_str(), "pob");
+    assert_eq!(unrated.subtitle_id, 3004006);
+    assert_eq!(unrated.uploaded_at, NaiveDate::from_ymd_opt(2019, 1, 1));
+    assert_eq!(unrated.format, SubtitleFormat::Srt);
+    assert_eq!((unrated.rating, unrated.imdb_rating), (None, None));
+    assert_eq!(unrated.uploaded_by, "");
+}
+
+#[test]
+fn entries_survive_a_trip_through_json() {
+    let entry = crawler::top_rated_subs(&fixture("typed.html"), &Ranking::default())
+        .unwrap()
+        .remove(0)
+        .entry;
+    let json = serde_json::to_value(&entry).unwrap();
+    assert_eq!(json["language"], "en");
+    assert_eq!(json["uploaded_at"], "2020-05-01");
+    assert_eq!(json["rating"], 8.5);
+    let back: crawler::SubsEntry = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        serde_json::to_value(&back).unwrap(),
+        serde_json::to_value(&entry).unwrap()
+    );
+}
+
+fn order(fixture_name: &str, ranking: &Ranking) -> Vec<u64> {
+    crawler::top_rated_subs(&fixture(fixture_name), ranking)
+        .unwrap()
+        .iter()
+        .map(|candidate| candidate.entry.subtitle_id)
+        .collect()
+}
+
+#[test]
+fn ranks_featured_rows_below_organic_ones_at_equal_score() {
+    let ranking = Ranking {
+        top_n: 10,
+        ..Ranking::default()
+    };
+    assert_eq!(
+        order("featured.html", &ranking),
+        [1000013, 1000011, 1000012]
+    );
+    let candidates = crawler::top_rated_subs(&fixture("featured.html"), &ranking).unwrap();
+    assert!(candidates[1].to_string().ends_with(" featured"));
+}
+
+#[test]
+fn featured_first_keeps_the_order_of_the_page() {
+    let ranking = Ranking {
+        top_n: 10,
+        featured_first: true,
+        ..Ranking::default()
+    };
+    assert_eq!(
+        order("featured.html", &ranking),
+        [1000011, 1000012, 1000013]
+    );
+}
+
+/// the strategy that took the page
+fn strategy(page: &str) -> Option<&'static str> {
+    let html = scraper::Html::parse_document(page);
+    crawler::parser_strategies()
+        .into_iter()
+        .find(|strategy| strategy.parse(&html).is_some())
+        .map(|strategy| strategy.name())
+}
+
+#[test]
+fn every_layout_has_its_strategy() {
+    assert_eq!(strategy(&fixture("search.html")), Some("table"));
+    assert_eq!(strategy(&fixture("cards.html")), Some("cards"));
+    assert_eq!(
+        strategy("<html><body><p>maintenance</p></body></html>"),
+        None
+    );
+    let error = crawler::top_rated_subs("<html></html>", &Ranking::default()).unwrap_err();
+    assert!(
+        error.to_string().contains("no search result table"),
+        "{error}"
+    );
+}
+
+#[test]
+fn reads_the_cards_layout() {
+    let ranking = Ranking {
+        top_n: 10,
+        ..Ranking::default()
+    };
+    let candidates = crawler::top_rated_subs(&fixture("cards.html"), &ranking).unwrap();
+    let [card, flagged] = &candidates[..] else {
+        panic!("{candidates:?}");
+    };
+    let card = &card.entry;
+    assert_eq!(card.subtitle_id, 2000001);
+    assert_eq!(card.language.as_str(), "pol");
+    assert_eq!(
+        card.release_names,
+        [
+            "Big.Buck.Bunny.2008.1080p.BluRay.x264",
+            "Big.Buck.Bunny.2008.2160p.UHD.BluRay.x265.HDR-SOMEVERYLONGGROUP",
+        ]
+    );
+    assert_eq!(card.uploaded_at, NaiveDate::from_ymd_opt(2021, 3, 4));
+    assert_eq!(card.downloads, 2048);
+    assert_eq!(card.format, SubtitleFormat::Srt);
+    assert_eq!((card.rating, card.imdb_rating), (Some(8.7), Some(7.2)));
+    assert_eq!(card.edits, 4);
+    assert_eq!(card.fps, Some(23.976));
+    assert!(!card.featured && !card.foreign_parts_only);
+
+    let flagged = &flagged.entry;
+    assert_eq!(flagged.cd_count, 1);
+    assert_eq!(flagged.format, SubtitleFormat::Ass);
+    assert_eq!(flagged.bad_reports, 2);
+    assert!(flagged.featured && flagged.foreign_parts_only);
+}
+
+#[test]
+fn takes_truncated_names_from_the_title() {
+    let entry = crawler::top_rated_subs(&fixture("truncated.html"), &Ranking::default())
+        .unwrap()
+        .remove(0)
+        .entry;
+    assert_eq!(
+        entry.name,
+        "The Extraordinarily Long Title of a Movie Nobody Can Fit on One Line (2008)"
+    );
+    assert_eq!(
+        entry.release_names,
+        [
+            "The.Extraordinarily.Long.Title.2008.1080p.BluRay.x264-GROUP",
+            "The.Extraordinarily.Long.Title.2008.720p.BluRay.x264-OTHERGROUP",
+            "Short.Name.2008.DVDRip",
+        ]
+    );
+}
@@ -0,0 +1,131 @@
+//! the daemon's requests and answers, and the socket they go over
+use opensubtitlescli::daemon::{self, Listener, Request, Response};
+use std::{
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::Duration,
+};
+
+#[test]
+fn requests_are_tagged_by_action() {
+    let request: Request = serde_json::from_str(
+        r#"{"action":"download","path":"/movies/Movie.mkv","language":"pol"}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        request,
+        Request::Download {
+            path: "/movies/Movie.mkv".into(),
+            language: Some("pol".into())
+        }
+    );
+    let request: Request =
+        serde_json::from_str(r#"{"action":"download","path":"Movie.mkv"}"#).unwrap();
+    assert_eq!(
+        request,
+        Request::Download {
+            path: "Movie.mkv".into(),
+            language: None
+        }
+    );
+    assert!(serde_json::from_str::<Request>(r#"{"action":"delete","path":"/"}"#).is_err());
+}
+
+#[test]
+fn answers_leave_out_what_they_lack() {
+    assert_eq!(
+        serde_json::to_string(&Response::written(vec!["/movies/Movie.srt".into()])).unwrap(),
+        r#"{"ok":true,"written":["/movies/Movie.srt"]}"#
+    );
+    assert_eq!(
+        serde_json::to_string(&Response::failed("no subtitles")).unwrap(),
+        r#"{"ok":false,"error":"no subtitles"}"#
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn sends_over_the_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let address = dir
+        .path()
+        .join("daemon.sock")
+        .to_string_lossy()
+        .into_owned();
+    // left behind by a daemon that was killed
+    drop(std::os::unix::net::UnixListener::bind(&address).unwrap());
+    let listener = Listener::bind(&address).await.unwrap();
+    let server = tokio::spawn(async move {
+        let stream = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let request: Request = daemon::read_message(&mut tokio::io::BufReader::new(reader))
+            .await
+            .unwrap()
+            .unwrap();
+        let Request::Download { path, .. } = request;
+        daemon::write_message(
+            &mut writer,
+            &Response::written(vec![path.with_extension("srt")]),
+        )
+        .await
+        .unwrap();
+    });
+    let request = Request::Download {
+        path: "/movies/Movie.mkv".into(),
+        language: None,
+    };
+    assert_eq!(
+        daemon::send(&address, &request).await.unwrap(),
+        Response::written(vec!["/movies/Movie.srt".into()])
+    );
+    server.await.unwrap();
+    assert!(!PathBuf::from(&address).exists(), "the socket stayed");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn refuses_a_socket_another_daemon_listens_on() {
+    let dir = tempfile::tempdir().unwrap();
+    let address = dir
+        .path()
+        .join("daemon.sock")
+        .to_string_lossy()
+        .into_owned();
+    let _listener = Listener::bind(&address).await.unwrap();
+    let report = Listener::bind(&address).await.unwrap_err();
+    assert!(report.to_string().contains("another daemon"), "{report:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn answers_failures_and_stops_on_sigterm() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("daemon.sock");
+    let mut daemon = Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(["--base-url", "http://127.0.0.1:9", "daemon", "--socket"])
+        .arg(&socket)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    for _ in 0..100 {
+        if socket.exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let send = Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .arg("send")
+        .arg(dir.path().join("missing.mkv"))
+        .arg("--socket")
+        .arg(&socket)
+        .output()
+        .unwrap();
+    assert!(!send.status.success());
+    let response: Response = serde_json::from_slice(&send.stdout).unwrap();
+    assert!(!response.ok && response.error.is_some(), "{response:?}");
+
+    unsafe { libc::kill(daemon.id() as i32, libc::SIGTERM) };
+    assert!(daemon.wait().unwrap().success());
+    assert!(!socket.exists());
+}
@@ -0,0 +1,248 @@
+//! `.opensubtitlescli.toml`, reading it, finding it and what a run takes from it
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use opensubtitlescli::dir_config::{self, DirConfig, Value, ALLOWED, FILE_NAME};
+use std::process::Command;
+
+#[test]
+fn reads_the_toml_subset() {
+    let pairs = dir_config::parse(
+        "# for the anime\n\
+         language = \"eng\"\n\
+         top-n = 3 # a comment after\n\
+         fps = 23.976\n\
+         strip-tags = true\n\
+         'keep-tags' = 'i'\n\
+         query = \"say \\\"hi\\\" \\u00e9\"\n\
+         format-preference = [\n  \"srt\",\n  \"ass\", # second\n]\n",
+    )
+    .unwrap();
+    assert_eq!(
+        pairs,
+        vec![
+            ("language".to_string(), Value::String("eng".to_string())),
+            ("top-n".to_string(), Value::Integer(3)),
+            ("fps".to_string(), Value::Float(23.976)),
+            ("strip-tags".to_string(), Value::Bool(true)),
+            ("keep-tags".to_string(), Value::String("i".to_string())),
+            (
+                "query".to_string(),
+                Value::String("say \"hi\" é".to_string())
+            ),
+            (
+                "format-preference".to_string(),
+                Value::Array(vec![
+                    Value::String("srt".to_string()),
+                    Value::String("ass".to_string())
+                ])
+            ),
+        ]
+    );
+    assert_eq!(pairs[6].1.to_string(), "srt,ass");
+}
+
+#[test]
+fn says_which_line_is_wrong() {
+    for (text, expected) in [
+        ("language = \"eng\"\n[embed]\n", "line 2: tables"),
+        ("language = eng\n", "line 1: `eng` isn't a value"),
+        (
+            "language = \"eng\"\nlanguage = \"pol\"\n",
+            "line 2: `language` is set twice",
+        ),
+        ("top-n 3\n", "line 1: expected `=`"),
+        ("language = \"eng\n", "line 1: the string isn't closed"),
+        ("top-n = 3 4\n", "line 1: expected the end of the line"),
+    ] {
+        let report = dir_config::parse(text).unwrap_err();
+        assert!(
+            report.to_string().contains(expected),
+            "{report} for {text:?}"
+        );
+    }
+}
+
+#[test]
+fn finds_the_nearest_file_up_from_the_movie() {
+    let dir = tempfile::tempdir().unwrap();
+    let season = dir.path().join("anime").join("Show S01");
+    std::fs::create_dir_all(&season).unwrap();
+    std::fs::write(dir.path().join(FILE_NAME), "language = \"pol\"\n").unwrap();
+    std::fs::write(
+        dir.path().join("anime").join(FILE_NAME),
+        "language = \"eng\"\nstrip-hi = \"aggressive\"\n",
+    )
+    .unwrap();
+    let config = DirConfig::find(&season.join("Show.S01E01.mkv"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        config.path,
+        dir.path()
+            .join("anime")
+            .join(FILE_NAME)
+            .canonicalize()
+            .unwrap()
+    );
+    assert_eq!(
+        config.values[0],
+        ("language".to_string(), Value::String("eng".to_string()))
+    );
+    // the directory of episodes itself
+    let config = DirConfig::find(&dir.path().join("anime")).unwrap().unwrap();
+    assert_eq!(config.values.len(), 2);
+    let config = DirConfig::find(&dir.path().join("Movie.mkv"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        config.values,
+        vec![("language".to_string(), Value::String("pol".to_string()))]
+    );
+}
+
+#[test]
+fn refuses_what_could_run_programs_or_leave_the_movie() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(FILE_NAME);
+    std::fs::write(
+        &path,
+        "language = \"eng\"\n\
+         ffmpeg-path = \"/tmp/evil\"\n\
+         sync = \"alass\"\n\
+         proxy = \"http://127.0.0.1:8080\"\n\
+         base-url = \"https://mirror.example\"\n\
+         username = \"someone\"\n\
+         password = \"hunter2\"\n\
+         history-file = \"/tmp/history.json\"\n\
+         embed-in-place = true\n\
+         max-download-mb = 100000\n\
+         keep-archive = \"/etc/passwd\"\n\
+         no-such-flag = 1\n",
+    )
+    .unwrap();
+    let config = DirConfig::read(&path).unwrap();
+    assert_eq!(
+        config.values,
+        vec![("language".to_string(), Value::String("eng".to_string()))]
+    );
+    assert_eq!(
+        config.refused,
+        [
+            "ffmpeg-path",
+            "sync",
+            "proxy",
+            "base-url",
+            "username",
+            "password",
+            "history-file",
+            "embed-in-place",
+            "max-download-mb",
+            "keep-archive",
+            "no-such-flag",
+        ]
+    );
+}
+
+#[test]
+fn reads_what_archives_offer() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(FILE_NAME);
+    std::fs::write(
+        &path,
+        "archive-exclude = [\"nfo\", \"txt\"]\nsubtitles-only = false\n",
+    )
+    .unwrap();
+    let config = DirConfig::read(&path).unwrap();
+    assert!(config.refused.is_empty(), "{:?}", config.refused);
+    assert_eq!(
+        config.values,
+        vec![
+            (
+                "archive-exclude".to_string(),
+                Value::Array(vec![
+                    Value::String("nfo".to_string()),
+                    Value::String("txt".to_string())
+                ])
+            ),
+            ("subtitles-only".to_string(), Value::Bool(false)),
+        ]
+    );
+}
+
+#[test]
+fn allows_only_flags_there_are() {
+    let output = Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .arg("--help")
+        .output()
+        .unwrap();
+    let help = String::from_utf8(output.stdout).unwrap();
+    for key in ALLOWED {
+        if *key == "auto" && !cfg!(feature = "tui") {
+            continue;
+        }
+        assert!(help.contains(&format!("--{key}")), "--{key} in --help");
+    }
+}
+
+/// `-l` left to the config, the fixtures are only served for polish
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn takes_the_directory_language_unless_given() {
+    use common::{read_fixture, MockServer};
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let movie_file = dir.path().join("Big.Buck.Bunny.2008.1080p.BluRay.x264.mkv");
+    let contents = (0..200_000u32).map(|n| (n % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&movie_file, contents).unwrap();
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    server.route(
+        &format!("/pl/search/sublanguageid-pol/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("search.html"),
+    );
+    server.route(
+        &format!("/pl/search/sublanguageid-eng/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("empty_search.html"),
+    );
+    server.route(
+        "/download/sub/1000001",
+        200,
+        &[("Content-Type", "application/zip")],
+        read_fixture("subtitles.zip"),
+    );
+    std::fs::write(
+        dir.path().join(FILE_NAME),
+        "language = \"pol\"\nauto = true\nbase-url = \"http://127.0.0.1:1\"\n",
+    )
+    .unwrap();
+    // the server runs on this test's runtime, the binary is waited for without blocking it
+    let download = |args: &[&str]| {
+        tokio::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+            .arg("--base-url")
+            .arg(server.base_url.as_str())
+            .args(args)
+            .arg("-m")
+            .arg(&movie_file)
+            .env("OPENSUBTITLESCLI_HISTORY", dir.path().join("history.json"))
+            .env("OPENSUBTITLESCLI_NO_UPDATE_CHECK", "true")
+            .output()
+    };
+    let output = download(&[]).await.unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(movie_file.with_extension("srt").exists());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("base-url"), "{stderr}");
+
+    std::fs::remove_file(movie_file.with_extension("srt")).unwrap();
+    let output = download(&["-l", "eng"]).await.unwrap();
+    assert!(!movie_file.with_extension("srt").exists(), "{output:?}");
+    assert!(server
+        .hits()
+        .iter()
+        .any(|hit| hit.contains("sublanguageid-eng")));
+}
@@ -0,0 +1,91 @@
+//! `--edit`, which editor is run and how it's handed the subtitles
+use opensubtitlescli::editor::Editor;
+use std::{path::Path, process::Command};
+
+fn editor(program: &str, args: &[&str]) -> Editor {
+    Editor {
+        program: program.to_string(),
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+    }
+}
+
+#[test]
+fn splits_the_command_like_a_shell() {
+    assert_eq!(
+        Editor::parse("code --wait").unwrap(),
+        editor("code", &["--wait"])
+    );
+    assert_eq!(
+        Editor::parse(r#"  "C:\Program Files\Notepad++\notepad++.exe" -multiInst '' "#).unwrap(),
+        editor(
+            r"C:\Program Files\Notepad++\notepad++.exe",
+            &["-multiInst", ""]
+        )
+    );
+    assert!(Editor::parse("vim '-c").is_err());
+    assert!(Editor::parse("   ").is_err());
+}
+
+#[test]
+fn prefers_the_wait_command_then_visual_then_editor() {
+    let env = |name: &str| match name {
+        "VISUAL" => Some("gvim -f".to_string()),
+        "EDITOR" => Some("nano".to_string()),
+        _ => None,
+    };
+    assert_eq!(
+        Editor::find(Some("subl -w"), env).unwrap(),
+        editor("subl", &["-w"])
+    );
+    assert_eq!(Editor::find(None, env).unwrap(), editor("gvim", &["-f"]));
+    let editor_only = |name: &str| (name == "EDITOR").then(|| "nano".to_string());
+    assert_eq!(Editor::find(None, editor_only).unwrap().program, "nano");
+    // some platform default, whatever it is
+    assert!(!Editor::find(None, |_| None).unwrap().program.is_empty());
+}
+
+#[test]
+fn puts_the_file_where_the_braces_are() {
+    let path = Path::new("/movies/Movie.srt");
+    assert_eq!(
+        editor("code", &["--wait"]).args_for(path),
+        ["--wait", "/movies/Movie.srt"]
+    );
+    assert_eq!(
+        editor("emacsclient", &["-c", "{}", "--alternate-editor="]).args_for(path),
+        ["-c", "/movies/Movie.srt", "--alternate-editor="]
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn waits_for_the_editor_and_tells_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("Movie.srt");
+    std::fs::write(&path, "1\n00:00:01,000 --> 00:00:02,000\nHello\n").unwrap();
+    let fixing = editor(
+        "sh",
+        &["-c", "sleep 0.1; printf 'there\\n' >> \"$1\"", "sh"],
+    );
+    assert!(fixing.edit(&path).await.unwrap());
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "1\n00:00:01,000 --> 00:00:02,000\nHello\nthere\n"
+    );
+    assert!(!editor("true", &[]).edit(&path).await.unwrap());
+    assert!(editor("false", &[]).edit(&path).await.is_err());
+}
+
+#[test]
+fn refuses_to_edit_in_auto_mode() {
+    let output = Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(["--edit", "-m", "Movie.mkv"])
+        .args(cfg!(feature = "tui").then_some("--auto"))
+        .env("OPENSUBTITLESCLI_UI_LANGUAGE", "en")
+        .env("OPENSUBTITLESCLI_NO_UPDATE_CHECK", "true")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("can't go with --auto"), "{stderr}");
+}
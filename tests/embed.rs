@@ -0,0 +1,306 @@
+//! the embedding and burning in commands. new tracks are numbered after the subtitle
+//! streams the movie keeps, `tests/fixtures/ffprobe` has what ffprobe says about movies with
+//! 0, 1 and 3 of them
+#![cfg(feature = "embed")]
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::read_fixture;
+use opensubtitlescli::{
+    embed::{self, Container, Embedder, Existing, FfmpegEmbedder, MkvmergeEmbedder, Track},
+    language::LanguageCode,
+    probe, tools,
+};
+use std::path::{Path, PathBuf};
+
+/// the subtitle streams of the fixture, none of them dropped
+fn existing(name: &str) -> Existing {
+    let json = String::from_utf8(read_fixture(&format!("ffprobe/{name}.json"))).unwrap();
+    Existing {
+        streams: probe::subtitles(probe::parse_streams(&json).unwrap()),
+        dropped: vec![],
+    }
+}
+
+fn track(path: &str, language: &str) -> Track {
+    Track {
+        path: PathBuf::from(path),
+        language: LanguageCode::new(language),
+        title: None,
+        default: false,
+        forced: false,
+    }
+}
+
+fn tracks() -> Vec<Track> {
+    vec![track("movie.pol.srt", "pol"), track("movie.eng.srt", "eng")]
+}
+
+fn arguments(embedder: &dyn Embedder, existing: &Existing, tracks: &[Track]) -> Vec<String> {
+    let command = embedder.command(
+        Path::new("movie.mkv"),
+        existing,
+        tracks,
+        Path::new("out.mkv"),
+    );
+    tools::arguments(&command)
+}
+
+fn ffmpeg(existing: &Existing, tracks: &[Track]) -> Vec<String> {
+    let embedder = FfmpegEmbedder {
+        container: Container::Mkv,
+        drop_attachments: false,
+    };
+    arguments(&embedder, existing, tracks)
+}
+
+fn mkvmerge(existing: &Existing, tracks: &[Track]) -> Vec<String> {
+    let embedder = MkvmergeEmbedder {
+        drop_attachments: false,
+    };
+    arguments(&embedder, existing, tracks)
+}
+
+/// the values of every `flag` given, in order
+fn values<'a>(arguments: &'a [String], flag: &str) -> Vec<&'a str> {
+    arguments
+        .windows(2)
+        .filter(|pair| pair[0] == flag)
+        .map(|pair| pair[1].as_str())
+        .collect()
+}
+
+/// the per-stream codec and language flags of the output's subtitle streams
+fn numbered(arguments: &[String]) -> Vec<String> {
+    arguments
+        .windows(2)
+        .filter(|pair| pair[0].starts_with("-c:s:") || pair[0].starts_with("-metadata:s:s:"))
+        .map(|pair| format!("{} {}", pair[0], pair[1]))
+        .collect()
+}
+
+#[test]
+fn the_fixtures_have_0_1_and_3_subtitle_streams() {
+    assert_eq!(existing("no_subtitles").streams.len(), 0);
+    assert_eq!(existing("one_subtitle").streams.len(), 1);
+    let three = existing("three_subtitles");
+    assert_eq!(
+        three
+            .streams
+            .iter()
+            .map(|stream| stream.index)
+            .collect::<Vec<_>>(),
+        [2, 3, 4]
+    );
+    assert_eq!(three.kept(), 3);
+}
+
+#[test]
+fn tracks_of_a_movie_without_subtitles_start_at_0() {
+    let arguments = ffmpeg(&existing("no_subtitles"), &tracks());
+    assert_eq!(
+        numbered(&arguments),
+        [
+            "-c:s:0 srt",
+            "-metadata:s:s:0 language=pol",
+            "-c:s:1 srt",
+            "-metadata:s:s:1 language=eng",
+        ]
+    );
+    // the movie, then one input per track
+    assert_eq!(
+        values(&arguments, "-i"),
+        ["movie.mkv", "movie.pol.srt", "movie.eng.srt"]
+    );
+    assert_eq!(values(&arguments, "-map"), ["0", "-0:t?", "0:t?", "1", "2"]);
+}
+
+#[test]
+fn tracks_come_after_the_one_subtitle_stream() {
+    let arguments = ffmpeg(&existing("one_subtitle"), &tracks());
+    assert_eq!(
+        numbered(&arguments),
+        [
+            "-c:s:1 srt",
+            "-metadata:s:s:1 language=pol",
+            "-c:s:2 srt",
+            "-metadata:s:s:2 language=eng",
+        ]
+    );
+}
+
+#[test]
+fn tracks_come_after_every_subtitle_stream() {
+    let arguments = ffmpeg(&existing("three_subtitles"), &tracks()[..1]);
+    assert_eq!(
+        numbered(&arguments),
+        ["-c:s:3 srt", "-metadata:s:s:3 language=pol"]
+    );
+    let arguments = ffmpeg(&existing("three_subtitles"), &tracks());
+    assert_eq!(
+        numbered(&arguments),
+        [
+            "-c:s:3 srt",
+            "-metadata:s:s:3 language=pol",
+            "-c:s:4 srt",
+            "-metadata:s:s:4 language=eng",
+        ]
+    );
+}
+
+#[test]
+fn dropped_streams_free_their_numbers() {
+    let mut existing = existing("three_subtitles");
+    existing.dropped = existing.in_language(&LanguageCode::new("pol"));
+    assert_eq!(existing.dropped, [1]);
+    let arguments = ffmpeg(&existing, &tracks());
+    assert!(
+        values(&arguments, "-map").contains(&"-0:s:1"),
+        "{arguments:?}"
+    );
+    assert_eq!(
+        numbered(&arguments),
+        [
+            "-c:s:2 srt",
+            "-metadata:s:s:2 language=pol",
+            "-c:s:3 srt",
+            "-metadata:s:s:3 language=eng",
+        ]
+    );
+    // mkvmerge goes by track ids, which count the video and audio too
+    let arguments = mkvmerge(&existing, &tracks());
+    assert_eq!(values(&arguments, "--subtitle-tracks"), ["!3"]);
+    assert_eq!(values(&arguments, "--language"), ["0:pol", "0:eng"]);
+}
+
+/// the first token of `text` up to one of `stops`, unescaped and unquoted like ffmpeg's
+/// `av_get_token` does at both levels, and what's left after it
+fn token<'a>(text: &'a str, stops: &[char]) -> (String, &'a str) {
+    let (mut token, mut chars) = (String::new(), text.char_indices());
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' => token.extend(chars.next().map(|(_, c)| c)),
+            '\'' => token.extend(chars.by_ref().map(|(_, c)| c).take_while(|c| *c != '\'')),
+            c if stops.contains(&c) => return (token, &text[idx..]),
+            c => token.push(c),
+        }
+    }
+    (token, "")
+}
+
+/// the file name ffmpeg reads out of the filter
+fn filename(filter: &str) -> String {
+    let (arguments, rest) = token(filter.split_once('=').unwrap().1, &['[', ']', ',', ';']);
+    assert_eq!(rest, "", "{filter}");
+    let option = arguments.strip_prefix("filename=").unwrap();
+    let (value, rest) = token(option, &[':']);
+    assert_eq!(rest, "", "{filter}");
+    value
+}
+
+#[test]
+fn burn_in_paths_come_through_both_levels_of_escaping() {
+    let cases = [
+        ("movie.srt", "subtitles=filename='movie.srt'"),
+        ("it's.srt", r"subtitles=filename='it'\\\''s.srt'"),
+        ("a:b.srt", r"subtitles=filename='a\:b.srt'"),
+        (
+            "[group] movie.srt",
+            "subtitles=filename='[group] movie.srt'",
+        ),
+        ("one, two.ass", "ass=filename='one, two.ass'"),
+        (
+            r"C:\Movies\movie.srt",
+            r"subtitles=filename='C\:\\Movies\\movie.srt'",
+        ),
+        (
+            "C:/Movies/movie.srt",
+            r"subtitles=filename='C\:/Movies/movie.srt'",
+        ),
+    ];
+    for (path, expected) in cases {
+        let filter = embed::burn_in_filter(Path::new(path));
+        assert_eq!(filter, expected, "{path}");
+        assert_eq!(filename(&filter), path, "{filter}");
+    }
+    let tricky = r"D:\it's [a], b; c\x.srt";
+    assert_eq!(filename(&embed::burn_in_filter(Path::new(tricky))), tricky);
+}
+
+/// a burned in movie took long to encode, it's replaced only with `--force`
+#[test]
+fn burning_in_overwrites_only_when_forced() {
+    let movie_file = Path::new("dir/movie.mkv");
+    let output = embed::burn_in_output(movie_file);
+    assert_eq!(output, Path::new("dir/movie.burned-in.mkv"));
+    for (overwrite, flag, other) in [(false, "-n", "-y"), (true, "-y", "-n")] {
+        let options = embed::BurnIn {
+            crf: 20,
+            preset: "medium".to_string(),
+            overwrite,
+        };
+        let command = embed::burn_in_command(movie_file, Path::new("movie.srt"), &output, &options);
+        let arguments = tools::arguments(&command);
+        assert_eq!(arguments[1], flag, "{arguments:?}");
+        assert!(!arguments.iter().any(|v| v == other), "{arguments:?}");
+    }
+}
+
+#[test]
+fn the_printed_command_is_the_one_run() {
+    let existing = existing("one_subtitle");
+    let embedder = FfmpegEmbedder {
+        container: Container::Mkv,
+        drop_attachments: false,
+    };
+    let arguments = embed::command_arguments(
+        &embedder,
+        Path::new("my movie.mkv"),
+        &existing,
+        &tracks(),
+        Some(Path::new("out.mkv")),
+    );
+    assert_eq!(arguments[..4], ["ffmpeg", "-y", "-i", "my movie.mkv"]);
+    assert_eq!(arguments.last().map(String::as_str), Some("out.mkv"));
+    // in place, the temporary file is written first
+    let in_place = embed::command_arguments(
+        &embedder,
+        Path::new("my movie.mkv"),
+        &existing,
+        &tracks(),
+        None,
+    );
+    assert_eq!(
+        in_place.last().map(String::as_str),
+        Some(".my movie.embedding.mkv")
+    );
+
+    let json = embed::format_command(&arguments, embed::PrintCommand::Json).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Vec<String>>(&json).unwrap(),
+        arguments
+    );
+    let shell = embed::format_command(&arguments, embed::PrintCommand::Shell).unwrap();
+    assert!(shell.starts_with("ffmpeg -y -i "), "{shell}");
+    assert!(!shell.contains("-i my movie.mkv"), "{shell}");
+}
+
+#[test]
+fn the_json_document_has_the_command_as_arguments() {
+    use opensubtitlescli::results::Results;
+    let results = Results::new(true, Path::new("movie.mkv"), "pol");
+    let arguments = vec![
+        "mkvmerge".to_string(),
+        "-o".to_string(),
+        "out.mkv".to_string(),
+    ];
+    results.embed_command(arguments, "mkvmerge -o out.mkv");
+    let document = serde_json::to_value(results.document()).unwrap();
+    assert_eq!(
+        document["embed_command"],
+        serde_json::json!(["mkvmerge", "-o", "out.mkv"])
+    );
+    let empty = serde_json::to_value(Results::default().document()).unwrap();
+    assert_eq!(empty["embed_command"], serde_json::Value::Null);
+}
@@ -0,0 +1,150 @@
+//! what a build without some features leaves out isn't offered at all, run these with
+//! `--no-default-features` and `--no-default-features --features api-backend` too
+use opensubtitlescli::{archive, http};
+use std::process::Command;
+
+fn opensubtitlescli(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// the long flags `--help` lists
+fn flags(args: &[&str]) -> Vec<String> {
+    let output = opensubtitlescli(&[args, &["--help"]].concat());
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter(|line| line.trim_start().starts_with('-'))
+        .filter_map(|line| {
+            line.split_whitespace()
+                .find(|word| word.starts_with("--"))
+                .map(|flag| flag.trim_end_matches(',').to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn offers_the_flags_of_built_features() {
+    let flags = flags(&[]);
+    let expected = [
+        ("--auto", cfg!(feature = "tui")),
+        ("--embed-in-place", cfg!(feature = "embed")),
+        ("--burn-in", cfg!(feature = "embed")),
+        ("--set-default", cfg!(feature = "embed")),
+        ("--no-history", cfg!(feature = "history")),
+        ("--history-file", cfg!(feature = "history")),
+        ("--no-update-check", cfg!(feature = "self-update")),
+        // the same in every build
+        ("--movie-file", true),
+        ("--auto-retime", true),
+        ("--embed-timeout", true),
+        ("--insecure", true),
+    ];
+    for (flag, built) in expected {
+        assert_eq!(
+            flags.iter().any(|v| v == flag),
+            built,
+            "{flag} in {flags:?}"
+        );
+    }
+}
+
+#[test]
+fn offers_the_subcommands_of_built_features() {
+    for subcommand in ["undo", "history", "rate", "report", "stats"] {
+        let output = opensubtitlescli(&[subcommand, "--help"]);
+        assert_eq!(
+            output.status.success(),
+            cfg!(feature = "history"),
+            "{output:?}"
+        );
+    }
+    assert_eq!(
+        opensubtitlescli(&["self-update", "--help"])
+            .status
+            .success(),
+        cfg!(feature = "self-update")
+    );
+    assert!(opensubtitlescli(&["extract-subs", "--help"])
+        .status
+        .success());
+}
+
+/// nobody to ask which streams to take
+#[cfg(not(feature = "tui"))]
+#[test]
+fn extracting_needs_the_streams_named() {
+    let output = opensubtitlescli(&["extract-subs", "movie.mkv"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--all"), "{stderr}");
+    let flags = flags(&["extract-subs"]);
+    assert!(flags.iter().any(|v| v == "--language"), "{flags:?}");
+}
+
+#[cfg(not(feature = "rar"))]
+#[test]
+fn refuses_rar_archives() {
+    let rar = b"Rar!\x1a\x07\x01\x00".to_vec();
+    let Err(report) = archive::open(rar, &archive::Options::default()) else {
+        panic!("opened a rar archive");
+    };
+    assert!(
+        format!("{report:?}").contains("`rar` feature"),
+        "{report:?}"
+    );
+}
+
+#[test]
+fn opens_zip_archives_in_every_build() {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    zip.start_file("movie.srt", zip::write::FileOptions::default())
+        .unwrap();
+    std::io::Write::write_all(&mut zip, b"1\n00:00:01,000 --> 00:00:02,000\nhello\n").unwrap();
+    let bytes = zip.finish().unwrap().into_inner();
+    let archive = archive::open(bytes, &archive::Options::default()).unwrap();
+    assert_eq!(archive.file_names(), vec!["movie.srt"]);
+}
+
+/// the site is only served over https, a build without tls says what it misses
+#[tokio::test]
+async fn https_needs_the_api_backend() {
+    use http::HttpFetch;
+    // nothing listens there, a build with tls fails to connect instead
+    let url = "https://127.0.0.1:9/".parse().unwrap();
+    let fetched = http::ReqwestFetch::default()
+        .get_text(http::Request::get(url))
+        .await;
+    let Err(report) = fetched else {
+        panic!("fetched from a closed port");
+    };
+    assert_eq!(
+        format!("{report:?}").contains("`api-backend` feature"),
+        !cfg!(any(feature = "native-tls", feature = "rustls-tls")),
+        "{report:?}"
+    );
+}
+
+/// `--no-default-features --features api-backend`, nothing but searching and downloading. it
+/// fetches over https, `https_needs_the_api_backend` checks that
+#[cfg(all(
+    feature = "api-backend",
+    not(any(
+        feature = "blocking",
+        feature = "embed",
+        feature = "history",
+        feature = "rar",
+        feature = "self-update",
+        feature = "tui"
+    ))
+))]
+#[test]
+fn the_smallest_build_searches_and_downloads() {
+    let flags = flags(&[]);
+    for flag in ["--movie-file", "--language", "--top-n", "--base-url"] {
+        assert!(flags.iter().any(|v| v == flag), "{flag} in {flags:?}");
+    }
+}
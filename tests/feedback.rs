@@ -0,0 +1,219 @@
+//! `rate` and `report`, the calls they make and how they fail without a recorded download
+//! or an account
+use opensubtitlescli::{
+    api::{self, Credentials},
+    client::ConfigError,
+    feedback::{Feedback, ReportReason},
+    http::{FakeHttp, Reply},
+    Client,
+};
+use std::sync::Arc;
+
+fn ok(members: &str) -> Reply {
+    Reply::ok(format!(
+        "<methodResponse><params><param><value><struct>{members}\
+         <member><name>status</name><value><string>200 OK</string></value></member>\
+         </struct></value></param></params></methodResponse>"
+    ))
+}
+
+fn http() -> Arc<FakeHttp> {
+    let http = FakeHttp::new();
+    http.reply(
+        api::API_URL,
+        ok("<member><name>token</name><value><string>secret-token</string></value></member>"),
+    )
+    .reply(api::API_URL, ok(""))
+    .reply(api::API_URL, ok(""));
+    Arc::new(http)
+}
+
+fn credentials() -> Credentials {
+    Credentials {
+        username: "someone".to_string(),
+        password: "hunter2".to_string(),
+    }
+}
+
+/// the body of every call made
+async fn sent(feedback: Feedback) -> Vec<String> {
+    let http = http();
+    Client::builder()
+        .http(http.clone())
+        .credentials(Some(credentials()))
+        .build()
+        .unwrap()
+        .feedback(&feedback)
+        .await
+        .unwrap();
+    http.requests()
+        .iter()
+        .map(|recorded| String::from_utf8_lossy(recorded.request.body.as_deref().unwrap()).into())
+        .collect()
+}
+
+#[tokio::test]
+async fn votes() {
+    let calls = sent(Feedback::Vote {
+        subtitle_id: 1000001,
+        stars: 8,
+    })
+    .await;
+    assert_eq!(calls.len(), 3, "{calls:?}");
+    let vote = &calls[1];
+    for expected in [
+        "<methodName>SubtitlesVote</methodName>",
+        "<string>secret-token</string>",
+        "<name>idsubtitle</name><value><string>1000001</string>",
+        "<name>score</name><value><int>8</int>",
+    ] {
+        assert!(vote.contains(expected), "{expected} in {vote}");
+    }
+    assert!(calls[2].contains("<methodName>LogOut</methodName>"));
+}
+
+#[tokio::test]
+async fn reports_with_the_reason_and_the_comment() {
+    let calls = sent(Feedback::Report {
+        subtitle_id: 1000001,
+        reason: ReportReason::OutOfSync,
+        comment: Some("two seconds late".to_string()),
+    })
+    .await;
+    let report = &calls[1];
+    for expected in [
+        "<methodName>AddComment</methodName>",
+        "<name>comment</name><value><string>out of sync: two seconds late</string>",
+        "<name>badsubtitle</name><value><int>1</int>",
+    ] {
+        assert!(report.contains(expected), "{expected} in {report}");
+    }
+}
+
+#[tokio::test]
+async fn refuses_feedback_the_site_wouldnt_take_before_logging_in() {
+    let http = Arc::new(FakeHttp::new());
+    let client = Client::builder()
+        .http(http.clone())
+        .credentials(Some(credentials()))
+        .build()
+        .unwrap();
+    for feedback in [
+        Feedback::Vote {
+            subtitle_id: 1000001,
+            stars: 11,
+        },
+        Feedback::Report {
+            subtitle_id: 1000001,
+            reason: ReportReason::Other,
+            comment: None,
+        },
+    ] {
+        assert!(client.feedback(&feedback).await.is_err());
+    }
+    assert!(http.requests().is_empty());
+}
+
+#[tokio::test]
+async fn an_account_is_needed_to_log_in() {
+    let http = http();
+    let builder = || Client::builder().http(http.clone());
+    let report = builder().account_required(true).build().unwrap_err();
+    assert_eq!(
+        ConfigError::find(&report),
+        Some(&ConfigError::MissingCredentials)
+    );
+    builder()
+        .account_required(true)
+        .credentials(Some(credentials()))
+        .build()
+        .unwrap();
+    // built for searching only, the feedback fails before anything is sent
+    let feedback = Feedback::Vote {
+        subtitle_id: 1000001,
+        stars: 8,
+    };
+    let report = builder()
+        .build()
+        .unwrap()
+        .feedback(&feedback)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        ConfigError::find(&report),
+        Some(&ConfigError::MissingCredentials)
+    );
+    assert!(http.requests().is_empty());
+}
+
+#[cfg(feature = "history")]
+mod from_the_history {
+    use opensubtitlescli::history::{content_hash, Download, Entry, History};
+    use std::{path::Path, process::Command};
+
+    fn rate(history: &Path, path: &Path) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+            .arg("--history-file")
+            .arg(history)
+            .arg("rate")
+            .arg(path)
+            .args(["--stars", "8"])
+            .env("OPENSUBTITLESCLI_UI_LANGUAGE", "en")
+            .env_remove("OPENSUBTITLESCLI_USERNAME")
+            .env_remove("OPENSUBTITLESCLI_PASSWORD")
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn fails_for_a_download_not_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = rate(
+            &dir.path().join("history.json"),
+            &dir.path().join("Movie.srt"),
+        );
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("before the history was kept"), "{stderr}");
+    }
+
+    #[tokio::test]
+    async fn fails_without_an_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie = dir.path().join("Movie.mkv");
+        let output = movie.with_extension("srt");
+        std::fs::write(&output, "1\n00:00:01,000 --> 00:00:02,000\nhello\n").unwrap();
+        let history = History::new(dir.path().join("history.json"));
+        history
+            .record(vec![Download {
+                run: "2026-10-14T12:00:00Z".parse().unwrap(),
+                movie,
+                movie_hash: None,
+                language: "pol".to_string(),
+                provider: "opensubtitles.org".to_string(),
+                subtitle_id: 1000001,
+                entry: Entry {
+                    name: "Movie (2019)".to_string(),
+                    release_name: None,
+                    format: "srt".to_string(),
+                    uploaded_by: "uploader".to_string(),
+                    rating: None,
+                    downloads: 1,
+                },
+                output: output.clone(),
+                downloaded_at: "2026-10-14T12:00:00Z".parse().unwrap(),
+                content_hash: content_hash(b""),
+                video: None,
+                selection: None,
+            }])
+            .await
+            .unwrap();
+        let rated = rate(history.path(), &output);
+        assert!(!rated.status.success());
+        let stderr = String::from_utf8_lossy(&rated.stderr);
+        assert!(
+            stderr.contains("needs a logged in opensubtitles.org account"),
+            "{stderr}"
+        );
+    }
+}
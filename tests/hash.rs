@@ -0,0 +1,176 @@
+//! the movie hash has to match what the site computed when the movie was uploaded
+use opensubtitlescli::hash::{
+    hash_file, hash_files, hash_for_file, hash_with, HashError, Method, HASH_CONCURRENCY,
+};
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// a file of `size` bytes in a pattern that differs in every 64 KiB block
+fn movie(dir: &TempDir, size: usize) -> PathBuf {
+    let path = dir.path().join(format!("{size}.mkv"));
+    let contents = (0..size)
+        .map(|n| ((n * 7919 + n / 251) % 256) as u8)
+        .collect::<Vec<_>>();
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// computed by the word at a time implementation the hash started out as
+const VECTORS: &[(usize, &str)] = &[
+    (131072, "4607c48244019c7e"),
+    (200_000, "e6a96623e3a1495e"),
+    (1_000_003, "5413d18e4e19e9cc"),
+];
+
+#[test]
+fn matches_known_vectors() {
+    let dir = tempfile::tempdir().unwrap();
+    for (size, expected) in VECTORS {
+        assert_eq!(
+            hash_for_file(movie(&dir, *size)).unwrap(),
+            *expected,
+            "{size}"
+        );
+    }
+}
+
+/// the sample the site's documentation publishes a hash for, too big to keep in the
+/// repository
+const BREAKDANCE: (u64, &str) = (12_909_756, "8e245d9679d31e12");
+
+#[test]
+#[ignore = "needs breakdance.avi, set OPENSUBTITLESCLI_BREAKDANCE to where it is"]
+fn matches_the_published_sample() {
+    let Some(path) = std::env::var_os("OPENSUBTITLESCLI_BREAKDANCE") else {
+        eprintln!("OPENSUBTITLESCLI_BREAKDANCE isn't set, skipping");
+        return;
+    };
+    let (size, expected) = BREAKDANCE;
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+    for method in [Method::Mmap, Method::Read] {
+        assert_eq!(hash_with(&path, method).unwrap(), expected, "{method:?}");
+    }
+}
+
+/// the size plus the little endian words of the first and the last 64 KiB, wrapping
+fn reference_hash(contents: &[u8]) -> String {
+    let sum = |block: &[u8]| {
+        block
+            .chunks(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .fold(0u64, u64::wrapping_add)
+    };
+    let size = contents.len();
+    let hash = (size as u64)
+        .wrapping_add(sum(&contents[..65536]))
+        .wrapping_add(sum(&contents[size - 65536..]));
+    format!("{hash:016x}")
+}
+
+#[test]
+fn matches_the_definition() {
+    let dir = tempfile::tempdir().unwrap();
+    let edges = [131072, 131072 + 7, 131072 + 8, 131072 * 2 - 1];
+    let random = (0..30u64).map(|seed| 131072 + (seed as usize * 104_729) % 2_000_000);
+    for (seed, size) in edges.into_iter().chain(random).enumerate() {
+        let path = dir.path().join(format!("{seed}.mkv"));
+        let contents = random_bytes(seed as u64 + 1000, size);
+        std::fs::write(&path, &contents).unwrap();
+        let hash = hash_for_file(&path).unwrap();
+        assert_eq!(hash, reference_hash(&contents), "{size}");
+        assert!(
+            hash.len() == 16 && hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')),
+            "{hash}"
+        );
+    }
+}
+
+#[test]
+fn keeps_leading_zeroes() {
+    let dir = tempfile::tempdir().unwrap();
+    for (size, expected) in [
+        (131072, "0000000000020000"),
+        (131072 + 7, "0000000000020007"),
+        // over 4 GiB, the size doesn't fit in 32 bits
+        (4_295_033_000, "00000001000100a8"),
+    ] {
+        let path = dir.path().join(format!("{size}.mkv"));
+        std::fs::File::create(&path).unwrap().set_len(size).unwrap();
+        for method in [Method::Mmap, Method::Read] {
+            assert_eq!(
+                hash_with(&path, method).unwrap(),
+                expected,
+                "{size} {method:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn zeroes_hash_to_the_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("zeroes.mkv");
+    std::fs::write(&path, vec![0u8; 131072]).unwrap();
+    assert_eq!(hash_for_file(&path).unwrap(), "0000000000020000");
+}
+
+#[test]
+fn refuses_files_under_128_kib() {
+    let dir = tempfile::tempdir().unwrap();
+    for size in [1000, 65536, 65537, 131072 - 7, 131071] {
+        let report = hash_for_file(movie(&dir, size)).unwrap_err();
+        assert_eq!(
+            HashError::find(&report),
+            Some(HashError::TooSmall { size: size as u64 })
+        );
+        assert!(report.to_string().contains("--query"), "{report}");
+    }
+}
+
+#[tokio::test]
+async fn hashes_batches_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = VECTORS
+        .iter()
+        .map(|(size, _)| movie(&dir, *size))
+        .collect::<Vec<_>>();
+    let hashes = hash_files(paths.clone(), Method::default(), HASH_CONCURRENCY).await;
+    for ((_, expected), hash) in VECTORS.iter().zip(hashes) {
+        assert_eq!(hash.unwrap(), *expected);
+    }
+    assert_eq!(
+        hash_file(paths[0].clone(), Method::default())
+            .await
+            .unwrap(),
+        VECTORS[0].1
+    );
+}
+
+/// xorshift, the same files on every run
+fn random_bytes(seed: u64, size: usize) -> Vec<u8> {
+    let mut state = seed.max(1);
+    (0..size)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        })
+        .collect()
+}
+
+#[test]
+fn mapping_and_reading_agree() {
+    let dir = tempfile::tempdir().unwrap();
+    let edges = [131072, 131073, 131072 + 8];
+    let random = (0..20u64).map(|seed| 131072 + (seed as usize * 40_503) % 400_000);
+    for (seed, size) in edges.into_iter().chain(random).enumerate() {
+        let path = dir.path().join(format!("{seed}.mkv"));
+        std::fs::write(&path, random_bytes(seed as u64, size)).unwrap();
+        assert_eq!(
+            hash_with(&path, Method::Mmap).unwrap(),
+            hash_with(&path, Method::Read).unwrap(),
+            "{size}"
+        );
+    }
+}
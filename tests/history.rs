@@ -0,0 +1,265 @@
+//! downloads recorded in the history file, and files written by other versions of it
+#![cfg(feature = "history")]
+use opensubtitlescli::history::{
+    content_hash, undo_steps, Download, Entry, History, Selection, Stats, Step, Video,
+    SCHEMA_VERSION,
+};
+use std::path::Path;
+
+fn download(dir: &Path, movie: &str, subtitle_id: u64) -> Download {
+    let movie = dir.join(movie);
+    Download {
+        run: "2026-10-14T12:00:00Z".parse().unwrap(),
+        output: movie.with_extension("srt"),
+        movie,
+        movie_hash: Some("33930e90499aa99c".to_string()),
+        language: "pol".to_string(),
+        provider: "opensubtitles.org".to_string(),
+        subtitle_id,
+        entry: Entry {
+            name: "Big Buck Bunny (2008)".to_string(),
+            release_name: Some("Big.Buck.Bunny.2008.1080p.BluRay.x264".to_string()),
+            format: "srt".to_string(),
+            uploaded_by: "uploader".to_string(),
+            rating: Some(9.5),
+            downloads: 1523,
+        },
+        downloaded_at: "2026-10-14T12:00:00Z".parse().unwrap(),
+        content_hash: content_hash(SUBTITLES),
+        video: None,
+        selection: Some(Selection::Auto),
+    }
+}
+
+const SUBTITLES: &[u8] = b"1\n00:00:01,000 --> 00:00:02,000\nhello\n";
+
+#[test]
+fn hashes_contents_as_sha256() {
+    assert_eq!(
+        content_hash(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn starts_out_empty() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.json"));
+    assert_eq!(history.load().unwrap(), vec![]);
+}
+
+#[tokio::test]
+async fn records_and_finds_downloads() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("data").join("history.json"));
+    let first = download(dir.path(), "first.mkv", 1);
+    let second = download(dir.path(), "second.mkv", 2);
+    history.record(vec![first.clone()]).await.unwrap();
+    history.record(vec![second.clone()]).await.unwrap();
+    assert_eq!(history.load().unwrap(), vec![first.clone(), second.clone()]);
+    assert_eq!(history.for_path(&first.movie).unwrap(), vec![first]);
+    assert_eq!(history.for_path(&second.output).unwrap(), vec![second]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn runs_at_the_same_time_keep_every_download() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.json");
+    let runs = (1..=8).map(|subtitle_id| {
+        let (history, download) = (
+            History::new(path.clone()),
+            download(dir.path(), "movie.mkv", subtitle_id),
+        );
+        tokio::spawn(async move { history.record(vec![download]).await })
+    });
+    for run in runs.collect::<Vec<_>>() {
+        run.await.unwrap().unwrap();
+    }
+    let mut recorded = History::new(path.clone())
+        .load()
+        .unwrap()
+        .into_iter()
+        .map(|download| download.subtitle_id)
+        .collect::<Vec<_>>();
+    recorded.sort();
+    assert_eq!(recorded, (1..=8).collect::<Vec<_>>());
+    assert!(!dir.path().join("history.json.lock").exists());
+}
+
+#[tokio::test]
+async fn takes_over_the_lock_of_a_crashed_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.json"));
+    let lock = std::fs::File::create(dir.path().join("history.json.lock")).unwrap();
+    let crashed = std::time::SystemTime::now() - std::time::Duration::from_secs(60);
+    lock.set_modified(crashed).unwrap();
+    history
+        .record(vec![download(dir.path(), "movie.mkv", 1)])
+        .await
+        .unwrap();
+    assert_eq!(history.load().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn purges_everything() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.json"));
+    history
+        .record(vec![download(dir.path(), "movie.mkv", 1)])
+        .await
+        .unwrap();
+    assert_eq!(history.purge().await.unwrap(), 1);
+    assert_eq!(history.load().unwrap(), vec![]);
+    assert_eq!(history.purge().await.unwrap(), 0);
+}
+
+#[test]
+fn migrates_the_first_schema() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.json");
+    let mut recorded = download(dir.path(), "movie.mkv", 1);
+    recorded.downloaded_at = "2026-10-14T12:00:01Z".parse().unwrap();
+    let mut first = serde_json::to_value(&recorded).unwrap();
+    first.as_object_mut().unwrap().remove("run");
+    first.as_object_mut().unwrap().remove("video");
+    first.as_object_mut().unwrap().remove("selection");
+    let stored = serde_json::json!({ "version": 1, "downloads": [first] });
+    std::fs::write(&path, stored.to_string()).unwrap();
+    // every download of the first schema is a run of its own
+    recorded.run = recorded.downloaded_at;
+    recorded.selection = None;
+    assert_eq!(History::new(path).load().unwrap(), vec![recorded]);
+}
+
+#[test]
+fn migrates_downloads_without_a_selection() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.json");
+    let mut recorded = download(dir.path(), "movie.mkv", 1);
+    let mut second = serde_json::to_value(&recorded).unwrap();
+    second.as_object_mut().unwrap().remove("selection");
+    let stored = serde_json::json!({ "version": 2, "downloads": [second] });
+    std::fs::write(&path, stored.to_string()).unwrap();
+    recorded.selection = None;
+    assert_eq!(History::new(path).load().unwrap(), vec![recorded]);
+}
+
+#[test]
+fn refuses_newer_schemas() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.json");
+    let stored = serde_json::json!({ "version": SCHEMA_VERSION + 1, "downloads": [] });
+    std::fs::write(&path, stored.to_string()).unwrap();
+    let report = History::new(path).load().unwrap_err();
+    assert!(
+        format!("{report:?}").contains("newer version"),
+        "{report:?}"
+    );
+}
+
+#[tokio::test]
+async fn finds_the_last_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let history = History::new(dir.path().join("history.json"));
+    let earlier = download(dir.path(), "movie.mkv", 1);
+    let mut later = download(dir.path(), "movie.mkv", 2);
+    later.run = "2026-10-15T12:00:00Z".parse().unwrap();
+    let other = download(dir.path(), "other.mkv", 3);
+    history
+        .record(vec![earlier.clone(), later.clone(), other.clone()])
+        .await
+        .unwrap();
+    assert_eq!(history.last_run(None).unwrap(), vec![later.clone()]);
+    let movie = dir.path().join("other.mkv");
+    assert_eq!(history.last_run(Some(&movie)).unwrap(), vec![other]);
+    history.forget(&[later]).await.unwrap();
+    let movie = dir.path().join("movie.mkv");
+    assert_eq!(history.last_run(Some(&movie)).unwrap(), vec![earlier]);
+}
+
+#[test]
+fn undoes_written_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let recorded = download(dir.path(), "movie.mkv", 1);
+    std::fs::write(&recorded.output, SUBTITLES).unwrap();
+    let steps = undo_steps(std::slice::from_ref(&recorded), false).unwrap();
+    assert_eq!(steps, vec![Step::Remove(recorded.output.clone())]);
+    std::fs::remove_file(&recorded.output).unwrap();
+    assert_eq!(undo_steps(&[recorded], false).unwrap(), vec![]);
+}
+
+#[test]
+fn refuses_to_undo_edited_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let recorded = download(dir.path(), "movie.mkv", 1);
+    std::fs::write(&recorded.output, b"edited").unwrap();
+    let report = undo_steps(&[recorded], false).unwrap_err();
+    assert!(format!("{report:?}").contains("changed"), "{report:?}");
+}
+
+#[tokio::test]
+async fn undoes_the_movie_with_subtitles() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut recorded = download(dir.path(), "movie.mkv", 1);
+    std::fs::write(&recorded.output, SUBTITLES).unwrap();
+    let with_subs = dir.path().join("movie.with-subs.mkv");
+    std::fs::write(&with_subs, b"movie").unwrap();
+    recorded.video = Some(Video {
+        path: with_subs.clone(),
+        backup: None,
+    });
+    let steps = undo_steps(std::slice::from_ref(&recorded), false).unwrap();
+    assert_eq!(steps, vec![Step::Remove(recorded.output.clone())]);
+    let steps = undo_steps(std::slice::from_ref(&recorded), true).unwrap();
+    assert_eq!(steps.last(), Some(&Step::Remove(with_subs)));
+
+    let backup = dir.path().join("movie.mkv.bak");
+    std::fs::write(&backup, b"movie").unwrap();
+    recorded.video = Some(Video {
+        path: recorded.movie.clone(),
+        backup: Some(backup.clone()),
+    });
+    let steps = undo_steps(&[recorded.clone()], false).unwrap();
+    for step in &steps {
+        step.apply().await.unwrap();
+    }
+    assert!(!recorded.output.exists() && !backup.exists());
+    assert_eq!(std::fs::read(&recorded.movie).unwrap(), b"movie");
+}
+
+#[test]
+fn counts_downloads_since_a_day() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut old = download(dir.path(), "old.mkv", 1);
+    old.downloaded_at = "2024-03-10T12:00:00Z".parse().unwrap();
+    old.selection = None;
+    old.entry.rating = None;
+    let mut english = download(dir.path(), "english.mkv", 2);
+    english.language = "eng".to_string();
+    english.selection = Some(Selection::Asked);
+    english.entry.rating = Some(7.5);
+    let recent = download(dir.path(), "recent.mkv", 3);
+    let downloads = [old, english, recent];
+
+    let stats = Stats::of(&downloads, None);
+    assert_eq!(stats.downloads, 3);
+    assert_eq!(
+        stats.per_language,
+        vec![("pol".to_string(), 2), ("eng".to_string(), 1)]
+    );
+    assert_eq!(
+        stats.per_month,
+        vec![("2024-03".to_string(), 1), ("2026-10".to_string(), 2)]
+    );
+    assert_eq!(stats.top_uploaders, vec![("uploader".to_string(), 3)]);
+    assert_eq!((stats.average_rating, stats.rated), (Some(8.5), 2));
+    assert_eq!(
+        (stats.auto, stats.asked, stats.unknown_selection),
+        (1, 1, 1)
+    );
+
+    let stats = Stats::of(&downloads, Some("2026-01-01".parse().unwrap()));
+    assert_eq!(stats.downloads, 2);
+    assert_eq!(stats.per_month, vec![("2026-10".to_string(), 2)]);
+    assert_eq!(stats.unknown_selection, 0);
+}
@@ -0,0 +1,109 @@
+//! what a torrent client hands the hook, and which of its files get subtitles
+use opensubtitlescli::hook::{self, TorrentClient};
+use std::{ffi::OsString, path::PathBuf};
+
+fn no_env(_: &str) -> Option<OsString> {
+    None
+}
+
+#[test]
+fn transmission_names_the_download_in_its_variables() {
+    let env = |name: &str| match name {
+        "TR_TORRENT_DIR" => Some("/downloads".into()),
+        "TR_TORRENT_NAME" => Some("Movie.2019.1080p".into()),
+        _ => None,
+    };
+    assert_eq!(
+        TorrentClient::Transmission.content_path(None, env).unwrap(),
+        PathBuf::from("/downloads/Movie.2019.1080p")
+    );
+    assert!(TorrentClient::Transmission
+        .content_path(None, no_env)
+        .is_err());
+}
+
+#[test]
+fn qbittorrent_needs_the_content_path() {
+    assert!(TorrentClient::Qbittorrent
+        .content_path(None, no_env)
+        .is_err());
+    assert_eq!(
+        TorrentClient::Qbittorrent
+            .content_path(Some("/downloads/Movie.mkv".into()), no_env)
+            .unwrap(),
+        PathBuf::from("/downloads/Movie.mkv")
+    );
+}
+
+#[test]
+fn finds_videos_in_the_torrent_folder_without_samples() {
+    let dir = tempfile::tempdir().unwrap();
+    let torrent = dir.path().join("Show.Samples.Of.Life.S01.1080p");
+    for file in [
+        "Show.S01E01.mkv",
+        "Season 2/Show.S02E01.MP4",
+        "Show.S01E01-sample.mkv",
+        "Sample/Show.S01E02.mkv",
+        "Show.S01E01.nfo",
+        "Show.S01E01.srt",
+    ] {
+        let path = torrent.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"").unwrap();
+    }
+    assert_eq!(
+        hook::video_files(&torrent).unwrap(),
+        vec![
+            torrent.join("Season 2/Show.S02E01.MP4"),
+            torrent.join("Show.S01E01.mkv")
+        ]
+    );
+}
+
+#[test]
+fn a_single_file_download_is_the_video() {
+    let dir = tempfile::tempdir().unwrap();
+    let movie = dir.path().join("Movie.2019.1080p.mkv");
+    std::fs::write(&movie, b"").unwrap();
+    assert_eq!(hook::video_files(&movie).unwrap(), vec![movie]);
+    let sample = dir.path().join("sample.mkv");
+    std::fs::write(&sample, b"").unwrap();
+    assert!(hook::video_files(&sample).unwrap().is_empty());
+}
+
+#[test]
+fn nothing_to_do_is_not_an_error() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("album.flac"), b"").unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(["hook", "--client", "qbittorrent"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+}
+
+/// the audio is probed first, a video skipped for it is never read
+#[cfg(unix)]
+#[test]
+fn a_video_skipped_for_its_audio_is_not_hashed() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("Movie.2019.1080p.mkv"), vec![0; 200_000]).unwrap();
+    let bin = tempfile::tempdir().unwrap();
+    let ffprobe = bin.path().join("ffprobe");
+    let streams =
+        r#"{"streams": [{"index": 1, "codec_type": "audio", "tags": {"language": "pol"}}]}"#;
+    std::fs::write(&ffprobe, format!("#!/bin/sh\necho '{streams}'\n")).unwrap();
+    std::fs::set_permissions(&ffprobe, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(["--language", "pol", "--skip-if-audio-matches", "--timings"])
+        .args(["hook", "--client", "qbittorrent"])
+        .arg(dir.path())
+        .env("PATH", bin.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("hashing"), "{stderr}");
+}
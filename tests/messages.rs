@@ -0,0 +1,86 @@
+//! the message catalog, every bundled language has every message
+use opensubtitlescli::messages::{self, placeholders, UiLanguage};
+use std::collections::{BTreeMap, BTreeSet};
+
+fn catalog(language: UiLanguage) -> BTreeMap<&'static str, &'static str> {
+    let catalog = language.catalog();
+    let keys = catalog.iter().map(|(key, _)| *key).collect::<BTreeSet<_>>();
+    assert_eq!(keys.len(), catalog.len(), "a key repeats in {language:?}");
+    catalog.iter().copied().collect()
+}
+
+#[test]
+fn every_language_has_every_message() {
+    let english = catalog(UiLanguage::En);
+    for language in UiLanguage::ALL {
+        let translated = catalog(language);
+        assert_eq!(
+            translated.keys().collect::<Vec<_>>(),
+            english.keys().collect::<Vec<_>>(),
+            "the keys of {language:?}"
+        );
+        for (key, text) in &translated {
+            assert!(!text.trim().is_empty(), "{key} is empty in {language:?}");
+            let mut expected = placeholders(english[key]);
+            let mut found = placeholders(text);
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected, "the placeholders of {key} in {language:?}");
+        }
+    }
+}
+
+#[test]
+fn fills_in_placeholders() {
+    assert_eq!(
+        messages::fill(
+            "rated subtitle {subtitle} {stars}/10",
+            &[("subtitle", &1000001), ("stars", &8)]
+        ),
+        "rated subtitle 1000001 8/10"
+    );
+    assert_eq!(
+        placeholders("{count} downloads since {day}"),
+        ["count", "day"]
+    );
+}
+
+#[test]
+fn picks_polish_from_the_locale() {
+    let locale = |vars: &'static [(&'static str, &'static str)]| {
+        UiLanguage::from_locale(move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        })
+    };
+    assert_eq!(locale(&[("LANG", "pl_PL.UTF-8")]), UiLanguage::Pl);
+    assert_eq!(
+        locale(&[("LC_ALL", "en_US.UTF-8"), ("LANG", "pl_PL.UTF-8")]),
+        UiLanguage::En
+    );
+    // empty ones don't count
+    assert_eq!(
+        locale(&[("LC_ALL", ""), ("LC_MESSAGES", "pl_PL")]),
+        UiLanguage::Pl
+    );
+    assert_eq!(locale(&[]), UiLanguage::En);
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn speaks_the_ui_language() {
+    let dir = tempfile::tempdir().unwrap();
+    let stats = |language: &str| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+            .args(["--ui-language", language, "--history-file"])
+            .arg(dir.path().join("history.json"))
+            .arg("stats")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(stats("en"), "0 downloads\n");
+    assert_eq!(stats("pl"), "pobrania: 0\n");
+}
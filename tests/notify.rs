@@ -0,0 +1,85 @@
+//! what the notifications say, and that nothing to show them with is no error
+use opensubtitlescli::notify::{movie_name, Notification};
+use std::{path::Path, process::Command};
+
+#[test]
+fn name_the_movie_and_the_language() {
+    let movie = Path::new("/movies/Movie.2019.1080p.mkv");
+    assert_eq!(movie_name(movie), "Movie.2019.1080p");
+    let downloaded =
+        Notification::downloaded(movie, "pol", &["/movies/Movie.2019.1080p.pol.srt".into()]);
+    assert_eq!(downloaded.summary, "pol subtitles for Movie.2019.1080p");
+    assert_eq!(downloaded.body, "Movie.2019.1080p.pol.srt");
+    let failed = Notification::failed(movie, "pol", "nothing to choose from");
+    assert_eq!(failed.summary, "no pol subtitles for Movie.2019.1080p");
+    assert_eq!(failed.body, "nothing to choose from");
+}
+
+#[test]
+fn batches_count_what_failed() {
+    let failed = ["/tv/Show.S01E03.mkv".into()];
+    let batch = Notification::batch("eng", 10, &[], &failed);
+    assert_eq!(batch.summary, "eng subtitles for 9 of 10 videos");
+    assert_eq!(batch.body, "failed: Show.S01E03");
+    assert_eq!(Notification::batch("eng", 2, &[], &[]).body, "all done");
+}
+
+#[test]
+fn skipped_videos_are_no_successes() {
+    let skipped = ["/tv/Show.S01E01.mkv".into(), "/tv/Show.S01E02.mkv".into()];
+    let failed = ["/tv/Show.S01E03.mkv".into()];
+    let batch = Notification::batch("eng", 10, &skipped, &failed);
+    assert_eq!(batch.summary, "eng subtitles for 7 of 10 videos");
+    assert_eq!(
+        batch.body,
+        "skipped: Show.S01E01, Show.S01E02\nfailed: Show.S01E03"
+    );
+}
+
+#[test]
+fn a_missing_notifier_is_logged() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(["--notify", "-l", "pol", "-m"])
+        .arg(dir.path().join("Movie.2019.mkv"))
+        .env("PATH", "")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("can't notify"), "{stderr}");
+    assert!(
+        stderr.contains("no pol subtitles for Movie.2019"),
+        "{stderr}"
+    );
+    assert!(!output.status.success());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn notify_send_gets_the_texts() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = tempfile::tempdir().unwrap();
+    let notify_send = dir.path().join("notify-send");
+    let shown = dir.path().join("shown");
+    std::fs::write(
+        &notify_send,
+        format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > {shown:?}\n"),
+    )
+    .unwrap();
+    std::fs::set_permissions(&notify_send, std::fs::Permissions::from_mode(0o755)).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(["--notify", "-l", "pol", "-m"])
+        .arg(dir.path().join("Movie.2019.mkv"))
+        .env("PATH", dir.path())
+        .output()
+        .unwrap();
+    let shown = std::fs::read_to_string(shown).unwrap();
+    let lines = shown.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[..2],
+        [
+            "--app-name=opensubtitlescli",
+            "no pol subtitles for Movie.2019"
+        ]
+    );
+}
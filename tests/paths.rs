@@ -0,0 +1,113 @@
+//! names from archives and templates stay valid on windows, checked without a windows host
+use opensubtitlescli::{
+    archive::{file_extension, sanitized_file_name, sanitized_path, Entry},
+    output::quoted,
+    subtitle::SubtitleFormat,
+};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn replaces_characters_ntfs_refuses() {
+    assert_eq!(
+        sanitized_file_name("Movie: Part 2?.srt"),
+        "Movie_ Part 2_.srt"
+    );
+    assert_eq!(sanitized_file_name("a<b>c\"d|e*f.srt"), "a_b_c_d_e_f.srt");
+    assert_eq!(sanitized_file_name("tab\there.srt"), "tab_here.srt");
+    assert_eq!(sanitized_file_name("plain.srt"), "plain.srt");
+}
+
+#[test]
+fn drops_trailing_dots_and_spaces() {
+    assert_eq!(sanitized_file_name("subtitle.srt. . "), "subtitle.srt");
+}
+
+#[test]
+fn prefixes_device_names() {
+    for name in [
+        "CON",
+        "con.srt",
+        "NUL.en.srt",
+        "Com1.srt",
+        "lpt9",
+        "aux .srt",
+    ] {
+        assert_eq!(sanitized_file_name(name), format!("_{name}"));
+    }
+    for name in ["CONTACT.srt", "console.srt", "COM10.srt", "nullable.srt"] {
+        assert_eq!(sanitized_file_name(name), name);
+    }
+}
+
+#[test]
+fn sanitizes_every_component_of_an_entry() {
+    assert_eq!(
+        sanitized_path(r"C:\Subs\CD1?\con.srt"),
+        Some(PathBuf::from("Subs").join("CD1_").join("_con.srt"))
+    );
+    assert_eq!(sanitized_path("../../.."), None);
+    let entry = Entry::new(0, "Season 1/Ep: 1.srt").unwrap();
+    assert_eq!(entry.file_name(), "Ep_ 1.srt");
+}
+
+#[test]
+fn takes_extensions_from_the_file_name() {
+    assert_eq!(file_extension("movie.en.srt").unwrap(), "srt");
+    assert!(file_extension("README").is_err());
+    assert!(file_extension(".hidden").is_err());
+    assert_eq!(
+        SubtitleFormat::from_path(Path::new("dir.v2").join("movie.ass").as_path()),
+        Some(SubtitleFormat::Ass)
+    );
+    assert_eq!(
+        SubtitleFormat::from_path(&Path::new("dir.v2").join("movie")),
+        None
+    );
+}
+
+#[test]
+fn quotes_paths_without_the_verbatim_prefix() {
+    assert_eq!(quoted(Path::new("movie.srt")), "\"movie.srt\"");
+    assert_eq!(
+        quoted(Path::new(r"\\?\C:\Movies\movie.srt")),
+        r#""C:\Movies\movie.srt""#
+    );
+    assert_eq!(
+        quoted(Path::new(r"\\?\UNC\server\share\movie.srt")),
+        r#""\\server\share\movie.srt""#
+    );
+}
+
+#[cfg(feature = "embed")]
+#[test]
+fn fills_in_the_output_template() {
+    use opensubtitlescli::{embed::output_path, language::LanguageCode};
+    let movie = Path::new("movies").join("Movie.2008.mkv");
+    let path = output_path(
+        &movie,
+        "{stem}.{language}.{container}",
+        None,
+        &LanguageCode::new("pol"),
+        "mkv",
+    )
+    .unwrap();
+    assert_eq!(path, Path::new("movies").join("Movie.2008.pol.mkv"));
+    let dir = Path::new("out");
+    let path = output_path(
+        &movie,
+        "{stem}.subs.{container}",
+        Some(dir),
+        &LanguageCode::new("eng"),
+        "mp4",
+    )
+    .unwrap();
+    assert_eq!(path, dir.join("Movie.2008.subs.mp4"));
+    assert!(output_path(
+        &movie,
+        "{title}.mkv",
+        None,
+        &LanguageCode::new("eng"),
+        "mkv"
+    )
+    .is_err());
+}
@@ -0,0 +1,192 @@
+//! the library end to end against a local server serving fixture pages and archives
+//!
+//! they bind a port on localhost, run them with `cargo test -- --ignored`
+mod common;
+
+use common::{read_fixture, MockServer};
+use opensubtitlescli::{
+    archive, crawler::NotAnArchive, http::RateLimited, output::SubtitleWriter,
+    subtitle::FormatPreference, Client, Ranking,
+};
+use std::{path::PathBuf, time::Duration};
+use tempfile::TempDir;
+
+const MOVIE: &str = "Big.Buck.Bunny.2008.1080p.BluRay.x264.mkv";
+
+/// a movie large enough to be hashed, along with its hash
+fn movie() -> (TempDir, PathBuf, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let movie_file = dir.path().join(MOVIE);
+    let contents = (0..200_000u32).map(|n| (n % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&movie_file, contents).unwrap();
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    (dir, movie_file, hash)
+}
+
+fn search_path(hash: &str) -> String {
+    format!("/pl/search/sublanguageid-pol/moviehash-{hash}")
+}
+
+fn client(server: &MockServer) -> Client {
+    Client::builder()
+        .base_url(server.base_url.as_str())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn downloads_the_best_rated_subtitle_next_to_the_movie() {
+    let server = MockServer::start().await;
+    let (_dir, movie_file, hash) = movie();
+    server.route(&search_path(&hash), 200, &[], read_fixture("search.html"));
+    server.route(
+        "/download/sub/1000001",
+        200,
+        &[("Content-Type", "application/zip")],
+        read_fixture("subtitles.zip"),
+    );
+    let client = client(&server);
+
+    let candidates = client
+        .search_by_hash("pol", &hash, &Ranking::default())
+        .await
+        .unwrap();
+    assert_eq!(candidates.len(), 1);
+    let entry = &candidates[0].entry;
+    assert_eq!(entry.subtitle_id, 1000001);
+    assert_eq!(entry.download_url.host_str(), Some("127.0.0.1"));
+
+    let bytes = client.download_entry(entry).await.unwrap();
+    let options = archive::Options::default();
+    let mut archive = archive::open(bytes, &options).unwrap();
+    let files = archive::subtitle_entries(
+        archive.as_ref(),
+        &FormatPreference::default(),
+        &options.filter,
+        &movie_file,
+    );
+    // the nfo is left out
+    assert_eq!(files.len(), 1);
+    let contents = archive.read(&files[0].entry).unwrap();
+    let written = SubtitleWriter::default()
+        .write(&movie_file.with_extension("srt"), &contents)
+        .await
+        .unwrap();
+
+    let expected = movie_file.with_extension("srt");
+    assert_eq!(written, vec![expected.clone()]);
+    assert_eq!(
+        expected.file_name().unwrap(),
+        "Big.Buck.Bunny.2008.1080p.BluRay.x264.srt"
+    );
+    let text = std::fs::read_to_string(&expected).unwrap();
+    assert!(text.contains("Zając się budzi."), "{text}");
+    assert_eq!(
+        server.hits(),
+        vec![search_path(&hash), "/download/sub/1000001".to_string()]
+    );
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn no_results_is_not_an_error() {
+    let server = MockServer::start().await;
+    let (_dir, _movie_file, hash) = movie();
+    server.route(
+        &search_path(&hash),
+        200,
+        &[],
+        read_fixture("empty_search.html"),
+    );
+
+    let candidates = client(&server)
+        .search_by_hash("pol", &hash, &Ranking::default())
+        .await
+        .unwrap();
+    assert!(candidates.is_empty());
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn html_instead_of_an_archive_is_refused() {
+    let server = MockServer::start().await;
+    server.route(
+        "/download/sub/1000001",
+        200,
+        &[("Content-Type", "text/html")],
+        b"<html><body>please log in</body></html>".to_vec(),
+    );
+    let client = client(&server);
+
+    let report = client
+        .download(server.base_url.join("/download/sub/1000001").unwrap())
+        .await
+        .unwrap_err();
+    let refused = report
+        .chain()
+        .find_map(|e| e.downcast_ref::<NotAnArchive>())
+        .unwrap();
+    assert_eq!(refused.content_type.as_deref(), Some("text/html"));
+    assert_eq!(refused.dumped, None);
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn too_many_requests_says_when_to_retry() {
+    let server = MockServer::start().await;
+    let (_dir, _movie_file, hash) = movie();
+    server.route(
+        &search_path(&hash),
+        429,
+        &[("Retry-After", "120")],
+        b"slow down".to_vec(),
+    );
+
+    let report = client(&server)
+        .search_by_hash("pol", &hash, &Ranking::default())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        report.downcast_ref::<RateLimited>(),
+        Some(&RateLimited {
+            retry_after: Some(Duration::from_secs(120))
+        }),
+        "{report:?}"
+    );
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn reads_the_reports_and_comments_of_the_subtitle_pages() {
+    let server = MockServer::start().await;
+    let (_dir, _movie_file, hash) = movie();
+    server.route(&search_path(&hash), 200, &[], read_fixture("reported.html"));
+    server.route(
+        "/pl/subtitles/1000001/big-buck-bunny-pl",
+        200,
+        &[],
+        read_fixture("subtitle_page.html"),
+    );
+    let ranking = Ranking {
+        top_n: 3,
+        ..Ranking::default()
+    };
+
+    let candidates = client(&server)
+        .search_by_hash("pol", &hash, &ranking)
+        .await
+        .unwrap();
+    let entry = &candidates
+        .iter()
+        .find(|candidate| candidate.entry.subtitle_id == 1000001)
+        .unwrap()
+        .entry;
+    assert_eq!((entry.bad_reports, entry.comments), (6, 3));
+    // the page that isn't there leaves the row's numbers
+    assert_eq!(candidates[0].entry.subtitle_id, 1000002);
+    assert_eq!(candidates[0].entry.bad_reports, 0);
+    assert!(server
+        .hits()
+        .contains(&"/pl/subtitles/1000002/big-buck-bunny-pl".to_string()));
+}
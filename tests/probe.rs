@@ -0,0 +1,135 @@
+//! what's read out of ffprobe's output, `tests/fixtures/ffprobe` has its json for a few
+//! movies
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::read_fixture;
+use opensubtitlescli::{
+    language::LanguageCode,
+    probe::{self, SubtitleStream},
+    srt::Timestamp,
+};
+
+fn streams(name: &str) -> Vec<probe::Stream> {
+    let json = String::from_utf8(read_fixture(&format!("ffprobe/{name}.json"))).unwrap();
+    probe::parse_streams(&json).unwrap()
+}
+
+#[test]
+fn every_stream_in_index_order() {
+    let streams = streams("three_subtitles");
+    let kinds = streams
+        .iter()
+        .map(|stream| (stream.index, stream.codec_type.as_deref().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            (0, "video"),
+            (1, "audio"),
+            (2, "subtitle"),
+            (3, "subtitle"),
+            (4, "subtitle"),
+            (5, "attachment"),
+        ]
+    );
+}
+
+#[test]
+fn subtitle_streams_with_their_tags_and_flags() {
+    let subtitles = probe::subtitles(streams("three_subtitles"));
+    let stream = |index: usize, codec: &str, language: &str, title: Option<&str>| SubtitleStream {
+        index,
+        codec: Some(codec.to_string()),
+        language: Some(language.to_string()),
+        title: title.map(str::to_string),
+        default: false,
+        forced: false,
+    };
+    assert_eq!(
+        subtitles,
+        [
+            SubtitleStream {
+                default: true,
+                ..stream(2, "subrip", "eng", Some("English"))
+            },
+            stream(3, "ass", "pol", None),
+            SubtitleStream {
+                forced: true,
+                ..stream(4, "hdmv_pgs_subtitle", "ger", Some("Forced"))
+            },
+        ]
+    );
+    assert_eq!(probe::subtitles(streams("no_subtitles")), []);
+    assert_eq!(probe::subtitles(streams("one_subtitle")).len(), 1);
+}
+
+#[test]
+fn tags_other_than_language_and_title_are_ignored() {
+    // mp4 files tag every stream with a handler, chapters come as a data stream
+    let streams = streams("mp4_mov_text");
+    assert_eq!(streams.len(), 4);
+    assert_eq!(streams[3].codec_name, None);
+    let subtitles = probe::subtitles(streams);
+    assert_eq!(subtitles.len(), 1);
+    assert_eq!(subtitles[0].codec.as_deref(), Some("mov_text"));
+    assert_eq!(subtitles[0].title, None);
+}
+
+#[test]
+fn languages_match_by_their_container_tags() {
+    let three = probe::subtitles(streams("three_subtitles"));
+    assert_eq!(probe::in_language(&three, &LanguageCode::new("deu")), [2]);
+    assert_eq!(probe::in_language(&three, &LanguageCode::new("pl")), [1]);
+    assert!(probe::in_language(&three, &LanguageCode::new("fre")).is_empty());
+    let mp4 = probe::subtitles(streams("mp4_mov_text"));
+    assert_eq!(probe::in_language(&mp4, &LanguageCode::new("ger")), [0]);
+}
+
+#[test]
+fn missing_sections_and_fields() {
+    // a file with no streams at all, and a stream with nothing but its index
+    assert_eq!(probe::parse_streams("{}").unwrap().len(), 0);
+    let streams = probe::parse_streams(r#"{"streams": [{"index": 7}]}"#).unwrap();
+    assert_eq!(streams[0].index, 7);
+    assert_eq!(streams[0].codec_type, None);
+    assert_eq!(streams[0].disposition.default, 0);
+    assert_eq!(probe::subtitles(streams), []);
+    let error = probe::parse_streams("Invalid data found when processing input")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("parsing the streams"), "{error}");
+}
+
+#[test]
+fn frame_rates() {
+    let cases = [
+        ("24000/1001\n24000/1001\n", Some(24000.0 / 1001.0)),
+        ("25/1\n25/1\n", Some(25.0)),
+        // variable frame rate files often have no average
+        ("0/0\n30000/1001\n", Some(30000.0 / 1001.0)),
+        ("30\n", Some(30.0)),
+        ("0/0\n0/0\n", None),
+        ("N/A\n", None),
+        ("", None),
+    ];
+    for (output, expected) in cases {
+        assert_eq!(probe::parse_frame_rate(output), expected, "{output:?}");
+    }
+}
+
+#[test]
+fn durations() {
+    let cases = [
+        ("5400.123000\n", Some(Timestamp(5_400_123))),
+        ("0.0005\n", Some(Timestamp(1))),
+        ("N/A\n", None),
+        ("0.000000\n", None),
+        ("-1.5\n", None),
+        ("", None),
+    ];
+    for (output, expected) in cases {
+        assert_eq!(probe::parse_duration(output), expected, "{output:?}");
+    }
+}
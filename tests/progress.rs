@@ -0,0 +1,106 @@
+//! ffmpeg's `-progress pipe:1` output, `tests/fixtures/progress/ffmpeg.txt` is a run of it
+//! from before the first frame to the end
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::read_fixture;
+use opensubtitlescli::{
+    progress::{Parser, Update},
+    srt::Timestamp,
+};
+
+fn updates(output: &str) -> Vec<Update> {
+    let mut parser = Parser::default();
+    output
+        .lines()
+        .filter_map(|line| parser.feed(line))
+        .collect()
+}
+
+fn update(done: i64, speed: Option<f64>) -> Update {
+    Update {
+        done: Timestamp(done),
+        speed,
+        finished: false,
+    }
+}
+
+#[test]
+fn one_update_per_block() {
+    let output = String::from_utf8(read_fixture("progress/ffmpeg.txt")).unwrap();
+    assert_eq!(
+        updates(&output),
+        [
+            // nothing written yet, ffmpeg doesn't know the time
+            update(0, None),
+            // audio starting a little before the video comes out negative
+            update(0, None),
+            update(10_010, Some(19.9)),
+            update(60_060, Some(24.1)),
+            Update {
+                finished: true,
+                ..update(150_150, Some(24.6))
+            },
+        ]
+    );
+}
+
+#[test]
+fn windows_line_endings() {
+    let output = String::from_utf8(read_fixture("progress/ffmpeg.txt")).unwrap();
+    let crlf = output.replace('\n', "\r\n");
+    assert_eq!(updates(&crlf), updates(&output));
+}
+
+#[test]
+fn the_time_carries_over_blocks_without_one() {
+    // older ffmpeg versions put microseconds under `out_time_ms` and no `out_time_us`
+    let output = "out_time_ms=2500000\nspeed=1.5x\nprogress=continue\n\
+                  out_time_ms=N/A\nspeed=N/A\nprogress=continue\n\
+                  frame=10\nprogress=end\n";
+    assert_eq!(
+        updates(output),
+        [
+            update(2_500, Some(1.5)),
+            update(2_500, None),
+            Update {
+                finished: true,
+                ..update(2_500, None)
+            },
+        ]
+    );
+}
+
+#[test]
+fn other_lines_are_no_updates() {
+    let output = "Input #0, matroska,webm, from 'movie.mkv':\n  Duration: 00:02:30.15\n\
+                  Progress: 42%\n\nprogress\n";
+    assert_eq!(updates(output), []);
+}
+
+/// `sh -c script` with the fixture's path as `$1`
+#[cfg(unix)]
+fn script(script: &str) -> tokio::process::Command {
+    let fixture = common::fixture("progress/ffmpeg.txt");
+    let mut command = tokio::process::Command::new("sh");
+    command.args(["-c", script, "sh"]).arg(fixture);
+    command
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn failures_say_why_without_the_progress() {
+    use opensubtitlescli::progress::{self, Stopped};
+    let command = script("cat \"$1\"; echo 'Conversion failed!' >&2; exit 1");
+    let report = progress::run(command, "embedding", None, None, |status| status.success())
+        .await
+        .unwrap_err();
+    assert_eq!(Stopped::find(&report), Some(Stopped::Failed));
+    let message = format!("{report:#}");
+    assert!(message.contains("Conversion failed!"), "{message}");
+    assert!(!message.contains("out_time"), "{message}");
+    let command = script("cat \"$1\"");
+    let succeeded = progress::run(command, "embedding", None, None, |status| status.success());
+    succeeded.await.unwrap();
+}
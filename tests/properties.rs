@@ -0,0 +1,262 @@
+//! the parsers take whatever the site and the uploaders send, none of it may panic
+//!
+//! inputs come from a seeded generator, so a failure names the seed to reproduce it with
+use opensubtitlescli::{
+    archive::{sanitized_path, Entry},
+    crawler::{self, Ranking},
+    srt::{self, Cue, Offset, RepairOptions, Srt, Timestamp},
+};
+use std::path::Component;
+
+const RUNS: u64 = 500;
+
+/// xorshift, the same inputs on every run
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, values: &'a [T]) -> &'a T {
+        &values[self.below(values.len())]
+    }
+
+    /// up to `max` of them
+    fn bytes(&mut self, max: usize) -> Vec<u8> {
+        (0..self.below(max)).map(|_| self.next() as u8).collect()
+    }
+
+    /// up to `max` of `pieces`, which bias it toward what the code looks for
+    fn text(&mut self, pieces: &[&str], max: usize) -> String {
+        (0..self.below(max)).map(|_| *self.pick(pieces)).collect()
+    }
+}
+
+/// the fixture cut, repeated and spliced with stray markup
+fn mutated(rng: &mut Rng, page: &str) -> String {
+    const SNIPPETS: &[&str] = &[
+        "<tr>",
+        "</tr>",
+        "<td>",
+        "</td>",
+        "<a href=\"/download/sub/",
+        "\">",
+        "</a>",
+        "<br>",
+        "<table id=\"search_results\">",
+        "</table>",
+        "onclick=\"",
+        "&amp;",
+        "&#",
+        "<!--",
+        "-->",
+        "x",
+        "9",
+        ".",
+        "/",
+        "\u{feff}",
+        "ż",
+        "\"",
+        "'",
+    ];
+    let mut chars = page.chars().collect::<Vec<_>>();
+    for _ in 0..1 + rng.below(8) {
+        let at = rng.below(chars.len() + 1);
+        match rng.below(4) {
+            0 => {
+                let end = (at + rng.below(64)).min(chars.len());
+                chars.drain(at..end);
+            }
+            1 => {
+                let end = (at + rng.below(256)).min(chars.len());
+                let copy = chars[at..end].to_vec();
+                chars.splice(at..at, copy);
+            }
+            2 => {
+                let snippet = rng.pick(SNIPPETS).chars().collect::<Vec<_>>();
+                chars.splice(at..at, snippet);
+            }
+            _ => {
+                let other = rng.below(chars.len() + 1);
+                let (from, to) = (at.min(other), at.max(other));
+                chars[from..to].reverse();
+            }
+        }
+    }
+    chars.into_iter().collect()
+}
+
+#[test]
+fn search_results_never_panic() {
+    let page = include_str!("fixtures/search.html");
+    let ranking = Ranking::default();
+    for seed in 0..RUNS {
+        let mut rng = Rng::new(seed);
+        let page = mutated(&mut rng, page);
+        let parsed = std::panic::catch_unwind(|| crawler::top_rated_subs(&page, &ranking).ok());
+        assert!(parsed.is_ok(), "seed {seed} panicked on:\n{page}");
+    }
+}
+
+#[test]
+fn srt_never_panics() {
+    const PIECES: &[&str] = &[
+        "1",
+        "2",
+        "00",
+        ":",
+        ",",
+        ".",
+        " --> ",
+        "-->",
+        "-",
+        "\n",
+        "\n\n",
+        "\r\n",
+        " ",
+        "\u{feff}",
+        "<i>",
+        "</i>",
+        "{\\an8}",
+        "[MUSIC]",
+        "text",
+        "99999999999999999999",
+        "9223372036854775807",
+        "\u{0}",
+        "ą",
+    ];
+    for seed in 0..RUNS {
+        let mut rng = Rng::new(seed);
+        let text = match seed % 2 {
+            0 => String::from_utf8_lossy(&rng.bytes(512)).into_owned(),
+            _ => rng.text(PIECES, 256),
+        };
+        let parsed = std::panic::catch_unwind(|| {
+            let (repaired, _) = srt::parse_lenient(&text).repair(RepairOptions::default());
+            let _ = srt::parse(&text);
+            repaired.to_string()
+        });
+        assert!(parsed.is_ok(), "seed {seed} panicked on {text:?}");
+    }
+}
+
+#[test]
+fn timestamps_never_panic() {
+    const NUMBERS: &[&str] = &[
+        "0",
+        "59",
+        "-1",
+        "99",
+        "9223372036854775807",
+        "-9223372036854775808",
+    ];
+    for seed in 0..RUNS {
+        let mut rng = Rng::new(seed);
+        let mut part = || match rng.below(2) {
+            0 => rng.pick(NUMBERS).to_string(),
+            _ => (rng.next() as i64).to_string(),
+        };
+        let timestamp = format!("{}:{}:{},{}", part(), part(), part(), part());
+        let parsed = std::panic::catch_unwind(|| {
+            let _ = timestamp.parse::<Timestamp>();
+            let _ = timestamp.parse::<Offset>();
+        });
+        assert!(parsed.is_ok(), "seed {seed} panicked on {timestamp:?}");
+    }
+}
+
+fn valid_srt(rng: &mut Rng) -> Srt {
+    const WORDS: &[&str] = &[
+        "Hello",
+        "there",
+        "<i>so</i>",
+        "ąę",
+        "- Yes.",
+        "1",
+        "...",
+        "♪",
+    ];
+    let mut start = 0;
+    let cues = (0..rng.below(20))
+        .map(|_| {
+            start += rng.below(10_000) as i64;
+            let end = start + rng.below(5_000) as i64;
+            let lines = (0..1 + rng.below(3))
+                .map(|_| {
+                    (0..1 + rng.below(6))
+                        .map(|_| *rng.pick(WORDS))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            Cue {
+                start: Timestamp(start),
+                end: Timestamp(end),
+                lines,
+            }
+        })
+        .collect();
+    Srt { cues }
+}
+
+#[test]
+fn valid_srt_round_trips() {
+    for seed in 0..RUNS {
+        let srt = valid_srt(&mut Rng::new(seed));
+        let written = srt.to_string();
+        assert_eq!(
+            srt::parse(&written).unwrap(),
+            srt,
+            "seed {seed}:\n{written}"
+        );
+        let crlf = written.replace('\n', "\r\n");
+        assert_eq!(srt::parse(&crlf).unwrap(), srt, "seed {seed} with crlf");
+    }
+}
+
+#[test]
+fn entry_names_stay_inside_the_output_directory() {
+    const PIECES: &[&str] = &[
+        "/", "\\", "..", ".", " ", ":", "C:", "*", "?", "<", ">", "|", "\"", "\u{0}", "\t", "CON",
+        "nul", "com1", "lpt9", "sub", "srt", "ż", "\u{202e}",
+    ];
+    const REFUSED: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+    const DEVICES: &[&str] = &["con", "prn", "aux", "nul", "com1", "lpt9"];
+    for seed in 0..RUNS {
+        let mut rng = Rng::new(seed);
+        let name = rng.text(PIECES, 16);
+        let Some(path) = sanitized_path(&name) else {
+            continue;
+        };
+        assert!(!path.is_absolute(), "seed {seed}: {name:?} became {path:?}");
+        for component in path.components() {
+            let Component::Normal(component) = component else {
+                panic!("seed {seed}: {name:?} became {path:?}");
+            };
+            let component = component.to_str().unwrap();
+            let device = component.split('.').next().unwrap().trim_end();
+            assert!(
+                !component.is_empty()
+                    && !component.contains(REFUSED)
+                    && !component.contains(char::is_control)
+                    && !component.ends_with(['.', ' '])
+                    && !DEVICES.contains(&device.to_lowercase().as_str()),
+                "seed {seed}: {name:?} became {path:?}"
+            );
+        }
+        let entry = Entry::new(0, &name).unwrap();
+        assert_eq!(entry.path, path);
+    }
+}
@@ -0,0 +1,122 @@
+//! a subtitle failing to download, unpack or write doesn't end the run while others are left
+//!
+//! they bind a port on localhost, run them with `cargo test -- --ignored`
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::{read_fixture, MockServer};
+
+/// the best rated of the two can't be downloaded, the other one can
+async fn serve() -> (MockServer, tempfile::TempDir, std::path::PathBuf) {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let movie_file = dir.path().join("Big.Buck.Bunny.2008.1080p.BluRay.x264.mkv");
+    let contents = (0..200_000u32).map(|n| (n % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&movie_file, contents).unwrap();
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    server.route(
+        &format!("/pl/search/sublanguageid-pol/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("search.html"),
+    );
+    server.route(
+        "/download/sub/1000001",
+        200,
+        &[("Content-Type", "application/zip")],
+        b"PK\x03\x04 cut short".to_vec(),
+    );
+    server.route(
+        "/download/sub/1000002",
+        200,
+        &[("Content-Type", "application/zip")],
+        read_fixture("subtitles.zip"),
+    );
+    (server, dir, movie_file)
+}
+
+/// the server runs on this test's runtime, the binary is waited for without blocking it
+async fn download(
+    server: &MockServer,
+    dir: &tempfile::TempDir,
+    movie_file: &std::path::Path,
+    args: &[&str],
+) -> std::process::Output {
+    tokio::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args(["--base-url", server.base_url.as_str(), "-l", "pol"])
+        .args(["--auto", "--top-n", "2"])
+        .args(args)
+        .arg("-m")
+        .arg(movie_file)
+        .env("OPENSUBTITLESCLI_HISTORY", dir.path().join("history.json"))
+        .env("OPENSUBTITLESCLI_NO_UPDATE_CHECK", "true")
+        .env("OPENSUBTITLESCLI_UI_LANGUAGE", "en")
+        .output()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn moves_on_to_the_next_subtitle_without_searching_again() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, &dir, &movie_file, &[]).await;
+    assert!(output.status.success(), "{output:?}");
+    assert!(movie_file.with_extension("srt").exists());
+    let hits = server.hits();
+    assert_eq!(
+        hits.iter().filter(|hit| hit.contains("/search/")).count(),
+        1,
+        "{hits:?}"
+    );
+    assert!(hits.iter().any(|hit| hit == "/download/sub/1000002"));
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn gives_up_after_max_attempts() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, &dir, &movie_file, &["--max-attempts", "1"]).await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("gave up after 1 subtitles failed"),
+        "{stderr}"
+    );
+    assert!(!server
+        .hits()
+        .iter()
+        .any(|hit| hit == "/download/sub/1000002"));
+}
+
+/// the best rated subtitles can't be written once unpacked, a folder is in their way
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn moves_on_when_writing_fails() {
+    use std::io::Write;
+    let (server, dir, movie_file) = serve().await;
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    zip.start_file("movie.ass", zip::write::FileOptions::default())
+        .unwrap();
+    zip.write_all(b"[Script Info]\nTitle: movie\n").unwrap();
+    server.route(
+        "/download/sub/1000001",
+        200,
+        &[("Content-Type", "application/zip")],
+        zip.finish().unwrap().into_inner(),
+    );
+    let in_the_way = movie_file.with_extension("ass");
+    std::fs::create_dir(&in_the_way).unwrap();
+    std::fs::write(in_the_way.join("kept.txt"), b"kept").unwrap();
+    let output = download(&server, &dir, &movie_file, &[]).await;
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("writing subtitle file"), "{stderr}");
+    assert!(in_the_way.join("kept.txt").exists());
+    assert!(movie_file.with_extension("srt").exists());
+    assert!(server
+        .hits()
+        .iter()
+        .any(|hit| hit == "/download/sub/1000002"));
+}
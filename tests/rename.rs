@@ -0,0 +1,141 @@
+//! `rename`, which subtitle files are paired with which videos and what they're renamed to
+use opensubtitlescli::rename::{self, Rename};
+use std::{fs, path::Path, process::Command};
+
+fn touch(dir: &Path, name: &str) {
+    fs::write(dir.join(name), b"").unwrap();
+}
+
+/// an srt long enough for its language to be guessed
+fn polish_srt() -> String {
+    (1..=20)
+        .map(|n| {
+            format!(
+                "{n}\r\n00:00:{n:02},000 --> 00:00:{n:02},900\r\nNie wiem, co to jest i gdzie on jest.\r\nTo nie jest tak, że ja tego nie chcę.\r\n\r\n"
+            )
+        })
+        .collect()
+}
+
+fn opensubtitlescli(dir: &Path, args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .arg("rename")
+        .arg(dir)
+        .args(args)
+        .env("OPENSUBTITLESCLI_NO_UPDATE_CHECK", "true")
+        .env("OPENSUBTITLESCLI_UI_LANGUAGE", "en")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn pairs_episodes_by_their_marker() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "Show.S01E01.720p.HDTV-GRP.mkv");
+    touch(dir.path(), "Show.S01E02.720p.HDTV-GRP.mkv");
+    touch(dir.path(), "show s01e02 english.srt");
+    touch(dir.path(), "show s01e01 english.srt");
+    let plan = rename::plan(dir.path(), rename::DEFAULT_TEMPLATE).unwrap();
+    assert_eq!(
+        plan.renames,
+        [
+            Rename {
+                subtitle: dir.path().join("show s01e01 english.srt"),
+                video: dir.path().join("Show.S01E01.720p.HDTV-GRP.mkv"),
+                language: Some("eng".to_string()),
+                target: dir.path().join("Show.S01E01.720p.HDTV-GRP.eng.srt"),
+            },
+            Rename {
+                subtitle: dir.path().join("show s01e02 english.srt"),
+                video: dir.path().join("Show.S01E02.720p.HDTV-GRP.mkv"),
+                language: Some("eng".to_string()),
+                target: dir.path().join("Show.S01E02.720p.HDTV-GRP.eng.srt"),
+            },
+        ]
+    );
+    assert!(plan.unmatched.is_empty());
+}
+
+#[test]
+fn leaves_subtitles_named_after_a_video_alone() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "Movie.2019.1080p.mkv");
+    touch(dir.path(), "Movie.2019.1080p.srt");
+    touch(dir.path(), "Movie.2019.1080p.pol.srt");
+    touch(dir.path(), "unrelated.srt");
+    let plan = rename::plan(dir.path(), rename::DEFAULT_TEMPLATE).unwrap();
+    assert!(plan.renames.is_empty(), "{plan:?}");
+    assert_eq!(plan.unmatched, [dir.path().join("unrelated.srt")]);
+}
+
+#[test]
+fn guesses_the_language_from_the_text() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "Movie.2019.1080p.WEB.mkv");
+    fs::write(dir.path().join("movie 2019.srt"), polish_srt()).unwrap();
+    fs::write(
+        dir.path().join("movie-2019-web.srt"),
+        b"1\r\n00:00:01,000 --> 00:00:02,000\r\nHi\r\n",
+    )
+    .unwrap();
+    let plan = rename::plan(dir.path(), rename::DEFAULT_TEMPLATE).unwrap();
+    let renamed = plan
+        .renames
+        .iter()
+        .map(|rename| (rename.language.as_deref(), rename.target.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        renamed,
+        [
+            (Some("pol"), dir.path().join("Movie.2019.1080p.WEB.pol.srt")),
+            // too little text to tell
+            (None, dir.path().join("Movie.2019.1080p.WEB.und.srt")),
+        ]
+    );
+}
+
+#[test]
+fn refuses_unknown_placeholders() {
+    let error = rename::target_path(Path::new("movie.mkv"), "{name}.{ext}", None, "srt")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("{stem}, {lang} and {ext}"), "{error}");
+    assert_eq!(
+        rename::target_path(Path::new("dir/movie.mkv"), "{stem}.{ext}", None, "ass").unwrap(),
+        Path::new("dir/movie.ass")
+    );
+}
+
+#[test]
+fn dry_run_renames_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "Movie.2019.mkv");
+    touch(dir.path(), "movie 2019 eng.srt");
+    let output = opensubtitlescli(dir.path(), &["--dry-run"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Movie.2019.eng.srt"), "{stdout}");
+    assert!(dir.path().join("movie 2019 eng.srt").exists());
+    assert!(!dir.path().join("Movie.2019.eng.srt").exists());
+}
+
+#[test]
+fn keeps_taken_names_without_force() {
+    let dir = tempfile::tempdir().unwrap();
+    touch(dir.path(), "Movie.2019.mkv");
+    fs::write(dir.path().join("movie 2019 eng.srt"), b"new").unwrap();
+    fs::write(dir.path().join("Movie.2019.eng.srt"), b"old").unwrap();
+    let output = opensubtitlescli(dir.path(), &["--yes"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        fs::read(dir.path().join("Movie.2019.eng.srt")).unwrap(),
+        b"old"
+    );
+    let output = opensubtitlescli(dir.path(), &["--yes", "--force"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        fs::read(dir.path().join("Movie.2019.eng.srt")).unwrap(),
+        b"new"
+    );
+    assert!(!dir.path().join("movie 2019 eng.srt").exists());
+}
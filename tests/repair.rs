@@ -0,0 +1,106 @@
+//! `--repair` and `verify`, `tests/fixtures/repair` has an srt per kind of breakage next to
+//! what it's repaired into
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::read_fixture;
+use opensubtitlescli::srt::{self, RepairOptions, Repairs};
+
+/// the fixture repaired and what the repair says it changed
+fn repaired(name: &str) -> (String, Repairs) {
+    let contents = read_fixture(&format!("repair/{name}.srt"));
+    let (srt, repairs) =
+        srt::parse_lenient(&String::from_utf8(contents).unwrap()).repair(RepairOptions::default());
+    let expected = read_fixture(&format!("repair/{name}.repaired.srt"));
+    assert_eq!(
+        srt.to_string(),
+        String::from_utf8(expected).unwrap(),
+        "{name}"
+    );
+    (srt.to_string(), repairs)
+}
+
+#[test]
+fn overlapping_cues_are_trimmed() {
+    let (_, repairs) = repaired("overlapping");
+    assert_eq!(
+        repairs,
+        Repairs {
+            overlaps: 2,
+            merged: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn missing_and_wrong_numbers_are_renumbered() {
+    let (_, repairs) = repaired("missing_index");
+    assert_eq!(
+        repairs,
+        Repairs {
+            misnumbered: 3,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn cues_are_put_in_order() {
+    let (_, repairs) = repaired("out_of_order");
+    assert_eq!(
+        repairs,
+        Repairs {
+            reordered: 4,
+            duplicates: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn zero_and_negative_durations_are_clamped() {
+    let (_, repairs) = repaired("durations");
+    // the zero length cue starting with the next one has nowhere to grow
+    assert_eq!(
+        repairs,
+        Repairs {
+            negative_durations: 4,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn garbage_blocks_are_dropped() {
+    let (_, repairs) = repaired("garbage");
+    assert_eq!(
+        repairs,
+        Repairs {
+            garbage: 2,
+            misnumbered: 3,
+            empty: 1,
+            ..Default::default()
+        }
+    );
+    let error = srt::parse(&String::from_utf8(read_fixture("repair/garbage.srt")).unwrap())
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("Ripped by someone"), "{error}");
+}
+
+#[test]
+fn repaired_fixtures_need_no_repair() {
+    for name in [
+        "overlapping",
+        "missing_index",
+        "out_of_order",
+        "durations",
+        "garbage",
+    ] {
+        let (text, _) = repaired(name);
+        let (_, repairs) = srt::parse_lenient(&text).repair(RepairOptions::default());
+        assert!(repairs.is_empty(), "{name}: {repairs}");
+    }
+}
@@ -0,0 +1,470 @@
+//! `--json`, the document a download run prints instead of the paths it wrote
+//!
+//! they bind a port on localhost, run them with `cargo test -- --ignored`
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::{read_fixture, MockServer};
+use opensubtitlescli::{
+    check::DurationCheck,
+    results::{Document, DurationChecked, Skipped, Synced},
+};
+use std::path::{Path, PathBuf};
+
+/// a movie and a search listing it, only the second row downloads and it's `archive`
+async fn serve_archive(archive: Vec<u8>) -> (MockServer, tempfile::TempDir, PathBuf) {
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let movie_file = dir.path().join("Big.Buck.Bunny.2008.1080p.BluRay.x264.mkv");
+    let contents = (0..200_000u32).map(|n| (n % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&movie_file, contents).unwrap();
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    server.route(
+        &format!("/pl/search/sublanguageid-pol/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("search.html"),
+    );
+    server.route(
+        "/download/sub/1000002",
+        200,
+        &[("Content-Type", "application/zip")],
+        archive,
+    );
+    (server, dir, movie_file)
+}
+
+async fn serve() -> (MockServer, tempfile::TempDir, PathBuf) {
+    serve_archive(read_fixture("subtitles.zip")).await
+}
+
+/// the fixture's subtitles under each of `names`
+fn zip_of(names: &[&str]) -> Vec<u8> {
+    use std::io::Read;
+    let mut fixture =
+        zip::ZipArchive::new(std::io::Cursor::new(read_fixture("subtitles.zip"))).unwrap();
+    let mut contents = vec![];
+    fixture
+        .by_name("Big.Buck.Bunny.2008.1080p.BluRay.x264.srt")
+        .unwrap()
+        .read_to_end(&mut contents)
+        .unwrap();
+    zip_with(names, &contents)
+}
+
+/// `contents` under each of `names`
+fn zip_with(names: &[&str], contents: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    for name in names {
+        writer
+            .start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        writer.write_all(contents).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn command(
+    server: &MockServer,
+    dir: &Path,
+    movie_file: &Path,
+    args: &[&str],
+) -> tokio::process::Command {
+    command_in("pol", server, dir, movie_file, args)
+}
+
+/// a run downloading subtitles in `language`
+fn command_in(
+    language: &str,
+    server: &MockServer,
+    dir: &Path,
+    movie_file: &Path,
+    args: &[&str],
+) -> tokio::process::Command {
+    let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"));
+    command
+        .args(["--base-url", server.base_url.as_str(), "-l", language])
+        .args(["--auto", "--top-n", "2", "--json"])
+        .args(args)
+        .arg("-m")
+        .arg(movie_file)
+        .env("OPENSUBTITLESCLI_HISTORY", dir.join("history.json"))
+        .env("OPENSUBTITLESCLI_NO_UPDATE_CHECK", "true")
+        .env("OPENSUBTITLESCLI_UI_LANGUAGE", "en");
+    command
+}
+
+async fn download(
+    server: &MockServer,
+    dir: &Path,
+    movie_file: &Path,
+    args: &[&str],
+) -> std::process::Output {
+    command(server, dir, movie_file, args)
+        .output()
+        .await
+        .unwrap()
+}
+
+/// a directory for `PATH` with shell scripts standing in for the tools
+#[cfg(unix)]
+fn fake_tools(tools: &[(&str, &str)]) -> tempfile::TempDir {
+    use std::os::unix::fs::PermissionsExt;
+    let bin = tempfile::tempdir().unwrap();
+    for (name, script) in tools {
+        let path = bin.path().join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    bin
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn stdout_is_one_document() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, dir.path(), &movie_file, &[]).await;
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let document = serde_json::from_str::<Document>(&stdout).unwrap();
+    assert_eq!(document.movie_file, movie_file);
+    assert_eq!(document.language, "pol");
+    let subtitle = document.subtitle.unwrap();
+    assert_eq!(subtitle.subtitle_id, 1000002);
+    assert!(!subtitle.foreign_parts_only);
+    assert_eq!(document.written, [movie_file.with_extension("srt")]);
+    // scripts look for the flag, it's there even when it's off
+    let value = serde_json::from_str::<serde_json::Value>(&stdout).unwrap();
+    assert_eq!(value["subtitle"]["foreign_parts_only"], false);
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn names_the_kept_archive() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, dir.path(), &movie_file, &["--keep-archive"]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    let archive = movie_file.with_extension("pol.zip");
+    assert_eq!(document.archive.as_ref(), Some(&archive));
+    assert_eq!(
+        std::fs::read(archive).unwrap(),
+        read_fixture("subtitles.zip")
+    );
+    // the archive isn't among the subtitles
+    assert_eq!(document.written, [movie_file.with_extension("srt")]);
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn lists_every_extracted_file() {
+    let archive = zip_of(&["Big.Buck.Bunny.srt", "Big.Buck.Bunny.SDH.srt"]);
+    let (server, dir, movie_file) = serve_archive(archive).await;
+    let output = download(&server, dir.path(), &movie_file, &["--extract-all"]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    let mut written = document.written;
+    written.sort();
+    assert_eq!(
+        written,
+        [
+            movie_file.with_extension("pol.Big.Buck.Bunny.SDH.srt"),
+            movie_file.with_extension("pol.Big.Buck.Bunny.srt"),
+        ]
+    );
+    assert!(written.iter().all(|path| path.exists()));
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn names_the_encoding_of_every_file() {
+    let contents = read_fixture("charset/pol.windows-1250.srt");
+    let archive = zip_with(&["Big.Buck.Bunny.2008.1080p.BluRay.x264.srt"], &contents);
+    let (server, dir, movie_file) = serve_archive(archive).await;
+    let output = download(&server, dir.path(), &movie_file, &[]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    let subtitle_file = movie_file.with_extension("srt");
+    assert_eq!(
+        document.encodings.get(&subtitle_file).map(String::as_str),
+        Some("windows-1250")
+    );
+    let written = std::fs::read_to_string(subtitle_file).unwrap();
+    assert!(written.contains("Zażółć gęślą jaźń."), "{written}");
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn downloads_every_language_of_a_list() {
+    let (server, dir, movie_file) = serve().await;
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    server.route(
+        &format!("/pl/search/sublanguageid-eng/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("search.html"),
+    );
+    let output = command_in("pol,eng", &server, dir.path(), &movie_file, &[])
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    // each named after its language, so neither overwrites the other
+    let written = [
+        movie_file.with_extension("pol.srt"),
+        movie_file.with_extension("eng.srt"),
+    ];
+    assert_eq!(document.written, written);
+    assert!(written.iter().all(|path| path.exists()));
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn joins_the_parts_into_one_file() {
+    let (server, dir, movie_file) = serve_archive(zip_of(&["CD1.srt", "CD2.srt"])).await;
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    // the subtitles that download are split in two
+    let search = String::from_utf8(read_fixture("search.html")).unwrap();
+    let (listed, last) = search.rsplit_once("<td>1CD</td>").unwrap();
+    server.route(
+        &format!("/pl/search/sublanguageid-pol/moviehash-{hash}"),
+        200,
+        &[],
+        format!("{listed}<td>2CD</td>{last}").into_bytes(),
+    );
+    server.route(
+        &format!("/pl/search/sublanguageid-eng/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("search.html"),
+    );
+    let args = ["--join-parts", "00:10:00,000"];
+    let output = command_in("pol,eng", &server, dir.path(), &movie_file, &args)
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    // the language after the one in parts is downloaded too
+    let joined = movie_file.with_extension("pol.srt");
+    assert_eq!(
+        document.written,
+        [joined.clone(), movie_file.with_extension("eng.srt")]
+    );
+    let joined = std::fs::read_to_string(joined).unwrap();
+    assert!(joined.contains("00:00:01,000 --> "), "{joined}");
+    assert!(joined.contains("00:10:01,000 --> "), "{joined}");
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn says_when_certificates_went_unverified() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, dir.path(), &movie_file, &[]).await;
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    assert!(!document.insecure);
+
+    let output = download(&server, dir.path(), &movie_file, &["--insecure"]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    assert!(document.insecure);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("warning: --insecure"), "{stderr}");
+}
+
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn has_the_timings_when_asked_for() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, dir.path(), &movie_file, &[]).await;
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    assert_eq!(document.timings, None);
+
+    let output = download(&server, dir.path(), &movie_file, &["--timings"]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    let timings = document.timings.unwrap();
+    let stages = timings.stages.iter().map(|timing| timing.stage.as_str());
+    assert!(
+        stages.clone().any(|stage| stage == "hashing"),
+        "{timings:?}"
+    );
+    for total in &timings.totals {
+        let count = stages.clone().filter(|stage| *stage == total.stage).count();
+        assert_eq!(total.count, count, "{timings:?}");
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn says_what_sync_did() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, dir.path(), &movie_file, &[]).await;
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    assert!(document.synced.is_empty(), "{document:?}");
+
+    // `ffsubsync movie -i subtitles -o synced`
+    let synced = "/bin/cp \"$3\" \"$5\"\necho 'INFO  offset seconds: -1.250' >&2";
+    let bin = fake_tools(&[("ffsubsync", synced)]);
+    let output = command(&server, dir.path(), &movie_file, &["--sync=ffsubsync"])
+        .env("PATH", bin.path())
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    let subtitle_file = movie_file.with_extension("srt");
+    assert_eq!(
+        document.synced.get(&subtitle_file),
+        Some(&Synced {
+            tool: "ffsubsync".to_string(),
+            offset: Some(-1.25),
+            error: None,
+        })
+    );
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn says_why_sync_failed() {
+    let (server, dir, movie_file) = serve().await;
+    let bin = fake_tools(&[("ffsubsync", "echo 'no audio stream' >&2\nexit 1")]);
+    let output = command(&server, dir.path(), &movie_file, &["--sync=ffsubsync"])
+        .env("PATH", bin.path())
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    let synced = &document.synced[&movie_file.with_extension("srt")];
+    assert_eq!(synced.offset, None);
+    let error = synced.error.as_deref().unwrap();
+    assert!(error.contains("no audio stream"), "{error}");
+    // the subtitles are there, unsynchronized
+    assert_eq!(document.written, [movie_file.with_extension("srt")]);
+}
+
+/// the duration check of a run with ffprobe saying the movie is `duration` seconds long
+#[cfg(unix)]
+async fn checked(
+    server: &MockServer,
+    dir: &Path,
+    movie_file: &Path,
+    duration: &str,
+) -> DurationChecked {
+    let bin = fake_tools(&[("ffprobe", &format!("echo {duration}"))]);
+    let output = command(server, dir, movie_file, &[])
+        .env("PATH", bin.path())
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    document.duration_check.unwrap()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn reports_the_duration_check() {
+    let (server, dir, movie_file) = serve().await;
+    // without ffprobe there's no duration to check against
+    let output = download(&server, dir.path(), &movie_file, &[]).await;
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    assert_eq!(document.duration_check, None);
+
+    // the fixture's cues run from 1s to 6s
+    assert_eq!(
+        checked(&server, dir.path(), &movie_file, "60.000000").await,
+        DurationChecked {
+            check: DurationCheck {
+                movie_duration_ms: 60_000,
+                first_cue_start_ms: 1_000,
+                last_cue_end_ms: 6_000,
+                tolerance_ms: 180_000,
+                latest_first_cue_ms: 15_000,
+            },
+            mismatch: None,
+        }
+    );
+    let checked = checked(&server, dir.path(), &movie_file, "2.0").await;
+    assert_eq!(checked.check.latest_first_cue_ms, 500);
+    let mismatch = checked.mismatch.unwrap();
+    assert!(mismatch.contains("over a quarter into"), "{mismatch}");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn a_rejection_leaves_existing_subtitles_alone() {
+    let (server, dir, movie_file) = serve().await;
+    let existing = movie_file.with_extension("srt");
+    let users = b"1\n00:00:01,000 --> 00:00:02,000\nthe user's own\n\n";
+    std::fs::write(&existing, users).unwrap();
+    // the fixture's cues start a second in, too late for a two second movie
+    let bin = fake_tools(&[("ffprobe", "echo 2.0")]);
+    let output = command(&server, dir.path(), &movie_file, &["--strict-duration"])
+        .env("PATH", bin.path())
+        .output()
+        .await
+        .unwrap();
+    assert!(!output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no subtitles fit the movie"), "{stderr}");
+    assert_eq!(std::fs::read(&existing).unwrap(), users);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn says_the_movie_was_skipped() {
+    let (server, dir, movie_file) = serve().await;
+    let streams =
+        r#"{"streams": [{"index": 1, "codec_type": "audio", "tags": {"language": "pol"}}]}"#;
+    let bin = fake_tools(&[("ffprobe", &format!("echo '{streams}'"))]);
+    let output = command(
+        &server,
+        dir.path(),
+        &movie_file,
+        &["--skip-if-audio-matches"],
+    )
+    .env("PATH", bin.path())
+    .output()
+    .await
+    .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    assert_eq!(document.skipped, Some(Skipped::AudioMatches));
+    assert!(document.subtitle.is_none());
+    assert!(document.written.is_empty());
+    let value = serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap();
+    assert_eq!(value["skipped"], "audio-matches");
+}
+
+/// a renamed copy of the movie gets the same subtitles, the history knows what for
+#[cfg(feature = "history")]
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn names_the_movie_the_same_subtitles_were_downloaded_for() {
+    let (server, dir, movie_file) = serve().await;
+    let output = download(&server, dir.path(), &movie_file, &[]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    assert!(document.downloaded_before.is_empty());
+    let renamed = dir.path().join("Big Buck Bunny (2008).mkv");
+    std::fs::rename(&movie_file, &renamed).unwrap();
+    let output = download(&server, dir.path(), &renamed, &[]).await;
+    assert!(output.status.success(), "{output:?}");
+    let document = serde_json::from_slice::<Document>(&output.stdout).unwrap();
+    let subtitle = renamed.with_extension("srt");
+    assert_eq!(document.written, vec![subtitle.clone()]);
+    assert_eq!(
+        document.downloaded_before,
+        [(subtitle, movie_file)].into_iter().collect()
+    );
+}
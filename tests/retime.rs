@@ -0,0 +1,127 @@
+//! `--retime-fps` and `--anchor`, the scale factors frame rate conversions come down to
+use opensubtitlescli::srt::{self, Anchor, Cue, FrameRates, Srt, Timestamp};
+
+fn rates(text: &str) -> FrameRates {
+    text.parse().unwrap()
+}
+
+fn cue(start: &str, end: &str) -> Cue {
+    Cue {
+        start: start.parse().unwrap(),
+        end: end.parse().unwrap(),
+        lines: vec!["text".to_string()],
+    }
+}
+
+#[test]
+fn conversions_scale_by_the_ratio_of_the_rates() {
+    let cases = [
+        ("25:23.976", 25.0 / 23.976),
+        ("23.976:25", 0.95904),
+        ("24:25", 0.96),
+        ("25:24", 25.0 / 24.0),
+        ("29.97:23.976", 1.25),
+        ("30:25", 1.2),
+    ];
+    for (text, scale) in cases {
+        let linear = rates(text).linear();
+        assert!((linear.scale - scale).abs() < 1e-9, "{text}: {linear:?}");
+        assert_eq!(linear.offset, 0.0, "{text}");
+    }
+}
+
+#[test]
+fn frames_keep_their_number() {
+    // frame 2500 is at 100s at 25 fps and 104.271s at 23.976
+    let mut srt = Srt {
+        cues: vec![cue("00:01:40,000", "00:01:42,000")],
+    };
+    srt.retime(rates("25:23.976").linear());
+    assert_eq!(srt.cues, [cue("00:01:44,271", "00:01:46,356")]);
+    srt.retime(rates("23.976:25").linear());
+    assert_eq!(srt.cues, [cue("00:01:40,000", "00:01:42,000")]);
+}
+
+#[test]
+fn common_factors_are_named() {
+    let cases = [
+        (1.042709, Some((25.0, 23.976))),
+        (0.95904, Some((23.976, 25.0))),
+        (1.041667, Some((25.0, 24.0))),
+        (0.96, Some((24.0, 25.0))),
+        (1.2, Some((30.0, 25.0))),
+        // 23.976:24 and 29.97:30 are the same factor, the first one is named
+        (0.999, Some((23.976, 24.0))),
+        (1.001, Some((24.0, 23.976))),
+        (1.0, None),
+        (1.1, None),
+        (1.03, None),
+    ];
+    for (scale, expected) in cases {
+        assert_eq!(srt::frame_rate_conversion(scale), expected, "{scale}");
+    }
+}
+
+#[test]
+fn anchors_find_the_conversion_too() {
+    let anchor = |from: i64, to: f64| Anchor {
+        from: Timestamp(from),
+        to: Timestamp((from as f64 * to).round() as i64),
+    };
+    let scale = 25.0 / 23.976;
+    let linear =
+        srt::Linear::from_anchors(anchor(60_000, scale), anchor(5_400_000, scale)).unwrap();
+    assert!(linear.offset.abs() < 1.0, "{linear:?}");
+    assert_eq!(
+        srt::frame_rate_conversion(linear.scale),
+        Some((25.0, 23.976))
+    );
+}
+
+#[test]
+fn rates_parse_and_compare() {
+    assert_eq!(
+        rates("25:23.976"),
+        FrameRates {
+            from: 25.0,
+            to: 23.976
+        }
+    );
+    assert_eq!(
+        rates(" 24 : 25 "),
+        FrameRates {
+            from: 24.0,
+            to: 25.0
+        }
+    );
+    for invalid in ["25", "0:25", "-25:24", "a:25", "25:"] {
+        assert!(invalid.parse::<FrameRates>().is_err(), "{invalid}");
+    }
+    assert!(rates("25:25").is_same());
+    assert!(rates("23.976:23.976").is_same());
+    assert!(!rates("23.976:24").is_same());
+}
+
+#[test]
+fn parts_follow_the_ones_before_them() {
+    let part = |start: &str, end: &str| Srt {
+        cues: vec![cue(start, end)],
+    };
+    let parts = vec![
+        part("00:00:01,000", "00:00:02,000"),
+        part("00:00:03,000", "00:00:04,000"),
+        part("00:00:00,500", "00:00:01,000"),
+    ];
+    let lengths = ["00:50:00,000", "00:45:00,500"].map(|v| v.parse::<Timestamp>().unwrap());
+    let joined = Srt::concatenate(parts.clone(), &lengths).unwrap();
+    assert_eq!(
+        joined.cues,
+        [
+            cue("00:00:01,000", "00:00:02,000"),
+            cue("00:50:03,000", "00:50:04,000"),
+            cue("01:35:01,000", "01:35:01,500"),
+        ]
+    );
+    // every part but the last needs its length
+    assert!(Srt::concatenate(parts, &lengths[..1]).is_err());
+}
@@ -0,0 +1,45 @@
+//! stages are reported in the order they ran and added up per stage
+use opensubtitlescli::timings::Timings;
+
+#[tokio::test]
+async fn adds_up_stages_in_the_order_they_ran() {
+    let timings = Timings::new();
+    timings.time_blocking("hashing", || ());
+    let fetched: Result<(), ()> = timings
+        .time_of("download", Some("first".to_string()), async { Err(()) })
+        .await;
+    assert!(fetched.is_err());
+    timings.time("extraction", async {}).await;
+    timings
+        .time_of("download", Some("second".to_string()), async {})
+        .await;
+
+    let stages = timings
+        .timings()
+        .into_iter()
+        .map(|timing| (timing.stage, timing.subject))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        stages,
+        vec![
+            ("hashing", None),
+            ("download", Some("first".to_string())),
+            ("extraction", None),
+            ("download", Some("second".to_string())),
+        ]
+    );
+    let totals = timings
+        .totals()
+        .into_iter()
+        .map(|total| (total.stage, total.count))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        totals,
+        vec![("hashing", 1), ("download", 2), ("extraction", 1)]
+    );
+    let report = timings.report();
+    assert!(
+        report.contains("download") && report.contains("2x"),
+        "{report}"
+    );
+}
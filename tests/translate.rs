@@ -0,0 +1,279 @@
+//! `--translate-from` against DeepL and LibreTranslate answers scripted in [`FakeHttp`]
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use opensubtitlescli::{
+    http::{FakeHttp, Reply},
+    srt,
+    translate::{self, Backend, TranslateError, Translator},
+};
+use std::{path::Path, time::Duration};
+
+const SUBTITLES: &str = "1\n00:00:01,000 --> 00:00:02,500\n<i>Run,</i> Forrest!\n\n\
+                         2\n00:00:03,000 --> 00:00:04,000\n{\\an8}Salt & pepper\n- Yes.\n\n\
+                         3\n00:00:05,000 --> 00:00:06,000\nGoodbye.\n";
+
+fn translator(backend: Backend, url: &str) -> Translator {
+    Translator {
+        url: url.parse().unwrap(),
+        batch_size: 2,
+        min_interval: Duration::ZERO,
+        backoff: Duration::from_millis(1),
+        ..Translator::new(backend, Some("secret:fx".to_string()))
+    }
+}
+
+fn posted(http: &FakeHttp) -> Vec<serde_json::Value> {
+    http.requests()
+        .iter()
+        .map(|recorded| serde_json::from_slice(recorded.request.body.as_deref().unwrap()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn translates_deepl_batches_keeping_tags_and_timing() {
+    let url = "https://api-free.deepl.com/v2/translate";
+    let http = FakeHttp::new();
+    http.reply(
+        url,
+        Reply::ok(
+            r#"{"translations":[{"text":"<i>Biegnij,</i> Forrest!"},
+                {"text":"Sól &amp; pieprz<br>- Tak."}]}"#,
+        ),
+    )
+    .reply(
+        url,
+        Reply::ok(r#"{"translations":[{"text":"Do widzenia."}]}"#),
+    );
+    let mut subtitles = srt::parse(SUBTITLES).unwrap();
+    let translated = translator(Backend::DeepL, url)
+        .translate(&http, &mut subtitles, "eng", "pol")
+        .await
+        .unwrap();
+    assert_eq!(translated.failed, Vec::<usize>::new());
+    assert_eq!(
+        subtitles.to_string(),
+        "1\n00:00:01,000 --> 00:00:02,500\n<i>Biegnij,</i> Forrest!\n\n\
+         2\n00:00:03,000 --> 00:00:04,000\n{\\an8}Sól & pieprz\n- Tak.\n\n\
+         3\n00:00:05,000 --> 00:00:06,000\nDo widzenia.\n"
+    );
+    let posted = posted(&http);
+    assert_eq!(posted.len(), 2);
+    assert_eq!(
+        posted[0],
+        serde_json::json!({
+            "text": ["<i>Run,</i> Forrest!", "Salt &amp; pepper<br>- Yes."],
+            "source_lang": "EN",
+            "target_lang": "PL",
+            "tag_handling": "html",
+        })
+    );
+    let headers = &http.requests()[0].request.headers;
+    assert!(headers.contains(&(
+        "Authorization".to_string(),
+        "DeepL-Auth-Key secret:fx".to_string()
+    )));
+}
+
+#[tokio::test]
+async fn posts_to_libretranslate_with_the_key_in_the_body() {
+    let url = "https://translate.example/translate";
+    let http = FakeHttp::new();
+    http.reply(
+        url,
+        Reply::ok(
+            r#"{"translatedText":["<i>Corre,</i> Forrest!","Sal &amp; pimienta<br/>- Sí."]}"#,
+        ),
+    )
+    .reply(url, Reply::ok(r#"{"translatedText":["Adiós."]}"#));
+    let mut subtitles = srt::parse(SUBTITLES).unwrap();
+    translator(Backend::LibreTranslate, url)
+        .translate(&http, &mut subtitles, "eng", "spa")
+        .await
+        .unwrap();
+    assert_eq!(subtitles.cues[1].lines, ["{\\an8}Sal & pimienta", "- Sí."]);
+    let posted = posted(&http);
+    assert_eq!(posted[1]["q"], serde_json::json!(["Goodbye."]));
+    assert_eq!(
+        (
+            &posted[0]["source"],
+            &posted[0]["target"],
+            &posted[0]["format"],
+            &posted[0]["api_key"]
+        ),
+        (
+            &serde_json::json!("en"),
+            &serde_json::json!("es"),
+            &serde_json::json!("html"),
+            &serde_json::json!("secret:fx")
+        )
+    );
+}
+
+#[tokio::test]
+async fn retries_when_rate_limited() {
+    let url = "https://translate.example/translate";
+    let http = FakeHttp::new();
+    http.reply(
+        url,
+        Reply::status(429, "slow down").retry_after(Duration::ZERO),
+    )
+    .reply(url, Reply::error("connection reset"))
+    .reply(url, Reply::ok(r#"{"translatedText":["Adiós."]}"#));
+    let mut subtitles = srt::parse("1\n00:00:05,000 --> 00:00:06,000\nGoodbye.\n").unwrap();
+    let translated = translator(Backend::LibreTranslate, url)
+        .translate(&http, &mut subtitles, "eng", "spa")
+        .await
+        .unwrap();
+    assert!(translated.failed.is_empty());
+    assert_eq!(subtitles.cues[0].lines, ["Adiós."]);
+    assert_eq!(http.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn failed_batches_keep_their_text() {
+    let url = "https://translate.example/translate";
+    let http = FakeHttp::new();
+    http.reply(
+        url,
+        Reply::ok(r#"{"translatedText":["<i>Corre,</i> Forrest!","Sal<br>- Sí."]}"#),
+    )
+    .reply(url, Reply::status(502, "bad gateway"));
+    let mut subtitles = srt::parse(SUBTITLES).unwrap();
+    let translated = translator(Backend::LibreTranslate, url)
+        .translate(&http, &mut subtitles, "eng", "spa")
+        .await
+        .unwrap();
+    assert_eq!(translated.cues, 3);
+    assert_eq!(translated.failed, [3]);
+    assert_eq!(subtitles.cues[2].lines, ["Goodbye."]);
+    // the first answer and the failing one with its three retries
+    assert_eq!(http.requests().len(), 5);
+
+    // nothing translated is no translation
+    let mut subtitles = srt::parse(SUBTITLES).unwrap();
+    let http = FakeHttp::new();
+    http.reply(url, Reply::status(500, ""));
+    let report = translator(Backend::LibreTranslate, url)
+        .translate(&http, &mut subtitles, "eng", "spa")
+        .await
+        .unwrap_err();
+    assert!(report.to_string().contains("translating"), "{report}");
+}
+
+#[tokio::test]
+async fn gives_up_on_a_refused_key_at_once() {
+    let url = "https://api-free.deepl.com/v2/translate";
+    let http = FakeHttp::new();
+    http.reply(url, Reply::status(403, "Forbidden"));
+    let mut subtitles = srt::parse(SUBTITLES).unwrap();
+    let report = translator(Backend::DeepL, url)
+        .translate(&http, &mut subtitles, "eng", "pol")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        TranslateError::find(&report),
+        Some(&TranslateError::Refused(403))
+    );
+    assert_eq!(http.requests().len(), 1);
+    let report = translator(Backend::DeepL, url)
+        .translate(&http, &mut subtitles, "eng", "xyz")
+        .await
+        .unwrap_err();
+    assert_eq!(
+        TranslateError::find(&report),
+        Some(&TranslateError::UnknownLanguage("xyz".to_string()))
+    );
+}
+
+#[test]
+fn names_languages_and_files_for_the_services() {
+    assert_eq!(Backend::DeepL.code("eng", true).as_deref(), Some("EN-US"));
+    assert_eq!(Backend::DeepL.code("eng", false).as_deref(), Some("EN"));
+    assert_eq!(Backend::DeepL.code("pob", true).as_deref(), Some("PT-BR"));
+    assert_eq!(
+        Backend::LibreTranslate.code("ger", true).as_deref(),
+        Some("de")
+    );
+    assert_eq!(
+        Backend::DeepL.default_url(Some("key")),
+        "https://api.deepl.com/v2/translate"
+    );
+    assert_eq!(
+        translate::machine_path(Path::new("/movies/Movie.2008.srt")),
+        Path::new("/movies/Movie.2008.machine.srt")
+    );
+}
+
+/// finnish has nothing, the english subtitles are translated by the local server
+#[tokio::test]
+#[ignore = "binds a local port"]
+async fn writes_the_translation_next_to_the_movie() {
+    use common::{read_fixture, MockServer};
+    let server = MockServer::start().await;
+    let dir = tempfile::tempdir().unwrap();
+    let movie_file = dir.path().join("Big.Buck.Bunny.2008.1080p.BluRay.x264.mkv");
+    let contents = (0..200_000u32).map(|n| (n % 251) as u8).collect::<Vec<_>>();
+    std::fs::write(&movie_file, contents).unwrap();
+    let hash = opensubtitlescli::hash_for_file(&movie_file).unwrap();
+    server.route(
+        &format!("/pl/search/sublanguageid-fin/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("empty_search.html"),
+    );
+    server.route(
+        &format!("/pl/search/sublanguageid-eng/moviehash-{hash}"),
+        200,
+        &[],
+        read_fixture("search.html"),
+    );
+    server.route(
+        "/download/sub/1000001",
+        200,
+        &[("Content-Type", "application/zip")],
+        read_fixture("subtitles.zip"),
+    );
+    server.route(
+        "/translate",
+        200,
+        &[],
+        br#"{"translatedText":["J\u00e4nis her\u00e4\u00e4.","Hyv\u00e4\u00e4 huomenta!"]}"#
+            .to_vec(),
+    );
+    // the server runs on this test's runtime, the binary is waited for without blocking it
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_opensubtitlescli"))
+        .args([
+            "--base-url",
+            server.base_url.as_str(),
+            "--auto",
+            "-l",
+            "fin",
+        ])
+        .args([
+            "--translate-from",
+            "eng",
+            "--translate-backend",
+            "libretranslate",
+        ])
+        .arg("--translate-url")
+        .arg(server.base_url.join("translate").unwrap().as_str())
+        .arg("-m")
+        .arg(&movie_file)
+        .env("OPENSUBTITLESCLI_HISTORY", dir.path().join("history.json"))
+        .env("OPENSUBTITLESCLI_NO_UPDATE_CHECK", "true")
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let translated = movie_file.with_extension("machine.srt");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("machine.srt"));
+    assert!(!movie_file.with_extension("srt").exists());
+    // with the line endings of the file it was translated from
+    assert_eq!(
+        std::fs::read_to_string(translated).unwrap(),
+        "1\r\n00:00:01,000 --> 00:00:03,500\r\nJänis herää.\r\n\r\n\
+         2\r\n00:00:04,000 --> 00:00:06,000\r\nHyvää huomenta!\r\n"
+    );
+}
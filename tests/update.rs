@@ -0,0 +1,177 @@
+//! `self-update` against a release scripted in [`FakeHttp`]
+#![cfg(feature = "self-update")]
+use opensubtitlescli::{
+    http::{FakeHttp, Reply},
+    update::{self, LastCheck, Release, UpdateError},
+};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, SystemTime};
+
+const URL: &str = "https://api.github.com/repos/Niedzwiedzw/opensubtitlescli/releases/latest";
+const TARGET: &str = "x86_64-unknown-linux-gnu";
+const BINARY: &[u8] = b"the new opensubtitlescli";
+
+fn release(tag: &str, assets: &[&str]) -> Release {
+    let assets = assets
+        .iter()
+        .map(|name| {
+            serde_json::json!({
+                "name": name,
+                "browser_download_url": format!("https://github.com/releases/download/{tag}/{name}"),
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::from_value(serde_json::json!({ "tag_name": tag, "assets": assets })).unwrap()
+}
+
+fn http(checksum: &str) -> FakeHttp {
+    let http = FakeHttp::new();
+    http.reply(
+        &format!("https://github.com/releases/download/v9.0.0/opensubtitlescli-{TARGET}"),
+        Reply::ok(BINARY),
+    )
+    .reply(
+        "https://github.com/releases/download/v9.0.0/SHA256SUMS",
+        Reply::ok(format!(
+            "{checksum}  opensubtitlescli-{TARGET}\n0000  opensubtitlescli-aarch64-apple-darwin\n"
+        )),
+    );
+    http
+}
+
+#[test]
+fn compares_versions() {
+    assert!(release("v9.0.0", &[]).is_newer_than("0.2.1").unwrap());
+    assert!(!release("v0.2.1", &[]).is_newer_than("0.2.1").unwrap());
+    assert!(!release("0.1.9", &[]).is_newer_than("0.2.1").unwrap());
+    assert!(release("nightly", &[]).version().is_err());
+}
+
+#[test]
+fn picks_the_asset_for_the_target() {
+    let release = release(
+        "v9.0.0",
+        &[
+            "opensubtitlescli-aarch64-apple-darwin",
+            "opensubtitlescli-x86_64-pc-windows-msvc.zip",
+            "opensubtitlescli-x86_64-unknown-linux-gnu",
+            "SHA256SUMS",
+        ],
+    );
+    let name = |target| release.asset_for(target).map(|asset| asset.name.as_str());
+    assert_eq!(
+        name(TARGET),
+        Some("opensubtitlescli-x86_64-unknown-linux-gnu")
+    );
+    assert_eq!(
+        name("x86_64-pc-windows-msvc"),
+        Some("opensubtitlescli-x86_64-pc-windows-msvc.zip")
+    );
+    assert_eq!(name("riscv64gc-unknown-linux-gnu"), None);
+}
+
+#[test]
+fn reads_checksums_in_both_modes() {
+    let sums = "aaaa  opensubtitlescli-x86_64-unknown-linux-gnu\nbbbb *opensubtitlescli.zip\n";
+    assert_eq!(
+        update::checksum_for(sums, "opensubtitlescli-x86_64-unknown-linux-gnu"),
+        Some("aaaa")
+    );
+    assert_eq!(
+        update::checksum_for(sums, "opensubtitlescli.zip"),
+        Some("bbbb")
+    );
+    assert_eq!(update::checksum_for(sums, "opensubtitlescli"), None);
+}
+
+#[tokio::test]
+async fn finds_a_newer_release() {
+    let http = FakeHttp::new();
+    http.reply(URL, Reply::ok(r#"{"tag_name":"v9.0.0","assets":[]}"#));
+    let newer = update::newer(&http, URL.parse().unwrap()).await.unwrap();
+    assert_eq!(
+        newer.map(|release| release.tag_name),
+        Some("v9.0.0".to_string())
+    );
+    let request = &http.requests()[0].request;
+    assert!(request
+        .headers
+        .iter()
+        .any(|(name, value)| name == "User-Agent" && value.starts_with("opensubtitlescli/")));
+}
+
+#[tokio::test]
+async fn downloads_a_binary_matching_its_checksum() {
+    let http = http(&format!("{:x}", Sha256::digest(BINARY)));
+    let release = release(
+        "v9.0.0",
+        &[&format!("opensubtitlescli-{TARGET}"), "SHA256SUMS"],
+    );
+    let binary = update::download_binary(&http, &release, TARGET)
+        .await
+        .unwrap();
+    assert_eq!(binary, BINARY);
+}
+
+#[tokio::test]
+async fn refuses_a_binary_not_matching_its_checksum() {
+    let http = http(&format!("{:x}", Sha256::digest(b"something else")));
+    let release = release(
+        "v9.0.0",
+        &[&format!("opensubtitlescli-{TARGET}"), "SHA256SUMS"],
+    );
+    let report = update::download_binary(&http, &release, TARGET)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        UpdateError::find(&report),
+        Some(&UpdateError::ChecksumMismatch(format!(
+            "opensubtitlescli-{TARGET}"
+        )))
+    );
+}
+
+#[tokio::test]
+async fn refuses_a_release_without_checksums() {
+    let release = release("v9.0.0", &[&format!("opensubtitlescli-{TARGET}")]);
+    let report = update::download_binary(&FakeHttp::new(), &release, TARGET)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        UpdateError::find(&report),
+        Some(UpdateError::NoChecksum(_))
+    ));
+}
+
+#[test]
+fn replaces_the_binary_keeping_its_permissions() {
+    let dir = tempfile::tempdir().unwrap();
+    let exe = dir.path().join("opensubtitlescli");
+    std::fs::write(&exe, "the old one").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    update::replace(&exe, BINARY).unwrap();
+    assert_eq!(std::fs::read(&exe).unwrap(), BINARY);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&exe).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+    // nothing left behind next to it
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn checks_once_a_day() {
+    let dir = tempfile::tempdir().unwrap();
+    let last_check = LastCheck::new(dir.path().join("cache").join("update-check"));
+    assert!(last_check.due(SystemTime::now()));
+    last_check.checked().unwrap();
+    assert!(!last_check.due(SystemTime::now()));
+    let tomorrow = SystemTime::now() + update::CHECK_INTERVAL + Duration::from_secs(1);
+    assert!(last_check.due(tomorrow));
+}
@@ -0,0 +1,253 @@
+//! `upload` against the XML-RPC api scripted in [`FakeHttp`]
+use opensubtitlescli::{
+    api::{self, Credentials, StatusError},
+    http::{FakeHttp, Reply},
+    upload::{self, Upload, UploadError, Uploaded},
+    xmlrpc::{self, Value},
+    Client,
+};
+use std::sync::Arc;
+
+fn answer(members: &str) -> Reply {
+    Reply::ok(format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<methodResponse><params><param><value>\
+         <struct>{members}</struct></value></param></params></methodResponse>"
+    ))
+}
+
+fn member(name: &str, value: &str) -> String {
+    format!("<member><name>{name}</name><value>{value}</value></member>")
+}
+
+fn ok() -> String {
+    member("status", "<string>200 OK</string>")
+}
+
+fn logged_in() -> Reply {
+    answer(&format!(
+        "{}{}",
+        member("token", "<string>secret-token</string>"),
+        ok()
+    ))
+}
+
+fn upload() -> Upload {
+    Upload {
+        movie_hash: "33930e90499aa99c".to_string(),
+        movie_size: 12_909_756,
+        movie_file_name: "Big.Buck.Bunny.2008.mkv".to_string(),
+        subtitle_file_name: "Big.Buck.Bunny.2008.pol.srt".to_string(),
+        subtitle: b"1\n00:00:01,000 --> 00:00:02,000\nkr\xc3\xb3lik\n".to_vec(),
+        language: "pol".to_string(),
+        imdb_id: None,
+        release_name: "Big.Buck.Bunny.2008".to_string(),
+        hearing_impaired: false,
+        forced: false,
+        comment: None,
+    }
+}
+
+fn credentials() -> Credentials {
+    Credentials {
+        username: "someone".to_string(),
+        password: "hunter2".to_string(),
+    }
+}
+
+fn client(http: &Arc<FakeHttp>) -> Client {
+    Client::builder()
+        .http(http.clone())
+        .credentials(Some(credentials()))
+        .build()
+        .unwrap()
+}
+
+/// the method names of the calls made, in order
+fn methods(http: &FakeHttp) -> Vec<String> {
+    http.requests()
+        .iter()
+        .map(|recorded| {
+            let body =
+                String::from_utf8_lossy(recorded.request.body.as_deref().unwrap_or_default())
+                    .into_owned();
+            let start = body.find("<methodName>").unwrap() + "<methodName>".len();
+            let end = body.find("</methodName>").unwrap();
+            body[start..end].to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn hashes_subtitles_as_md5() {
+    assert_eq!(
+        upload::subtitle_hash(b""),
+        "d41d8cd98f00b204e9800998ecf8427e"
+    );
+    assert_eq!(
+        upload::subtitle_hash(b"The quick brown fox jumps over the lazy dog"),
+        "9e107d9d372bb6826bd81d3542a419d6"
+    );
+    // longer than a block
+    assert_eq!(
+        upload::subtitle_hash(&[b'a'; 100]),
+        "36a92cc94a9e0fa21f625f8bfb007adf"
+    );
+}
+
+#[test]
+fn reads_responses_and_faults() {
+    let value = xmlrpc::response(
+        "<methodResponse><params><param><value><struct>\
+         <member><name>data</name><value><array><data><value><i4>7</i4></value>\
+         <value>plain &amp; untyped</value></data></array></value></member>\
+         <member><name>empty</name><value><string/></value></member>\
+         <member><name>none</name><value><nil/></value></member>\
+         </struct></value></param></params></methodResponse>",
+    )
+    .unwrap();
+    assert_eq!(
+        value.get("data"),
+        Some(&Value::Array(vec![
+            Value::Int(7),
+            Value::string("plain & untyped")
+        ]))
+    );
+    assert_eq!(value.get("empty"), Some(&Value::string("")));
+    assert_eq!(value.get("none"), Some(&Value::Nil));
+    let report = xmlrpc::response(
+        "<methodResponse><fault><value><struct>\
+         <member><name>faultCode</name><value><int>4</int></value></member>\
+         <member><name>faultString</name><value><string>Too many parameters</string></value></member>\
+         </struct></value></fault></methodResponse>",
+    )
+    .unwrap_err();
+    let fault = report.downcast_ref::<xmlrpc::Fault>().unwrap();
+    assert_eq!(
+        (fault.code, fault.message.as_str()),
+        (4, "Too many parameters")
+    );
+}
+
+#[test]
+fn escapes_what_it_sends() {
+    let body = xmlrpc::call("LogIn", &[Value::string("a<b & c")]);
+    assert!(body.contains("<string>a&lt;b &amp; c</string>"), "{body}");
+}
+
+#[tokio::test]
+async fn uploads_a_new_subtitle() {
+    let http = Arc::new(FakeHttp::new());
+    http.reply(api::API_URL, logged_in())
+        .reply(
+            api::API_URL,
+            answer(&format!(
+                "{}{}{}",
+                member("alreadyindb", "<int>0</int>"),
+                member(
+                    "data",
+                    "<array><data><value><struct><member><name>IDMovieImdb</name>\
+                     <value><string>1254207</string></value></member></struct></value></data></array>"
+                ),
+                ok()
+            )),
+        )
+        .reply(
+            api::API_URL,
+            answer(&format!(
+                "{}{}",
+                member(
+                    "data",
+                    "<string>http://www.opensubtitles.org/subtitles/9000001/big-buck-bunny-pl</string>"
+                ),
+                ok()
+            )),
+        )
+        .reply(api::API_URL, answer(&ok()));
+    let uploaded = client(&http).upload(&upload()).await.unwrap();
+    assert_eq!(
+        uploaded,
+        Uploaded::New(
+            "http://www.opensubtitles.org/subtitles/9000001/big-buck-bunny-pl".to_string()
+        )
+    );
+    assert_eq!(
+        methods(&http),
+        ["LogIn", "TryUploadSubtitles", "UploadSubtitles", "LogOut"]
+    );
+    let requests = http.requests();
+    let uploaded = String::from_utf8_lossy(requests[2].request.body.as_deref().unwrap());
+    for expected in [
+        "<name>idmovieimdb</name><value><string>1254207</string>",
+        "<name>sublanguageid</name><value><string>pol</string>",
+        "<name>moviereleasename</name><value><string>Big.Buck.Bunny.2008</string>",
+        "<name>subcontent</name>",
+        "<string>secret-token</string>",
+    ] {
+        assert!(uploaded.contains(expected), "{expected} in {uploaded}");
+    }
+}
+
+#[tokio::test]
+async fn stops_at_a_subtitle_already_in_the_database() {
+    let http = Arc::new(FakeHttp::new());
+    http.reply(api::API_URL, logged_in())
+        .reply(
+            api::API_URL,
+            answer(&format!(
+                "{}{}{}",
+                member("alreadyindb", "<int>1</int>"),
+                member(
+                    "data",
+                    "<struct><member><name>IDSubtitle</name>\
+                     <value><string>4000001</string></value></member></struct>"
+                ),
+                ok()
+            )),
+        )
+        .reply(api::API_URL, answer(&ok()));
+    let uploaded = client(&http).upload(&upload()).await.unwrap();
+    assert_eq!(
+        uploaded,
+        Uploaded::AlreadyInDatabase(Some(
+            "https://www.opensubtitles.org/subtitles/4000001".to_string()
+        ))
+    );
+    assert_eq!(methods(&http), ["LogIn", "TryUploadSubtitles", "LogOut"]);
+}
+
+#[tokio::test]
+async fn needs_an_imdb_id_for_an_unknown_movie() {
+    let http = Arc::new(FakeHttp::new());
+    http.reply(api::API_URL, logged_in())
+        .reply(
+            api::API_URL,
+            answer(&format!(
+                "{}{}",
+                member("alreadyindb", "<int>0</int>"),
+                ok()
+            )),
+        )
+        .reply(api::API_URL, answer(&ok()));
+    let report = client(&http).upload(&upload()).await.unwrap_err();
+    assert_eq!(UploadError::find(&report), Some(&UploadError::UnknownMovie));
+    // logged out all the same
+    assert_eq!(methods(&http), ["LogIn", "TryUploadSubtitles", "LogOut"]);
+}
+
+#[tokio::test]
+async fn reports_a_refused_login() {
+    let http = Arc::new(FakeHttp::new());
+    http.reply(
+        api::API_URL,
+        answer(&member("status", "<string>401 Unauthorized</string>")),
+    );
+    let report = client(&http).upload(&upload()).await.unwrap_err();
+    assert_eq!(
+        report.downcast_ref::<StatusError>(),
+        Some(&StatusError {
+            method: "LogIn".to_string(),
+            status: "401 Unauthorized".to_string()
+        })
+    );
+    assert!(!format!("{:?}", credentials()).contains("hunter2"));
+}
@@ -0,0 +1,46 @@
+//! `--convert-to vtt` and WebVTT downloads, `tests/fixtures/vtt` has files of either format
+//! next to what they convert into
+// the helpers only the library tests use go unused here
+#[allow(dead_code)]
+mod common;
+
+use common::read_fixture;
+use opensubtitlescli::{srt, subtitle::format::vtt};
+
+fn fixture(name: &str) -> String {
+    String::from_utf8(read_fixture(&format!("vtt/{name}"))).unwrap()
+}
+
+#[test]
+fn cue_settings_and_notes_are_dropped() {
+    let converted = vtt::to_srt(&fixture("settings.vtt")).unwrap();
+    assert_eq!(converted.to_string(), fixture("settings.srt"));
+}
+
+#[test]
+fn srt_text_is_escaped() {
+    let srt = srt::parse(&fixture("escaped.srt")).unwrap();
+    assert_eq!(vtt::render(&srt), fixture("escaped.vtt"));
+}
+
+#[test]
+fn vtt_to_srt_to_vtt_keeps_the_cues() {
+    for name in ["settings.vtt", "escaped.vtt"] {
+        let converted = vtt::to_srt(&fixture(name)).unwrap();
+        let srt = srt::parse(&converted.to_string()).unwrap();
+        assert_eq!(srt, converted, "{name}");
+        let rendered = vtt::render(&srt);
+        assert_eq!(vtt::to_srt(&rendered).unwrap(), converted, "{name}");
+        // the settings, notes and identifiers are gone from what's written
+        assert!(!rendered.contains("NOTE"), "{rendered}");
+        assert!(!rendered.contains("align:"), "{rendered}");
+    }
+}
+
+#[test]
+fn a_missing_header_is_refused() {
+    let error = vtt::to_srt("00:00:01.000 --> 00:00:02.000\nHi\n")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("WEBVTT"), "{error}");
+}